@@ -7,7 +7,9 @@ use itertools::Itertools;
 use shin_core::format::audio::AudioSource;
 use shin_core::format::picture::SimpleMergedPicture;
 use shin_core::format::rom::{IndexEntry, IndexFile};
+use shin_core::game::{GameId, GameProfile};
 use shin_core::vm::command::{CommandResult, RuntimeCommand};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
 use std::path::PathBuf;
@@ -155,28 +157,106 @@ enum FontCommand {
         /// Path to the output directory
         output_path: PathBuf,
     },
+    /// Rasterize a TTF/OTF font into a FNT file
+    Encode {
+        /// Path to the TTF/OTF font file
+        source_font_path: PathBuf,
+        /// Path to the output FNT file
+        output_path: PathBuf,
+        /// Font size in pixels (the size of the em square)
+        #[clap(long, default_value = "96")]
+        size: f32,
+        /// Include all the characters found in this UTF-8 text file (for example, the game script)
+        #[clap(long)]
+        charset: Option<PathBuf>,
+        /// Include the characters from these ranges (hex codepoints, like `20-7e,400-4ff`)
+        #[clap(long, default_value = "20-7e")]
+        ranges: String,
+    },
 }
 
 #[derive(clap::Subcommand, Debug)]
 enum BustupCommand {
-    /// Convert a BUP file into a bunch of PNG files (one base image, one per expression, and one per mouth position)
+    /// Convert a BUP file into a manifest.ron file and a bunch of PNG files (one base image, one per expression, and one per mouth position)
     Decode {
         /// Path to the BUP file
         bustup_path: PathBuf,
         /// Path to the output directory
         output_path: PathBuf,
     },
+    /// Render a single expression with a mouth position on top of the base image into a PNG file
+    Compose {
+        /// Path to the BUP file
+        bustup_path: PathBuf,
+        /// Name of the expression
+        expression: String,
+        /// Index of the mouth position (0 is a closed mouth)
+        mouth: usize,
+        /// Path to the output PNG file (defaults to `<bustup>_<expression>_<mouth>.png`)
+        #[clap(short, long)]
+        output_path: Option<PathBuf>,
+    },
+    /// Build a BUP file from a manifest.ron file and PNG layers (like the ones produced by `decode`)
+    ///
+    /// Layers can be either cropped images placed at their `position` or full-size images at the default position,
+    /// the transparent borders are trimmed automatically
+    Encode {
+        /// Path to the manifest.ron file, layer paths are relative to its directory
+        manifest_path: PathBuf,
+        /// Path to the output BUP file
+        output_path: PathBuf,
+        /// Store the chunks without a palette
+        ///
+        /// Dictionary encoding is lossy for chunks with more than 256 colors
+        #[clap(long)]
+        no_dict_encoding: bool,
+    },
+}
+
+/// Describes the layers of a bustup for `sdu bustup encode`
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct BustupManifest {
+    origin: (u16, u16),
+    base: PathBuf,
+    expressions: BTreeMap<String, BustupManifestExpression>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct BustupManifestExpression {
+    face: Option<BustupManifestLayer>,
+    /// `None` for mouth positions without an image
+    mouths: Vec<Option<BustupManifestLayer>>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct BustupManifestLayer {
+    image: PathBuf,
+    /// Position of the top-left corner of the image relative to the base image
+    #[serde(default)]
+    position: (u32, u32),
 }
 
 #[derive(clap::Subcommand, Debug)]
 enum TextureArchiveCommand {
-    /// Convert a TXA file into a bunch of PNG files (one per texture)
+    /// Convert a TXA file into a metadata.txt file and a bunch of PNG files (one per texture)
     Decode {
         /// Path to the TXA file
         texture_archive_path: PathBuf,
         /// Path to the output directory
         output_path: PathBuf,
     },
+    /// Convert a directory produced by `decode` (metadata.txt and PNG files) back into a TXA file
+    Encode {
+        /// Path to the directory with the metadata.txt and PNG files
+        input_path: PathBuf,
+        /// Path to the output TXA file
+        output_path: PathBuf,
+        /// Store the textures without a palette, even if the original archive used one
+        ///
+        /// Dictionary encoding is lossy for textures with more than 256 colors
+        #[clap(long)]
+        no_dict_encoding: bool,
+    },
 }
 
 #[derive(clap::Subcommand, Debug)]
@@ -188,6 +268,27 @@ enum AudioCommand {
         /// Path to the output WAV file
         output_path: PathBuf,
     },
+    /// Convert a WAV, FLAC or OGG Vorbis file into a NXA file
+    ///
+    /// The loop points are taken from the `smpl` chunk of WAV files, unless specified explicitly
+    Encode {
+        /// Path to the input audio file
+        input_path: PathBuf,
+        /// Path to the output NXA file
+        output_path: PathBuf,
+        /// Bitrate, in kbit/s
+        #[clap(long, default_value = "128")]
+        bitrate: u32,
+        /// Amount of samples in one opus frame (at the output sample rate)
+        #[clap(long, default_value = "960")]
+        frame_samples: u16,
+        /// Where to start playing after looping, in samples of the input file
+        #[clap(long)]
+        loop_start: Option<u32>,
+        /// Where to jump back to the loop start, in samples of the input file (defaults to the end of the file)
+        #[clap(long)]
+        loop_end: Option<u32>,
+    },
 }
 
 #[derive(clap::Subcommand, Debug)]
@@ -205,6 +306,9 @@ enum SavedataCommand {
         /// It is run through a hash function to produce the actual key
         #[clap(long)]
         key_seed: Option<String>,
+        /// Game whose key to use (defaults to umineko)
+        #[clap(long)]
+        game: Option<GameId>,
     },
     /// Obfuscate the save file
     Obfuscate {
@@ -219,6 +323,9 @@ enum SavedataCommand {
         /// It is run through a hash function to produce the actual key
         #[clap(long)]
         key_seed: Option<String>,
+        /// Game whose key to use (defaults to umineko)
+        #[clap(long)]
+        game: Option<GameId>,
     },
     /// Decode the save file into a human-readable format
     Decode {
@@ -226,6 +333,9 @@ enum SavedataCommand {
         save_path: PathBuf,
         /// Path to the output yaml file
         output_path: PathBuf,
+        /// Game whose key to use (defaults to umineko)
+        #[clap(long)]
+        game: Option<GameId>,
     },
 }
 
@@ -616,6 +726,113 @@ fn font_command(command: FontCommand) -> Result<()> {
             }
             Ok(())
         }
+        FontCommand::Encode {
+            source_font_path,
+            output_path,
+            size,
+            charset,
+            ranges,
+        } => {
+            use shin_core::format::font::{read_lazy_font, FontBuilder, GlyphBitmap};
+            use std::collections::{BTreeSet, HashMap};
+            use std::io::Write;
+
+            let source_font = std::fs::read(source_font_path).context("Reading the font")?;
+            let source_font =
+                fontdue::Font::from_bytes(source_font, fontdue::FontSettings::default())
+                    .map_err(|e| anyhow::anyhow!("Parsing the font: {}", e))?;
+
+            let mut characters = BTreeSet::new();
+            for range in ranges.split(',').filter(|r| !r.is_empty()) {
+                let (start, end) = range.split_once('-').unwrap_or((range, range));
+                let start = u32::from_str_radix(start.trim(), 16)
+                    .with_context(|| format!("Parsing the range {:?}", range))?;
+                let end = u32::from_str_radix(end.trim(), 16)
+                    .with_context(|| format!("Parsing the range {:?}", range))?;
+                characters.extend((start..=end).filter_map(char::from_u32));
+            }
+            if let Some(charset) = charset {
+                let charset = std::fs::read_to_string(charset).context("Reading the charset")?;
+                characters.extend(charset.chars().filter(|c| !c.is_control()));
+            }
+
+            let rasterize = |glyph_index: u16| -> Result<GlyphBitmap> {
+                let (metrics, coverage) = source_font.rasterize_indexed(glyph_index, size);
+                Ok(GlyphBitmap {
+                    bearing_x: metrics.xmin.try_into().context("bearing_x out of range")?,
+                    bearing_y: (metrics.ymin + metrics.height as i32)
+                        .try_into()
+                        .context("bearing_y out of range")?,
+                    advance_width: (metrics.advance_width.round() as i32)
+                        .try_into()
+                        .context("advance_width out of range")?,
+                    image: image::GrayImage::from_raw(
+                        metrics.width as u32,
+                        metrics.height as u32,
+                        coverage,
+                    )
+                    .unwrap(),
+                })
+            };
+
+            let line_metrics = source_font
+                .horizontal_line_metrics(size)
+                .context("The font has no horizontal line metrics")?;
+            let ascent = line_metrics.ascent.round() as u16;
+            let descent = (-line_metrics.descent).round() as u16;
+
+            // glyph 0 is the .notdef glyph, which is what should be displayed for missing characters
+            let mut builder = FontBuilder::new(ascent, descent, rasterize(0)?);
+            let mut glyph_ids = HashMap::new();
+            let mut missing = 0;
+            for character in characters {
+                let Ok(codepoint) = u16::try_from(character as u32) else {
+                    eprintln!("Skipping {:?}: FNT supports only the BMP", character);
+                    continue;
+                };
+                let glyph_index = source_font.lookup_glyph_index(character);
+                if glyph_index == 0 {
+                    missing += 1;
+                    continue;
+                }
+                let glyph_id = match glyph_ids.get(&glyph_index) {
+                    Some(&glyph_id) => glyph_id,
+                    None => {
+                        let glyph = rasterize(glyph_index).with_context(|| {
+                            format!("Rasterizing the glyph for {:?}", character)
+                        })?;
+                        let glyph_id = builder.add_glyph(glyph);
+                        glyph_ids.insert(glyph_index, glyph_id);
+                        glyph_id
+                    }
+                };
+                builder.map_character(codepoint, glyph_id);
+            }
+            if missing > 0 {
+                eprintln!(
+                    "{} characters are missing from the font, they will use the fallback glyph",
+                    missing
+                );
+            }
+
+            let mut output = BufWriter::new(File::create(&output_path)?);
+            builder.write(&mut output)?;
+            output.flush()?;
+
+            // make sure the game will be able to load it
+            let font = read_lazy_font(&mut BufReader::new(File::open(&output_path)?))
+                .context("Reading back the written font")?;
+            for glyph in font.get_glyphs().values() {
+                glyph.decompress();
+            }
+            println!(
+                "Wrote {} glyphs, line height is {}",
+                font.get_glyphs().len(),
+                font.get_line_height()
+            );
+
+            Ok(())
+        }
     }
 }
 
@@ -625,56 +842,134 @@ fn bustup_command(command: BustupCommand) -> Result<()> {
             bustup_path,
             output_path,
         } => {
-            use std::fmt::Write;
-
             let bustup = std::fs::read(bustup_path)?;
             let bustup = shin_core::format::bustup::read_bustup(&bustup)?;
 
             std::fs::create_dir_all(&output_path)?;
 
-            let mut metadata = String::new();
-            writeln!(metadata, "expressions:")?;
-            for (expression_name, expression) in bustup.expressions.iter().sorted_by_key(|v| v.0) {
-                writeln!(metadata, "  \"{}\":", expression_name.replace('\"', "\\\""))?;
-                writeln!(
-                    metadata,
-                    "    face_pos: {:?}",
-                    (
-                        expression.face_chunk.offset_x,
-                        expression.face_chunk.offset_y
-                    )
-                )?;
-                writeln!(metadata, "    mouths:")?;
-                for (i, mouth) in expression.mouth_chunks.iter().enumerate() {
-                    writeln!(
-                        metadata,
-                        "      {}: {:?}",
-                        i,
-                        (mouth.offset_x, mouth.offset_y)
-                    )?;
+            fn save_layer(
+                chunk: &shin_core::format::picture::PictureChunk,
+                output_path: &std::path::Path,
+                name: String,
+            ) -> Result<Option<BustupManifestLayer>> {
+                if chunk.is_empty() {
+                    return Ok(None);
                 }
+                chunk.data.save(output_path.join(&name))?;
+                Ok(Some(BustupManifestLayer {
+                    image: PathBuf::from(name),
+                    position: (chunk.offset_x, chunk.offset_y),
+                }))
             }
-            std::fs::write(output_path.join("metadata.txt"), metadata)?;
 
-            bustup.base_image.save(output_path.join("base.png"))?;
+            let mut manifest = BustupManifest {
+                origin: bustup.origin,
+                base: PathBuf::from("base.png"),
+                expressions: BTreeMap::new(),
+            };
+            bustup.base_image.save(output_path.join(&manifest.base))?;
 
             for (expression_name, expression) in bustup.expressions.iter() {
-                if !expression.face_chunk.is_empty() {
-                    expression
-                        .face_chunk
-                        .data
-                        .save(output_path.join(format!("{}_face.png", expression_name)))?;
-                }
+                let face = save_layer(
+                    &expression.face_chunk,
+                    &output_path,
+                    format!("{}_face.png", expression_name),
+                )?;
+                let mouths = expression
+                    .mouth_chunks
+                    .iter()
+                    .enumerate()
+                    .map(|(i, mouth)| {
+                        save_layer(
+                            mouth,
+                            &output_path,
+                            format!("{}_mouth_{}.png", expression_name, i),
+                        )
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                manifest.expressions.insert(
+                    expression_name.clone(),
+                    BustupManifestExpression { face, mouths },
+                );
+            }
 
-                for (i, mouth) in expression.mouth_chunks.iter().enumerate() {
-                    if !mouth.is_empty() {
-                        mouth.data.save(
-                            output_path.join(format!("{}_mouth_{}.png", expression_name, i)),
-                        )?;
-                    }
-                }
+            std::fs::write(
+                output_path.join("manifest.ron"),
+                ron::ser::to_string_pretty(&manifest, ron::ser::PrettyConfig::default())?,
+            )?;
+
+            Ok(())
+        }
+        BustupCommand::Compose {
+            bustup_path,
+            expression,
+            mouth,
+            output_path,
+        } => {
+            let output_path = output_path.unwrap_or_else(|| {
+                let stem = bustup_path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy();
+                PathBuf::from(format!("{}_{}_{}.png", stem, expression, mouth))
+            });
+
+            let bustup = std::fs::read(&bustup_path)?;
+            let bustup = shin_core::format::bustup::read_bustup(&bustup)?;
+
+            let image = bustup.compose(&expression, mouth)?;
+            image.save(&output_path)?;
+
+            Ok(())
+        }
+        BustupCommand::Encode {
+            manifest_path,
+            output_path,
+            no_dict_encoding,
+        } => {
+            use shin_core::format::bustup::BustupWriter;
+            use shin_core::format::picture::PictureChunk;
+            use std::io::Write;
+
+            let manifest = std::fs::read_to_string(&manifest_path).context("Reading manifest")?;
+            let manifest: BustupManifest = ron::from_str(&manifest).context("Parsing manifest")?;
+            let base_path = manifest_path.parent().unwrap_or(std::path::Path::new("."));
+
+            let read_layer = |layer: &Option<BustupManifestLayer>| -> Result<PictureChunk> {
+                let Some(layer) = layer else {
+                    return Ok(PictureChunk::empty());
+                };
+                let image = image::open(base_path.join(&layer.image))
+                    .with_context(|| format!("Reading layer {:?}", layer.image))?
+                    .into_rgba8();
+                Ok(PictureChunk::from_image(
+                    layer.position.0,
+                    layer.position.1,
+                    &image,
+                ))
+            };
+
+            let base_image = image::open(base_path.join(&manifest.base))
+                .with_context(|| format!("Reading base image {:?}", manifest.base))?
+                .into_rgba8();
+
+            let mut writer = BustupWriter::new(manifest.origin, base_image, !no_dict_encoding);
+            for (name, expression) in &manifest.expressions {
+                let face = read_layer(&expression.face)?;
+                let mouths = expression
+                    .mouths
+                    .iter()
+                    .map(read_layer)
+                    .collect::<Result<Vec<_>>>()?;
+                writer.add_expression(name.clone(), face, mouths);
             }
 
+            let mut output =
+                BufWriter::new(File::create(output_path).context("Creating output file")?);
+            writer.write(&mut output)?;
+            output.flush()?;
+
             Ok(())
         }
     }
@@ -686,7 +981,7 @@ fn texture_archive_command(command: TextureArchiveCommand) -> Result<()> {
             texture_archive_path,
             output_path,
         } => {
-            // use std::fmt::Write;
+            use std::fmt::Write;
 
             let texture_archive = std::fs::read(texture_archive_path)?;
             let texture_archive =
@@ -694,15 +989,78 @@ fn texture_archive_command(command: TextureArchiveCommand) -> Result<()> {
 
             std::fs::create_dir_all(&output_path)?;
 
-            // let mut metadata = String::new();
-            // TODO: write metadata
-            // std::fs::write(output_path.join("metadata.txt"), metadata)?;
+            // keep the textures in the archive order, so that the encoded archive matches the original
+            let mut names = vec![""; texture_archive.textures.len()];
+            for (texture_name, &index) in texture_archive.name_to_index.iter() {
+                names[index] = texture_name;
+            }
+            let mut vindices = vec![0; texture_archive.textures.len()];
+            for (&vindex, &index) in texture_archive.vindex_to_index.iter() {
+                vindices[index] = vindex;
+            }
+
+            let mut metadata = String::new();
+            writeln!(
+                metadata,
+                "use_dict_encoding: {}",
+                texture_archive.use_dict_encoding
+            )?;
+            writeln!(metadata, "textures:")?;
+            for (name, vindex) in names.iter().zip(vindices.iter()) {
+                writeln!(metadata, "  {:04}: {}", vindex, name)?;
+            }
+            std::fs::write(output_path.join("metadata.txt"), metadata)?;
 
             for (texture_name, index) in texture_archive.name_to_index.iter() {
                 let texture = &texture_archive.textures[*index];
                 texture.save(output_path.join(format!("{}.png", texture_name)))?;
             }
 
+            Ok(())
+        }
+        TextureArchiveCommand::Encode {
+            input_path,
+            output_path,
+            no_dict_encoding,
+        } => {
+            use shin_core::format::texture_archive::TextureArchiveBuilder;
+            use std::io::Write;
+
+            let metadata = std::fs::read_to_string(input_path.join("metadata.txt"))
+                .context("Reading metadata.txt")?;
+            let mut lines = metadata.lines();
+
+            let use_dict_encoding = lines
+                .next()
+                .and_then(|l| l.strip_prefix("use_dict_encoding: "))
+                .context("Expected `use_dict_encoding: <bool>` on the first line")?
+                .parse::<bool>()
+                .context("Parsing use_dict_encoding")?;
+            if lines.next() != Some("textures:") {
+                anyhow::bail!("Expected `textures:` on the second line");
+            }
+
+            let mut builder = TextureArchiveBuilder::new(use_dict_encoding && !no_dict_encoding);
+            for line in lines.filter(|l| !l.trim().is_empty()) {
+                let (vindex, name) = line
+                    .trim_start()
+                    .split_once(": ")
+                    .with_context(|| format!("Invalid texture line: {:?}", line))?;
+                let vindex = vindex
+                    .parse::<u16>()
+                    .with_context(|| format!("Invalid virtual index: {:?}", vindex))?;
+
+                let texture = image::open(input_path.join(format!("{}.png", name)))
+                    .with_context(|| format!("Reading texture {:?}", name))?
+                    .into_rgba8();
+                builder.add_texture(name.to_string(), vindex, texture);
+            }
+
+            let mut output =
+                BufWriter::new(File::create(output_path).context("Creating output file")?);
+            builder.write(&mut output)?;
+            output.flush()?;
+
             Ok(())
         }
     }
@@ -745,6 +1103,171 @@ fn audio_command(command: AudioCommand) -> Result<()> {
 
             Ok(())
         }
+        AudioCommand::Encode {
+            input_path,
+            output_path,
+            bitrate,
+            frame_samples,
+            loop_start,
+            loop_end,
+        } => {
+            use shin_core::format::audio::{encode_audio, resample, AudioEncoderSettings};
+
+            let (mut samples, channel_count, sample_rate) =
+                read_pcm(&input_path).context("Reading the input file")?;
+            if channel_count > 2 {
+                anyhow::bail!("Only mono and stereo audio is supported");
+            }
+
+            let smpl_loop = if input_path
+                .extension()
+                .map_or(false, |e| e.eq_ignore_ascii_case("wav"))
+            {
+                read_wav_loop_points(&input_path).context("Reading the WAV loop points")?
+            } else {
+                None
+            };
+            let loop_start = loop_start.or(smpl_loop.map(|(start, _)| start));
+            let loop_end = loop_end.or(smpl_loop.map(|(_, end)| end));
+
+            // opus supports only a handful of sample rates
+            let output_sample_rate = if [8000, 12000, 16000, 24000, 48000].contains(&sample_rate) {
+                sample_rate
+            } else {
+                eprintln!("Resampling from {} Hz to 48000 Hz", sample_rate);
+                samples = resample(&samples, channel_count, sample_rate, 48000);
+                48000
+            };
+            let convert_position = |position: u32| {
+                (position as u64 * output_sample_rate as u64 / sample_rate as u64) as u32
+            };
+
+            let settings = AudioEncoderSettings {
+                bitrate: bitrate * 1000,
+                frame_samples,
+                loop_start: convert_position(loop_start.unwrap_or(0)),
+                loop_end: loop_end.map(convert_position),
+            };
+            let encoded = encode_audio(&samples, channel_count, output_sample_rate, &settings)?;
+            std::fs::write(output_path, encoded).context("Writing the output file")?;
+
+            Ok(())
+        }
+    }
+}
+
+/// Decode an audio file into interleaved samples, returning them with the channel count and sample rate
+fn read_pcm(path: &std::path::Path) -> Result<(Vec<f32>, u16, u32)> {
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::codecs::DecoderOptions;
+    use symphonia::core::errors::Error;
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::meta::MetadataOptions;
+    use symphonia::core::probe::Hint;
+
+    let file = File::open(path)?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let mut format = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .context("Unsupported format")?
+        .format;
+    let track = format.default_track().context("No audio track found")?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .context("Unknown sample rate")?;
+    let channel_count = track
+        .codec_params
+        .channels
+        .context("Unknown channel layout")?
+        .count() as u16;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .context("Unsupported codec")?;
+
+    let mut samples = Vec::new();
+    let mut sample_buffer = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e).context("Reading a packet"),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = decoder.decode(&packet).context("Decoding a packet")?;
+        let buffer = sample_buffer.get_or_insert_with(|| {
+            SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec())
+        });
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend_from_slice(buffer.samples());
+    }
+
+    Ok((samples, channel_count, sample_rate))
+}
+
+/// Read the first loop from the `smpl` chunk of a WAV file, returning `(start, end)` with an exclusive end
+fn read_wav_loop_points(path: &std::path::Path) -> Result<Option<(u32, u32)>> {
+    use bytes::Buf;
+
+    let data = std::fs::read(path)?;
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        anyhow::bail!("Not a RIFF WAVE file");
+    }
+
+    let mut chunks = &data[12..];
+    while chunks.len() >= 8 {
+        let id = &chunks[0..4];
+        let size = u32::from_le_bytes(chunks[4..8].try_into().unwrap()) as usize;
+        let body = chunks
+            .get(8..8 + size)
+            .context("Truncated chunk in the WAV file")?;
+
+        // the header is 36 bytes, the first loop follows right after it
+        if id == b"smpl" && body.len() >= 36 + 24 {
+            let mut header = &body[28..36];
+            let loop_count = header.get_u32_le();
+            if loop_count == 0 {
+                return Ok(None);
+            }
+            let mut first_loop = &body[36..60];
+            let _cue_point_id = first_loop.get_u32_le();
+            let _loop_type = first_loop.get_u32_le();
+            let start = first_loop.get_u32_le();
+            // the end is inclusive in the smpl chunk
+            let end = first_loop.get_u32_le() + 1;
+            return Ok(Some((start, end)));
+        }
+
+        // chunks are padded to an even size
+        let next = (8 + size + 1) & !1;
+        chunks = chunks.get(next..).unwrap_or_default();
+    }
+
+    Ok(None)
+}
+
+fn savedata_key(game: Option<GameId>, key: Option<u32>, key_seed: Option<&str>) -> Result<u32> {
+    use shin_core::format::save::Savedata;
+
+    match (key, key_seed) {
+        (Some(key), _) => Ok(key),
+        (None, Some(key_seed)) => Ok(Savedata::obfuscation_key_from_seed(key_seed)),
+        (None, None) => GameProfile::get(game.unwrap_or(GameId::Umineko)).save_key(),
     }
 }
 
@@ -757,15 +1280,12 @@ fn savedata_command(command: SavedataCommand) -> Result<()> {
             output_path,
             key,
             key_seed,
+            game,
         } => {
             let savedata = std::fs::read(save_path)?;
 
-            let key = key.or_else(|| key_seed.as_deref().map(Savedata::obfuscation_key_from_seed));
-
-            let savedata = match key {
-                None => Savedata::deobfuscate(&savedata),
-                Some(key) => Savedata::deobfuscate_with_key(&savedata, key),
-            }?;
+            let key = savedata_key(game, key, key_seed.as_deref())?;
+            let savedata = Savedata::deobfuscate_with_key(&savedata, key)?;
 
             std::fs::write(output_path, savedata)?;
 
@@ -776,15 +1296,12 @@ fn savedata_command(command: SavedataCommand) -> Result<()> {
             output_path,
             key,
             key_seed,
+            game,
         } => {
             let savedata = std::fs::read(save_path)?;
 
-            let key = key.or_else(|| key_seed.as_deref().map(Savedata::obfuscation_key_from_seed));
-
-            let savedata = match key {
-                None => Savedata::obfuscate(&savedata),
-                Some(key) => Savedata::obfuscate_with_key(&savedata, key),
-            };
+            let key = savedata_key(game, key, key_seed.as_deref())?;
+            let savedata = Savedata::obfuscate_with_key(&savedata, key);
 
             std::fs::write(output_path, savedata)?;
 
@@ -794,9 +1311,10 @@ fn savedata_command(command: SavedataCommand) -> Result<()> {
         SavedataCommand::Decode {
             save_path,
             output_path,
+            game,
         } => {
             let savedata = std::fs::read(save_path)?;
-            let savedata = Savedata::decode(&savedata)?;
+            let savedata = Savedata::decode_with_key(&savedata, savedata_key(game, None, None)?)?;
 
             ron::ser::to_writer_pretty(
                 File::create(output_path).context("Creating output file")?,
@@ -0,0 +1,248 @@
+//! Kira backends used by the engine.
+//!
+//! kira's [AudioManager](kira::manager::AudioManager) is generic over the backend, but we want to select it at runtime.
+//! [ShinBackend] wraps all the supported backends in one type.
+
+use crate::wav::WavSink;
+use anyhow::{anyhow, Result};
+use kira::dsp::Frame;
+use kira::manager::backend::cpal::CpalBackend;
+use kira::manager::backend::{Backend, Renderer};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::error;
+
+/// Sample rate used by the backends not backed by an audio device ([AudioBackend::Manual], [AudioBackend::Null] and [AudioBackend::WavFile])
+pub const MANUAL_BACKEND_SAMPLE_RATE: u32 = 48000;
+
+/// How often the [ThreadedBackend] renders the audio
+const THREADED_BACKEND_PERIOD: Duration = Duration::from_millis(5);
+
+/// Selects which backend the [AudioManager](crate::AudioManager) will use
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AudioBackend {
+    /// Output to the default audio device
+    Cpal,
+    /// Do not output anything, but still process the audio in real time, so that the sounds play and finish as usual
+    Null,
+    /// Mix everything into a WAV file, in real time
+    WavFile(PathBuf),
+    /// Do not output anything. The audio is only processed when [AudioManager::advance](crate::AudioManager::advance) is called.
+    ///
+    /// This makes the audio timing deterministic, which is needed for headless runs.
+    Manual,
+}
+
+/// A renderer that is driven by the engine instead of an audio device
+#[derive(Clone)]
+pub struct ManualRenderer {
+    renderer: Arc<Mutex<Option<Renderer>>>,
+    /// Fractional samples left over from previous `advance` calls
+    remainder: Arc<Mutex<f64>>,
+    sample_rate: u32,
+}
+
+impl ManualRenderer {
+    pub(crate) fn new(sample_rate: u32) -> Self {
+        Self {
+            renderer: Arc::new(Mutex::new(None)),
+            remainder: Arc::new(Mutex::new(0.0)),
+            sample_rate,
+        }
+    }
+
+    /// Process the audio for the specified amount of time, returning the mixed frames
+    pub(crate) fn advance(&self, duration: Duration) -> Vec<Frame> {
+        let mut remainder = self.remainder.lock().unwrap();
+        let samples = duration.as_secs_f64() * self.sample_rate as f64 + *remainder;
+        let count = samples.floor();
+        *remainder = samples - count;
+
+        let mut renderer = self.renderer.lock().unwrap();
+        let Some(renderer) = renderer.as_mut() else {
+            return Vec::new();
+        };
+
+        renderer.on_start_processing();
+        (0..count as usize).map(|_| renderer.process()).collect()
+    }
+}
+
+/// Renders the audio on a separate thread in real time, optionally writing it into a WAV file
+pub struct ThreadedBackend {
+    sink: Option<WavSink>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ThreadedBackend {
+    fn new(sink: Option<WavSink>) -> Self {
+        Self {
+            sink,
+            stop: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        }
+    }
+
+    fn start(&mut self, mut renderer: Renderer) -> Result<()> {
+        let mut sink = self.sink.take();
+        let stop = self.stop.clone();
+
+        let thread = std::thread::Builder::new()
+            .name("audio renderer".to_string())
+            .spawn(move || {
+                let start = Instant::now();
+                let mut rendered = 0u64;
+                let mut buffer = Vec::new();
+
+                while !stop.load(Ordering::Relaxed) {
+                    let target =
+                        (start.elapsed().as_secs_f64() * MANUAL_BACKEND_SAMPLE_RATE as f64) as u64;
+
+                    renderer.on_start_processing();
+                    buffer.clear();
+                    buffer.extend((rendered..target).map(|_| renderer.process()));
+                    rendered = target;
+
+                    if let Some(s) = sink.as_mut() {
+                        if let Err(e) = s.write(&buffer) {
+                            error!("Writing the audio failed, stopping the recording: {:?}", e);
+                            sink = None;
+                        }
+                    }
+
+                    std::thread::sleep(THREADED_BACKEND_PERIOD);
+                }
+
+                if let Some(sink) = sink {
+                    if let Err(e) = sink.finalize() {
+                        error!("Finalizing the audio recording failed: {:?}", e);
+                    }
+                }
+            })?;
+
+        self.thread = Some(thread);
+        Ok(())
+    }
+}
+
+impl Drop for ThreadedBackend {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[derive(Default)]
+pub enum ShinBackendSettings {
+    #[default]
+    Cpal,
+    Threaded(Option<WavSink>),
+    Manual(ManualRenderer),
+}
+
+pub enum ShinBackend {
+    Cpal(CpalBackend),
+    Threaded(ThreadedBackend),
+    Manual(ManualRenderer),
+}
+
+impl Backend for ShinBackend {
+    type Settings = ShinBackendSettings;
+    type Error = anyhow::Error;
+
+    fn setup(settings: Self::Settings) -> Result<(Self, u32), Self::Error> {
+        match settings {
+            ShinBackendSettings::Cpal => {
+                let (backend, sample_rate) = CpalBackend::setup(())
+                    .map_err(|e| anyhow!("Setting up cpal backend: {:?}", e))?;
+                Ok((Self::Cpal(backend), sample_rate))
+            }
+            ShinBackendSettings::Threaded(sink) => Ok((
+                Self::Threaded(ThreadedBackend::new(sink)),
+                MANUAL_BACKEND_SAMPLE_RATE,
+            )),
+            ShinBackendSettings::Manual(renderer) => {
+                let sample_rate = renderer.sample_rate;
+                Ok((Self::Manual(renderer), sample_rate))
+            }
+        }
+    }
+
+    fn start(&mut self, renderer: Renderer) -> Result<(), Self::Error> {
+        match self {
+            Self::Cpal(backend) => backend
+                .start(renderer)
+                .map_err(|e| anyhow!("Starting cpal backend: {:?}", e)),
+            Self::Threaded(backend) => backend.start(renderer),
+            Self::Manual(manual) => {
+                *manual.renderer.lock().unwrap() = Some(renderer);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl AudioBackend {
+    pub(crate) fn settings(self) -> Result<(ShinBackendSettings, Option<ManualRenderer>)> {
+        Ok(match self {
+            AudioBackend::Cpal => (ShinBackendSettings::Cpal, None),
+            AudioBackend::Null => (ShinBackendSettings::Threaded(None), None),
+            AudioBackend::WavFile(path) => {
+                let sink = WavSink::create(&path, MANUAL_BACKEND_SAMPLE_RATE)?;
+                (ShinBackendSettings::Threaded(Some(sink)), None)
+            }
+            AudioBackend::Manual => {
+                let renderer = ManualRenderer::new(MANUAL_BACKEND_SAMPLE_RATE);
+                (
+                    ShinBackendSettings::Manual(renderer.clone()),
+                    Some(renderer),
+                )
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AudioManager;
+
+    #[test]
+    fn manual_backend_keeps_the_fractional_samples() {
+        let manager = AudioManager::with_backend(AudioBackend::Manual).unwrap();
+
+        // 6857.14 samples per step
+        let step = Duration::from_secs(1) / 7;
+        let total = (0..7).map(|_| manager.advance(step).len()).sum::<usize>();
+        assert!((47999..=48000).contains(&total), "{}", total);
+    }
+
+    #[test]
+    fn wav_file_backend() {
+        let path =
+            std::env::temp_dir().join(format!("shin-audio-backend-{}.wav", std::process::id()));
+
+        let manager = AudioManager::with_backend(AudioBackend::WavFile(path.clone())).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        // stops the rendering thread and finalizes the file
+        drop(manager);
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().sample_rate, MANUAL_BACKEND_SAMPLE_RATE);
+        let samples = reader
+            .samples::<f32>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // nothing is playing, but the silence is still recorded in real time
+        assert!(samples.len() >= 2 * MANUAL_BACKEND_SAMPLE_RATE as usize / 20);
+        assert!(samples.iter().all(|&s| s == 0.0));
+    }
+}
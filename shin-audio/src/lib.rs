@@ -1,22 +1,31 @@
+mod backend;
 mod data;
 mod handle;
 mod manager;
+mod mixer;
 mod resampler;
 mod sound;
+mod wav;
 
 use kira::track::TrackId;
 use shin_core::time::Tween;
 use shin_core::vm::command::types::{Pan, Volume};
 
+pub use backend::{AudioBackend, MANUAL_BACKEND_SAMPLE_RATE};
 pub use data::AudioData;
 pub use handle::AudioHandle;
 pub use manager::AudioManager;
+pub use mixer::{AudioBus, MixerSettings};
 pub use shin_core::format::audio::AudioFile;
+pub use wav::WavSink;
 
 pub struct AudioSettings {
     pub track: TrackId,
     pub fade_in: Tween,
+    /// Where to jump back after reaching the loop end, `None` disables looping
     pub loop_start: Option<u32>,
+    /// Where to jump back to the loop start (exclusive), `None` means the end of the audio
+    pub loop_end: Option<u32>,
     pub volume: Volume,
     pub pan: Pan,
     // TODO: support play speed (needs research)
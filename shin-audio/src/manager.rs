@@ -1,30 +1,97 @@
+use crate::backend::{AudioBackend, ManualRenderer, ShinBackend};
+use crate::mixer::{AudioBus, Mixer, MixerSettings};
+use crate::{AudioData, AudioHandle};
+use anyhow::{Context, Result};
+use kira::dsp::Frame;
 use kira::manager::AudioManagerSettings;
-use kira::sound::SoundData;
+use kira::track::TrackId;
+use shin_core::format::audio::AudioFrameSource;
 use std::sync::Mutex;
+use std::time::Duration;
+use tracing::warn;
 
-type Backend = kira::manager::backend::cpal::CpalBackend;
+type Backend = ShinBackend;
 
 pub struct AudioManager {
     manager: Mutex<kira::manager::AudioManager<Backend>>,
+    manual_renderer: Option<ManualRenderer>,
+    mixer: Mutex<Mixer>,
 }
 
 impl AudioManager {
-    pub fn new() -> Self {
-        let manager = kira::manager::AudioManager::new(AudioManagerSettings::default())
-            .expect("Failed to create kira audio manager");
+    /// Create an audio manager outputting to the default audio device
+    ///
+    /// If there is no usable audio device, falls back to the [AudioBackend::Null] backend, so that the game can still run.
+    pub fn new() -> Result<Self> {
+        Self::with_backend(AudioBackend::Cpal).or_else(|e| {
+            warn!(
+                "Failed to initialize the audio output, continuing without sound: {:?}",
+                e
+            );
+            Self::with_backend(AudioBackend::Null)
+        })
+    }
+
+    pub fn with_backend(backend: AudioBackend) -> Result<Self> {
+        let (backend_settings, manual_renderer) = backend.settings()?;
+
+        let mut manager = kira::manager::AudioManager::new(AudioManagerSettings {
+            backend_settings,
+            ..Default::default()
+        })
+        .context("Creating kira audio manager")?;
+        let mixer =
+            Mixer::new(&mut manager, MixerSettings::default()).context("Creating the mixer")?;
 
-        Self {
+        Ok(Self {
             manager: Mutex::new(manager),
-        }
+            manual_renderer,
+            mixer: Mutex::new(mixer),
+        })
     }
 
-    pub fn play<S: SoundData>(&self, data: S) -> S::Handle
-    where
-        S::Error: std::fmt::Debug,
-    {
+    pub fn play<S: AudioFrameSource + Send + 'static>(&self, data: AudioData<S>) -> AudioHandle {
+        let is_voice = data.settings.track == self.bus_track(AudioBus::Voice);
+
         let mut manager = self.manager.lock().unwrap();
+        let handle = manager.play(data).expect("Failed to start playing audio");
+        drop(manager);
+
+        if is_voice {
+            self.mixer.lock().unwrap().add_voice(handle.shared.clone());
+        }
+
+        handle
+    }
 
-        manager.play(data).expect("Failed to start playing audio")
+    /// Returns the track sounds of the specified category should be played on (or routed to)
+    ///
+    /// Voices must be played on the [AudioBus::Voice] track directly for the voice focus to work
+    pub fn bus_track(&self, bus: AudioBus) -> TrackId {
+        self.mixer.lock().unwrap().track_id(bus)
+    }
+
+    pub fn mixer_settings(&self) -> MixerSettings {
+        self.mixer.lock().unwrap().settings()
+    }
+
+    pub fn set_mixer_settings(&self, settings: MixerSettings) {
+        self.mixer.lock().unwrap().set_settings(settings);
+    }
+
+    /// Update the state of the mixer (voice focus), should be called every frame
+    pub fn update(&self) {
+        self.mixer.lock().unwrap().update();
+    }
+
+    /// Process the audio for the specified amount of time, returning the mixed frames
+    ///
+    /// Only does something when using the [AudioBackend::Manual] backend, otherwise the audio device is driving the processing
+    pub fn advance(&self, duration: Duration) -> Vec<Frame> {
+        match &self.manual_renderer {
+            Some(renderer) => renderer.advance(duration),
+            None => Vec::new(),
+        }
     }
 
     pub fn kira_manager(&self) -> &Mutex<kira::manager::AudioManager<Backend>> {
@@ -0,0 +1,318 @@
+//! Mixer sub-tracks ("buses") for the different categories of sounds.
+//!
+//! Each bus has its own volume, controlled by the game settings.
+//! While a voice is playing, the BGM bus can be ducked ("voice focus").
+
+use crate::sound::Shared;
+use anyhow::{Context, Result};
+use kira::manager::backend::Backend;
+use kira::track::{TrackBuilder, TrackHandle, TrackId, TrackRoutes};
+use kira::tween::Tween;
+use shin_core::format::save::Settings;
+use shin_core::vm::command::types::{AudioWaitStatus, Volume};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Volume multiplier applied to the BGM while a voice is playing (if voice focus is enabled)
+const VOICE_FOCUS_BGM_VOLUME: f32 = 0.4;
+const VOICE_FOCUS_FADE_IN: Duration = Duration::from_millis(250);
+const VOICE_FOCUS_FADE_OUT: Duration = Duration::from_millis(500);
+const SETTINGS_FADE: Duration = Duration::from_millis(15);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AudioBus {
+    Bgm,
+    Se,
+    Voice,
+    System,
+}
+
+impl AudioBus {
+    pub const ALL: [AudioBus; 4] = [
+        AudioBus::Bgm,
+        AudioBus::Se,
+        AudioBus::Voice,
+        AudioBus::System,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MixerSettings {
+    pub bgm_volume: Volume,
+    pub se_volume: Volume,
+    pub voice_volume: Volume,
+    pub system_volume: Volume,
+    /// Duck the BGM while a voice is playing
+    pub voice_focus: bool,
+}
+
+impl MixerSettings {
+    pub fn volume(&self, bus: AudioBus) -> Volume {
+        match bus {
+            AudioBus::Bgm => self.bgm_volume,
+            AudioBus::Se => self.se_volume,
+            AudioBus::Voice => self.voice_volume,
+            AudioBus::System => self.system_volume,
+        }
+    }
+}
+
+impl Default for MixerSettings {
+    fn default() -> Self {
+        Self {
+            bgm_volume: Volume::default(),
+            se_volume: Volume::default(),
+            voice_volume: Volume::default(),
+            system_volume: Volume::default(),
+            voice_focus: true,
+        }
+    }
+}
+
+impl From<&Settings> for MixerSettings {
+    /// The volumes in the save are stored in percents
+    fn from(settings: &Settings) -> Self {
+        let volume = |v: u8| Volume((v.min(100) as f32) / 100.0);
+
+        Self {
+            bgm_volume: volume(settings.v0_bgmvol),
+            se_volume: volume(settings.v1_sfxvol),
+            voice_volume: volume(settings.v2_voicevol),
+            system_volume: volume(settings.v3_sysvol),
+            voice_focus: settings.v4_voicefocus,
+        }
+    }
+}
+
+fn linear_tween(duration: Duration) -> Tween {
+    Tween {
+        duration,
+        ..Default::default()
+    }
+}
+
+pub(crate) struct Mixer {
+    tracks: [TrackHandle; 4],
+    settings: MixerSettings,
+    /// Sounds playing on the voice bus, used to decide whether to duck the BGM
+    voices: Vec<Arc<Shared>>,
+    bgm_ducked: bool,
+}
+
+impl Mixer {
+    pub fn new<B: Backend>(
+        manager: &mut kira::manager::AudioManager<B>,
+        settings: MixerSettings,
+    ) -> Result<Self> {
+        let mut tracks = Vec::with_capacity(AudioBus::ALL.len());
+        for bus in AudioBus::ALL {
+            let track = manager
+                .add_sub_track(
+                    TrackBuilder::new()
+                        .volume(settings.volume(bus).0 as f64)
+                        .routes(TrackRoutes::parent(TrackId::Main)),
+                )
+                .with_context(|| format!("Creating the {:?} bus", bus))?;
+            tracks.push(track);
+        }
+
+        Ok(Self {
+            tracks: tracks.try_into().unwrap_or_else(|_| unreachable!()),
+            settings,
+            voices: Vec::new(),
+            bgm_ducked: false,
+        })
+    }
+
+    pub fn track_id(&self, bus: AudioBus) -> TrackId {
+        self.tracks[bus.index()].id()
+    }
+
+    pub fn settings(&self) -> MixerSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: MixerSettings) {
+        self.settings = settings;
+        for bus in AudioBus::ALL {
+            self.apply_volume(bus, linear_tween(SETTINGS_FADE));
+        }
+    }
+
+    pub fn add_voice(&mut self, shared: Arc<Shared>) {
+        self.voices.push(shared);
+    }
+
+    /// Forget the voices that finished playing and (un)duck the BGM
+    pub fn update(&mut self) {
+        self.voices.retain(|shared| {
+            let status = AudioWaitStatus::from_bits_truncate(
+                shared.wait_status.load(std::sync::atomic::Ordering::SeqCst),
+            );
+            !status.contains(AudioWaitStatus::STOPPED)
+        });
+
+        let should_duck = self.settings.voice_focus && !self.voices.is_empty();
+        if should_duck != self.bgm_ducked {
+            self.bgm_ducked = should_duck;
+            let fade = if should_duck {
+                VOICE_FOCUS_FADE_IN
+            } else {
+                VOICE_FOCUS_FADE_OUT
+            };
+            self.apply_volume(AudioBus::Bgm, linear_tween(fade));
+        }
+    }
+
+    /// The volume of the bus, taking the voice focus into account
+    fn effective_volume(&self, bus: AudioBus) -> f32 {
+        let volume = self.settings.volume(bus).0;
+        if bus == AudioBus::Bgm && self.bgm_ducked {
+            volume * VOICE_FOCUS_BGM_VOLUME
+        } else {
+            volume
+        }
+    }
+
+    fn apply_volume(&mut self, bus: AudioBus, tween: Tween) {
+        let volume = self.effective_volume(bus);
+
+        self.tracks[bus.index()]
+            .set_volume(volume as f64, tween)
+            .expect("Failed to set the bus volume");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kira::manager::backend::mock::{MockBackend, MockBackendSettings};
+    use kira::manager::AudioManagerSettings;
+    use std::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+
+    fn settings(bgm: u8, se: u8, voice: u8, system: u8, voice_focus: bool) -> Settings {
+        Settings {
+            v0_bgmvol: bgm,
+            v1_sfxvol: se,
+            v2_voicevol: voice,
+            v3_sysvol: system,
+            v4_voicefocus: voice_focus,
+            v5_voicepanapot: false,
+            v6: false,
+            v7: 0,
+            v8: 0,
+            v9_msgspeed: 50,
+            v10_skipspeed: 50,
+            v11_disallowskipunread: false,
+            v12: false,
+            v13_msgwinalpha: 50,
+            v14_showroutenavi: false,
+            v15: false,
+            v16_showtoucheffect: false,
+            v17_showscenetitle: false,
+            v18_showsongtitle: false,
+            v19: 0,
+        }
+    }
+
+    fn mixer(settings: MixerSettings) -> Mixer {
+        let mut manager = kira::manager::AudioManager::<MockBackend>::new(AudioManagerSettings {
+            backend_settings: MockBackendSettings::default(),
+            ..Default::default()
+        })
+        .unwrap();
+        Mixer::new(&mut manager, settings).unwrap()
+    }
+
+    fn playing_voice() -> Arc<Shared> {
+        Arc::new(Shared {
+            wait_status: AtomicI32::new(AudioWaitStatus::PLAYING.bits()),
+            position: AtomicU32::new(0),
+            amplitude: AtomicU32::new(0),
+        })
+    }
+
+    #[test]
+    fn settings_from_save() {
+        let mixer_settings = MixerSettings::from(&settings(100, 50, 20, 127, false));
+
+        assert_eq!(mixer_settings.volume(AudioBus::Bgm), Volume(1.0));
+        assert_eq!(mixer_settings.volume(AudioBus::Se), Volume(0.5));
+        assert_eq!(mixer_settings.volume(AudioBus::Voice), Volume(0.2));
+        // the 7-bit fields can store more than 100%
+        assert_eq!(mixer_settings.volume(AudioBus::System), Volume(1.0));
+        assert!(!mixer_settings.voice_focus);
+    }
+
+    #[test]
+    fn separate_bus_tracks() {
+        let mut mixer = mixer(MixerSettings::default());
+
+        let track_ids = AudioBus::ALL.map(|bus| mixer.track_id(bus));
+        for (i, a) in track_ids.iter().enumerate() {
+            for b in &track_ids[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+
+        let new_settings = MixerSettings {
+            bgm_volume: Volume(0.25),
+            ..MixerSettings::default()
+        };
+        mixer.set_settings(new_settings);
+        assert_eq!(mixer.settings(), new_settings);
+        assert_eq!(mixer.track_id(AudioBus::Bgm), track_ids[0]);
+    }
+
+    #[test]
+    fn voice_focus_ducks_the_bgm() {
+        let mut mixer = mixer(MixerSettings {
+            bgm_volume: Volume(0.5),
+            ..MixerSettings::default()
+        });
+        mixer.update();
+        assert_eq!(mixer.effective_volume(AudioBus::Bgm), 0.5);
+
+        let voice = playing_voice();
+        mixer.add_voice(voice.clone());
+        mixer.update();
+        assert_eq!(
+            mixer.effective_volume(AudioBus::Bgm),
+            0.5 * VOICE_FOCUS_BGM_VOLUME
+        );
+        // the other buses are not affected
+        assert_eq!(mixer.effective_volume(AudioBus::Se), 1.0);
+        assert_eq!(mixer.effective_volume(AudioBus::Voice), 1.0);
+
+        // the BGM comes back when the voice stops
+        voice
+            .wait_status
+            .store(AudioWaitStatus::STOPPED.bits(), Ordering::SeqCst);
+        mixer.update();
+        assert_eq!(mixer.effective_volume(AudioBus::Bgm), 0.5);
+        assert!(mixer.voices.is_empty());
+    }
+
+    #[test]
+    fn no_ducking_without_voice_focus() {
+        let mut mixer = mixer(MixerSettings {
+            voice_focus: false,
+            ..MixerSettings::default()
+        });
+        mixer.add_voice(playing_voice());
+        mixer.update();
+        assert_eq!(mixer.effective_volume(AudioBus::Bgm), 1.0);
+
+        // enabling it while the voice plays ducks the BGM on the next update
+        mixer.set_settings(MixerSettings::default());
+        mixer.update();
+        assert_eq!(
+            mixer.effective_volume(AudioBus::Bgm),
+            VOICE_FOCUS_BGM_VOLUME
+        );
+    }
+}
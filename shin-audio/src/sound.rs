@@ -24,7 +24,7 @@ pub enum Command {
 
 pub(crate) struct Shared {
     pub wait_status: AtomicI32,
-    // TODO: use it to implement BGMSYNC (I don't know which unit it uses)
+    // used to implement BGMSYNC
     // in ms, relative to the start of the sound
     pub position: AtomicU32,
     // used for lip sync
@@ -55,17 +55,20 @@ pub enum PlaybackState {
 
 pub struct SampleProvider<S: AudioFrameSource + Send> {
     source: AudioSource<S>,
-    loop_start: Option<u32>,
     resampler: Resampler,
     fractional_position: f64,
     reached_eof: bool,
 }
 
 impl<S: AudioFrameSource + Send> SampleProvider<S> {
-    fn new(audio: S, loop_start: Option<u32>) -> Self {
+    fn new(audio: S, loop_start: Option<u32>, loop_end: Option<u32>) -> Self {
+        let mut source = AudioSource::new(audio);
+        if let Some(loop_start) = loop_start {
+            source.set_loop(loop_start, loop_end);
+        }
+
         Self {
-            source: AudioSource::new(audio),
-            loop_start,
+            source,
             resampler: Resampler::new(0),
             fractional_position: 0.0,
             reached_eof: false,
@@ -73,19 +76,12 @@ impl<S: AudioFrameSource + Send> SampleProvider<S> {
     }
 
     fn push_frame_to_resampler(&mut self) {
+        // the looping is handled by the source
         let frame = match self.source.read_sample() {
             Some((left, right)) => Frame { left, right },
             None => {
-                if let Some(loop_start) = self.loop_start {
-                    self.source
-                        .samples_seek(loop_start)
-                        .expect("Could not seek to loop start");
-
-                    return self.push_frame_to_resampler();
-                } else {
-                    self.reached_eof = true;
-                    Frame::ZERO
-                }
+                self.reached_eof = true;
+                Frame::ZERO
             }
         };
 
@@ -133,7 +129,11 @@ impl<S: AudioFrameSource + Send> AudioSound<S> {
             volume: Tweener::new(data.settings.volume.0),
             panning: Tweener::new(data.settings.pan.0),
             volume_fade,
-            sample_provider: SampleProvider::new(data.source, data.settings.loop_start),
+            sample_provider: SampleProvider::new(
+                data.source,
+                data.settings.loop_start,
+                data.settings.loop_end,
+            ),
         }
     }
 
@@ -0,0 +1,78 @@
+//! Writing the mixed audio into WAV files.
+
+use anyhow::{Context, Result};
+use hound::{SampleFormat, WavSpec};
+use kira::dsp::Frame;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Writes stereo frames into a 32-bit float WAV file
+pub struct WavSink {
+    writer: hound::WavWriter<BufWriter<File>>,
+}
+
+impl WavSink {
+    pub fn create(path: &Path, sample_rate: u32) -> Result<Self> {
+        let writer = hound::WavWriter::create(
+            path,
+            WavSpec {
+                channels: 2,
+                sample_rate,
+                bits_per_sample: 32,
+                sample_format: SampleFormat::Float,
+            },
+        )
+        .with_context(|| format!("Creating {}", path.display()))?;
+
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, frames: &[Frame]) -> Result<()> {
+        for frame in frames {
+            self.writer.write_sample(frame.left)?;
+            self.writer.write_sample(frame.right)?;
+        }
+        Ok(())
+    }
+
+    /// Write the WAV header with the final length. Dropping the sink does it too, but ignores the errors.
+    pub fn finalize(self) -> Result<()> {
+        self.writer.finalize().context("Finalizing the WAV file")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let path = std::env::temp_dir().join(format!("shin-audio-wav-{}.wav", std::process::id()));
+        let frames = (0..1000)
+            .map(|i| Frame::new((i as f32 / 1000.0).sin(), -(i as f32) / 1000.0))
+            .collect::<Vec<_>>();
+
+        let mut sink = WavSink::create(&path, 44100).unwrap();
+        sink.write(&frames[..300]).unwrap();
+        sink.write(&frames[300..]).unwrap();
+        sink.finalize().unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.sample_rate, 44100);
+        assert_eq!(spec.sample_format, SampleFormat::Float);
+        let samples = reader
+            .samples::<f32>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let read_frames = samples
+            .chunks_exact(2)
+            .map(|s| Frame::new(s[0], s[1]))
+            .collect::<Vec<_>>();
+        assert_eq!(read_frames, frames);
+    }
+}
@@ -0,0 +1,71 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use shin_core::format::lz77::{compress, decompress, CompressionLevel};
+
+/// Something resembling a glyph or a picture block: runs, repeated rows & a bit of noise
+fn sample_data(len: usize) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut data = Vec::with_capacity(len);
+    while data.len() < len {
+        match rng.gen_range(0..4) {
+            0 => data.push(rng.gen()),
+            1 => data.extend(std::iter::repeat(rng.gen::<u8>()).take(rng.gen_range(1..64))),
+            _ if data.len() > 256 => {
+                let start = data.len() - rng.gen_range(1..256);
+                for i in 0..rng.gen_range(3..64) {
+                    data.push(data[start + i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    data.truncate(len);
+    data
+}
+
+fn lz77(c: &mut Criterion) {
+    let data = sample_data(256 * 1024);
+
+    let mut group = c.benchmark_group("lz77");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+
+    for level in [CompressionLevel::Fast, CompressionLevel::Best] {
+        let mut compressed = Vec::new();
+        compress::<10>(&data, &mut compressed, level);
+        println!(
+            "{:?}: {} -> {} bytes ({:.1}%)",
+            level,
+            data.len(),
+            compressed.len(),
+            compressed.len() as f64 / data.len() as f64 * 100.0
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("compress", format!("{:?}", level)),
+            &data,
+            |b, data| {
+                b.iter(|| {
+                    let mut output = Vec::new();
+                    compress::<10>(data, &mut output, level);
+                    output
+                })
+            },
+        );
+    }
+
+    let mut compressed = Vec::new();
+    compress::<10>(&data, &mut compressed, CompressionLevel::Best);
+    group.bench_with_input("decompress", &compressed, |b, compressed| {
+        b.iter(|| {
+            let mut output = Vec::new();
+            decompress::<10>(compressed, &mut output);
+            output
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, lz77);
+criterion_main!(benches);
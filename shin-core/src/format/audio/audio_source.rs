@@ -1,4 +1,5 @@
 use anyhow::Result;
+use tracing::error;
 
 type Sample = (f32, f32);
 
@@ -111,6 +112,10 @@ pub struct AudioSource<S: AudioFrameSource> {
     source: S,
     reader: AudioBufferReader,
     skip_left: u32,
+    /// Position of the next sample to be returned by [AudioSource::read_sample], without the pre-skip
+    position: u32,
+    /// Loop start & end, the end being `None` means looping at the end of the stream
+    loop_points: Option<(u32, Option<u32>)>,
 }
 
 impl<S: AudioFrameSource> AudioSource<S> {
@@ -122,9 +127,19 @@ impl<S: AudioFrameSource> AudioSource<S> {
             source,
             reader: AudioBufferReader::new(AudioBuffer::with_capacity(buffer_capacity)),
             skip_left: pre_skip,
+            position: 0,
+            loop_points: None,
         }
     }
 
+    /// Make the source jump back to `loop_start` after reaching `loop_end`, which is exclusive
+    ///
+    /// If `loop_end` is `None` (or is not after the `loop_start`), the loop happens at the end of the stream.
+    pub fn set_loop(&mut self, loop_start: u32, loop_end: Option<u32>) {
+        let loop_end = loop_end.filter(|&end| end > loop_start);
+        self.loop_points = Some((loop_start, loop_end));
+    }
+
     pub fn sample_rate(&self) -> u32 {
         self.source.sample_rate()
     }
@@ -144,10 +159,47 @@ impl<S: AudioFrameSource> AudioSource<S> {
         // seek to the raw position, minus the pre-roll, but include the pre-roll in the skip
         let skip = self.source.samples_seek(raw_position - pre_roll)? + pre_roll;
         self.skip_left = self.reader.skip_samples(skip);
+        self.position = sample_position;
         Ok(())
     }
 
+    fn jump_to_loop_start(&mut self) -> Option<()> {
+        let (loop_start, _) = self.loop_points?;
+        if let Err(e) = self.samples_seek(loop_start) {
+            error!("Could not seek to the loop start, stopping: {:?}", e);
+            self.loop_points = None;
+            return None;
+        }
+        Some(())
+    }
+
     pub fn read_sample(&mut self) -> Option<Sample> {
+        if let Some((_, Some(loop_end))) = self.loop_points {
+            if self.position >= loop_end {
+                self.jump_to_loop_start()?;
+            }
+        }
+
+        match self.read_raw_sample() {
+            Some(sample) => {
+                self.position += 1;
+                Some(sample)
+            }
+            None => match self.loop_points {
+                // the stream ended before the loop end (or there is no loop end)
+                // (checking the position to not spin forever if there is nothing to play after the loop start)
+                Some((loop_start, _)) if self.position > loop_start => {
+                    self.jump_to_loop_start()?;
+                    let sample = self.read_raw_sample()?;
+                    self.position += 1;
+                    Some(sample)
+                }
+                _ => None,
+            },
+        }
+    }
+
+    fn read_raw_sample(&mut self) -> Option<Sample> {
         if self.skip_left > 0 {
             self.skip_left = self.reader.skip_samples(self.skip_left);
         }
@@ -159,7 +211,7 @@ impl<S: AudioFrameSource> AudioSource<S> {
                 if !self.source.read_frame(self.reader.inner_mut()) {
                     return None;
                 }
-                self.read_sample()
+                self.read_raw_sample()
             }
         }
     }
@@ -179,3 +231,108 @@ impl<S: AudioFrameSource> AudioSource<S> {
         &self.source
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Produces samples equal to their raw index (including the pre-skip), in frames of 7 samples
+    struct CountingSource {
+        position: u32,
+        len: u32,
+    }
+
+    const FRAME_SAMPLES: u32 = 7;
+    const PRE_SKIP: u32 = 5;
+
+    impl AudioFrameSource for CountingSource {
+        fn max_frame_size(&self) -> usize {
+            FRAME_SAMPLES as usize
+        }
+        fn sample_rate(&self) -> u32 {
+            48000
+        }
+        fn pre_skip(&self) -> u32 {
+            PRE_SKIP
+        }
+        fn pre_roll(&self) -> u32 {
+            3
+        }
+        fn read_frame(&mut self, destination: &mut AudioBuffer) -> bool {
+            if self.position >= self.len {
+                return false;
+            }
+            let end = (self.position + FRAME_SAMPLES).min(self.len);
+            for i in self.position..end {
+                destination.push((i as f32, -(i as f32)));
+            }
+            self.position = end;
+            true
+        }
+        fn samples_seek(&mut self, sample_position: u32) -> Result<u32> {
+            self.position = sample_position / FRAME_SAMPLES * FRAME_SAMPLES;
+            Ok(sample_position % FRAME_SAMPLES)
+        }
+        fn current_sample_position(&self) -> u32 {
+            self.position
+        }
+    }
+
+    fn read_positions(source: &mut AudioSource<CountingSource>, count: usize) -> Vec<u32> {
+        (0..count)
+            .map_while(|_| source.read_sample())
+            .map(|(left, right)| {
+                assert_eq!(left, -right);
+                left as u32 - PRE_SKIP
+            })
+            .collect()
+    }
+
+    #[test]
+    fn no_loop() {
+        let mut source = AudioSource::new(CountingSource {
+            position: 0,
+            len: 40 + PRE_SKIP,
+        });
+        assert_eq!(
+            read_positions(&mut source, 100),
+            (0..40).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn loop_at_loop_end() {
+        let mut source = AudioSource::new(CountingSource {
+            position: 0,
+            len: 100 + PRE_SKIP,
+        });
+        source.set_loop(11, Some(30));
+
+        let expected = (0..30).chain(11..30).chain(11..30).collect::<Vec<_>>();
+        assert_eq!(read_positions(&mut source, expected.len()), expected);
+    }
+
+    #[test]
+    fn loop_at_end_of_stream() {
+        let mut source = AudioSource::new(CountingSource {
+            position: 0,
+            len: 25 + PRE_SKIP,
+        });
+        source.set_loop(3, None);
+
+        let expected = (0..25).chain(3..25).chain(3..25).collect::<Vec<_>>();
+        assert_eq!(read_positions(&mut source, expected.len()), expected);
+    }
+
+    #[test]
+    fn loop_end_after_end_of_stream() {
+        let mut source = AudioSource::new(CountingSource {
+            position: 0,
+            len: 25 + PRE_SKIP,
+        });
+        source.set_loop(0, Some(1000));
+
+        let expected = (0..25).chain(0..25).collect::<Vec<_>>();
+        assert_eq!(read_positions(&mut source, expected.len()), expected);
+    }
+}
@@ -0,0 +1,177 @@
+//! Encoding of NXA files.
+//!
+//! The game expects all the opus frames to have the same size in bytes (it seeks by multiplying the frame number by the frame size),
+//! so the encoder is run in CBR mode and the packets that come out smaller are padded.
+
+use super::{AudioInfo, NxaHeader};
+use anyhow::{bail, Context, Result};
+use binrw::BinWrite;
+use opus::{Application, Bitrate, Channels};
+use std::io::Cursor;
+
+#[derive(Debug, Clone)]
+pub struct AudioEncoderSettings {
+    /// Target bitrate, in bits per second
+    pub bitrate: u32,
+    /// Amount of samples in one frame, must correspond to a duration supported by opus (2.5, 5, 10, 20, 40 or 60 ms)
+    pub frame_samples: u16,
+    /// Where to start playing after looping in samples
+    pub loop_start: u32,
+    /// Where to jump back to the loop start in samples, `None` means the end of the audio
+    pub loop_end: Option<u32>,
+}
+
+impl Default for AudioEncoderSettings {
+    fn default() -> Self {
+        Self {
+            bitrate: 128000,
+            // 20 ms at 48 kHz
+            frame_samples: 960,
+            loop_start: 0,
+            loop_end: None,
+        }
+    }
+}
+
+/// Encode interleaved PCM samples into a NXA file
+pub fn encode_audio(
+    samples: &[f32],
+    channel_count: u16,
+    sample_rate: u32,
+    settings: &AudioEncoderSettings,
+) -> Result<Vec<u8>> {
+    let channels = match channel_count {
+        1 => Channels::Mono,
+        2 => Channels::Stereo,
+        _ => bail!("Unsupported channel count: {}", channel_count),
+    };
+    if samples.len() % channel_count as usize != 0 {
+        bail!("The number of samples is not a multiple of the channel count");
+    }
+    let num_samples = (samples.len() / channel_count as usize) as u32;
+    let loop_end = settings.loop_end.unwrap_or(num_samples);
+    if loop_end > num_samples {
+        bail!(
+            "Loop end ({}) is after the end of the audio ({})",
+            loop_end,
+            num_samples
+        );
+    }
+    if settings.loop_start > loop_end {
+        bail!(
+            "Loop start ({}) is after the loop end ({})",
+            settings.loop_start,
+            loop_end
+        );
+    }
+
+    let frame_samples = settings.frame_samples as usize;
+    let frame_size = settings.bitrate as u64 * frame_samples as u64 / (8 * sample_rate as u64);
+    let frame_size: u16 = frame_size
+        .try_into()
+        .context("Frame size does not fit into 16 bits, use a lower bitrate")?;
+
+    let mut encoder = opus::Encoder::new(sample_rate, channels, Application::Audio)
+        .context("Creating the opus encoder")?;
+    encoder.set_vbr(false)?;
+    encoder.set_bitrate(Bitrate::Bits(settings.bitrate as i32))?;
+    // the decoder output is delayed by the encoder lookahead, so it has to be skipped
+    let pre_skip: u16 = encoder.get_lookahead()?.try_into()?;
+
+    // pad with silence to fit the lookahead and to fill the last frame
+    let total_samples = num_samples as usize + pre_skip as usize;
+    let frame_count = (total_samples + frame_samples - 1) / frame_samples;
+    let mut padded = samples.to_vec();
+    padded.resize(frame_count * frame_samples * channel_count as usize, 0.0);
+
+    let mut data = Vec::with_capacity(frame_count * frame_size as usize);
+    let mut packet = vec![0u8; frame_size as usize];
+    for (i, frame) in padded
+        .chunks_exact(frame_samples * channel_count as usize)
+        .enumerate()
+    {
+        let len = encoder
+            .encode_float(frame, &mut packet)
+            .with_context(|| format!("Encoding frame {}", i))?;
+        if len < packet.len() {
+            opus::packet::pad(&mut packet, len).with_context(|| format!("Padding frame {}", i))?;
+        }
+        data.extend_from_slice(&packet);
+    }
+
+    let mut header = NxaHeader {
+        version: 2,
+        file_size: 0,
+        info: AudioInfo {
+            sample_rate,
+            channel_count,
+            frame_size,
+            frame_samples: settings.frame_samples,
+            pre_skip,
+            num_samples,
+            loop_start: settings.loop_start,
+            loop_end,
+        },
+    };
+
+    // the header size does not depend on its contents, so write it once to learn the size
+    let mut output = Cursor::new(Vec::new());
+    header.write(&mut output)?;
+    header.file_size = (output.get_ref().len() + data.len()) as u32;
+    output.set_position(0);
+    header.write(&mut output)?;
+
+    let mut output = output.into_inner();
+    output.extend_from_slice(&data);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::audio::{read_audio, AudioSource};
+
+    #[test]
+    fn encode_and_decode() {
+        let sample_rate = 48000;
+        let num_samples = 48000 + 123;
+        let samples = (0..num_samples)
+            .flat_map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                let value = (t * 440.0 * std::f32::consts::TAU).sin() * 0.5;
+                [value, value]
+            })
+            .collect::<Vec<_>>();
+
+        let settings = AudioEncoderSettings {
+            loop_start: 1000,
+            ..Default::default()
+        };
+        let encoded = encode_audio(&samples, 2, sample_rate, &settings).unwrap();
+
+        let audio = read_audio(&encoded).unwrap();
+        let info = audio.info().clone();
+        assert_eq!(info.sample_rate, sample_rate);
+        assert_eq!(info.channel_count, 2);
+        assert_eq!(info.frame_samples, 960);
+        assert_eq!(info.frame_size, 320);
+        assert_eq!(info.num_samples, num_samples);
+        assert_eq!(info.loop_start, 1000);
+        assert_eq!(info.loop_end, num_samples);
+
+        let mut source = AudioSource::new(audio.decode().unwrap());
+        let mut signal_energy = 0.0;
+        let mut error_energy = 0.0;
+        for i in 0..num_samples as usize {
+            let (left, _) = source.read_sample().unwrap();
+            let expected = samples[i * 2];
+            signal_energy += expected * expected;
+            error_energy += (left - expected) * (left - expected);
+        }
+
+        // the pre-skip should align the decoded audio with the input, so the error is small
+        let snr = 10.0 * (signal_energy / error_energy).log10();
+        assert!(snr > 20.0, "SNR is too low: {} dB", snr);
+    }
+}
@@ -1,8 +1,12 @@
 //! Support for NXA format, storing opus audio. (The format seems to be specific for Nintendo Switch?)
 
 mod audio_source;
+mod encoder;
+mod resample;
 
 pub use audio_source::{AudioBuffer, AudioFrameSource, AudioSource};
+pub use encoder::{encode_audio, AudioEncoderSettings};
+pub use resample::resample;
 
 use anyhow::{bail, Result};
 use binrw::{BinRead, BinWrite};
@@ -38,7 +42,7 @@ pub struct AudioInfo {
     pub num_samples: u32,
     /// Where to start playing after looping in samples.
     pub loop_start: u32,
-    /// Where to loop from in samples (usually happens at the end of the file).
+    /// Where to jump back to the loop start in samples (usually at the end of the file).
     pub loop_end: u32,
 }
 
@@ -52,6 +56,11 @@ impl AudioFile {
         &self.info
     }
 
+    /// Size of the encoded data, in bytes (the audio is decoded on the fly while playing)
+    pub fn size_in_bytes(&self) -> usize {
+        self.data.len()
+    }
+
     pub fn decode(self) -> Result<AudioDecoder<Self>> {
         AudioDecoder::new(self)
     }
@@ -195,8 +204,13 @@ pub fn read_audio(data: &[u8]) -> Result<AudioFile> {
     let header = NxaHeader::read_le(&mut cur)?;
 
     assert_eq!(header.file_size, data.len() as u32);
-    // how are we supposed to loop when the loop end is not in the end of the file?
-    assert_eq!(header.info.loop_end, header.info.num_samples);
+    if header.info.loop_end > header.info.num_samples {
+        bail!(
+            "Loop end {} is after the end of the file ({} samples)",
+            header.info.loop_end,
+            header.info.num_samples
+        );
+    }
 
     let mut data = Vec::new();
     cur.read_to_end(&mut data)?;
@@ -206,3 +220,76 @@ pub fn read_audio(data: &[u8]) -> Result<AudioFile> {
         data,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seamless_loop() {
+        const SAMPLE_RATE: u32 = 48000;
+        // 480 Hz, so that the period is exactly 100 samples
+        const PERIOD: u32 = 100;
+        const LOOP_START: u32 = 96 * PERIOD;
+        const LOOP_END: u32 = 480 * PERIOD;
+
+        let sine = |i: u32| (i as f32 / PERIOD as f32 * std::f32::consts::TAU).sin() * 0.5;
+
+        // put silence after the loop end, it should never be heard
+        let samples = (0..LOOP_END + 12000)
+            .flat_map(|i| {
+                let value = if i < LOOP_END { sine(i) } else { 0.0 };
+                [value, value]
+            })
+            .collect::<Vec<_>>();
+
+        let settings = AudioEncoderSettings {
+            loop_start: LOOP_START,
+            loop_end: Some(LOOP_END),
+            ..Default::default()
+        };
+        let encoded = encode_audio(&samples, 2, SAMPLE_RATE, &settings).unwrap();
+        let audio = read_audio(&encoded).unwrap();
+        assert_eq!(audio.info().loop_end, LOOP_END);
+
+        let mut source = AudioSource::new(audio.decode().unwrap());
+        source.set_loop(LOOP_START, Some(LOOP_END));
+
+        // the loop start and end are in the same phase of the sine, so the output should be a continuous sine
+        // check around the two seams
+        let length = LOOP_END + 2 * (LOOP_END - LOOP_START);
+        let output = (0..length)
+            .map(|_| source.read_sample().unwrap().0)
+            .collect::<Vec<_>>();
+
+        for seam in [LOOP_END, 2 * LOOP_END - LOOP_START] {
+            let window = seam - 2000..seam + 2000;
+
+            let mut signal_energy = 0.0;
+            let mut error_energy = 0.0;
+            let mut max_error = 0.0f32;
+            for i in window {
+                let expected = sine(i);
+                let error = output[i as usize] - expected;
+                signal_energy += expected * expected;
+                error_energy += error * error;
+                max_error = max_error.max(error.abs());
+            }
+
+            let snr = 10.0 * (signal_energy / error_energy).log10();
+            assert!(
+                snr > 20.0,
+                "SNR around the seam at {} is too low: {} dB",
+                seam,
+                snr
+            );
+            // a gap or a discontinuity would show up as a large spike
+            assert!(
+                max_error < 0.2,
+                "Error around the seam at {} is too large: {}",
+                seam,
+                max_error
+            );
+        }
+    }
+}
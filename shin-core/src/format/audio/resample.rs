@@ -0,0 +1,127 @@
+//! Resampling, used to convert audio to a sample rate supported by opus before encoding.
+
+/// Resample interleaved samples with a windowed sinc filter
+///
+/// The output has `samples.len() * to_rate / from_rate` frames (rounded down).
+pub fn resample(samples: &[f32], channel_count: u16, from_rate: u32, to_rate: u32) -> Vec<f32> {
+    const HALF_TAPS: isize = 32;
+
+    let channels = channel_count as usize;
+    let input_len = samples.len() / channels;
+    let output_len = (input_len as u64 * to_rate as u64 / from_rate as u64) as usize;
+    let ratio = from_rate as f64 / to_rate as f64;
+    // when downsampling, the cutoff has to be lowered to avoid aliasing
+    let cutoff = (1.0 / ratio).min(1.0);
+
+    let kernel = |x: f64| -> f64 {
+        if x.abs() >= HALF_TAPS as f64 {
+            return 0.0;
+        }
+        let sinc = if x == 0.0 {
+            1.0
+        } else {
+            let x = std::f64::consts::PI * x * cutoff;
+            x.sin() / x
+        };
+        // hann window
+        let window = 0.5 + 0.5 * (std::f64::consts::PI * x / HALF_TAPS as f64).cos();
+        sinc * window * cutoff
+    };
+
+    let mut output = vec![0.0; output_len * channels];
+    for i in 0..output_len {
+        let position = i as f64 * ratio;
+        let center = position.floor() as isize;
+        for j in (center - HALF_TAPS + 1)..=(center + HALF_TAPS) {
+            if j < 0 || j as usize >= input_len {
+                continue;
+            }
+            let weight = kernel(position - j as f64) as f32;
+            for channel in 0..channels {
+                output[i * channels + channel] += samples[j as usize * channels + channel] * weight;
+            }
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f32, sample_rate: u32, num_samples: usize) -> Vec<f32> {
+        (0..num_samples)
+            .map(|i| {
+                (i as f32 / sample_rate as f32 * frequency * std::f32::consts::TAU).sin() * 0.5
+            })
+            .collect()
+    }
+
+    /// Compare the signals ignoring the edges, where the filter doesn't have enough input
+    fn snr(expected: &[f32], actual: &[f32]) -> f32 {
+        let mut signal_energy = 0.0;
+        let mut error_energy = 0.0;
+        for (expected, actual) in expected
+            .iter()
+            .zip(actual)
+            .skip(100)
+            .take(expected.len() - 200)
+        {
+            signal_energy += expected * expected;
+            error_energy += (expected - actual) * (expected - actual);
+        }
+        10.0 * (signal_energy / error_energy).log10()
+    }
+
+    #[test]
+    fn upsample() {
+        let input = sine(440.0, 44100, 44100);
+        let output = resample(&input, 1, 44100, 48000);
+        assert_eq!(output.len(), 48000);
+
+        let snr = snr(&sine(440.0, 48000, 48000), &output);
+        assert!(snr > 40.0, "SNR is too low: {} dB", snr);
+    }
+
+    #[test]
+    fn downsample_stereo() {
+        // the channels have different frequencies to catch mixing them up
+        let left = sine(440.0, 96000, 9600);
+        let right = sine(1000.0, 96000, 9600);
+        let input = left
+            .iter()
+            .zip(&right)
+            .flat_map(|(&l, &r)| [l, r])
+            .collect::<Vec<_>>();
+
+        let output = resample(&input, 2, 96000, 48000);
+        assert_eq!(output.len(), 4800 * 2);
+
+        let left = output.iter().step_by(2).copied().collect::<Vec<_>>();
+        let right = output
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .copied()
+            .collect::<Vec<_>>();
+        let left_snr = snr(&sine(440.0, 48000, 4800), &left);
+        let right_snr = snr(&sine(1000.0, 48000, 4800), &right);
+        assert!(left_snr > 40.0, "SNR is too low: {} dB", left_snr);
+        assert!(right_snr > 40.0, "SNR is too low: {} dB", right_snr);
+    }
+
+    #[test]
+    fn downsample_removes_aliasing() {
+        // above the output nyquist frequency, should be filtered out instead of folding back
+        let input = sine(30000.0, 96000, 9600);
+        let output = resample(&input, 1, 96000, 48000);
+
+        let energy = output[100..output.len() - 100]
+            .iter()
+            .map(|s| s * s)
+            .sum::<f32>()
+            / (output.len() - 200) as f32;
+        assert!(energy < 1e-4, "too much energy left: {}", energy);
+    }
+}
@@ -1,19 +1,21 @@
-//! Support for BUP files, storing the character bustup sprites.
+//! Support for decoding and encoding BUP files, storing the character bustup sprites.
 
-use crate::format::picture::{read_picture_chunk, PictureChunk};
-use anyhow::{bail, Result};
+use crate::format::picture::{read_picture_chunk, write_picture_chunk, PictureChunk};
+use anyhow::{bail, Context, Result};
 use binrw::{BinRead, BinWrite};
 use bitvec::bitbox;
-use image::RgbaImage;
+use image::{GenericImageView, RgbaImage};
 use rayon::prelude::*;
 use std::collections::HashMap;
+use std::io::{Cursor, Seek, Write};
+use std::marker::PhantomData;
 use std::ops::DerefMut;
 use std::sync::Mutex;
 
-use crate::format::text::ZeroString;
+use crate::format::text::{encode_sjis_string, SJisString, ZeroString};
 
 #[derive(BinRead, BinWrite, Debug)]
-#[br(little, magic = b"BUP4")]
+#[brw(little, magic = b"BUP4")]
 #[br(assert(version == 4))]
 #[bw(assert(*version == 4))]
 struct BustupHeader {
@@ -88,6 +90,44 @@ pub struct BustupExpression {
     pub mouth_chunks: Vec<PictureChunk>,
 }
 
+impl Bustup {
+    /// Flatten the base image with the face and the mouth of an expression, the way the game displays them
+    ///
+    /// The mouth index is ignored if the expression has no mouths.
+    pub fn compose(&self, expression: &str, mouth_index: usize) -> Result<RgbaImage> {
+        let expression = self
+            .expressions
+            .get(expression)
+            .with_context(|| format!("No expression {:?} in bustup", expression))?;
+
+        let mouth_chunk = if expression.mouth_chunks.is_empty() {
+            None
+        } else {
+            Some(expression.mouth_chunks.get(mouth_index).with_context(|| {
+                format!(
+                    "Mouth index {} is out of range, the expression has {} mouths",
+                    mouth_index,
+                    expression.mouth_chunks.len()
+                )
+            })?)
+        };
+
+        let mut image = self.base_image.clone();
+        for chunk in std::iter::once(&expression.face_chunk).chain(mouth_chunk) {
+            if !chunk.is_empty() {
+                image::imageops::overlay(
+                    &mut image,
+                    &chunk.data,
+                    chunk.offset_x as i64,
+                    chunk.offset_y as i64,
+                );
+            }
+        }
+
+        Ok(image)
+    }
+}
+
 fn cleanup_unused_areas(chunk: &mut PictureChunk) {
     let mut bitbox = bitbox![0u32; chunk.data.width() as usize * chunk.data.height() as usize];
     let coord_to_index = |x: u32, y: u32| (y * chunk.data.width() + x) as usize;
@@ -200,3 +240,244 @@ pub fn read_bustup(source: &[u8]) -> Result<Bustup> {
             .collect(),
     })
 }
+
+/// The base image is split into square chunks of this size, skipping the fully transparent ones
+const BASE_CHUNK_SIZE: u32 = 256;
+
+/// Builds a BUP file from the base image and the expression layers
+pub struct BustupWriter {
+    origin: (u16, u16),
+    use_dict_encoding: bool,
+    base_image: RgbaImage,
+    expressions: Vec<(String, PictureChunk, Vec<PictureChunk>)>,
+}
+
+impl BustupWriter {
+    /// Dictionary encoding stores each chunk with a 256-color palette (and a separate alpha channel),
+    /// chunks with more colors are quantized, so it is lossy in general
+    pub fn new(origin: (u16, u16), base_image: RgbaImage, use_dict_encoding: bool) -> Self {
+        Self {
+            origin,
+            use_dict_encoding,
+            base_image,
+            expressions: Vec::new(),
+        }
+    }
+
+    /// Add an expression, the chunks are positioned relative to the base image
+    ///
+    /// Use [PictureChunk::from_image] to make chunks from layers and [PictureChunk::empty] for an expression without a face.
+    pub fn add_expression(
+        &mut self,
+        name: String,
+        face_chunk: PictureChunk,
+        mouth_chunks: Vec<PictureChunk>,
+    ) {
+        self.expressions.push((name, face_chunk, mouth_chunks));
+    }
+
+    fn base_chunks(&self) -> Vec<PictureChunk> {
+        let (width, height) = self.base_image.dimensions();
+        let mut chunks = Vec::new();
+        for y in (0..height).step_by(BASE_CHUNK_SIZE as usize) {
+            for x in (0..width).step_by(BASE_CHUNK_SIZE as usize) {
+                let tile = self
+                    .base_image
+                    .view(
+                        x,
+                        y,
+                        BASE_CHUNK_SIZE.min(width - x),
+                        BASE_CHUNK_SIZE.min(height - y),
+                    )
+                    .to_image();
+                let chunk = PictureChunk::from_image(x, y, &tile);
+                if !chunk.is_empty() {
+                    chunks.push(chunk);
+                }
+            }
+        }
+        chunks
+    }
+
+    pub fn write<W: Write + Seek>(&self, writer: &mut W) -> Result<()> {
+        let viewport_width: u16 = self
+            .base_image
+            .width()
+            .try_into()
+            .context("Base image is too wide")?;
+        let viewport_height: u16 = self
+            .base_image
+            .height()
+            .try_into()
+            .context("Base image is too tall")?;
+
+        let base_chunks = self.base_chunks();
+        let additional_chunks = self
+            .expressions
+            .iter()
+            .flat_map(|(_, face, mouths)| std::iter::once(face).chain(mouths.iter()));
+
+        // chunks are independent, so they can be compressed in parallel
+        let encoded_chunks = base_chunks
+            .iter()
+            .chain(additional_chunks)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|chunk| write_picture_chunk(chunk, self.use_dict_encoding))
+            .collect::<Result<Vec<_>>>()?;
+
+        // chunks are identified by their index in `encoded_chunks`, offsets are filled in when the header size is known
+        let chunk_desc = |chunk_id: usize| BustupChunkDesc {
+            offset: 0,
+            size: encoded_chunks[chunk_id].len() as u32,
+            chunk_id: chunk_id as u32,
+        };
+
+        let mut next_chunk_id = base_chunks.len();
+        let mut expressions = Vec::with_capacity(self.expressions.len());
+        for (name, _, mouths) in &self.expressions {
+            let name_size = encode_sjis_string(name)
+                .with_context(|| format!("Encoding expression name {:?}", name))?
+                .len()
+                + 1;
+
+            let face = chunk_desc(next_chunk_id);
+            next_chunk_id += 1;
+            let mouth_chunks = (next_chunk_id..next_chunk_id + mouths.len())
+                .map(chunk_desc)
+                .collect::<Vec<_>>();
+            next_chunk_id += mouths.len();
+
+            expressions.push(BustupExpressionDesc {
+                // NOTE: not used when reading, assuming it's the size of the whole entry
+                header_length: (32 + ((name_size + 3) & !3) + 12 * mouths.len()) as u32,
+                f_4: 0,
+                f_8: 0,
+                f_c: 0,
+                face,
+                mount_chunk_count: mouths.len() as u32,
+                expression_name: SJisString(name.clone(), PhantomData),
+                mouth_chunks,
+            });
+        }
+
+        let mut header = BustupHeader {
+            version: 4,
+            file_size: 0,
+            origin_x: self.origin.0,
+            origin_y: self.origin.1,
+            viewport_width,
+            viewport_height,
+            // TODO: the meaning of these is unknown
+            f_14: 0,
+            f_18: 0,
+            f_1c: 0,
+            f_20: 0,
+            f_24: 0,
+            f_28: 0,
+            f_2c: 0,
+            f_30: 0,
+            base_chunks_count: base_chunks.len() as u32,
+            base_chunks: (0..base_chunks.len()).map(chunk_desc).collect(),
+            expression_count: expressions.len() as u32,
+            expressions,
+        };
+
+        // the header size does not depend on the offsets, so write it once to learn the size
+        let mut header_buffer = Cursor::new(Vec::new());
+        header.write(&mut header_buffer)?;
+        let header_size = header_buffer.get_ref().len();
+
+        let mut offsets = Vec::with_capacity(encoded_chunks.len());
+        let mut offset = (header_size + 0xf) & !0xf;
+        for chunk in &encoded_chunks {
+            offsets.push(offset as u32);
+            offset = (offset + chunk.len() + 0xf) & !0xf;
+        }
+        header.file_size = offset as u32;
+
+        let descs = header.base_chunks.iter_mut().chain(
+            header
+                .expressions
+                .iter_mut()
+                .flat_map(|e| std::iter::once(&mut e.face).chain(e.mouth_chunks.iter_mut())),
+        );
+        for desc in descs {
+            desc.offset = offsets[desc.chunk_id as usize];
+        }
+
+        let mut output = Cursor::new(Vec::with_capacity(offset));
+        header.write(&mut output)?;
+        let mut output = output.into_inner();
+        for (chunk, &chunk_offset) in encoded_chunks.iter().zip(&offsets) {
+            output.resize(chunk_offset as usize, 0);
+            output.extend_from_slice(chunk);
+        }
+        output.resize(offset, 0);
+
+        writer.write_all(&output)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(width: u32, height: u32, color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, image::Rgba(color))
+    }
+
+    #[test]
+    fn write_and_compose() {
+        let mut base = filled(300, 280, [10, 20, 30, 255]);
+        // leave a transparent area to check that it survives the trimming
+        image::imageops::replace(&mut base, &filled(40, 40, [0, 0, 0, 0]), 0, 0);
+
+        let face = filled(20, 10, [200, 100, 50, 255]);
+        let mouth = filled(8, 4, [255, 0, 0, 255]);
+
+        let mut writer = BustupWriter::new((150, 270), base.clone(), true);
+        writer.add_expression(
+            "normal".to_string(),
+            PictureChunk::from_image(100, 50, &face),
+            vec![
+                PictureChunk::empty(),
+                PictureChunk::from_image(106, 60, &mouth),
+            ],
+        );
+        writer.add_expression("無表情".to_string(), PictureChunk::empty(), vec![]);
+
+        let mut output = Cursor::new(Vec::new());
+        writer.write(&mut output).unwrap();
+        let bustup = read_bustup(output.get_ref()).unwrap();
+
+        assert_eq!(bustup.origin, (150, 270));
+        assert_eq!(bustup.base_image, base);
+        assert_eq!(bustup.compose("無表情", 0).unwrap(), base);
+
+        let mut expected = base.clone();
+        image::imageops::replace(&mut expected, &face, 100, 50);
+        assert_eq!(bustup.compose("normal", 0).unwrap(), expected);
+        image::imageops::replace(&mut expected, &mouth, 106, 60);
+        assert_eq!(bustup.compose("normal", 1).unwrap(), expected);
+
+        assert!(bustup.compose("normal", 2).is_err());
+        assert!(bustup.compose("missing", 0).is_err());
+    }
+
+    #[test]
+    fn write_lossless() {
+        // more colors than a dictionary can hold
+        let base = RgbaImage::from_fn(300, 20, |x, y| image::Rgba([x as u8, y as u8 * 8, 7, 255]));
+
+        let mut writer = BustupWriter::new((0, 0), base.clone(), false);
+        writer.add_expression("normal".to_string(), PictureChunk::empty(), vec![]);
+
+        let mut output = Cursor::new(Vec::new());
+        writer.write(&mut output).unwrap();
+        let bustup = read_bustup(output.get_ref()).unwrap();
+        assert_eq!(bustup.base_image, base);
+    }
+}
@@ -4,13 +4,13 @@ use crate::format::lz77;
 use anyhow::anyhow;
 use binrw::{BinRead, BinResult, BinWrite, Endian, FilePtr32, VecArgs};
 use glam::{vec2, Vec2};
-use image::GrayImage;
+use image::{imageops, GrayImage};
 use std::borrow::Cow;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::io;
-use std::io::{Read, Seek, SeekFrom};
-use strum::EnumIter;
+use std::io::{Read, Seek, SeekFrom, Write};
+use strum::{EnumIter, IntoEnumIterator};
 
 #[derive(BinRead, BinWrite, Debug)]
 #[brw(little, magic = b"FNT4")]
@@ -239,16 +239,29 @@ impl BinRead for Glyph {
 
 pub trait GlyphTrait: for<'a> BinRead<Args<'a> = ()> {
     fn get_info(&self) -> GlyphInfo;
+    /// Size of the image data stored for the glyph, in bytes
+    fn size_in_bytes(&self) -> usize;
 }
 impl GlyphTrait for Glyph {
     fn get_info(&self) -> GlyphInfo {
         self.info
     }
+    fn size_in_bytes(&self) -> usize {
+        self.mip_level_0.as_raw().len()
+            + self.mip_level_1.as_raw().len()
+            + self.mip_level_2.as_raw().len()
+            + self.mip_level_3.as_raw().len()
+    }
 }
 impl GlyphTrait for LazyGlyph {
     fn get_info(&self) -> GlyphInfo {
         self.info
     }
+    fn size_in_bytes(&self) -> usize {
+        match &self.data {
+            GlyphData::Raw(data) | GlyphData::Compressed(data) => data.len(),
+        }
+    }
 }
 
 pub struct Font<G: GlyphTrait = Glyph> {
@@ -295,6 +308,16 @@ impl<G: GlyphTrait> Font<G> {
     pub fn get_glyphs(&self) -> &HashMap<GlyphId, G> {
         &self.glyphs
     }
+
+    /// Approximate memory taken by the font: the character mapping and the glyph data
+    pub fn size_in_bytes(&self) -> usize {
+        std::mem::size_of_val(&*self.characters)
+            + self
+                .glyphs
+                .values()
+                .map(|g| g.size_in_bytes())
+                .sum::<usize>()
+    }
 }
 
 fn stream_size(reader: &mut impl Seek) -> BinResult<u64> {
@@ -396,3 +419,214 @@ pub fn read_font<R: Read + Seek>(reader: &mut R) -> BinResult<Font> {
 pub fn read_lazy_font<R: Read + Seek>(reader: &mut R) -> BinResult<LazyFont> {
     Font::read_le(reader)
 }
+
+/// A rendered glyph bitmap with its metrics, to be put into a font by the [FontBuilder]
+#[derive(Debug, Clone)]
+pub struct GlyphBitmap {
+    /// Distance between the current position of the pen and left of the glyph bitmap
+    pub bearing_x: i8,
+    /// Distance between the baseline and the top of the glyph bitmap
+    pub bearing_y: i8,
+    /// Amount of horizontal pen movements after drawing the glyph
+    pub advance_width: u8,
+    /// The glyph coverage, without padding
+    pub image: GrayImage,
+}
+
+/// Glyph textures are at least this large, so that all the mip levels are non-empty
+const MIN_GLYPH_TEXTURE_SIZE: u32 = 8;
+/// Texture size has to fit into an u8 and be a power of 2
+const MAX_GLYPH_TEXTURE_SIZE: u32 = 128;
+
+impl GlyphBitmap {
+    fn texture_size(&self) -> (u32, u32) {
+        let (width, height) = self.image.dimensions();
+        (
+            width.next_power_of_two().max(MIN_GLYPH_TEXTURE_SIZE),
+            height.next_power_of_two().max(MIN_GLYPH_TEXTURE_SIZE),
+        )
+    }
+
+    /// Pad the bitmap to the texture size and generate all the mip levels, compressing the result
+    fn encode(&self) -> anyhow::Result<(GlyphHeader, Vec<u8>)> {
+        let (width, height) = self.image.dimensions();
+        let (texture_width, texture_height) = self.texture_size();
+        if texture_width > MAX_GLYPH_TEXTURE_SIZE || texture_height > MAX_GLYPH_TEXTURE_SIZE {
+            anyhow::bail!(
+                "Glyph is too large: {}x{}, the maximum is {}x{}",
+                width,
+                height,
+                MAX_GLYPH_TEXTURE_SIZE,
+                MAX_GLYPH_TEXTURE_SIZE
+            );
+        }
+
+        let mut mip_level = GrayImage::new(texture_width, texture_height);
+        imageops::replace(&mut mip_level, &self.image, 0, 0);
+
+        let mut data = Vec::new();
+        for level in GlyphMipLevel::iter() {
+            if level != GlyphMipLevel::Level0 {
+                mip_level = downscale_mip_level(&mip_level);
+            }
+            data.extend_from_slice(mip_level.as_raw());
+        }
+
+        let mut compressed = Vec::new();
+        lz77::compress::<10>(&data, &mut compressed, lz77::CompressionLevel::Best);
+
+        let header = GlyphHeader {
+            bearing_x: self.bearing_x,
+            bearing_y: self.bearing_y,
+            actual_width: width as u8,
+            actual_height: height as u8,
+            advance_width: self.advance_width,
+            unused: 0,
+            texture_width: texture_width as u8,
+            texture_height: texture_height as u8,
+            compressed_size: compressed
+                .len()
+                .try_into()
+                .map_err(|_| anyhow!("Compressed glyph is too large"))?,
+        };
+
+        Ok((header, compressed))
+    }
+}
+
+/// Box filter, each texel of the next level is an average of 2x2 texels of the previous one
+fn downscale_mip_level(image: &GrayImage) -> GrayImage {
+    GrayImage::from_fn(image.width() / 2, image.height() / 2, |x, y| {
+        let sum = [(0, 0), (1, 0), (0, 1), (1, 1)]
+            .iter()
+            .map(|&(dx, dy)| image.get_pixel(x * 2 + dx, y * 2 + dy)[0] as u32)
+            .sum::<u32>();
+        image::Luma([((sum + 2) / 4) as u8])
+    })
+}
+
+/// Assembles a FNT font from glyph bitmaps
+pub struct FontBuilder {
+    ascent: u16,
+    descent: u16,
+    characters: Box<[GlyphId; 0x10000]>,
+    glyphs: Vec<GlyphBitmap>,
+}
+
+impl FontBuilder {
+    /// Create a font with the fallback glyph, it is used for all the characters that are not mapped explicitly
+    pub fn new(ascent: u16, descent: u16, fallback_glyph: GlyphBitmap) -> Self {
+        Self {
+            ascent,
+            descent,
+            characters: box_array![GlyphId(0); 0x10000],
+            glyphs: vec![fallback_glyph],
+        }
+    }
+
+    pub fn add_glyph(&mut self, glyph: GlyphBitmap) -> GlyphId {
+        self.glyphs.push(glyph);
+        GlyphId(self.glyphs.len() as u32 - 1)
+    }
+
+    pub fn map_character(&mut self, character: u16, glyph_id: GlyphId) {
+        assert!((glyph_id.0 as usize) < self.glyphs.len());
+        self.characters[character as usize] = glyph_id;
+    }
+
+    pub fn write<W: Write + Seek>(&self, writer: &mut W) -> anyhow::Result<()> {
+        // glyphs are independent, so they can be compressed in parallel
+        let encoded_glyphs = {
+            use rayon::prelude::*;
+            self.glyphs
+                .par_iter()
+                .map(|glyph| glyph.encode())
+                .collect::<anyhow::Result<Vec<_>>>()?
+        };
+
+        const HEADER_SIZE: u32 = 16;
+        const GLYPH_HEADER_SIZE: u32 = 10;
+
+        let mut glyph_offsets = Vec::with_capacity(encoded_glyphs.len());
+        let mut offset = HEADER_SIZE + 4 * 0x10000;
+        for (_, data) in &encoded_glyphs {
+            glyph_offsets.push(offset);
+            offset += GLYPH_HEADER_SIZE + data.len() as u32;
+        }
+
+        FontHeader {
+            version: 1,
+            size: offset,
+            ascent: self.ascent,
+            descent: self.descent,
+        }
+        .write(writer)?;
+
+        for glyph_id in self.characters.iter() {
+            glyph_offsets[glyph_id.0 as usize].write_le(writer)?;
+        }
+
+        for (header, data) in &encoded_glyphs {
+            header.write(writer)?;
+            writer.write_all(data)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_glyph(width: u32, height: u32, advance_width: u8) -> GlyphBitmap {
+        GlyphBitmap {
+            bearing_x: 1,
+            bearing_y: height as i8,
+            advance_width,
+            image: GrayImage::from_fn(width, height, |x, y| image::Luma([(x * 16 + y) as u8])),
+        }
+    }
+
+    #[test]
+    fn write_and_read() {
+        let mut builder = FontBuilder::new(20, 4, test_glyph(5, 5, 6));
+        let a = builder.add_glyph(test_glyph(10, 14, 11));
+        let b = builder.add_glyph(test_glyph(13, 17, 14));
+        builder.map_character('a' as u16, a);
+        builder.map_character('b' as u16, b);
+        builder.map_character('c' as u16, b);
+
+        let mut data = io::Cursor::new(Vec::new());
+        builder.write(&mut data).unwrap();
+        data.set_position(0);
+
+        let font = read_lazy_font(&mut data).unwrap();
+        assert_eq!(font.get_ascent(), 20);
+        assert_eq!(font.get_descent(), 4);
+        assert_eq!(font.get_glyphs().len(), 3);
+
+        let glyph = font.get_glyph_for_character('b' as u16);
+        let info = glyph.get_info();
+        assert_eq!(info.actual_size(), (13, 17));
+        assert_eq!(info.texture_size(), (16, 32));
+        assert_eq!(info.advance_width, 14);
+        assert_eq!(info.bearing_y, 17);
+
+        let glyph = glyph.decompress();
+        let image = glyph.get_image(GlyphMipLevel::Level0);
+        assert_eq!(image.dimensions(), (16, 32));
+        assert_eq!(image.get_pixel(3, 5)[0], 3 * 16 + 5);
+        // padding
+        assert_eq!(image.get_pixel(15, 5)[0], 0);
+        assert_eq!(glyph.get_image(GlyphMipLevel::Level3).dimensions(), (2, 4));
+
+        // unmapped characters use the fallback glyph
+        assert_eq!(
+            font.get_glyph_for_character('z' as u16)
+                .get_info()
+                .actual_size(),
+            (5, 5)
+        );
+    }
+}
@@ -10,13 +10,21 @@
 //! The minimum offset is 1, so the actual offset is offset + 1.
 //! The minimum length is 3, so the actual length is length + 3.
 //!
-//! Encoding is (to be) implemented using a sliding window and a greedy algorithm.
-//! Theoretically the efficiency can be improved by using a bit of backtracking,
-//!     but it seems this improves compression ratio only by several percent (not worth the time).
+//! Encoding is implemented using a sliding window with hash chains to find the matches.
+//! There are two modes (see [CompressionLevel]):
+//!     the fast one greedily takes the longest match found in a limited number of candidates,
+//!     the best one finds the longest match at every position and then picks the cheapest sequence of literals & references.
+//! The latter is noticeably slower, but the result is within a couple percent of the files shipped with the game.
 
-use bytes::Buf;
+use bytes::{Buf, BufMut};
 use std::io;
 
+const MIN_MATCH_LENGTH: usize = 3;
+const HASH_BITS: u32 = 15;
+const NO_POSITION: u32 = u32::MAX;
+/// How many candidates to check for each match in the fast mode
+const FAST_MAX_CHAIN: usize = 16;
+
 pub fn decompress<const OFFSET_BITS: u32>(input: &[u8], output: &mut Vec<u8>) {
     let mut input = io::Cursor::new(input);
 
@@ -54,3 +62,285 @@ pub fn decompress<const OFFSET_BITS: u32>(input: &[u8], output: &mut Vec<u8>) {
         }
     }
 }
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum CompressionLevel {
+    /// Greedy matching over a limited number of candidates
+    Fast,
+    /// Optimal parsing over the longest matches at each position
+    #[default]
+    Best,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(u8),
+    Reference { offset: usize, length: usize },
+}
+
+/// Finds the matches in the window using hash chains of the 3-byte prefixes
+struct MatchFinder<'a> {
+    input: &'a [u8],
+    head: Vec<u32>,
+    prev: Vec<u32>,
+    max_offset: usize,
+    max_length: usize,
+    max_chain: usize,
+}
+
+impl<'a> MatchFinder<'a> {
+    fn new<const OFFSET_BITS: u32>(input: &'a [u8], max_chain: usize) -> Self {
+        Self {
+            input,
+            head: vec![NO_POSITION; 1 << HASH_BITS],
+            prev: vec![NO_POSITION; input.len()],
+            max_offset: 1 << OFFSET_BITS,
+            max_length: (1 << (16 - OFFSET_BITS)) - 1 + MIN_MATCH_LENGTH,
+            max_chain,
+        }
+    }
+
+    fn hash(&self, position: usize) -> Option<usize> {
+        let prefix = self.input.get(position..position + MIN_MATCH_LENGTH)?;
+        let value = (prefix[0] as u32) << 16 | (prefix[1] as u32) << 8 | prefix[2] as u32;
+        Some((value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize)
+    }
+
+    /// Make the position available for matching at the next positions. Must be called in order
+    fn insert(&mut self, position: usize) {
+        if let Some(hash) = self.hash(position) {
+            self.prev[position] = self.head[hash];
+            self.head[hash] = position as u32;
+        }
+    }
+
+    /// Find the longest (and then closest) match for the position, returning `(offset, length)`
+    fn find(&self, position: usize) -> (usize, usize) {
+        let Some(hash) = self.hash(position) else {
+            return (0, 0);
+        };
+        let max_length = self.max_length.min(self.input.len() - position);
+
+        let mut best = (0, 0);
+        let mut candidate = self.head[hash];
+        let mut chain = 0;
+        while candidate != NO_POSITION && chain < self.max_chain {
+            let candidate_position = candidate as usize;
+            let offset = position - candidate_position;
+            if offset > self.max_offset {
+                break;
+            }
+
+            // the match can overlap with the current position, the decoder handles it
+            let length = (0..max_length)
+                .take_while(|&i| self.input[candidate_position + i] == self.input[position + i])
+                .count();
+            // the candidates are visited from the closest one, so only take strictly longer matches
+            if length > best.1 {
+                best = (offset, length);
+                if length == max_length {
+                    break;
+                }
+            }
+
+            candidate = self.prev[candidate_position];
+            chain += 1;
+        }
+
+        if best.1 >= MIN_MATCH_LENGTH {
+            best
+        } else {
+            (0, 0)
+        }
+    }
+}
+
+fn parse_greedy(mut finder: MatchFinder) -> Vec<Token> {
+    let input = finder.input;
+    let mut tokens = Vec::new();
+
+    let mut position = 0;
+    while position < input.len() {
+        let (offset, length) = finder.find(position);
+        if length > 0 {
+            tokens.push(Token::Reference { offset, length });
+            for p in position..position + length {
+                finder.insert(p);
+            }
+            position += length;
+        } else {
+            tokens.push(Token::Literal(input[position]));
+            finder.insert(position);
+            position += 1;
+        }
+    }
+
+    tokens
+}
+
+fn parse_optimal(mut finder: MatchFinder) -> Vec<Token> {
+    let input = finder.input;
+
+    let matches = (0..input.len())
+        .map(|position| {
+            let found = finder.find(position);
+            finder.insert(position);
+            found
+        })
+        .collect::<Vec<_>>();
+
+    // the cost of each token in bits, including its flag in the map
+    const LITERAL_COST: usize = 9;
+    const REFERENCE_COST: usize = 17;
+
+    // cost[i] is the minimal cost of encoding input[i..]
+    let mut cost = vec![0usize; input.len() + 1];
+    let mut choice = vec![0usize; input.len()];
+    for position in (0..input.len()).rev() {
+        cost[position] = LITERAL_COST + cost[position + 1];
+        choice[position] = 1;
+
+        // any prefix of the longest match is also a valid match
+        let (_, longest) = matches[position];
+        for length in MIN_MATCH_LENGTH..=longest {
+            let candidate_cost = REFERENCE_COST + cost[position + length];
+            if candidate_cost < cost[position] {
+                cost[position] = candidate_cost;
+                choice[position] = length;
+            }
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while position < input.len() {
+        let length = choice[position];
+        if length == 1 {
+            tokens.push(Token::Literal(input[position]));
+        } else {
+            let (offset, _) = matches[position];
+            tokens.push(Token::Reference { offset, length });
+        }
+        position += length;
+    }
+
+    tokens
+}
+
+fn write_tokens<const OFFSET_BITS: u32>(tokens: &[Token], output: &mut Vec<u8>) {
+    for chunk in tokens.chunks(8) {
+        let map_position = output.len();
+        output.push(0);
+
+        for (i, token) in chunk.iter().enumerate() {
+            match *token {
+                Token::Literal(value) => output.push(value),
+                Token::Reference { offset, length } => {
+                    output[map_position] |= 1 << i;
+                    let spec = ((length - MIN_MATCH_LENGTH) << OFFSET_BITS) | (offset - 1);
+                    output.put_u16(spec as u16); // big endian Oo
+                }
+            }
+        }
+    }
+}
+
+/// Compress the data, so that it can be decompressed with [decompress] using the same `OFFSET_BITS`
+pub fn compress<const OFFSET_BITS: u32>(
+    input: &[u8],
+    output: &mut Vec<u8>,
+    level: CompressionLevel,
+) {
+    assert!(
+        (1..16).contains(&OFFSET_BITS),
+        "OFFSET_BITS must leave some bits for the length"
+    );
+
+    let tokens = match level {
+        CompressionLevel::Fast => {
+            parse_greedy(MatchFinder::new::<OFFSET_BITS>(input, FAST_MAX_CHAIN))
+        }
+        CompressionLevel::Best => parse_optimal(MatchFinder::new::<OFFSET_BITS>(input, usize::MAX)),
+    };
+
+    write_tokens::<OFFSET_BITS>(&tokens, output);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn round_trip<const OFFSET_BITS: u32>(data: &[u8]) -> [usize; 2] {
+        [CompressionLevel::Fast, CompressionLevel::Best].map(|level| {
+            let mut compressed = Vec::new();
+            compress::<OFFSET_BITS>(data, &mut compressed, level);
+            let mut decompressed = Vec::new();
+            decompress::<OFFSET_BITS>(&compressed, &mut decompressed);
+            assert_eq!(decompressed, data, "round trip failed with {:?}", level);
+            compressed.len()
+        })
+    }
+
+    /// Random data with a lot of repetitions, similar to what the images look like
+    fn random_data(rng: &mut StdRng, len: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(len);
+        while data.len() < len {
+            match rng.gen_range(0..4) {
+                0 => data.push(rng.gen()),
+                1 => data.extend(std::iter::repeat(rng.gen::<u8>()).take(rng.gen_range(1..100))),
+                _ if !data.is_empty() => {
+                    let start = rng.gen_range(0..data.len());
+                    let length = rng.gen_range(1..300);
+                    for i in 0..length {
+                        data.push(data[start + i]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        data.truncate(len);
+        data
+    }
+
+    #[test]
+    fn round_trip_simple() {
+        round_trip::<10>(b"");
+        round_trip::<10>(b"a");
+        round_trip::<10>(b"ab");
+        round_trip::<10>(b"abcabcabcabcabcabcabcabcabcabc");
+        round_trip::<10>(&[0; 5000]);
+        round_trip::<12>(&(0..=255).cycle().take(10000).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn round_trip_random() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..200 {
+            let len = rng.gen_range(0..5000);
+            let data = random_data(&mut rng, len);
+            round_trip::<8>(&data);
+            round_trip::<10>(&data);
+            round_trip::<12>(&data);
+        }
+    }
+
+    #[test]
+    fn best_is_not_worse() {
+        let mut rng = StdRng::seed_from_u64(1337);
+        for _ in 0..50 {
+            let data = random_data(&mut rng, 20000);
+            let [fast, best] = round_trip::<10>(&data);
+            assert!(best <= fast, "best: {}, fast: {}", best, fast);
+        }
+    }
+
+    #[test]
+    fn uses_maximal_references() {
+        // a run of zeroes is a literal followed by references of maximal length with offset 1
+        let mut compressed = Vec::new();
+        compress::<10>(&[0; 67], &mut compressed, CompressionLevel::Fast);
+        assert_eq!(compressed, [0b10, 0, 0xfc, 0x00]);
+    }
+}
@@ -11,4 +11,6 @@ pub mod mask;
 pub mod picture;
 pub mod save;
 pub mod scenario;
+pub mod switch;
+pub mod sysse;
 pub mod texture_archive;
@@ -5,9 +5,10 @@ use binrw::prelude::*;
 use binrw::Endian;
 use bitflags::bitflags;
 use bytemuck::{Pod, Zeroable};
-use image::{ImageBuffer, RgbaImage};
+use image::{GenericImageView, ImageBuffer, RgbaImage};
 use itertools::Itertools;
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::io;
 use std::sync::Mutex;
 
@@ -77,7 +78,7 @@ impl BinWrite for CompressionFlags {
 }
 
 #[derive(BinRead, BinWrite, Debug)]
-#[br(little)]
+#[brw(little)]
 struct PicChunkHeader {
     compression_flags: CompressionFlags,
     opaque_vertex_count: u16,
@@ -104,7 +105,7 @@ impl PicChunkHeader {
 }
 
 #[derive(BinRead, BinWrite, Debug, Copy, Clone)]
-#[br(little)]
+#[brw(little)]
 pub struct PicVertexEntry {
     pub from_x: u16,
     pub from_y: u16,
@@ -183,6 +184,51 @@ impl PictureChunk {
         }
     }
 
+    /// Make a chunk out of an image placed at (offset_x, offset_y), trimming the fully transparent borders
+    ///
+    /// The whole chunk is covered by a single transparent region. Returns an empty chunk if the image is fully transparent.
+    pub fn from_image(offset_x: u32, offset_y: u32, image: &RgbaImage) -> Self {
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        for (x, y, pixel) in image.enumerate_pixels() {
+            if pixel.0[3] != 0 {
+                let (min_x, min_y, max_x, max_y) = bounds.get_or_insert((x, y, x, y));
+                *min_x = (*min_x).min(x);
+                *min_y = (*min_y).min(y);
+                *max_x = (*max_x).max(x);
+                *max_y = (*max_y).max(y);
+            }
+        }
+        let Some((min_x, min_y, max_x, max_y)) = bounds else {
+            return Self::empty();
+        };
+
+        let width = max_x - min_x + 1;
+        let height = max_y - min_y + 1;
+
+        // the region bounds are clamped to the last pixel when reading,
+        // so leave a transparent column & row at the end for nothing to be cut off
+        let mut data = RgbaImage::new(width + 1, height + 1);
+        image::imageops::replace(
+            &mut data,
+            &image.view(min_x, min_y, width, height).to_image(),
+            0,
+            0,
+        );
+
+        Self {
+            offset_x: offset_x + min_x,
+            offset_y: offset_y + min_y,
+            opaque_vertices: Vec::new(),
+            transparent_vertices: vec![PicVertexEntry {
+                from_x: 0,
+                from_y: 0,
+                to_x: width as u16,
+                to_y: height as u16,
+            }],
+            data,
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.data.width() == 0 && self.data.height() == 0
     }
@@ -375,10 +421,253 @@ pub fn read_texture(
             stride,
         )
     } else {
-        todo!("decode differential")
+        decode_differential(
+            target_image,
+            &data[..differential_stride * height as usize],
+            width as usize,
+            differential_stride,
+        )
+    }
+}
+
+/// Predict a pixel for the differential encoding: the one to the left, or the one above for the first column
+///
+/// NOTE: this layout is our best understanding of the format, no game files using it were found to check against.
+#[inline]
+fn differential_prediction(image: &RgbaImage, x: u32, y: u32) -> [u8; 4] {
+    if x > 0 {
+        image.get_pixel(x - 1, y).0
+    } else if y > 0 {
+        image.get_pixel(0, y - 1).0
+    } else {
+        [0; 4]
+    }
+}
+
+fn decode_differential(image: &mut RgbaImage, data: &[u8], width: usize, stride: usize) {
+    for (y, row) in data.chunks(stride).enumerate() {
+        for (x, delta) in row[..width * 4].chunks_exact(4).enumerate() {
+            let (x, y) = (x as u32, y as u32);
+            let prediction = differential_prediction(image, x, y);
+            let pixel = image.get_pixel_mut(x, y);
+            for i in 0..4 {
+                pixel.0[i] = prediction[i].wrapping_add(delta[i]);
+            }
+        }
+    }
+}
+
+fn encode_differential(image: &RgbaImage, stride: usize) -> Vec<u8> {
+    let mut data = vec![0; stride * image.height() as usize];
+    for (y, row) in data.chunks_mut(stride).enumerate() {
+        for (x, delta) in row[..image.width() as usize * 4]
+            .chunks_exact_mut(4)
+            .enumerate()
+        {
+            let (x, y) = (x as u32, y as u32);
+            let prediction = differential_prediction(image, x, y);
+            let pixel = image.get_pixel(x, y);
+            for i in 0..4 {
+                delta[i] = pixel.0[i].wrapping_sub(prediction[i]);
+            }
+        }
+    }
+    data
+}
+
+/// Build a dictionary of at most 256 colors and map every pixel to an index in it
+///
+/// The colors are used as-is if there are few enough of them, otherwise the image is quantized (which is lossy).
+/// Without inline alpha the alpha is stored separately, so it is not put into the dictionary.
+fn build_dictionary(image: &RgbaImage, use_inline_alpha: bool) -> ([Rgba8; 0x100], Vec<u8>) {
+    let key = |pixel: &image::Rgba<u8>| {
+        let [r, g, b, a] = pixel.0;
+        [r, g, b, if use_inline_alpha { a } else { 0xff }]
+    };
+
+    let mut dictionary = [Rgba8::default(); 0x100];
+
+    let mut exact = HashMap::new();
+    for pixel in image.pixels() {
+        let color = key(pixel);
+        let next_index = exact.len();
+        exact.entry(color).or_insert(next_index);
+        if exact.len() > 0x100 {
+            break;
+        }
+    }
+
+    if exact.len() <= 0x100 {
+        for (&[r, g, b, a], &index) in &exact {
+            dictionary[index] = Rgba8 { r, g, b, a };
+        }
+        let indices = image.pixels().map(|p| exact[&key(p)] as u8).collect();
+        return (dictionary, indices);
+    }
+
+    let pixels = image.pixels().flat_map(key).collect::<Vec<_>>();
+    // sample every pixel: the textures are small, and sparser sampling loses the colors at the extremes
+    let quantizer = color_quant::NeuQuant::new(1, 0x100, &pixels);
+    for (entry, color) in dictionary
+        .iter_mut()
+        .zip(quantizer.color_map_rgba().chunks_exact(4))
+    {
+        *entry = *bytemuck::from_bytes(color);
+        if !use_inline_alpha {
+            // the quantizer might drift the alpha a bit, but it must be opaque
+            entry.a = 0xff;
+        }
+    }
+    let indices = pixels
+        .chunks_exact(4)
+        .map(|p| quantizer.index_of(p) as u8)
+        .collect();
+
+    (dictionary, indices)
+}
+
+/// A texture encoded by [write_texture], in the form [read_texture] expects it
+pub struct EncodedTexture {
+    pub data: Vec<u8>,
+    /// Size of the lz77-compressed data, 0 if the data is stored uncompressed
+    pub compressed_size: usize,
+    pub decompressed_size: usize,
+}
+
+/// Encode a texture without compressing it
+fn encode_texture_data(
+    image: &RgbaImage,
+    use_dict_encoding: bool,
+    use_inline_alpha: bool,
+) -> Vec<u8> {
+    let width = image.width();
+    let height = image.height();
+
+    let differential_stride = ((width * 4 + 0xf) & 0xfffffff0) as usize;
+    let dictionary_stride = ((width + 3) & 0xfffffffc) as usize;
+
+    if use_dict_encoding {
+        let stride = dictionary_stride;
+        let (dictionary, indices) = build_dictionary(image, use_inline_alpha);
+
+        let mut data = Vec::with_capacity(0x400 + stride * height as usize * 2);
+        data.extend_from_slice(bytemuck::bytes_of(&dictionary));
+        for row in indices.chunks(width as usize) {
+            data.extend_from_slice(row);
+            data.resize(data.len() + stride - width as usize, 0);
+        }
+        if !use_inline_alpha {
+            for row in image.rows() {
+                data.extend(row.map(|p| p.0[3]));
+                data.resize(data.len() + stride - width as usize, 0);
+            }
+        }
+        data
+    } else {
+        encode_differential(image, differential_stride)
     }
 }
 
+/// Encode a texture, the inverse of [read_texture]
+///
+/// The data is compressed only if it makes it smaller.
+pub fn write_texture(
+    image: &RgbaImage,
+    use_dict_encoding: bool,
+    use_inline_alpha: bool,
+) -> EncodedTexture {
+    let data = encode_texture_data(image, use_dict_encoding, use_inline_alpha);
+
+    let decompressed_size = data.len();
+    let mut compressed = Vec::new();
+    super::lz77::compress::<12>(&data, &mut compressed, super::lz77::CompressionLevel::Best);
+
+    if compressed.len() < decompressed_size {
+        EncodedTexture {
+            compressed_size: compressed.len(),
+            data: compressed,
+            decompressed_size,
+        }
+    } else {
+        EncodedTexture {
+            data,
+            compressed_size: 0,
+            decompressed_size,
+        }
+    }
+}
+
+/// Encode a picture chunk, the inverse of [read_picture_chunk]
+///
+/// Empty chunks are encoded as an empty slice.
+pub fn write_picture_chunk(chunk: &PictureChunk, use_dict_encoding: bool) -> Result<Vec<u8>> {
+    use binrw::BinWriterExt;
+
+    if chunk.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let width: u16 = chunk.data.width().try_into().context("Chunk is too wide")?;
+    let height: u16 = chunk
+        .data
+        .height()
+        .try_into()
+        .context("Chunk is too tall")?;
+
+    // the differential encoding stores the alpha together with the color anyways
+    let use_inline_alpha = !use_dict_encoding;
+
+    let texture = write_texture(&chunk.data, use_dict_encoding, use_inline_alpha);
+    // the compressed size has to fit into the header, store the data uncompressed otherwise
+    let (data, compressed_size) = match u16::try_from(texture.compressed_size) {
+        Ok(compressed_size) => (texture.data, compressed_size),
+        Err(_) => (
+            encode_texture_data(&chunk.data, use_dict_encoding, use_inline_alpha),
+            0,
+        ),
+    };
+
+    let mut compression_flags = CompressionFlags::empty();
+    compression_flags.set(CompressionFlags::USE_INLINE_ALPHA, use_inline_alpha);
+    compression_flags.set(CompressionFlags::USE_DICT_ENCODING, use_dict_encoding);
+
+    const HEADER_SIZE: usize = 20;
+    const VERTEX_SIZE: usize = 8;
+    let vertices_end = HEADER_SIZE
+        + VERTEX_SIZE * (chunk.opaque_vertices.len() + chunk.transparent_vertices.len());
+    // align the texture data to 16 bytes
+    let padding = ((vertices_end + 0xf) & !0xf) - vertices_end;
+
+    let header = PicChunkHeader {
+        compression_flags,
+        opaque_vertex_count: chunk.opaque_vertices.len() as u16,
+        transparent_vertex_count: chunk.transparent_vertices.len() as u16,
+        padding_before_data: (padding / 2) as u16,
+        offset_x: chunk.offset_x as u16,
+        offset_y: chunk.offset_y as u16,
+        width,
+        height,
+        compressed_size,
+        unknown_bool: 0,
+    };
+
+    let mut writer = io::Cursor::new(Vec::with_capacity(vertices_end + padding + data.len()));
+    writer.write_le(&header)?;
+    for vertex in chunk
+        .opaque_vertices
+        .iter()
+        .chain(chunk.transparent_vertices.iter())
+    {
+        writer.write_le(vertex)?;
+    }
+
+    let mut output = writer.into_inner();
+    output.resize(vertices_end + padding, 0);
+    output.extend_from_slice(&data);
+
+    Ok(output)
+}
+
 /// Read a picture chunk from the data
 ///
 /// If the chunk data is an empty slice, the function will return an empry image chunk
@@ -490,3 +779,43 @@ pub fn read_picture<'a, B: PictureBuilder<'a>>(
 
     listener.build()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_differential_layout() {
+        // 2x2 pixels, the rows are padded to 16 bytes
+        #[rustfmt::skip]
+        let data = [
+            10, 20, 30, 255, 5, 5, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            1, 1, 1, 0, 2, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let mut image = RgbaImage::new(2, 2);
+        read_texture(&data, 0, &mut image, false, true);
+
+        assert_eq!(image.get_pixel(0, 0).0, [10, 20, 30, 255]);
+        assert_eq!(image.get_pixel(1, 0).0, [15, 25, 35, 255]);
+        assert_eq!(image.get_pixel(0, 1).0, [11, 21, 31, 255]);
+        assert_eq!(image.get_pixel(1, 1).0, [13, 23, 33, 255]);
+    }
+
+    #[test]
+    fn differential_roundtrip() {
+        let image = RgbaImage::from_fn(13, 7, |x, y| {
+            image::Rgba([(x * 37) as u8, (y * 91) as u8, (x * y) as u8, 255 - x as u8])
+        });
+        let texture = write_texture(&image, false, true);
+
+        let mut decoded = RgbaImage::new(13, 7);
+        read_texture(
+            &texture.data,
+            texture.compressed_size,
+            &mut decoded,
+            false,
+            true,
+        );
+        assert_eq!(decoded, image);
+    }
+}
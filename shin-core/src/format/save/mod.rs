@@ -1,5 +1,6 @@
 //! Support for decrypting and decoding save files.
 
+use crate::game::{GameId, GameProfile};
 use anyhow::Result;
 use bitbuffer::{BitRead, BitWrite, BitWriteStream, Endianness};
 use chrono::{NaiveDate, NaiveDateTime};
@@ -15,7 +16,12 @@ type Endian = bitbuffer::BigEndian;
 const ENDIAN: Endian = bitbuffer::BigEndian;
 type BitReadStream<'a, E = Endian> = bitbuffer::BitReadStream<'a, E>;
 
-static GAME_KEY: Lazy<u32> = Lazy::new(|| crc32::crc32("うみねこのなく頃に咲".as_bytes(), 0));
+/// Key used by the methods not taking a key explicitly, the one of Umineko
+static GAME_KEY: Lazy<u32> = Lazy::new(|| {
+    GameProfile::get(GameId::Umineko)
+        .save_key()
+        .expect("Umineko save key is known")
+});
 
 fn read_u8<E: Endianness>(reader: &mut BitReadStream<E>) -> bitbuffer::Result<u8> {
     reader.read_int(8)
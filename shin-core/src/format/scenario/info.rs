@@ -87,6 +87,13 @@ pub struct VoiceMappingInfoItem {
 }
 pub type VoiceMappingInfo = Vec<VoiceMappingInfoItem>;
 
+/// Path of the voice file with the name used by `VOICEPLAY` (like `00/awase6042_o`)
+///
+/// NOTE: the voices are not listed in the info tables, the layout is assumed to follow the one of the other audio directories
+pub fn voice_path(name: &str) -> String {
+    format!("/voice/{}.nxa", name.to_ascii_lowercase())
+}
+
 #[derive(Debug, BinRead, BinWrite)]
 pub struct Section64InfoItem {
     pub unk1: U16String,
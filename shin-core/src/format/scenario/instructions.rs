@@ -490,6 +490,7 @@ impl BinWrite for BitmaskNumberArray {
 /// Message ID - a 24-bit integer
 ///
 /// It is used to check whether a message was seen before.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct MessageId(pub u32);
 
 impl BinRead for MessageId {
@@ -0,0 +1,80 @@
+//! Static listing of the scenario code.
+//!
+//! Instructions in the SNR file have variable length, so it's impossible to disassemble "backwards" from an arbitrary address.
+//! [CodeListing] does a linear sweep over the code section once and remembers where each instruction starts,
+//! making it possible to show the code around an address and to look up things like message locations.
+
+use crate::format::scenario::instructions::{CodeAddress, Instruction, MessageId};
+use crate::format::scenario::Scenario;
+use crate::vm::command::CompiletimeCommand;
+use std::collections::HashMap;
+use tracing::debug;
+
+pub struct CodeListing {
+    /// Start addresses of all instructions, sorted
+    addresses: Vec<CodeAddress>,
+    /// Addresses of MSGSET commands, by message id
+    messages: HashMap<u32, CodeAddress>,
+}
+
+impl CodeListing {
+    /// Sweep over the code section of the scenario, starting from the entrypoint
+    ///
+    /// The sweep stops at the end of the file or at the first instruction that fails to parse
+    pub fn new(scenario: &Scenario) -> Self {
+        let mut addresses = Vec::new();
+        let mut messages = HashMap::new();
+
+        let end = scenario.raw().len() as u32;
+        let mut reader = scenario.instruction_reader(scenario.entrypoint_address());
+        while reader.position().0 < end {
+            let address = reader.position();
+            let instruction = match reader.read() {
+                Ok(instruction) => instruction,
+                Err(e) => {
+                    debug!("CodeListing: stopping the sweep at {:?}: {}", address, e);
+                    break;
+                }
+            };
+
+            if let Instruction::Command(CompiletimeCommand::MSGSET(msgset)) = &instruction {
+                messages.entry(msgset.msg_id.0).or_insert(address);
+            }
+
+            addresses.push(address);
+        }
+
+        Self {
+            addresses,
+            messages,
+        }
+    }
+
+    /// Start addresses of all the instructions in the listing, sorted
+    pub fn addresses(&self) -> &[CodeAddress] {
+        &self.addresses
+    }
+
+    /// Find the index of the instruction containing the address (or the closest one after it)
+    pub fn index_of(&self, address: CodeAddress) -> usize {
+        self.addresses.partition_point(|&a| a < address)
+    }
+
+    /// Translate an instruction address to the listing of another version of the scenario
+    ///
+    /// The instructions are matched by their index, so this works when the versions have the same instructions
+    /// that only differ in their lengths (e.g. when the text of messages was edited).
+    pub fn map_address(&self, other: &CodeListing, address: CodeAddress) -> Option<CodeAddress> {
+        if self.addresses.len() != other.addresses.len() {
+            return None;
+        }
+
+        let index = self.addresses.binary_search(&address).ok()?;
+        Some(other.addresses[index])
+    }
+
+    /// Find the address of the MSGSET command showing the message with the specified id
+    pub fn find_message(&self, id: MessageId) -> Option<CodeAddress> {
+        self.messages.get(&id.0).copied()
+    }
+}
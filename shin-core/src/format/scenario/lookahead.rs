@@ -0,0 +1,294 @@
+//! Static look-ahead over the scenario code, finding the assets that will probably be needed soon.
+//!
+//! The code is not executed. Instead, starting from an address, the instruction stream is followed along all the paths the VM could take
+//! (both outcomes of conditional jumps, all entries of jump tables, into subroutines and back) in breadth-first order,
+//! until a limit on the number of visited instructions is reached.
+//!
+//! Only the asset ids that are constants in the code can be resolved, the ones computed at runtime are skipped.
+
+use crate::format::scenario::info::{voice_path, ScenarioInfoTables};
+use crate::format::scenario::instructions::{CodeAddress, Instruction, NumberSpec};
+use crate::format::scenario::Scenario;
+use crate::vm::command::types::LayerType;
+use crate::vm::command::CompiletimeCommand;
+use anyhow::Result;
+use num_traits::FromPrimitive;
+use std::collections::{HashSet, VecDeque};
+use tracing::trace;
+
+/// An asset referenced by a command
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AssetReference {
+    Picture(i32),
+    Bustup(i32),
+    Bgm(i32),
+    Se(i32),
+    /// Voices are referenced by name, not through the info tables
+    Voice(String),
+}
+
+impl AssetReference {
+    /// Path of the referenced asset, `None` if the id is not in the info tables
+    pub fn path(&self, tables: &ScenarioInfoTables) -> Option<String> {
+        fn get<T>(table: &[T], id: i32) -> Option<&T> {
+            usize::try_from(id).ok().and_then(|id| table.get(id))
+        }
+
+        match self {
+            AssetReference::Picture(id) => get(&tables.picture_info, *id).map(|i| i.path()),
+            AssetReference::Bustup(id) => get(&tables.bustup_info, *id).map(|i| i.path()),
+            AssetReference::Bgm(id) => get(&tables.bgm_info, *id).map(|i| i.path()),
+            AssetReference::Se(id) => get(&tables.se_info, *id).map(|i| i.path()),
+            AssetReference::Voice(name) => Some(voice_path(name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpcomingAsset {
+    pub reference: AssetReference,
+    /// Number of instructions between the start address and the command referencing the asset, along the shortest path
+    pub distance: usize,
+}
+
+/// Find the assets referenced by the code reachable from `start`, nearest first
+///
+/// At most `max_instructions` instructions are visited. Each asset is reported only once, at its shortest distance.
+pub fn find_upcoming_assets(
+    scenario: &Scenario,
+    start: CodeAddress,
+    max_instructions: usize,
+) -> Vec<UpcomingAsset> {
+    walk(
+        |address| {
+            let mut reader = scenario.instruction_reader(address);
+            let instruction = reader.read()?;
+            Ok((instruction, reader.position()))
+        },
+        start,
+        max_instructions,
+    )
+}
+
+/// `read` decodes the instruction at an address, returning it with the address of the next one
+fn walk(
+    mut read: impl FnMut(CodeAddress) -> Result<(Instruction, CodeAddress)>,
+    start: CodeAddress,
+    max_instructions: usize,
+) -> Vec<UpcomingAsset> {
+    let mut result = Vec::new();
+    let mut seen_assets = HashSet::new();
+
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    queue.push_back((start, 0));
+
+    while let Some((address, distance)) = queue.pop_front() {
+        if visited.len() >= max_instructions {
+            break;
+        }
+        if !visited.insert(address) {
+            continue;
+        }
+
+        let (instruction, next) = match read(address) {
+            Ok(v) => v,
+            Err(e) => {
+                trace!("Lookahead: stopping the path at {:?}: {}", address, e);
+                continue;
+            }
+        };
+
+        if let Instruction::Command(command) = &instruction {
+            if let Some(reference) = referenced_asset(command) {
+                if seen_assets.insert(reference.clone()) {
+                    result.push(UpcomingAsset {
+                        reference,
+                        distance,
+                    });
+                }
+            }
+        }
+
+        let distance = distance + 1;
+        match instruction {
+            Instruction::j { target } => queue.push_back((target, distance)),
+            Instruction::jc { target, .. } => {
+                queue.push_back((next, distance));
+                queue.push_back((target, distance));
+            }
+            Instruction::jt { table, .. } => {
+                // the VM does not jump when the index is out of the table bounds
+                queue.push_back((next, distance));
+                queue.extend(table.0.iter().map(|&target| (target, distance)));
+            }
+            Instruction::gosub { target } | Instruction::call { target, .. } => {
+                queue.push_back((target, distance));
+                queue.push_back((next, distance));
+            }
+            // the return address is only known at runtime, but it was already queued by the caller (if the caller was visited)
+            Instruction::retsub {} | Instruction::r#return {} => {}
+            Instruction::Command(CompiletimeCommand::EXIT(exit)) if exit.arg1 == 0 => {}
+            _ => queue.push_back((next, distance)),
+        }
+    }
+
+    result
+}
+
+fn constant(number: NumberSpec) -> Option<i32> {
+    match number {
+        NumberSpec::Constant(value) => Some(value),
+        NumberSpec::Memory(_) => None,
+    }
+}
+
+fn referenced_asset(command: &CompiletimeCommand) -> Option<AssetReference> {
+    match command {
+        CompiletimeCommand::LAYERLOAD(cmd) => {
+            let id = constant(cmd.params.0[0])?;
+            match LayerType::from_i32(constant(cmd.layer_type)?)? {
+                LayerType::Picture => Some(AssetReference::Picture(id)),
+                LayerType::Bustup => Some(AssetReference::Bustup(id)),
+                // movies are streamed, there is nothing to prefetch
+                _ => None,
+            }
+        }
+        CompiletimeCommand::BGMPLAY(cmd) => constant(cmd.bgm_data_id).map(AssetReference::Bgm),
+        CompiletimeCommand::SEPLAY(cmd) => constant(cmd.se_data_id).map(AssetReference::Se),
+        // see the SEONCE command for the meaning of the arguments
+        CompiletimeCommand::SEONCE(cmd) => constant(cmd.arg1).map(AssetReference::Se),
+        CompiletimeCommand::VOICEPLAY(cmd) => {
+            Some(AssetReference::Voice(cmd.name.as_str().to_string()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::scenario::instructions::{BitmaskNumberArray, JumpCond, JumpCondType};
+    use crate::format::text::SJisString;
+    use crate::vm::command::compiletime;
+    use std::collections::HashMap;
+    use std::marker::PhantomData;
+
+    fn bgmplay(id: NumberSpec) -> Instruction {
+        Instruction::Command(CompiletimeCommand::BGMPLAY(compiletime::BGMPLAY {
+            bgm_data_id: id,
+            fade_in_time: NumberSpec::Constant(0),
+            no_repeat: NumberSpec::Constant(0),
+            volume: NumberSpec::Constant(1000),
+        }))
+    }
+
+    fn layerload(layer_type: LayerType, id: i32) -> Instruction {
+        let mut params = [NumberSpec::Constant(0); 8];
+        params[0] = NumberSpec::Constant(id);
+        Instruction::Command(CompiletimeCommand::LAYERLOAD(compiletime::LAYERLOAD {
+            layer_id: NumberSpec::Constant(1),
+            layer_type: NumberSpec::Constant(layer_type as i32),
+            leave_uninitialized: NumberSpec::Constant(0),
+            params: BitmaskNumberArray(params),
+        }))
+    }
+
+    fn voiceplay(name: &str) -> Instruction {
+        Instruction::Command(CompiletimeCommand::VOICEPLAY(compiletime::VOICEPLAY {
+            name: SJisString(name.to_string(), PhantomData),
+            volume: NumberSpec::Constant(1000),
+            flags: NumberSpec::Constant(0),
+        }))
+    }
+
+    fn exit() -> Instruction {
+        Instruction::Command(CompiletimeCommand::EXIT(compiletime::EXIT {
+            arg1: 0,
+            arg2: NumberSpec::Constant(0),
+        }))
+    }
+
+    /// Run the walk over a "program" where each instruction takes up one address
+    fn run(program: Vec<Instruction>, max_instructions: usize) -> Vec<UpcomingAsset> {
+        let mut program = program
+            .into_iter()
+            .enumerate()
+            .map(|(i, instruction)| (i as u32, instruction))
+            .collect::<HashMap<_, _>>();
+
+        walk(
+            |address| {
+                let instruction = program
+                    .remove(&address.0)
+                    .ok_or_else(|| anyhow::anyhow!("No instruction at {:?}", address))?;
+                Ok((instruction, CodeAddress(address.0 + 1)))
+            },
+            CodeAddress(0),
+            max_instructions,
+        )
+    }
+
+    #[test]
+    fn follows_branches() {
+        let program = vec![
+            /* 0 */
+            Instruction::jc {
+                cond: JumpCond {
+                    is_negated: false,
+                    condition: JumpCondType::Equal,
+                },
+                left: NumberSpec::Constant(0),
+                right: NumberSpec::Constant(0),
+                target: CodeAddress(4),
+            },
+            /* 1 */ layerload(LayerType::Picture, 5),
+            /* 2 */ bgmplay(NumberSpec::Constant(3)),
+            /* 3 */ exit(),
+            /* 4 */ Instruction::gosub {
+                target: CodeAddress(7),
+            },
+            /* 5 */ voiceplay("10/A0000001"),
+            /* 6 */ exit(),
+            /* 7 */ layerload(LayerType::Bustup, 2),
+            /* 8 */ bgmplay(NumberSpec::Constant(3)),
+            /* 9 */ Instruction::retsub {},
+            /* 10 */ layerload(LayerType::Picture, 6),
+        ];
+
+        let assets = run(program, 100)
+            .into_iter()
+            .map(|a| (a.reference, a.distance))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            assets,
+            vec![
+                (AssetReference::Picture(5), 1),
+                (AssetReference::Bgm(3), 2),
+                (AssetReference::Bustup(2), 2),
+                (AssetReference::Voice("10/A0000001".to_string()), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn respects_limit() {
+        let program = vec![
+            layerload(LayerType::Picture, 1),
+            bgmplay(NumberSpec::Constant(1)),
+            layerload(LayerType::Picture, 2),
+            exit(),
+        ];
+
+        let assets = run(program, 2)
+            .into_iter()
+            .map(|a| a.reference)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            assets,
+            vec![AssetReference::Picture(1), AssetReference::Bgm(1)]
+        );
+    }
+}
@@ -4,6 +4,8 @@
 
 pub mod info;
 pub mod instructions;
+pub mod listing;
+pub mod lookahead;
 pub mod types;
 
 use crate::format::scenario::info::ScenarioInfoTables;
@@ -0,0 +1,705 @@
+//! Support for the containers the game data is shipped in on the Switch
+//!
+//! The game data (`data.rom`) is stored in the RomFS of the game, which in turn is stored in an NCA, which in turn is stored in an NSP (a PFS0 archive).
+//!
+//! Only the unencrypted variants are supported: a raw RomFS image, a decrypted NCA or an NSP with decrypted NCAs.
+//! Encrypted containers need to be decrypted (or the RomFS extracted) with an external tool, like hactoolnet.
+
+use anyhow::{anyhow, bail, Context, Result};
+use binrw::{binread, BinRead, NullString};
+use std::io;
+use std::io::{Cursor, SeekFrom};
+use std::ops::Range;
+use tracing::{debug, trace};
+
+/// The name of the file containing the game data in the RomFS
+pub const DATA_ROM_PATH: &str = "/data.rom";
+
+const NCA_MEDIA_UNIT: u64 = 0x200;
+const NCA_SECTION_COUNT: usize = 4;
+const NCA_FS_HEADERS_OFFSET: u64 = 0x400;
+const ROMFS_HEADER_SIZE: u64 = 0x50;
+const ROMFS_NONE: u32 = 0xffffffff;
+/// Sizes of the RomFS directory & file entries without the name
+const ROMFS_DIR_ENTRY_SIZE: usize = 0x18;
+const ROMFS_FILE_ENTRY_SIZE: usize = 0x20;
+/// How deep containers can be nested, protects against cycles in malformed files
+const MAX_NESTING: u32 = 4;
+
+#[binread]
+#[br(magic = b"PFS0", little)]
+struct Pfs0Header {
+    #[br(temp)]
+    file_count: u32,
+    string_table_size: u32,
+    _reserved: u32,
+    #[br(count = file_count)]
+    entries: Vec<Pfs0Entry>,
+}
+
+#[derive(BinRead)]
+#[br(little)]
+struct Pfs0Entry {
+    data_offset: u64,
+    data_size: u64,
+    name_offset: u32,
+    _reserved: u32,
+}
+
+#[derive(BinRead)]
+#[br(little)]
+struct NcaSectionEntry {
+    media_start: u32,
+    media_end: u32,
+    _reserved: u64,
+}
+
+#[derive(BinRead)]
+#[br(little)]
+struct NcaFsHeader {
+    _version: u16,
+    fs_type: u8,
+    hash_type: u8,
+    encryption_type: u8,
+    _padding: [u8; 3],
+    ivfc: IvfcHeader,
+}
+
+#[derive(BinRead)]
+#[br(magic = b"IVFC", little)]
+struct IvfcHeader {
+    _version: u32,
+    _master_hash_size: u32,
+    max_layers: u32,
+    levels: [IvfcLevel; 6],
+}
+
+#[derive(BinRead)]
+#[br(little)]
+struct IvfcLevel {
+    offset: u64,
+    size: u64,
+    _block_size_log2: u32,
+    _reserved: u32,
+}
+
+const NCA_FS_TYPE_ROMFS: u8 = 0;
+const NCA_HASH_TYPE_IVFC: u8 = 3;
+const NCA_ENCRYPTION_NONE: u8 = 1;
+
+#[derive(BinRead)]
+#[br(little)]
+struct RomFsHeader {
+    header_size: u64,
+    _dir_hash_offset: u64,
+    _dir_hash_size: u64,
+    dir_meta_offset: u64,
+    dir_meta_size: u64,
+    _file_hash_offset: u64,
+    _file_hash_size: u64,
+    file_meta_offset: u64,
+    file_meta_size: u64,
+    data_offset: u64,
+}
+
+#[binread]
+#[br(little)]
+struct RomFsDirEntry {
+    _parent: u32,
+    sibling: u32,
+    child_dir: u32,
+    child_file: u32,
+    _next_hash: u32,
+    #[br(temp)]
+    name_size: u32,
+    #[br(count = name_size)]
+    name: Vec<u8>,
+}
+
+#[binread]
+#[br(little)]
+struct RomFsFileEntry {
+    _parent: u32,
+    sibling: u32,
+    data_offset: u64,
+    data_size: u64,
+    _next_hash: u32,
+    #[br(temp)]
+    name_size: u32,
+    #[br(count = name_size)]
+    name: Vec<u8>,
+}
+
+/// A file inside a RomFS image
+#[derive(Debug, Copy, Clone)]
+struct RomFsFile {
+    data_offset: u64,
+    data_size: u64,
+}
+
+/// The metadata tables of a RomFS image, allows looking up files by path
+struct RomFs {
+    dir_meta: Vec<u8>,
+    file_meta: Vec<u8>,
+    data_offset: u64,
+}
+
+impl RomFs {
+    /// Reads the metadata tables of a RomFS image located at `range`
+    fn read<S: io::Read + io::Seek>(reader: &mut S, range: &Range<u64>) -> Result<Self> {
+        reader.seek(SeekFrom::Start(range.start))?;
+        let header = RomFsHeader::read(reader).context("Reading RomFS header")?;
+        if header.header_size != ROMFS_HEADER_SIZE {
+            bail!("Invalid RomFS header size: 0x{:x}", header.header_size);
+        }
+
+        let mut read_table = |offset: u64, size: u64| -> Result<Vec<u8>> {
+            // check the bounds before allocating, the sizes in a malformed image can be huge
+            if offset
+                .checked_add(size)
+                .map_or(true, |end| end > range.end - range.start)
+            {
+                bail!("The table is out of bounds of the RomFS");
+            }
+            let mut table = vec![0; size as usize];
+            reader.seek(SeekFrom::Start(range.start + offset))?;
+            reader.read_exact(&mut table)?;
+            Ok(table)
+        };
+
+        Ok(Self {
+            dir_meta: read_table(header.dir_meta_offset, header.dir_meta_size)
+                .context("Reading RomFS directory table")?,
+            file_meta: read_table(header.file_meta_offset, header.file_meta_size)
+                .context("Reading RomFS file table")?,
+            data_offset: header.data_offset,
+        })
+    }
+
+    fn dir_entry(&self, offset: u32) -> Result<RomFsDirEntry> {
+        let mut cursor = Cursor::new(&self.dir_meta);
+        cursor.set_position(offset as u64);
+        Ok(RomFsDirEntry::read(&mut cursor)?)
+    }
+
+    fn file_entry(&self, offset: u32) -> Result<RomFsFileEntry> {
+        let mut cursor = Cursor::new(&self.file_meta);
+        cursor.set_position(offset as u64);
+        Ok(RomFsFileEntry::read(&mut cursor)?)
+    }
+
+    fn find_file(&self, path: &str) -> Result<RomFsFile> {
+        let path = path
+            .strip_prefix('/')
+            .ok_or_else(|| anyhow!("Path must start with /"))?;
+        let (dirs, filename) = match path.rsplit_once('/') {
+            Some((dirs, filename)) => (Some(dirs), filename),
+            None => (None, path),
+        };
+
+        // a malformed image can have a sibling chain that loops,
+        // but a valid chain can't be longer than the number of entries that fit into the table
+        let max_dirs = self.dir_meta.len() / ROMFS_DIR_ENTRY_SIZE;
+        let max_files = self.file_meta.len() / ROMFS_FILE_ENTRY_SIZE;
+
+        // the root directory is always the first entry
+        let mut dir = self.dir_entry(0)?;
+        for part in dirs.into_iter().flat_map(|d| d.split('/')) {
+            let mut child = dir.child_dir;
+            let mut visited = 0;
+            dir = loop {
+                if child == ROMFS_NONE {
+                    bail!("Invalid path, directory not found: {:?}", path);
+                }
+                if visited == max_dirs {
+                    bail!("The RomFS directory table contains a loop");
+                }
+                visited += 1;
+                let entry = self.dir_entry(child)?;
+                if entry.name == part.as_bytes() {
+                    break entry;
+                }
+                child = entry.sibling;
+            };
+        }
+
+        let mut child = dir.child_file;
+        let mut visited = 0;
+        while child != ROMFS_NONE {
+            if visited == max_files {
+                bail!("The RomFS file table contains a loop");
+            }
+            visited += 1;
+            let entry = self.file_entry(child)?;
+            if entry.name == filename.as_bytes() {
+                return Ok(RomFsFile {
+                    data_offset: self.data_offset + entry.data_offset,
+                    data_size: entry.data_size,
+                });
+            }
+            child = entry.sibling;
+        }
+
+        bail!("Invalid path, file not found: {:?}", path)
+    }
+}
+
+fn read_magic<S: io::Read + io::Seek>(reader: &mut S, offset: u64) -> Result<[u8; 4]> {
+    let mut magic = [0; 4];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut magic)?;
+    Ok(magic)
+}
+
+/// Finds the byte range of the RomFS in a decrypted NCA located at `range`
+fn find_nca_romfs<S: io::Read + io::Seek>(
+    reader: &mut S,
+    range: &Range<u64>,
+) -> Result<Range<u64>> {
+    reader.seek(SeekFrom::Start(range.start + 0x240))?;
+    let sections = <[NcaSectionEntry; NCA_SECTION_COUNT]>::read_le(reader)?;
+
+    for (index, section) in sections.iter().enumerate() {
+        if section.media_start == 0 && section.media_end == 0 {
+            continue;
+        }
+        reader.seek(SeekFrom::Start(
+            range.start + NCA_FS_HEADERS_OFFSET + index as u64 * NCA_MEDIA_UNIT,
+        ))?;
+        let Ok(fs_header) = NcaFsHeader::read(reader) else {
+            trace!("NCA section {} is not an IVFC-hashed section", index);
+            continue;
+        };
+        if fs_header.fs_type != NCA_FS_TYPE_ROMFS || fs_header.hash_type != NCA_HASH_TYPE_IVFC {
+            continue;
+        }
+        if fs_header.encryption_type != NCA_ENCRYPTION_NONE {
+            bail!("The RomFS section of the NCA is encrypted, decrypt it first");
+        }
+
+        let level_count = (fs_header.ivfc.max_layers as usize)
+            .checked_sub(1)
+            .filter(|&c| (1..=fs_header.ivfc.levels.len()).contains(&c))
+            .ok_or_else(|| anyhow!("Invalid IVFC layer count: {}", fs_header.ivfc.max_layers))?;
+        // the last level contains the actual data, the others are hashes of it
+        let data_level = &fs_header.ivfc.levels[level_count - 1];
+
+        let section_start = range.start + section.media_start as u64 * NCA_MEDIA_UNIT;
+        let start = section_start + data_level.offset;
+        let end = start + data_level.size;
+        if end > range.end {
+            bail!("NCA RomFS section is out of bounds");
+        }
+        return Ok(start..end);
+    }
+
+    bail!("NCA has no RomFS section")
+}
+
+/// Finds the byte range of `data.rom` in a container located at `range`
+fn find_data_rom_in<S: io::Read + io::Seek>(
+    reader: &mut S,
+    range: Range<u64>,
+    depth: u32,
+) -> Result<Range<u64>> {
+    if depth > MAX_NESTING {
+        bail!("Containers are nested too deep");
+    }
+
+    match &read_magic(reader, range.start)? {
+        b"ROM2" => {
+            trace!("Found a ROM at 0x{:x}", range.start);
+            return Ok(range);
+        }
+        b"PFS0" => {
+            trace!("Found a PFS0 at 0x{:x}", range.start);
+            reader.seek(SeekFrom::Start(range.start))?;
+            let header = Pfs0Header::read(reader).context("Reading PFS0 header")?;
+            let string_table_offset = reader.stream_position()?;
+            let data_start = string_table_offset + header.string_table_size as u64;
+
+            let mut errors = Vec::new();
+            for entry in header.entries {
+                reader.seek(SeekFrom::Start(
+                    string_table_offset + entry.name_offset as u64,
+                ))?;
+                let name = String::from_utf8_lossy(&NullString::read_le(reader)?.0).into_owned();
+                let start = data_start + entry.data_offset;
+                let entry_range = start..start + entry.data_size;
+                if entry_range.end > range.end {
+                    bail!("PFS0 entry {:?} is out of bounds", name);
+                }
+                // the NSPs also contain tickets, certificates & such, skip them
+                if !name.ends_with(".nca") {
+                    continue;
+                }
+                match find_data_rom_in(reader, entry_range, depth + 1) {
+                    Ok(range) => return Ok(range),
+                    Err(err) => errors.push(format!("{}: {:#}", name, err)),
+                }
+            }
+            bail!("No NCA in the PFS0 contains the game data: {:?}", errors);
+        }
+        _ => {}
+    }
+
+    if range.end - range.start > NCA_FS_HEADERS_OFFSET
+        && matches!(&read_magic(reader, range.start + 0x200)?, b"NCA3" | b"NCA2")
+    {
+        trace!("Found an NCA at 0x{:x}", range.start);
+        let romfs = find_nca_romfs(reader, &range)?;
+        return find_data_rom_in(reader, romfs, depth + 1);
+    }
+
+    let romfs =
+        RomFs::read(reader, &range).context("Not a ROM, PFS0, decrypted NCA or RomFS image")?;
+    trace!("Found a RomFS at 0x{:x}", range.start);
+    let file = romfs.find_file(DATA_ROM_PATH)?;
+    match range
+        .start
+        .checked_add(file.data_offset)
+        .and_then(|start| Some(start..start.checked_add(file.data_size)?))
+    {
+        Some(data_rom) if data_rom.end <= range.end => Ok(data_rom),
+        _ => bail!("{} is out of bounds of the RomFS", DATA_ROM_PATH),
+    }
+}
+
+/// Finds the game data (`data.rom`) in a Switch dump
+///
+/// Accepts a ROM itself, a RomFS image, a decrypted NCA or an NSP containing decrypted NCAs.
+///
+/// Returns a [SubReader] covering just the ROM, that can be passed to [RomReader](super::rom::RomReader).
+pub fn open_data_rom<S: io::Read + io::Seek>(mut reader: S) -> Result<SubReader<S>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let range = find_data_rom_in(&mut reader, 0..len, 0)?;
+    debug!(
+        "Found the game data at 0x{:x}..0x{:x}",
+        range.start, range.end
+    );
+    Ok(SubReader::new(reader, range))
+}
+
+/// Implements `Read` and `Seek` over a byte range of another stream
+pub struct SubReader<S: io::Read + io::Seek> {
+    inner: S,
+    range: Range<u64>,
+    position: u64,
+    /// Position of the underlying stream, `None` if unknown. Used to avoid seeking it on each read, which would drop the buffer of a `BufReader`
+    inner_position: Option<u64>,
+}
+
+impl<S: io::Read + io::Seek> SubReader<S> {
+    pub fn new(inner: S, range: Range<u64>) -> Self {
+        Self {
+            inner,
+            range,
+            position: 0,
+            inner_position: None,
+        }
+    }
+
+    /// The byte range of the underlying stream this reader covers
+    pub fn range(&self) -> Range<u64> {
+        self.range.clone()
+    }
+
+    fn len(&self) -> u64 {
+        self.range.end - self.range.start
+    }
+}
+
+impl<S: io::Read + io::Seek> io::Read for SubReader<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let to_read =
+            std::cmp::min(buf.len() as u64, self.len().saturating_sub(self.position)) as usize;
+        if to_read == 0 {
+            return Ok(0);
+        }
+
+        let inner_position = self.range.start + self.position;
+        if self.inner_position != Some(inner_position) {
+            // forget the position in case the seek fails midway
+            self.inner_position = None;
+            self.inner.seek(SeekFrom::Start(inner_position))?;
+        }
+        let read = match self.inner.read(&mut buf[..to_read]) {
+            Ok(read) => read,
+            Err(e) => {
+                self.inner_position = None;
+                return Err(e);
+            }
+        };
+        self.position += read as u64;
+        self.inner_position = Some(inner_position + read as u64);
+        Ok(read)
+    }
+}
+
+impl<S: io::Read + io::Seek> io::Seek for SubReader<S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(pos) => self.len().checked_add_signed(pos),
+            SeekFrom::Current(pos) => self.position.checked_add_signed(pos),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek"))?;
+        self.position = std::cmp::min(self.len(), new_pos);
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Seek};
+
+    fn pad4(data: &mut Vec<u8>) {
+        data.resize((data.len() + 3) & !3, 0);
+    }
+
+    /// Builds a RomFS image with the given files in the root directory
+    fn build_romfs(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut dir_meta = Vec::new();
+        // root directory
+        for v in [0, ROMFS_NONE, ROMFS_NONE, 0, ROMFS_NONE, 0] {
+            dir_meta.extend_from_slice(&v.to_le_bytes());
+        }
+
+        let mut file_meta = Vec::new();
+        let mut data = Vec::new();
+        for (index, (name, contents)) in files.iter().enumerate() {
+            let next_offset = file_meta.len() + 0x20 + ((name.len() + 3) & !3);
+            let sibling = if index + 1 == files.len() {
+                ROMFS_NONE
+            } else {
+                next_offset as u32
+            };
+            file_meta.extend_from_slice(&0u32.to_le_bytes());
+            file_meta.extend_from_slice(&sibling.to_le_bytes());
+            file_meta.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            file_meta.extend_from_slice(&(contents.len() as u64).to_le_bytes());
+            file_meta.extend_from_slice(&ROMFS_NONE.to_le_bytes());
+            file_meta.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            file_meta.extend_from_slice(name.as_bytes());
+            pad4(&mut file_meta);
+
+            data.extend_from_slice(contents);
+            pad4(&mut data);
+        }
+
+        let data_offset = ROMFS_HEADER_SIZE;
+        let dir_meta_offset = data_offset + data.len() as u64;
+        let file_meta_offset = dir_meta_offset + dir_meta.len() as u64;
+
+        let mut result = Vec::new();
+        for v in [
+            ROMFS_HEADER_SIZE,
+            // the hash tables are not used
+            dir_meta_offset,
+            0,
+            dir_meta_offset,
+            dir_meta.len() as u64,
+            file_meta_offset,
+            0,
+            file_meta_offset,
+            file_meta.len() as u64,
+            data_offset,
+        ] {
+            result.extend_from_slice(&v.to_le_bytes());
+        }
+        result.extend(data);
+        result.extend(dir_meta);
+        result.extend(file_meta);
+        result
+    }
+
+    /// Wraps a RomFS image into a decrypted NCA
+    fn build_nca(romfs: &[u8]) -> Vec<u8> {
+        let section_start = 0xc00u64;
+        let mut result = vec![0; section_start as usize];
+        result[0x200..0x204].copy_from_slice(b"NCA3");
+
+        let section_end = section_start + ((romfs.len() as u64 + 0x1ff) & !0x1ff);
+        result[0x240..0x244]
+            .copy_from_slice(&((section_start / NCA_MEDIA_UNIT) as u32).to_le_bytes());
+        result[0x244..0x248]
+            .copy_from_slice(&((section_end / NCA_MEDIA_UNIT) as u32).to_le_bytes());
+
+        let fs_header = &mut result[0x400..0x600];
+        fs_header[2] = NCA_FS_TYPE_ROMFS;
+        fs_header[3] = NCA_HASH_TYPE_IVFC;
+        fs_header[4] = NCA_ENCRYPTION_NONE;
+        fs_header[8..12].copy_from_slice(b"IVFC");
+        fs_header[0x14..0x18].copy_from_slice(&7u32.to_le_bytes());
+        // no hash levels, the data level is at the start of the section
+        let data_level = 0x18 + 5 * 0x18;
+        fs_header[data_level + 8..data_level + 16]
+            .copy_from_slice(&(romfs.len() as u64).to_le_bytes());
+
+        result.extend_from_slice(romfs);
+        result.resize(section_end as usize, 0);
+        result
+    }
+
+    /// Wraps the files into a PFS0 archive
+    fn build_pfs0(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut string_table = Vec::new();
+        let mut entries = Vec::new();
+        let mut data = Vec::new();
+        for (name, contents) in files {
+            entries.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            entries.extend_from_slice(&(contents.len() as u64).to_le_bytes());
+            entries.extend_from_slice(&(string_table.len() as u32).to_le_bytes());
+            entries.extend_from_slice(&0u32.to_le_bytes());
+            string_table.extend_from_slice(name.as_bytes());
+            string_table.push(0);
+            data.extend_from_slice(contents);
+        }
+        pad4(&mut string_table);
+
+        let mut result = b"PFS0".to_vec();
+        result.extend_from_slice(&(files.len() as u32).to_le_bytes());
+        result.extend_from_slice(&(string_table.len() as u32).to_le_bytes());
+        result.extend_from_slice(&0u32.to_le_bytes());
+        result.extend(entries);
+        result.extend(string_table);
+        result.extend(data);
+        result
+    }
+
+    fn read_data_rom(container: Vec<u8>) -> Vec<u8> {
+        let mut reader = open_data_rom(Cursor::new(container)).unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    const FAKE_ROM: &[u8] = b"ROM2 is not really a valid rom, but it's enough here";
+
+    #[test]
+    fn plain_rom() {
+        assert_eq!(read_data_rom(FAKE_ROM.to_vec()), FAKE_ROM);
+    }
+
+    #[test]
+    fn romfs() {
+        let romfs = build_romfs(&[("icon.png", b"not an icon"), ("data.rom", FAKE_ROM)]);
+        assert_eq!(read_data_rom(romfs), FAKE_ROM);
+    }
+
+    #[test]
+    fn nsp() {
+        let romfs = build_romfs(&[("data.rom", FAKE_ROM)]);
+        let nca = build_nca(&romfs);
+        let nsp = build_pfs0(&[("ticket.tik", b"whatever"), ("program.nca", &nca)]);
+        assert_eq!(read_data_rom(nsp), FAKE_ROM);
+    }
+
+    #[test]
+    fn missing_data_rom() {
+        let romfs = build_romfs(&[("icon.png", b"not an icon")]);
+        assert!(open_data_rom(Cursor::new(romfs)).is_err());
+        assert!(open_data_rom(Cursor::new(b"garbage".to_vec())).is_err());
+    }
+
+    /// Points the sibling of the first entry of the table at `meta_offset_index` (in the header) at itself
+    fn make_sibling_loop(romfs: &mut [u8], meta_offset_index: usize) {
+        let header_offset = meta_offset_index * 8;
+        let meta_offset =
+            u64::from_le_bytes(romfs[header_offset..header_offset + 8].try_into().unwrap())
+                as usize;
+        romfs[meta_offset + 4..meta_offset + 8].copy_from_slice(&0u32.to_le_bytes());
+    }
+
+    #[test]
+    fn romfs_file_loop() {
+        let mut romfs = build_romfs(&[("icon.png", b"not an icon")]);
+        make_sibling_loop(&mut romfs, 7);
+        let err = open_data_rom(Cursor::new(romfs)).err().unwrap();
+        assert!(format!("{:#}", err).contains("loop"), "{:#}", err);
+    }
+
+    #[test]
+    fn romfs_dir_loop() {
+        let mut romfs = build_romfs(&[("data.rom", FAKE_ROM)]);
+        // the root is its own only child & sibling
+        let dir_meta_offset = u64::from_le_bytes(romfs[24..32].try_into().unwrap()) as usize;
+        romfs[dir_meta_offset + 8..dir_meta_offset + 12].copy_from_slice(&0u32.to_le_bytes());
+        make_sibling_loop(&mut romfs, 3);
+
+        let range = 0..romfs.len() as u64;
+        let romfs = RomFs::read(&mut Cursor::new(romfs), &range).unwrap();
+        let err = romfs.find_file("/a/data.rom").err().unwrap();
+        assert!(format!("{:#}", err).contains("loop"), "{:#}", err);
+        assert!(romfs.find_file(DATA_ROM_PATH).is_ok());
+    }
+
+    #[test]
+    fn sub_reader_seek() {
+        let mut reader = SubReader::new(Cursor::new((0..100u8).collect::<Vec<_>>()), 10..20);
+        let mut buf = [0; 4];
+        reader.seek(SeekFrom::End(-2)).unwrap();
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], &[18, 19]);
+        reader.seek(SeekFrom::Start(1)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [11, 12, 13, 14]);
+    }
+
+    /// Counts the seeks done on the underlying stream
+    struct SeekCounter {
+        inner: Cursor<Vec<u8>>,
+        seeks: usize,
+    }
+
+    impl Read for SeekCounter {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for SeekCounter {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.seeks += 1;
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn sub_reader_seeks_only_when_needed() {
+        let inner = SeekCounter {
+            inner: Cursor::new((0..100u8).collect::<Vec<_>>()),
+            seeks: 0,
+        };
+        let mut reader = SubReader::new(inner, 10..20);
+        let mut buf = [0; 4];
+
+        reader.read_exact(&mut buf).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [14, 15, 16, 17]);
+        assert_eq!(reader.inner.seeks, 1);
+
+        // seeking to the current position does not make the next read seek
+        reader.seek(SeekFrom::Start(8)).unwrap();
+        reader.read_exact(&mut buf[..2]).unwrap();
+        assert_eq!(reader.inner.seeks, 1);
+        // reading at the end does not touch the underlying stream
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(reader.inner.seeks, 1);
+
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [10, 11, 12, 13]);
+        assert_eq!(reader.inner.seeks, 2);
+    }
+
+    #[test]
+    fn romfs_table_out_of_bounds() {
+        let mut romfs = build_romfs(&[("data.rom", FAKE_ROM)]);
+        // file_meta_size
+        romfs[0x40..0x48].copy_from_slice(&u64::MAX.to_le_bytes());
+        let err = open_data_rom(Cursor::new(romfs)).err().unwrap();
+        assert!(format!("{:#}", err).contains("out of bounds"), "{:#}", err);
+    }
+}
@@ -0,0 +1,174 @@
+//! Support for `/sysse.bin`, storing the system sound effects (menu cursor, confirm, cancel, etc.)
+//!
+//! The file starts with a table: the number of sounds followed by an offset (from the start of the file) of each sound.
+//! Each sound is a complete NXA file (see [audio](super::audio)), its size is taken from the NXA header.
+//! The index of a sound in the table is what the `SYSSE` command refers to.
+
+use crate::format::audio::{read_audio, AudioFile};
+use anyhow::{bail, Context, Result};
+use binrw::{binread, BinReaderExt};
+use std::io;
+use std::sync::Arc;
+
+#[binread]
+#[derive(Debug)]
+#[br(little)]
+struct SysseHeader {
+    #[br(temp)]
+    count: u32,
+    #[br(count = count)]
+    offsets: Vec<u32>,
+}
+
+const NXA_MAGIC: &[u8; 4] = b"NXA1";
+
+pub struct SystemSoundBundle {
+    sounds: Vec<Arc<AudioFile>>,
+}
+
+impl SystemSoundBundle {
+    pub fn get(&self, index: usize) -> Option<&Arc<AudioFile>> {
+        self.sounds.get(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sounds.is_empty()
+    }
+
+    /// Total size of the encoded sounds, in bytes
+    pub fn size_in_bytes(&self) -> usize {
+        self.sounds.iter().map(|s| s.size_in_bytes()).sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<AudioFile>> {
+        self.sounds.iter()
+    }
+}
+
+pub fn read_system_sounds(data: &[u8]) -> Result<SystemSoundBundle> {
+    let header: SysseHeader = io::Cursor::new(data)
+        .read_le()
+        .context("Reading the sysse header")?;
+
+    let table_end = 4 + 4 * header.offsets.len();
+
+    let sounds = header
+        .offsets
+        .iter()
+        .enumerate()
+        .map(|(index, &offset)| {
+            let offset = offset as usize;
+            if offset < table_end {
+                bail!(
+                    "System sound {} at 0x{:x} overlaps the offset table",
+                    index,
+                    offset
+                );
+            }
+
+            let nxa_header = data.get(offset..offset + 12).with_context(|| {
+                format!("System sound {} at 0x{:x} is out of bounds", index, offset)
+            })?;
+            if &nxa_header[..4] != NXA_MAGIC {
+                bail!(
+                    "System sound {} at 0x{:x} is not an NXA file",
+                    index,
+                    offset
+                );
+            }
+            let file_size = u32::from_le_bytes(nxa_header[8..12].try_into().unwrap()) as usize;
+
+            let file = data.get(offset..offset + file_size).with_context(|| {
+                format!(
+                    "System sound {} at 0x{:x} ({} bytes) extends past the end of the file",
+                    index, offset, file_size
+                )
+            })?;
+
+            let audio = read_audio(file)
+                .with_context(|| format!("Parsing system sound {} at 0x{:x}", index, offset))?;
+            Ok(Arc::new(audio))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(SystemSoundBundle { sounds })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::audio::{encode_audio, AudioEncoderSettings};
+
+    fn sound(length: usize) -> Vec<u8> {
+        let samples = (0..length)
+            .map(|i| (i as f32 * 0.05).sin() * 0.25)
+            .collect::<Vec<_>>();
+        encode_audio(&samples, 1, 48000, &AudioEncoderSettings::default()).unwrap()
+    }
+
+    /// Lays out the sounds after the offset table, in order, aligned to 16 bytes
+    fn make_bundle(sounds: &[Vec<u8>]) -> (Vec<u8>, Vec<u32>) {
+        let mut offsets = Vec::new();
+        let mut offset = (4 + 4 * sounds.len() + 0xf) & !0xf;
+        for sound in sounds {
+            offsets.push(offset as u32);
+            offset = (offset + sound.len() + 0xf) & !0xf;
+        }
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&(sounds.len() as u32).to_le_bytes());
+        for offset in &offsets {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        for (sound, &offset) in sounds.iter().zip(&offsets) {
+            data.resize(offset as usize, 0);
+            data.extend_from_slice(sound);
+        }
+        (data, offsets)
+    }
+
+    #[test]
+    fn read_bundle() {
+        let (data, _) = make_bundle(&[sound(4800), sound(9600), sound(2400)]);
+
+        let bundle = read_system_sounds(&data).unwrap();
+        assert_eq!(bundle.len(), 3);
+        for (audio, length) in bundle.iter().zip([4800, 9600, 2400]) {
+            assert_eq!(audio.info().num_samples, length);
+            assert_eq!(audio.info().channel_count, 1);
+        }
+    }
+
+    #[test]
+    fn sounds_are_indexed_by_the_table() {
+        // the table doesn't have to be in file order
+        let (mut data, offsets) = make_bundle(&[sound(4800), sound(9600)]);
+        data[4..8].copy_from_slice(&offsets[1].to_le_bytes());
+        data[8..12].copy_from_slice(&offsets[0].to_le_bytes());
+
+        let bundle = read_system_sounds(&data).unwrap();
+        assert_eq!(bundle.get(0).unwrap().info().num_samples, 9600);
+        assert_eq!(bundle.get(1).unwrap().info().num_samples, 4800);
+    }
+
+    #[test]
+    fn invalid_offsets() {
+        let (data, offsets) = make_bundle(&[sound(4800)]);
+
+        let mut not_nxa = data.clone();
+        not_nxa[4..8].copy_from_slice(&(offsets[0] + 4).to_le_bytes());
+        assert!(read_system_sounds(&not_nxa).is_err());
+
+        let mut out_of_bounds = data.clone();
+        out_of_bounds[4..8].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        assert!(read_system_sounds(&out_of_bounds).is_err());
+
+        let mut in_table = data;
+        in_table[4..8].copy_from_slice(&0u32.to_le_bytes());
+        assert!(read_system_sounds(&in_table).is_err());
+    }
+}
@@ -125,6 +125,70 @@ pub fn read_sjis_string<T: io::Read>(s: &mut T, byte_size: Option<usize>) -> io:
     Ok(res)
 }
 
+/// Maps characters back to their (a variant of) Shift-JIS encoding
+///
+/// Built by inverting the decoding tables, single-byte encodings are preferred when a character has both.
+static SJIS_ENCODE_TABLE: Lazy<HashMap<char, u16>> = Lazy::new(|| {
+    let mut table = HashMap::new();
+
+    for (i, &c) in ASCII_TABLE.iter().enumerate() {
+        table.entry(c).or_insert(0x20 + i as u16);
+    }
+    for (i, &c) in KATAKANA_TABLE.iter().enumerate() {
+        table.entry(c).or_insert(0xa0 + i as u16);
+    }
+    for (addr, &c) in JIS_TABLE.iter().enumerate() {
+        if c == '\0' {
+            continue;
+        }
+        // the inverse of the address computation in `convert_double_sjis_char`
+        let (pair, column) = (addr / 188, addr % 188);
+        let first = if pair < 31 {
+            0x81 + pair
+        } else {
+            0xe0 + pair - 31
+        };
+        let second = if column < 0x3f {
+            column + 0x40
+        } else {
+            column + 0x41
+        };
+        table
+            .entry(c)
+            .or_insert(((first as u16) << 8) | second as u16);
+    }
+
+    table
+});
+
+/// Converts UTF-8 to (a variant of) Shift-JIS, the inverse of [read_sjis_string]
+///
+/// The null terminator is not included in the output.
+pub fn encode_sjis_string(s: &str) -> io::Result<Vec<u8>> {
+    let mut res = Vec::with_capacity(s.len());
+
+    for c in s.chars() {
+        if (c as u32) < 0x20 {
+            res.push(c as u8);
+            continue;
+        }
+
+        let &code = SJIS_ENCODE_TABLE.get(&c).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("character {:?} can't be encoded in Shift-JIS", c),
+            )
+        })?;
+        if code > 0xff {
+            res.extend_from_slice(&code.to_be_bytes());
+        } else {
+            res.push(code as u8);
+        }
+    }
+
+    Ok(res)
+}
+
 const FIXUP_ENCODED: &str = "｢｣ｧｨｩｪｫｬｭｮｱｲｳｴｵｶｷｸｹｺｻｼｽｾｿﾀﾁﾂﾃﾄﾅﾆﾇﾈﾉﾊﾋﾌﾍﾎﾏﾐﾑﾒﾓﾔﾕﾖﾗﾘﾙﾚﾛﾜｦﾝｰｯ､ﾟﾞ･?｡";
 const FIXUP_DECODED: &str = "「」ぁぃぅぇぉゃゅょあいうえおかきくけこさしすせそたちつてとなにぬねのはひふへほまみむめもやゆよらりるれろわをんーっ、？！…　。";
 
@@ -161,6 +225,20 @@ mod tests {
         assert_eq!(s, "あいうえお");
     }
 
+    #[test]
+    fn test_sjis_encode() {
+        let s = "Hello, あいうえお ｱｲｳ 漢字！";
+        let encoded = encode_sjis_string(s).unwrap();
+        let decoded =
+            read_sjis_string(&mut io::Cursor::new(&encoded), Some(encoded.len())).unwrap();
+        assert_eq!(decoded, s);
+
+        assert_eq!(
+            encode_sjis_string("あいうえお").unwrap(),
+            b"\x82\xa0\x82\xa2\x82\xa4\x82\xa6\x82\xa8"
+        );
+    }
+
     include!("sjis_to_utf8_tests.rs");
     include!("sjis_unmapped_tests.rs");
 }
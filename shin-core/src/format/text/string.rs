@@ -38,24 +38,37 @@ pub trait StringLengthDesc:
 {
     /// Should return the length of the string, in bytes, including the null terminator.
     fn get_length(&self) -> Option<usize>;
+    /// Should construct the length descriptor for a string of `length` bytes, including the null terminator.
+    ///
+    /// Returns `None` if the length can't be represented.
+    fn from_length(length: usize) -> Option<Self>;
 }
 
 impl StringLengthDesc for u8 {
     fn get_length(&self) -> Option<usize> {
         Some(*self as usize)
     }
+    fn from_length(length: usize) -> Option<Self> {
+        length.try_into().ok()
+    }
 }
 
 impl StringLengthDesc for u16 {
     fn get_length(&self) -> Option<usize> {
         Some(*self as usize)
     }
+    fn from_length(length: usize) -> Option<Self> {
+        length.try_into().ok()
+    }
 }
 
 impl StringLengthDesc for () {
     fn get_length(&self) -> Option<usize> {
         None
     }
+    fn from_length(_: usize) -> Option<Self> {
+        Some(())
+    }
 }
 
 /// A string that is encoded in Shift-JIS when written to a file.
@@ -96,11 +109,24 @@ impl<L: StringLengthDesc, F: StringFixup> BinWrite for SJisString<L, F> {
 
     fn write_options<W: Write + Seek>(
         &self,
-        _writer: &mut W,
-        _endian: Endian,
+        writer: &mut W,
+        endian: Endian,
         _: (),
     ) -> BinResult<()> {
-        todo!()
+        // TODO: extra allocation in case of no fixup
+        let encoded = text::encode_sjis_string(&F::encode(self.0.clone()))?;
+
+        // "+ 1" for the null terminator
+        let len = L::from_length(encoded.len() + 1).ok_or_else(|| binrw::Error::AssertFail {
+            pos: writer.stream_position().unwrap_or_default(),
+            message: format!("String is too long: {} bytes", encoded.len()),
+        })?;
+        len.write_options(writer, endian, ())?;
+
+        writer.write_all(&encoded)?;
+        writer.write_all(&[0])?;
+
+        Ok(())
     }
 }
 impl<L: StringLengthDesc, F: StringFixup> AsRef<str> for SJisString<L, F> {
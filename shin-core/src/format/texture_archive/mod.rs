@@ -1,12 +1,15 @@
-//! Support for decoding TXA texture archives.
+//! Support for decoding and encoding TXA texture archives.
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use binrw::{BinRead, BinWrite};
 use image::RgbaImage;
 use rayon::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Seek, Write};
+use std::marker::PhantomData;
 
-use crate::format::text::ZeroString;
+use crate::format::picture::{write_texture, EncodedTexture};
+use crate::format::text::{encode_sjis_string, SJisString, ZeroString};
 
 #[derive(BinRead, BinWrite, Debug)]
 #[brw(little, magic = b"TXA4")]
@@ -39,6 +42,7 @@ struct TxaIndexEntry {
 }
 
 pub struct TextureArchive {
+    pub use_dict_encoding: bool,
     pub textures: Vec<RgbaImage>,
     pub name_to_index: HashMap<String, usize>,
     pub vindex_to_index: HashMap<u16, usize>,
@@ -112,8 +116,197 @@ pub fn read_texture_archive(source: &[u8]) -> Result<TextureArchive> {
         .collect();
 
     Ok(TextureArchive {
+        use_dict_encoding: header.use_dict_encoding != 0,
         textures,
         name_to_index,
         vindex_to_index,
     })
 }
+
+/// Size of the fixed part of the [TxaIndexEntry], without the name
+const INDEX_ENTRY_FIXED_SIZE: usize = 20;
+
+/// Builds a TXA archive from a set of textures
+pub struct TextureArchiveBuilder {
+    use_dict_encoding: bool,
+    textures: Vec<(String, u16, RgbaImage)>,
+}
+
+impl TextureArchiveBuilder {
+    /// Dictionary encoding stores the textures with a 256-color palette,
+    /// textures with more colors are quantized, so it is lossy in general
+    pub fn new(use_dict_encoding: bool) -> Self {
+        Self {
+            use_dict_encoding,
+            textures: Vec::new(),
+        }
+    }
+
+    pub fn add_texture(&mut self, name: String, virtual_index: u16, image: RgbaImage) {
+        self.textures.push((name, virtual_index, image));
+    }
+
+    pub fn write<W: Write + Seek>(&self, writer: &mut W) -> Result<()> {
+        let mut names = HashSet::new();
+        let mut virtual_indices = HashSet::new();
+        for (name, virtual_index, image) in &self.textures {
+            if !names.insert(name) {
+                bail!("Duplicate texture name: {:?}", name);
+            }
+            if !virtual_indices.insert(virtual_index) {
+                bail!("Duplicate virtual index: {}", virtual_index);
+            }
+            if image.width() > u16::MAX as u32 || image.height() > u16::MAX as u32 {
+                bail!(
+                    "Texture {:?} is too large: {}x{}",
+                    name,
+                    image.width(),
+                    image.height()
+                );
+            }
+        }
+
+        // textures are independent, so they can be compressed in parallel
+        let encoded_textures = self
+            .textures
+            .par_iter()
+            .map(|(_, _, image)| write_texture(image, self.use_dict_encoding, true))
+            .collect::<Vec<EncodedTexture>>();
+
+        let index = self
+            .textures
+            .iter()
+            .zip(&encoded_textures)
+            .map(
+                |((name, virtual_index, image), encoded)| -> Result<TxaIndexEntry> {
+                    let name_size = encode_sjis_string(name)
+                        .with_context(|| format!("Encoding texture name {:?}", name))?
+                        .len()
+                        + 1;
+                    Ok(TxaIndexEntry {
+                        // entries are padded to 4 bytes
+                        entry_length: ((INDEX_ENTRY_FIXED_SIZE + name_size + 3) & !3) as u16,
+                        virtual_index: *virtual_index,
+                        width: image.width() as u16,
+                        height: image.height() as u16,
+                        // filled in when the index size is known
+                        data_offset: 0,
+                        data_compressed_size: encoded.compressed_size as u32,
+                        data_decompressed_size: encoded.decompressed_size as u32,
+                        name: SJisString(name.clone(), PhantomData),
+                    })
+                },
+            )
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut header = TxaHeader {
+            version: 2,
+            file_size: 0,
+            use_dict_encoding: self.use_dict_encoding as u32,
+            count: index.len() as u32,
+            max_decompressed_size: encoded_textures
+                .iter()
+                .map(|v| v.decompressed_size as u32)
+                .max()
+                .unwrap_or(0),
+            index_size: 0,
+            index,
+        };
+
+        // the header size does not depend on the offsets, so write it once to learn the size
+        let mut header_buffer = Cursor::new(Vec::new());
+        header.write(&mut header_buffer)?;
+        let header_size = header_buffer.get_ref().len();
+        header.index_size = header.index.iter().map(|v| v.entry_length as u32).sum();
+
+        let mut offset = (header_size + 0xf) & !0xf;
+        for (entry, encoded) in header.index.iter_mut().zip(&encoded_textures) {
+            entry.data_offset = offset as u32;
+            offset = (offset + encoded.data.len() + 0xf) & !0xf;
+        }
+        header.file_size = offset as u32;
+
+        let mut output = Cursor::new(Vec::with_capacity(offset));
+        header.write(&mut output)?;
+        let mut output = output.into_inner();
+        for (entry, encoded) in header.index.iter().zip(&encoded_textures) {
+            output.resize(entry.data_offset as usize, 0);
+            output.extend_from_slice(&encoded.data);
+        }
+        output.resize(offset, 0);
+
+        writer.write_all(&output)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn roundtrip(builder: &TextureArchiveBuilder) -> TextureArchive {
+        let mut output = Cursor::new(Vec::new());
+        builder.write(&mut output).unwrap();
+        read_texture_archive(output.get_ref()).unwrap()
+    }
+
+    #[test]
+    fn write_and_read_dict() {
+        let palette = [
+            [0, 0, 0, 0],
+            [255, 0, 0, 255],
+            [0, 255, 0, 128],
+            [1, 2, 3, 4],
+        ];
+        let image = RgbaImage::from_fn(13, 7, |x, y| {
+            image::Rgba(palette[((x * 3 + y) % palette.len() as u32) as usize])
+        });
+
+        let mut builder = TextureArchiveBuilder::new(true);
+        builder.add_texture("first".to_string(), 5, image.clone());
+        builder.add_texture("テクスチャ".to_string(), 2, RgbaImage::new(1, 1));
+
+        let archive = roundtrip(&builder);
+        assert_eq!(archive.textures.len(), 2);
+        assert_eq!(archive.get_texture("first"), Some(&image));
+        assert_eq!(archive.get_texture_by_vindex(5), Some(&image));
+        assert_eq!(
+            archive.get_texture_by_vindex(2),
+            archive.get_texture("テクスチャ")
+        );
+    }
+
+    #[test]
+    fn write_and_read_quantized() {
+        let image = RgbaImage::from_fn(64, 64, |x, y| {
+            image::Rgba([x as u8 * 4, y as u8 * 4, 0, 255])
+        });
+
+        let mut builder = TextureArchiveBuilder::new(true);
+        builder.add_texture("gradient".to_string(), 0, image.clone());
+
+        let archive = roundtrip(&builder);
+        let decoded = archive.get_texture("gradient").unwrap();
+        assert_eq!(decoded.dimensions(), image.dimensions());
+        for (a, b) in decoded.pixels().zip(image.pixels()) {
+            for i in 0..4 {
+                assert!((a.0[i] as i32 - b.0[i] as i32).abs() < 32);
+            }
+        }
+    }
+
+    #[test]
+    fn write_and_read_differential() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let image = RgbaImage::from_fn(17, 9, |_, _| image::Rgba(rng.gen()));
+
+        let mut builder = TextureArchiveBuilder::new(false);
+        builder.add_texture("noise".to_string(), 1, image.clone());
+
+        let archive = roundtrip(&builder);
+        assert_eq!(archive.get_texture("noise"), Some(&image));
+    }
+}
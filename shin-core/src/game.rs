@@ -0,0 +1,246 @@
+//! Parameters that differ between the games running on the shin engine.
+//!
+//! Most of the engine was reverse-engineered from Umineko, so it is the only fully supported game.
+//! The other games have profiles so that they can be chosen on the command line,
+//! but the fields that differ from Umineko are not known for them yet and are marked as such (`None` or [ASSUMED_ASSET_PATHS]).
+
+use crate::format::save::Savedata;
+use crate::format::scenario::Scenario;
+use crate::vm::command::types::MessageboxType;
+use anyhow::{anyhow, bail, Result};
+use bytes::Bytes;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GameId {
+    Umineko,
+    HigurashiHou,
+    Kaleidoscope,
+    Konosuba,
+    SugarStyle,
+}
+
+impl GameId {
+    pub const ALL: [GameId; 5] = [
+        GameId::Umineko,
+        GameId::HigurashiHou,
+        GameId::Kaleidoscope,
+        GameId::Konosuba,
+        GameId::SugarStyle,
+    ];
+
+    /// Short name, used on the command line
+    pub fn short_name(self) -> &'static str {
+        match self {
+            GameId::Umineko => "umineko",
+            GameId::HigurashiHou => "higurashi",
+            GameId::Kaleidoscope => "kaleidoscope",
+            GameId::Konosuba => "konosuba",
+            GameId::SugarStyle => "sugarstyle",
+        }
+    }
+}
+
+impl Display for GameId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.short_name())
+    }
+}
+
+impl FromStr for GameId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        GameId::ALL
+            .into_iter()
+            .find(|id| id.short_name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown game {:?}, expected one of: {}",
+                    s,
+                    GameId::ALL.map(|id| id.short_name()).join(", ")
+                )
+            })
+    }
+}
+
+/// The variant of the scenario format: command opcodes and the layout of the info tables
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScenarioDialect {
+    /// The one implemented by [Scenario] and [crate::vm::command]
+    Umineko,
+}
+
+impl ScenarioDialect {
+    pub fn load_scenario(self, data: Bytes) -> Result<Scenario> {
+        match self {
+            ScenarioDialect::Umineko => Scenario::new(data),
+        }
+    }
+}
+
+/// Paths of the assets the engine loads by name (as opposed to the ones referenced by the scenario)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AssetPaths {
+    pub scenario: &'static str,
+    pub system_font: &'static str,
+    pub msgtex: &'static str,
+    pub sysse: &'static str,
+    pub medium_font: &'static str,
+    pub bold_font: &'static str,
+}
+
+const UMINEKO_ASSET_PATHS: AssetPaths = AssetPaths {
+    scenario: "/main.snr",
+    system_font: "/system.fnt",
+    msgtex: "/msgtex.txa",
+    sysse: "/sysse.bin",
+    medium_font: "/newrodin-medium.fnt",
+    bold_font: "/newrodin-bold.fnt",
+};
+
+/// Asset paths for the games whose layout was not checked, assumed to be the same as Umineko's because they share the engine
+const ASSUMED_ASSET_PATHS: AssetPaths = UMINEKO_ASSET_PATHS;
+
+#[derive(Debug)]
+pub struct GameProfile {
+    pub id: GameId,
+    pub title: &'static str,
+    /// Nintendo Switch title id, `None` if not known
+    pub title_id: Option<u64>,
+    /// The string the save obfuscation key is derived from, `None` if not known
+    pub save_key_seed: Option<&'static str>,
+    /// The command opcodes & info tables layout, `None` if the dialect used by the game is not known yet
+    pub scenario_dialect: Option<ScenarioDialect>,
+    pub asset_paths: AssetPaths,
+    /// Messagebox styles the game uses, the others are rendered as [MessageboxType::Neutral]
+    ///
+    /// `None` if not known, all the styles are accepted then
+    pub messagebox_types: Option<&'static [MessageboxType]>,
+}
+
+pub static PROFILES: [GameProfile; 5] = [
+    GameProfile {
+        id: GameId::Umineko,
+        title: "Umineko no Naku Koro ni Saku ~Nekobako to Musou no Koukyoukyoku~",
+        title_id: Some(0x01006A300BA2C000),
+        save_key_seed: Some("うみねこのなく頃に咲"),
+        scenario_dialect: Some(ScenarioDialect::Umineko),
+        asset_paths: UMINEKO_ASSET_PATHS,
+        messagebox_types: Some(&[
+            MessageboxType::Neutral,
+            MessageboxType::WitchSpace,
+            MessageboxType::Ushiromiya,
+            MessageboxType::Transparent,
+            MessageboxType::Novel,
+            MessageboxType::NoText,
+        ]),
+    },
+    GameProfile {
+        id: GameId::HigurashiHou,
+        title: "Higurashi no Naku Koro ni Hou",
+        title_id: None,
+        save_key_seed: None,
+        scenario_dialect: None,
+        asset_paths: ASSUMED_ASSET_PATHS,
+        messagebox_types: None,
+    },
+    GameProfile {
+        id: GameId::Kaleidoscope,
+        title: "Gensou Rougoku no Kaleidoscope",
+        title_id: None,
+        save_key_seed: None,
+        scenario_dialect: None,
+        asset_paths: ASSUMED_ASSET_PATHS,
+        messagebox_types: None,
+    },
+    GameProfile {
+        id: GameId::Konosuba,
+        title:
+            "Kono Subarashii Sekai ni Shukufuku wo! Kibou no Meikyuu to Tsudoishi Boukenshata Plus",
+        title_id: None,
+        save_key_seed: None,
+        scenario_dialect: None,
+        asset_paths: ASSUMED_ASSET_PATHS,
+        messagebox_types: None,
+    },
+    GameProfile {
+        id: GameId::SugarStyle,
+        title: "Sugar*Style",
+        title_id: None,
+        save_key_seed: None,
+        scenario_dialect: None,
+        asset_paths: ASSUMED_ASSET_PATHS,
+        messagebox_types: None,
+    },
+];
+
+impl GameProfile {
+    pub fn get(id: GameId) -> &'static GameProfile {
+        PROFILES.iter().find(|p| p.id == id).unwrap()
+    }
+
+    pub fn by_title_id(title_id: u64) -> Option<&'static GameProfile> {
+        PROFILES.iter().find(|p| p.title_id == Some(title_id))
+    }
+
+    /// Detect the game by trying to load its scenario with the supported dialects
+    pub fn detect(scenario_data: &Bytes) -> Result<&'static GameProfile> {
+        PROFILES
+            .iter()
+            .find(|profile| profile.load_scenario(scenario_data.clone()).is_ok())
+            .ok_or_else(|| {
+                anyhow!("Could not detect the game: the scenario is not in any of the supported formats")
+            })
+    }
+
+    pub fn save_key(&self) -> Result<u32> {
+        match self.save_key_seed {
+            Some(seed) => Ok(Savedata::obfuscation_key_from_seed(seed)),
+            None => bail!("The save key for {} is not known", self.title),
+        }
+    }
+
+    pub fn load_scenario(&self, data: Bytes) -> Result<Scenario> {
+        match self.scenario_dialect {
+            Some(dialect) => dialect.load_scenario(data),
+            None => bail!("The scenario format of {} is not supported yet", self.title),
+        }
+    }
+
+    pub fn supports_messagebox_type(&self, messagebox_type: MessageboxType) -> bool {
+        self.messagebox_types
+            .map_or(true, |types| types.contains(&messagebox_type))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_are_complete() {
+        for id in GameId::ALL {
+            assert_eq!(GameProfile::get(id).id, id);
+            assert_eq!(id.short_name().parse::<GameId>().unwrap(), id);
+        }
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let umineko = GameProfile::get(GameId::Umineko);
+        assert!(umineko.save_key().is_ok());
+        assert_eq!(
+            GameProfile::by_title_id(0x01006A300BA2C000).map(|p| p.id),
+            Some(GameId::Umineko)
+        );
+        assert!(umineko.supports_messagebox_type(MessageboxType::WitchSpace));
+
+        let higurashi = GameProfile::get(GameId::HigurashiHou);
+        assert!(higurashi.save_key().is_err());
+        assert!(higurashi.load_scenario(Bytes::new()).is_err());
+        // the styles are not known, so none of them are replaced
+        assert!(higurashi.supports_messagebox_type(MessageboxType::WitchSpace));
+    }
+}
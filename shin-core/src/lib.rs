@@ -7,6 +7,7 @@
 extern crate self as shin_core;
 
 pub mod format;
+pub mod game;
 pub mod layout;
 pub mod time;
 pub mod vm;
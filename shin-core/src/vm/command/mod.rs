@@ -212,6 +212,8 @@ pub enum Command {
         target_status: NumberSpec,
     },
     /// ¿Play an SE without a slot?
+    ///
+    /// The engine assumes that the arguments mirror those of [SEPLAY](Command::SEPLAY) without the slot (`arg1` is the SE id, `arg2` the volume and `arg3` the pan)
     #[cmd(opcode = 0x9bu8)]
     SEONCE {
         arg1: NumberSpec,
@@ -236,7 +238,11 @@ pub enum Command {
     },
     /// Play a system sound effect (from `/sysse.bin`)
     #[cmd(opcode = 0x9fu8)]
-    SYSSE { arg1: NumberSpec, arg2: NumberSpec },
+    SYSSE {
+        /// Index of the sound in `/sysse.bin`
+        sysse_id: NumberSpec,
+        arg2: NumberSpec,
+    },
 
     /// Set current save info at specified level
     /// (0 - scenario name, 1 - chapter name)
@@ -33,15 +33,20 @@ pub enum LayerProperty {
     Rotation = 18,
     Rotation2 = 19,
 
+    // Color effects. The meaning is guessed from the initial values
+    /// Negative, in 1000th of the full inversion
     Prop20 = 20,
+    /// Sepia, in 1000th of the full tint
     Prop21 = 21,
 
     ShowLayer = 22,
+    /// Added to the red, green, blue & alpha channels, in 1000th
     Prop23 = 23,
     Prop24 = 24,
     Prop25 = 25,
     Prop26 = 26,
     Prop27 = 27,
+    /// Multiplier of the red, green, blue & alpha channels, in 1000th
     Prop28 = 28,
     Prop29 = 29,
     Prop30 = 30,
@@ -94,8 +99,10 @@ pub enum LayerProperty {
     Prop68 = 68,
 
     // "Blur" effect
+    /// Blur radius, in virtual pixels
     Prop69 = 69,
     // "Pixelize" effect
+    /// Mosaic block size, in virtual pixels
     PixelizeSize = 70,
 
     // "Dissolve" Effect, used by the witch
@@ -107,9 +114,12 @@ pub enum LayerProperty {
     Prop74 = 74,
     Prop75 = 75,
 
-    // "Waves" effect
+    // "Waves" effect (a raster scroll)
+    /// Amplitude of the horizontal wave, in virtual pixels
     Prop76 = 76,
+    /// Wavelength, in virtual pixels
     Prop77 = 77,
+    /// Speed, in 1000th of the wave per tick
     Prop78 = 78,
 
     // dunno
@@ -174,6 +184,7 @@ impl LayerProperty {
             TranslateX | TranslateY | TranslateX2 | TranslateY2 |
             WobbleXMode | WobbleXPeriod | WobbleXAmplitude | WobbleXBias |
             WobbleYMode | WobbleYPeriod | WobbleYAmplitude | WobbleYBias |
+            PixelizeSize |
 
             // this one is not, actually, implemented
             // everything seems to work fine, so ignoring it for now
@@ -181,6 +192,30 @@ impl LayerProperty {
             RenderPosition
         )
     }
+
+    /// Whether the property has an implementation based on a guess of its meaning (see the comments on the variants)
+    ///
+    /// These are not [implemented](Self::is_implemented): the engine applies them, but the result might not match the original engine.
+    pub fn is_guessed(&self) -> bool {
+        use LayerProperty::*;
+        matches!(
+            self,
+            Prop20
+                | Prop21
+                | Prop23
+                | Prop24
+                | Prop25
+                | Prop26
+                | Prop28
+                | Prop29
+                | Prop30
+                | Prop31
+                | Prop69
+                | Prop76
+                | Prop77
+                | Prop78
+        )
+    }
 }
 
 impl FromVmCtx<NumberSpec> for LayerProperty {
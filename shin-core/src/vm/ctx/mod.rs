@@ -5,9 +5,19 @@ pub use from_vm_ctx::*;
 use crate::format::scenario::instructions::{
     CodeAddress, Expression, ExpressionTerm, JumpCond, JumpCondType, MemoryAddress, NumberSpec,
 };
+use anyhow::{anyhow, Result};
 use smallvec::SmallVec;
 use tracing::warn;
 
+/// An entry of the call stack
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CallStackSlot {
+    /// Pushed by [gosub](super::Instruction::gosub) and [call](super::Instruction::call)
+    ReturnAddress(CodeAddress),
+    /// Pushed by [push](super::Instruction::push)
+    Value(i32),
+}
+
 /// Contains the full VM state
 ///
 /// It consists of a memory, two stacks (call and data)
@@ -19,7 +29,7 @@ pub struct VmCtx {
     /// Stores the return address for each call instruction
     ///
     /// Also [push](super::Instruction::push) uses this stack for some reason
-    call_stack: Vec<CodeAddress>,
+    call_stack: Vec<CallStackSlot>,
     /// Data stack
     ///
     /// Stores the arguments for each call instruction
@@ -49,6 +59,21 @@ impl VmCtx {
         self.prng_state
     }
 
+    /// Get the main memory (mem1) contents
+    pub fn memory(&self) -> &[i32] {
+        &self.memory
+    }
+
+    /// Get the call stack contents, the most recent entry is the last one
+    pub fn call_stack(&self) -> &[CallStackSlot] {
+        &self.call_stack
+    }
+
+    /// Get the data stack (mem3) contents, the top of the stack is the last element
+    pub fn arguments_stack(&self) -> &[i32] {
+        &self.arguments_stack
+    }
+
     /// Get the value from memory
     ///
     /// The address can be a stack offset (mem3) or main memory address (mem1)
@@ -106,11 +131,51 @@ impl VmCtx {
     }
 
     pub fn push_code_stack(&mut self, addr: CodeAddress) {
-        self.call_stack.push(addr);
+        self.call_stack.push(CallStackSlot::ReturnAddress(addr));
     }
 
     pub fn pop_code_stack(&mut self) -> CodeAddress {
-        self.call_stack.pop().unwrap()
+        match self.call_stack.pop().unwrap() {
+            CallStackSlot::ReturnAddress(addr) => addr,
+            // the game does not seem to do this, but the original engine would allow it
+            CallStackSlot::Value(value) => CodeAddress(value.try_into().unwrap()),
+        }
+    }
+
+    pub fn push_value_stack(&mut self, value: i32) {
+        self.call_stack.push(CallStackSlot::Value(value));
+    }
+
+    pub fn pop_value_stack(&mut self) -> i32 {
+        match self.call_stack.pop().unwrap() {
+            CallStackSlot::ReturnAddress(addr) => addr.0.try_into().unwrap(),
+            CallStackSlot::Value(value) => value,
+        }
+    }
+
+    /// Translate the return addresses on the call stack, see [Scripter::relocate](super::Scripter::relocate)
+    ///
+    /// The values saved with [push](super::Instruction::push) are left as is.
+    /// Fails (leaving the stack unchanged) if any of the return addresses can't be translated.
+    pub(super) fn relocate_call_stack(
+        &mut self,
+        map_address: impl Fn(CodeAddress) -> Option<CodeAddress>,
+    ) -> Result<()> {
+        let call_stack = self
+            .call_stack
+            .iter()
+            .map(|&slot| match slot {
+                CallStackSlot::ReturnAddress(addr) => map_address(addr)
+                    .map(CallStackSlot::ReturnAddress)
+                    .ok_or_else(|| {
+                        anyhow!("Could not find the return address {:?} in the new scenario", addr)
+                    }),
+                CallStackSlot::Value(_) => Ok(slot),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.call_stack = call_stack;
+
+        Ok(())
     }
 
     pub fn push_data_stack_frame(&mut self, val: &[i32]) {
@@ -213,3 +278,34 @@ impl VmCtx {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relocate_only_return_addresses() {
+        let mut ctx = VmCtx::new(0, 0);
+        ctx.push_code_stack(CodeAddress(10));
+        ctx.push_value_stack(10);
+        ctx.push_code_stack(CodeAddress(20));
+
+        ctx.relocate_call_stack(|addr| Some(CodeAddress(addr.0 + 5)))
+            .unwrap();
+        assert_eq!(
+            ctx.call_stack(),
+            [
+                CallStackSlot::ReturnAddress(CodeAddress(15)),
+                CallStackSlot::Value(10),
+                CallStackSlot::ReturnAddress(CodeAddress(25)),
+            ]
+        );
+
+        // a return address that can't be translated fails the whole relocation
+        ctx.relocate_call_stack(|addr| (addr.0 != 25).then_some(CodeAddress(0)))
+            .unwrap_err();
+        assert_eq!(ctx.pop_code_stack(), CodeAddress(25));
+        assert_eq!(ctx.pop_value_stack(), 10);
+        assert_eq!(ctx.pop_code_stack(), CodeAddress(15));
+    }
+}
@@ -32,7 +32,7 @@ use crate::format::scenario::instructions::{
 use crate::format::scenario::{InstructionReader, Scenario};
 use crate::vm::breakpoint::{BreakpointHandle, CodeBreakpointSet};
 use crate::vm::command::{CommandResult, RuntimeCommand};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use smallvec::SmallVec;
 use tracing::{instrument, trace};
 
@@ -236,22 +236,21 @@ impl Scripter {
             }
             Instruction::push { values } => {
                 // unfortunately the game uses the call stack for both code addresses and sometimes data...
-                // we just cast the data provided to CodeOffset and hope for the best
-                // what could go wrong?
+                // the slots remember what they hold, so that the data is not mistaken for code addresses
                 let values = values
                     .0
                     .into_iter()
-                    .map(|v| CodeAddress(self.ctx.get_number(v).try_into().unwrap()))
-                    .collect::<SmallVec<[CodeAddress; 6]>>();
+                    .map(|v| self.ctx.get_number(v))
+                    .collect::<SmallVec<[i32; 6]>>();
                 trace!(?pc, ?values, "push");
 
                 for value in values {
-                    self.ctx.push_code_stack(value)
+                    self.ctx.push_value_stack(value)
                 }
             }
             Instruction::pop { dest } => {
                 let values = (0..dest.0.len())
-                    .map(|_| self.ctx.pop_code_stack().0.try_into().unwrap())
+                    .map(|_| self.ctx.pop_value_stack())
                     .collect::<SmallVec<[i32; 6]>>();
                 trace!(?pc, ?values, "pop");
 
@@ -283,6 +282,12 @@ impl Scripter {
         self.position
     }
 
+    /// Get the VM execution context (memory & stacks)
+    #[inline]
+    pub fn ctx(&self) -> &VmCtx {
+        &self.ctx
+    }
+
     /// Run the VM until a command is encountered
     ///
     /// You should pass the result of the previous command to this function (use `CommandResult::None` if the VM is just starting)
@@ -305,6 +310,28 @@ impl Scripter {
         }
     }
 
+    /// Switch to another version of the scenario, translating the code addresses with `map_address`
+    ///
+    /// This is used to reload the scenario while it's running, the memory is kept intact.
+    /// Fails (leaving the scripter unchanged) if the address of the next instruction or any of the return addresses can't be translated.
+    ///
+    /// Note that the breakpoints are not translated.
+    pub fn relocate(
+        &mut self,
+        scenario: &Scenario,
+        map_address: impl Fn(CodeAddress) -> Option<CodeAddress>,
+    ) -> Result<()> {
+        let next = self.instruction_reader.position();
+        let new_next = map_address(next)
+            .ok_or_else(|| anyhow!("Could not find {:?} in the new scenario", next))?;
+
+        self.ctx.relocate_call_stack(&map_address)?;
+        self.position = map_address(self.position).unwrap_or(new_next);
+        self.instruction_reader = scenario.instruction_reader(new_next);
+
+        Ok(())
+    }
+
     /// Install a breakpoint at the given code address
     pub fn add_breakpoint(&mut self, address: CodeAddress) -> BreakpointHandle {
         self.breakpoints.add_breakpoint(address)
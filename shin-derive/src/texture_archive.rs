@@ -60,6 +60,12 @@ pub fn impl_texture_archive(input: Structure) -> TokenStream {
                 #ident: self.#ident.expect(#missing_field_error)
             }
         });
+        let size_in_bytes = var.ast().fields.iter().map(|f| {
+            let ident = f.ident.as_ref().unwrap();
+            quote! {
+                + self.#ident.size_in_bytes()
+            }
+        });
         let ident = &var.ast().ident;
         let builder_ident = Ident::new(&format!("{}Builder", input.ast().ident), Span::call_site());
 
@@ -95,6 +101,10 @@ pub fn impl_texture_archive(input: Structure) -> TokenStream {
 
             impl #texture_archive for #ident {
                 type Builder = #builder_ident;
+
+                fn size_in_bytes(&self) -> usize {
+                    0 #(#size_in_bytes)*
+                }
             }
         }
     } else {
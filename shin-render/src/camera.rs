@@ -1,4 +1,4 @@
-use glam::{vec3, Mat4};
+use glam::{vec3, vec4, Mat4, Vec4};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -9,59 +9,234 @@ struct CameraParams {
 pub const VIRTUAL_WIDTH: f32 = 1920.0;
 pub const VIRTUAL_HEIGHT: f32 = 1080.0;
 
+/// How the virtual screen is put into the window
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ScalingMode {
+    /// Scale to fit the window keeping the aspect ratio, with bars on the sides
+    #[default]
+    Fit,
+    /// Scale by the largest integer factor that fits the window, so that each virtual pixel is a square of whole window pixels
+    ///
+    /// Falls back to [ScalingMode::Fit] when the window is smaller than the virtual screen.
+    Integer,
+    /// Stretch to fill the whole window, ignoring the aspect ratio
+    Stretch,
+}
+
+/// The resolution the layers are rendered at
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum RenderResolution {
+    /// Render at the size the virtual screen takes in the window (supersampling the assets when the window is larger than the virtual screen)
+    #[default]
+    Native,
+    /// Always render at the virtual resolution and scale the result
+    Virtual,
+}
+
+/// Settings of how the rendered image is presented in the window
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PresentationSettings {
+    pub scaling: ScalingMode,
+    pub resolution: RenderResolution,
+    /// Color of the bars around the virtual screen (RGBA)
+    pub bar_color: Vec4,
+}
+
+impl Default for PresentationSettings {
+    fn default() -> Self {
+        Self {
+            scaling: ScalingMode::default(),
+            resolution: RenderResolution::default(),
+            bar_color: vec4(0.0, 0.0, 0.0, 1.0),
+        }
+    }
+}
+
+impl PresentationSettings {
+    /// Whether the final image should be sampled without filtering
+    pub fn pixel_perfect(&self) -> bool {
+        self.scaling == ScalingMode::Integer && self.resolution == RenderResolution::Virtual
+    }
+}
+
 pub struct Camera {
     /// Projection matrix to draw onto the screen
     screen_projection_matrix: Mat4,
     render_buffer_size: (u32, u32),
+    /// Size of the virtual screen in the window, in window pixels
+    viewport_size: (u32, u32),
+    presentation: PresentationSettings,
 }
 
 impl Camera {
     pub fn new(window_size: (u32, u32)) -> Self {
+        Self::with_presentation(window_size, PresentationSettings::default())
+    }
+
+    pub fn with_presentation(window_size: (u32, u32), presentation: PresentationSettings) -> Self {
         let (window_width, window_height) = window_size;
+        let (window_width, window_height) = (window_width as f32, window_height as f32);
 
-        let w = window_width as f32 / VIRTUAL_WIDTH;
-        let h = window_height as f32 / VIRTUAL_HEIGHT;
+        let w = window_width / VIRTUAL_WIDTH;
+        let h = window_height / VIRTUAL_HEIGHT;
 
-        let scale = w.min(h);
+        let fit_scale = w.min(h);
 
-        let (viewport_width, viewport_height) = if w < h {
-            (VIRTUAL_WIDTH, VIRTUAL_HEIGHT * h / w)
-        } else {
-            (VIRTUAL_WIDTH * w / h, VIRTUAL_HEIGHT)
+        let (viewport_width, viewport_height) = match presentation.scaling {
+            ScalingMode::Fit => (VIRTUAL_WIDTH * fit_scale, VIRTUAL_HEIGHT * fit_scale),
+            ScalingMode::Integer => {
+                let scale = if fit_scale >= 1.0 {
+                    fit_scale.floor()
+                } else {
+                    fit_scale
+                };
+                (VIRTUAL_WIDTH * scale, VIRTUAL_HEIGHT * scale)
+            }
+            ScalingMode::Stretch => (window_width, window_height),
         };
 
+        // the virtual screen is centered in the window
+        let (mut offset_x, mut offset_y) = (
+            (window_width - viewport_width) / 2.0,
+            (window_height - viewport_height) / 2.0,
+        );
+        if presentation.scaling == ScalingMode::Integer {
+            // keep the pixels aligned to the window pixels
+            offset_x = offset_x.floor();
+            offset_y = offset_y.floor();
+        }
+        let center_x = (offset_x + viewport_width / 2.0) / window_width * 2.0 - 1.0;
+        let center_y = 1.0 - (offset_y + viewport_height / 2.0) / window_height * 2.0;
+
         // It seems that we are basically one traslation away from matching the game output
         // TODO: figure out a proper way to move the coordinate space of smth
         // because this creates a strip of black pixels on the right and bottom
         let translation = Mat4::from_translation(vec3(-1.0, -1.0, 0.0));
 
         let mut screen_projection = Mat4::IDENTITY;
-        screen_projection.x_axis.x = 2.0 / viewport_width;
-        screen_projection.y_axis.y = -2.0 / viewport_height;
+        screen_projection.x_axis.x = 2.0 * viewport_width / (window_width * VIRTUAL_WIDTH);
+        screen_projection.y_axis.y = -2.0 * viewport_height / (window_height * VIRTUAL_HEIGHT);
         screen_projection.z_axis.z = 1.0 / 1000.0;
         screen_projection.w_axis.w = 1.0;
-        let screen_projection = screen_projection * translation;
+        let screen_projection =
+            Mat4::from_translation(vec3(center_x, center_y, 0.0)) * screen_projection * translation;
 
-        let render_buffer_size = (
-            (VIRTUAL_WIDTH * scale) as u32,
-            (VIRTUAL_HEIGHT * scale) as u32,
-        );
+        let viewport_size = (viewport_width as u32, viewport_height as u32);
+        let render_buffer_size = match presentation.resolution {
+            RenderResolution::Native => (viewport_size.0.max(1), viewport_size.1.max(1)),
+            RenderResolution::Virtual => (VIRTUAL_WIDTH as u32, VIRTUAL_HEIGHT as u32),
+        };
 
         Self {
             screen_projection_matrix: screen_projection,
             render_buffer_size,
+            viewport_size,
+            presentation,
         }
     }
 
     pub fn resize(&mut self, size: (u32, u32)) {
-        *self = Self::new(size);
+        *self = Self::with_presentation(size, self.presentation);
+    }
+
+    pub fn presentation(&self) -> PresentationSettings {
+        self.presentation
+    }
+
+    /// Changes the presentation settings, the camera has to be resized to the window size afterwards
+    pub fn set_presentation(&mut self, presentation: PresentationSettings) {
+        self.presentation = presentation;
     }
 
     pub fn render_buffer_size(&self) -> (u32, u32) {
         self.render_buffer_size
     }
 
+    pub fn viewport_size(&self) -> (u32, u32) {
+        self.viewport_size
+    }
+
     pub fn screen_projection_matrix(&self) -> Mat4 {
         self.screen_projection_matrix
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{vec2, Vec2};
+
+    /// Where a point of the virtual screen ends up in the window, in window pixels
+    fn project(camera: &Camera, window_size: (u32, u32), point: Vec2) -> Vec2 {
+        // undo the fudge translation
+        let ndc = camera.screen_projection_matrix().project_point3(vec3(
+            point.x + 1.0,
+            point.y + 1.0,
+            0.0,
+        ));
+        vec2(
+            (ndc.x + 1.0) / 2.0 * window_size.0 as f32,
+            (1.0 - ndc.y) / 2.0 * window_size.1 as f32,
+        )
+    }
+
+    fn settings(scaling: ScalingMode, resolution: RenderResolution) -> PresentationSettings {
+        PresentationSettings {
+            scaling,
+            resolution,
+            ..Default::default()
+        }
+    }
+
+    const TOP_LEFT: Vec2 = vec2(-VIRTUAL_WIDTH / 2.0, -VIRTUAL_HEIGHT / 2.0);
+    const BOTTOM_RIGHT: Vec2 = vec2(VIRTUAL_WIDTH / 2.0, VIRTUAL_HEIGHT / 2.0);
+
+    #[test]
+    fn fit() {
+        let window = (2000, 1080);
+        let camera = Camera::new(window);
+        assert_eq!(camera.viewport_size(), (1920, 1080));
+        assert_eq!(camera.render_buffer_size(), (1920, 1080));
+        assert!(project(&camera, window, TOP_LEFT).abs_diff_eq(vec2(40.0, 0.0), 0.01));
+        assert!(project(&camera, window, BOTTOM_RIGHT).abs_diff_eq(vec2(1960.0, 1080.0), 0.01));
+
+        let window = (960, 1000);
+        let camera = Camera::new(window);
+        assert_eq!(camera.viewport_size(), (960, 540));
+        assert!(project(&camera, window, TOP_LEFT).abs_diff_eq(vec2(0.0, 230.0), 0.01));
+    }
+
+    #[test]
+    fn integer() {
+        let window = (4000, 2500);
+        let camera = Camera::with_presentation(
+            window,
+            settings(ScalingMode::Integer, RenderResolution::Virtual),
+        );
+        assert_eq!(camera.viewport_size(), (3840, 2160));
+        assert_eq!(camera.render_buffer_size(), (1920, 1080));
+        assert!(project(&camera, window, TOP_LEFT).abs_diff_eq(vec2(80.0, 170.0), 0.01));
+        assert!(camera.presentation().pixel_perfect());
+
+        // the offset is rounded to whole pixels
+        let window = (1921, 1081);
+        let camera = Camera::with_presentation(
+            window,
+            settings(ScalingMode::Integer, RenderResolution::Native),
+        );
+        assert_eq!(camera.render_buffer_size(), (1920, 1080));
+        assert!(project(&camera, window, TOP_LEFT).abs_diff_eq(vec2(0.0, 0.0), 0.01));
+    }
+
+    #[test]
+    fn stretch() {
+        let window = (1000, 1000);
+        let camera = Camera::with_presentation(
+            window,
+            settings(ScalingMode::Stretch, RenderResolution::Native),
+        );
+        assert_eq!(camera.render_buffer_size(), (1000, 1000));
+        assert!(project(&camera, window, TOP_LEFT).abs_diff_eq(vec2(0.0, 0.0), 0.01));
+        assert!(project(&camera, window, BOTTOM_RIGHT).abs_diff_eq(vec2(1000.0, 1000.0), 0.01));
+    }
+}
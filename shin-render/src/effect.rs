@@ -0,0 +1,415 @@
+use crate::{
+    GpuCommonResources, LazyRenderTarget, Pipelines, RenderTarget, Renderable, VIRTUAL_HEIGHT,
+    VIRTUAL_WIDTH,
+};
+use glam::{vec2, Mat4, Vec2, Vec4};
+use std::ptr;
+
+/// A post-processing effect, applied to the whole contents of a layer
+///
+/// All the distances are in virtual screen pixels, so the effects look the same regardless of the render resolution.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Effect {
+    /// Gaussian blur with the given radius
+    Blur { radius: f32 },
+    /// Pixelization into square blocks of the given size
+    Mosaic { size: f32 },
+    /// Maps the (non-premultiplied) color of each pixel with `matrix * color + offset`
+    ColorTransform { matrix: Mat4, offset: Vec4 },
+    /// Shifts each row horizontally along a sine wave
+    RasterScroll {
+        amplitude: f32,
+        wavelength: f32,
+        /// Phase of the wave, in radians
+        phase: f32,
+    },
+}
+
+impl Effect {
+    /// The color transform that doesn't change anything
+    pub const IDENTITY_COLOR: Effect = Effect::ColorTransform {
+        matrix: Mat4::IDENTITY,
+        offset: Vec4::ZERO,
+    };
+
+    /// Inverts the colors, `amount` going from 0.0 (no change) to 1.0 (fully inverted)
+    pub fn negative(amount: f32) -> Self {
+        let scale = 1.0 - 2.0 * amount;
+        Effect::ColorTransform {
+            matrix: Mat4::from_diagonal(Vec4::new(scale, scale, scale, 1.0)),
+            offset: Vec4::new(amount, amount, amount, 0.0),
+        }
+    }
+
+    /// Tints the colors with sepia, `amount` going from 0.0 (no change) to 1.0 (fully sepia)
+    pub fn sepia(amount: f32) -> Self {
+        // glam matrices are column-major, so each column is the contribution of one input channel
+        let sepia = Mat4::from_cols(
+            Vec4::new(0.393, 0.349, 0.272, 0.0),
+            Vec4::new(0.769, 0.686, 0.534, 0.0),
+            Vec4::new(0.189, 0.168, 0.131, 0.0),
+            Vec4::new(0.0, 0.0, 0.0, 1.0),
+        );
+        Effect::ColorTransform {
+            matrix: Mat4::IDENTITY * (1.0 - amount) + sepia * amount,
+            offset: Vec4::ZERO,
+        }
+    }
+
+    /// Multiplies the channels by `multiply`, then adds `add` to them
+    pub fn tint(multiply: Vec4, add: Vec4) -> Self {
+        Effect::ColorTransform {
+            matrix: Mat4::from_diagonal(multiply),
+            offset: add,
+        }
+    }
+
+    /// Combines two color transforms into one, equivalent to applying `self` and then `next`
+    ///
+    /// Returns `None` if either of the effects is not a color transform.
+    pub fn then_color(self, next: Effect) -> Option<Effect> {
+        match (self, next) {
+            (
+                Effect::ColorTransform {
+                    matrix: first_matrix,
+                    offset: first_offset,
+                },
+                Effect::ColorTransform {
+                    matrix: next_matrix,
+                    offset: next_offset,
+                },
+            ) => Some(Effect::ColorTransform {
+                matrix: next_matrix * first_matrix,
+                offset: next_matrix * first_offset + next_offset,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Whether applying the effect would leave the image as is
+    pub fn is_noop(&self) -> bool {
+        match *self {
+            Effect::Blur { radius } => radius <= 0.0,
+            Effect::Mosaic { size } => size <= 1.0,
+            Effect::ColorTransform { matrix, offset } => {
+                matrix == Mat4::IDENTITY && offset == Vec4::ZERO
+            }
+            Effect::RasterScroll {
+                amplitude,
+                wavelength,
+                ..
+            } => amplitude == 0.0 || wavelength <= 0.0,
+        }
+    }
+}
+
+/// Applies a list of [Effect]s to a layer
+///
+/// The effects are applied one after another, each rendering from one intermediate render target into another.
+/// The render targets are only created when there are effects to render, and are dropped when the effects end.
+pub struct EffectStack {
+    effects: Vec<Effect>,
+    targets: [LazyRenderTarget; 2],
+    label: String,
+}
+
+impl EffectStack {
+    pub fn new(label: &str) -> Self {
+        Self {
+            effects: Vec::new(),
+            targets: Self::new_targets(label),
+            label: label.to_string(),
+        }
+    }
+
+    fn new_targets(label: &str) -> [LazyRenderTarget; 2] {
+        [0, 1].map(|i| LazyRenderTarget::new(&format!("{} Effect RenderTarget {}", label, i)))
+    }
+
+    pub fn effects(&self) -> &[Effect] {
+        &self.effects
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Sets the effects to apply, skipping the ones that would do nothing
+    pub fn set_effects(&mut self, effects: impl IntoIterator<Item = Effect>) {
+        self.effects.clear();
+        self.effects
+            .extend(effects.into_iter().filter(|e| !e.is_noop()));
+
+        if self.effects.is_empty() {
+            self.targets = Self::new_targets(&self.label);
+        }
+    }
+
+    pub fn resize(&mut self, resources: &GpuCommonResources) {
+        for target in &mut self.targets {
+            target.resize(resources);
+        }
+    }
+
+    /// Renders `content` with the effects applied
+    ///
+    /// If there are no effects, `content` is rendered directly into `render_pass`,
+    /// otherwise it is first rendered into an intermediate render target.
+    pub fn render<'enc>(
+        &'enc self,
+        resources: &'enc GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'enc>,
+        content: &'enc impl Renderable,
+        transform: Mat4,
+        projection: Mat4,
+    ) {
+        if self.effects.is_empty() {
+            content.render(resources, render_pass, transform, projection);
+            return;
+        }
+        let target = self.targets[0].get(resources);
+
+        {
+            let mut encoder = resources.start_encoder();
+            let mut render_pass = target.begin_srgb_render_pass(
+                &mut encoder,
+                Some(&format!("{} Content RenderPass", self.label)),
+            );
+            content.render(
+                resources,
+                &mut render_pass,
+                transform,
+                target.projection_matrix(),
+            );
+        }
+
+        self.draw(resources, render_pass, target, projection);
+    }
+
+    /// Draws the contents of `source` with the effects applied
+    ///
+    /// This is used by the layers that already render into their own render target.
+    pub fn draw<'enc>(
+        &'enc self,
+        resources: &'enc GpuCommonResources,
+        render_pass: &mut wgpu::RenderPass<'enc>,
+        source: &'enc RenderTarget,
+        projection: Mat4,
+    ) {
+        let result = self.apply(resources, source);
+
+        resources.draw_sprite(
+            render_pass,
+            result.vertex_source(),
+            result.bind_group(),
+            projection,
+        );
+    }
+
+    /// Runs the effect passes, returning the render target with the result
+    fn apply<'a>(
+        &'a self,
+        resources: &GpuCommonResources,
+        source: &'a RenderTarget,
+    ) -> &'a RenderTarget {
+        if self.effects.is_empty() {
+            return source;
+        }
+        let first = self.targets[0].get(resources);
+        let second = self.targets[1].get(resources);
+
+        let mut current = source;
+        for pass in self.effects.iter().flat_map(|e| e.passes()) {
+            // ping-pong between the two targets, never writing into the one we read from
+            let target = if ptr::eq(current, first) {
+                second
+            } else {
+                first
+            };
+
+            {
+                let mut encoder = resources.start_encoder();
+                let mut render_pass = target.begin_srgb_render_pass(
+                    &mut encoder,
+                    Some(&format!("{} {} RenderPass", self.label, pass.name())),
+                );
+                pass.draw(
+                    &resources.pipelines,
+                    &mut render_pass,
+                    current,
+                    target.projection_matrix(),
+                );
+            }
+
+            current = target;
+        }
+
+        current
+    }
+}
+
+/// A single shader pass, an [Effect] can consist of multiple ones
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum EffectPass {
+    Blur {
+        direction: Vec2,
+    },
+    Mosaic {
+        block_size: Vec2,
+    },
+    ColorTransform {
+        matrix: Mat4,
+        offset: Vec4,
+    },
+    RasterScroll {
+        amplitude: f32,
+        frequency: f32,
+        phase: f32,
+    },
+}
+
+impl Effect {
+    /// Converts the effect to the shader passes, with the distances in texture coordinates
+    pub(crate) fn passes(&self) -> impl Iterator<Item = EffectPass> {
+        let passes = match *self {
+            // the blur is separable, so it's done in a horizontal and a vertical pass
+            Effect::Blur { radius } => [
+                Some(EffectPass::Blur {
+                    direction: vec2(radius / VIRTUAL_WIDTH, 0.0),
+                }),
+                Some(EffectPass::Blur {
+                    direction: vec2(0.0, radius / VIRTUAL_HEIGHT),
+                }),
+            ],
+            Effect::Mosaic { size } => [
+                Some(EffectPass::Mosaic {
+                    block_size: vec2(size / VIRTUAL_WIDTH, size / VIRTUAL_HEIGHT),
+                }),
+                None,
+            ],
+            Effect::ColorTransform { matrix, offset } => {
+                [Some(EffectPass::ColorTransform { matrix, offset }), None]
+            }
+            Effect::RasterScroll {
+                amplitude,
+                wavelength,
+                phase,
+            } => [
+                Some(EffectPass::RasterScroll {
+                    amplitude: amplitude / VIRTUAL_WIDTH,
+                    frequency: VIRTUAL_HEIGHT / wavelength,
+                    phase,
+                }),
+                None,
+            ],
+        };
+
+        passes.into_iter().flatten()
+    }
+}
+
+impl EffectPass {
+    fn name(&self) -> &'static str {
+        match self {
+            EffectPass::Blur { .. } => "Blur",
+            EffectPass::Mosaic { .. } => "Mosaic",
+            EffectPass::ColorTransform { .. } => "ColorTransform",
+            EffectPass::RasterScroll { .. } => "RasterScroll",
+        }
+    }
+
+    fn draw<'a>(
+        &self,
+        pipelines: &'a Pipelines,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: &'a RenderTarget,
+        transform: Mat4,
+    ) {
+        let vertices = source.vertex_source();
+        let texture = source.bind_group();
+
+        match *self {
+            EffectPass::Blur { direction } => {
+                pipelines
+                    .blur
+                    .draw(render_pass, vertices, texture, transform, direction)
+            }
+            EffectPass::Mosaic { block_size } => {
+                pipelines
+                    .mosaic
+                    .draw(render_pass, vertices, texture, transform, block_size)
+            }
+            EffectPass::ColorTransform { matrix, offset } => pipelines.color_transform.draw(
+                render_pass,
+                vertices,
+                texture,
+                transform,
+                matrix,
+                offset,
+            ),
+            EffectPass::RasterScroll {
+                amplitude,
+                frequency,
+                phase,
+            } => pipelines.raster_scroll.draw(
+                render_pass,
+                vertices,
+                texture,
+                transform,
+                amplitude,
+                frequency,
+                phase,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn apply_color(effect: Effect, color: Vec4) -> Vec4 {
+        match effect {
+            Effect::ColorTransform { matrix, offset } => matrix * color + offset,
+            _ => panic!("not a color transform"),
+        }
+    }
+
+    #[test]
+    fn negative() {
+        let color = Vec4::new(0.25, 0.5, 1.0, 0.75);
+        assert_eq!(apply_color(Effect::negative(0.0), color), color);
+        assert_eq!(
+            apply_color(Effect::negative(1.0), color),
+            Vec4::new(0.75, 0.5, 0.0, 0.75)
+        );
+        assert!(Effect::negative(0.0).is_noop());
+    }
+
+    #[test]
+    fn sepia_keeps_alpha() {
+        let color = Vec4::new(1.0, 1.0, 1.0, 0.5);
+        let result = apply_color(Effect::sepia(1.0), color);
+        assert!((result.x - 1.351).abs() < 1e-4);
+        assert_eq!(result.w, 0.5);
+        assert!(Effect::sepia(0.0).is_noop());
+    }
+
+    #[test]
+    fn then_color() {
+        let color = Vec4::new(0.25, 0.5, 1.0, 0.75);
+        let first = Effect::tint(Vec4::new(0.5, 1.0, 1.0, 1.0), Vec4::new(0.0, 0.1, 0.0, 0.0));
+        let next = Effect::negative(1.0);
+        let combined = first.then_color(next).unwrap();
+
+        let expected = apply_color(next, apply_color(first, color));
+        assert!(
+            (apply_color(combined, color) - expected)
+                .abs()
+                .max_element()
+                < 1e-6
+        );
+
+        assert_eq!(first.then_color(Effect::Blur { radius: 1.0 }), None);
+    }
+}
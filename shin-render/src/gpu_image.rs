@@ -23,6 +23,19 @@ impl LazyGpuImage {
         }
     }
 
+    /// Size of the pixel data, in bytes
+    pub fn size_in_bytes(&self) -> usize {
+        self.image.as_raw().len()
+    }
+
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    pub fn origin(&self) -> Vec2 {
+        self.origin
+    }
+
     pub fn gpu_image(&self, resources: &GpuCommonResources) -> &GpuImage {
         self.gpu_image.get_or_init(|| {
             GpuImage::load(resources, &self.image, self.origin, self.label.as_deref())
@@ -45,6 +58,11 @@ impl LazyGpuTexture {
         }
     }
 
+    /// Size of the pixel data, in bytes
+    pub fn size_in_bytes(&self) -> usize {
+        self.image.as_raw().len()
+    }
+
     pub fn gpu_texture(&self, resources: &GpuCommonResources) -> &GpuTexture {
         self.gpu_texture
             .get_or_init(|| GpuTexture::load(resources, &self.image, self.label.as_deref()))
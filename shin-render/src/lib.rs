@@ -4,20 +4,25 @@ use std::ops::{Deref, DerefMut};
 mod bind_groups;
 mod camera;
 mod common_resources;
+mod effect;
 mod gpu_image;
 mod pillarbox;
 mod pipelines;
 mod render_target;
+pub mod software;
 mod vertex_buffer;
 pub mod vertices;
 
 pub use bind_groups::{BindGroupLayouts, TextureBindGroup, YuvTextureBindGroup};
-pub use camera::{Camera, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+pub use camera::{
+    Camera, PresentationSettings, RenderResolution, ScalingMode, VIRTUAL_HEIGHT, VIRTUAL_WIDTH,
+};
 pub use common_resources::GpuCommonResources;
+pub use effect::{Effect, EffectStack};
 pub use gpu_image::{GpuImage, GpuTexture, LazyGpuImage, LazyGpuTexture};
 pub use pillarbox::Pillarbox;
 pub use pipelines::Pipelines;
-pub use render_target::RenderTarget;
+pub use render_target::{LazyRenderTarget, RenderTarget};
 pub use vertex_buffer::{IndexBuffer, PosVertexBuffer, SpriteVertexBuffer, Vertex, VertexBuffer};
 
 pub const SRGB_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
@@ -1,12 +1,13 @@
 use crate::vertices::{PosVertex, VertexSource};
 use crate::{GpuCommonResources, Renderable, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
-use glam::{vec3, vec4, Mat4};
+use glam::{vec3, vec4, Mat4, Vec4};
 use wgpu::util::DeviceExt;
 
 pub struct Pillarbox {
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     num_indices: u32,
+    color: Vec4,
 }
 
 impl Pillarbox {
@@ -103,8 +104,13 @@ impl Pillarbox {
             vertex_buffer,
             index_buffer,
             num_indices: indices.len() as u32,
+            color: vec4(0.0, 0.0, 0.0, 1.0),
         }
     }
+
+    pub fn set_color(&mut self, color: Vec4) {
+        self.color = color;
+    }
 }
 
 impl Renderable for Pillarbox {
@@ -125,7 +131,7 @@ impl Renderable for Pillarbox {
                 instances: 0..1,
             },
             projection * transform,
-            vec4(0.0, 0.0, 0.0, 1.0),
+            self.color,
         );
         render_pass.pop_debug_group();
     }
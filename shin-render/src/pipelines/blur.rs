@@ -0,0 +1,72 @@
+use crate::vertices::{PosColTexVertex, VertexSource};
+use crate::{pipelines, BindGroupLayouts, TextureBindGroup};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec2};
+use std::mem;
+use wgpu::include_wgsl;
+
+#[derive(Pod, Zeroable, Copy, Clone, Debug)]
+#[repr(C)]
+struct BlurParams {
+    pub transform: Mat4,
+    /// Offset of the outermost tap, in texture coordinates
+    pub direction: Vec2,
+    pub _padding: Vec2,
+}
+
+pub struct BlurPipeline(wgpu::RenderPipeline);
+
+impl BlurPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        texture_format: wgpu::TextureFormat,
+    ) -> Self {
+        let shader_module = device.create_shader_module(include_wgsl!("blur.wgsl"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("BlurPipeline Layout"),
+            bind_group_layouts: &[&bind_group_layouts.texture],
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                range: 0..(mem::size_of::<BlurParams>() as u32),
+            }],
+        });
+
+        Self(pipelines::make_pipeline(
+            device,
+            texture_format,
+            shader_module,
+            layout,
+            PosColTexVertex::desc(),
+            // the effect passes overwrite the whole target
+            None,
+            "BlurPipeline",
+        ))
+    }
+
+    /// Blurs the texture along `direction` (the offset of the outermost sample, in texture coordinates)
+    ///
+    /// A full blur is done with two passes, a horizontal and a vertical one.
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, PosColTexVertex>,
+        texture: &'a TextureBindGroup,
+        transform: Mat4,
+        direction: Vec2,
+    ) {
+        render_pass.set_pipeline(&self.0);
+        render_pass.set_bind_group(0, &texture.0, &[]);
+        render_pass.set_push_constants(
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            0,
+            bytemuck::cast_slice(&[BlurParams {
+                transform,
+                direction,
+                _padding: Vec2::ZERO,
+            }]),
+        );
+        source.draw(render_pass);
+    }
+}
@@ -0,0 +1,46 @@
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) texture_coordinate: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coordinate: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+struct BlurParams {
+    transform: mat4x4<f32>,
+    // offset of the outermost tap, in texture coordinates
+    direction: vec2<f32>,
+}
+
+var<push_constant> params: BlurParams;
+
+@vertex
+fn vertex_main(input: VertexIn) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = params.transform * vec4<f32>(input.position, 1.0);
+    output.texture_coordinate = input.texture_coordinate;
+    return output;
+}
+
+@fragment
+fn fragment_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let taps = 8;
+    var sum = vec4<f32>(0.0);
+    var weight_sum = 0.0;
+    for (var i = -taps; i <= taps; i = i + 1) {
+        let t = f32(i) / f32(taps);
+        // gaussian with sigma = half of the radius
+        let weight = exp(-2.0 * t * t);
+        sum += textureSample(source_texture, source_sampler, input.texture_coordinate + params.direction * t) * weight;
+        weight_sum += weight;
+    }
+    return sum / weight_sum;
+}
@@ -0,0 +1,70 @@
+use crate::vertices::{PosColTexVertex, VertexSource};
+use crate::{pipelines, BindGroupLayouts, TextureBindGroup};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec4};
+use std::mem;
+use wgpu::include_wgsl;
+
+#[derive(Pod, Zeroable, Copy, Clone, Debug)]
+#[repr(C)]
+struct ColorTransformParams {
+    pub transform: Mat4,
+    pub color_matrix: Mat4,
+    pub color_offset: Vec4,
+}
+
+pub struct ColorTransformPipeline(wgpu::RenderPipeline);
+
+impl ColorTransformPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        texture_format: wgpu::TextureFormat,
+    ) -> Self {
+        let shader_module = device.create_shader_module(include_wgsl!("color_transform.wgsl"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("ColorTransformPipeline Layout"),
+            bind_group_layouts: &[&bind_group_layouts.texture],
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                range: 0..(mem::size_of::<ColorTransformParams>() as u32),
+            }],
+        });
+
+        Self(pipelines::make_pipeline(
+            device,
+            texture_format,
+            shader_module,
+            layout,
+            PosColTexVertex::desc(),
+            // the effect passes overwrite the whole target
+            None,
+            "ColorTransformPipeline",
+        ))
+    }
+
+    /// Maps the color of each pixel with `color_matrix * color + color_offset`
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, PosColTexVertex>,
+        texture: &'a TextureBindGroup,
+        transform: Mat4,
+        color_matrix: Mat4,
+        color_offset: Vec4,
+    ) {
+        render_pass.set_pipeline(&self.0);
+        render_pass.set_bind_group(0, &texture.0, &[]);
+        render_pass.set_push_constants(
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            0,
+            bytemuck::cast_slice(&[ColorTransformParams {
+                transform,
+                color_matrix,
+                color_offset,
+            }]),
+        );
+        source.draw(render_pass);
+    }
+}
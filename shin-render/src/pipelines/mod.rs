@@ -1,4 +1,8 @@
+mod blur;
+mod color_transform;
 mod fill;
+mod mosaic;
+mod raster_scroll;
 mod sprite;
 mod text;
 mod text_outline;
@@ -7,7 +11,11 @@ mod yuv_sprite;
 use crate::bind_groups::BindGroupLayouts;
 use crate::{RAW_TEXTURE_FORMAT, SRGB_TEXTURE_FORMAT};
 
+use blur::BlurPipeline;
+use color_transform::ColorTransformPipeline;
 use fill::FillPipeline;
+use mosaic::MosaicPipeline;
+use raster_scroll::RasterScrollPipeline;
 use sprite::SpritePipeline;
 use text::TextPipeline;
 use text_outline::TextOutlinePipeline;
@@ -61,6 +69,11 @@ pub struct Pipelines {
     pub fill: FillPipeline,
     pub text: TextPipeline,
     pub text_outline: TextOutlinePipeline,
+    // those are the passes of the layer effects (see [crate::EffectStack])
+    pub blur: BlurPipeline,
+    pub mosaic: MosaicPipeline,
+    pub color_transform: ColorTransformPipeline,
+    pub raster_scroll: RasterScrollPipeline,
     // those are pipelines using screen's texture format (not our preferred RGBA format)
     // they are only used for the final render pass
     pub sprite_screen: SpritePipeline,
@@ -79,6 +92,18 @@ impl Pipelines {
             fill: FillPipeline::new(device, bind_group_layouts, SRGB_TEXTURE_FORMAT),
             text: TextPipeline::new(device, bind_group_layouts, SRGB_TEXTURE_FORMAT),
             text_outline: TextOutlinePipeline::new(device, bind_group_layouts, SRGB_TEXTURE_FORMAT),
+            blur: BlurPipeline::new(device, bind_group_layouts, SRGB_TEXTURE_FORMAT),
+            mosaic: MosaicPipeline::new(device, bind_group_layouts, SRGB_TEXTURE_FORMAT),
+            color_transform: ColorTransformPipeline::new(
+                device,
+                bind_group_layouts,
+                SRGB_TEXTURE_FORMAT,
+            ),
+            raster_scroll: RasterScrollPipeline::new(
+                device,
+                bind_group_layouts,
+                SRGB_TEXTURE_FORMAT,
+            ),
 
             sprite_screen: SpritePipeline::new(device, bind_group_layouts, surface_texture_format),
             fill_screen: FillPipeline::new(device, bind_group_layouts, surface_texture_format),
@@ -0,0 +1,70 @@
+use crate::vertices::{PosColTexVertex, VertexSource};
+use crate::{pipelines, BindGroupLayouts, TextureBindGroup};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec2};
+use std::mem;
+use wgpu::include_wgsl;
+
+#[derive(Pod, Zeroable, Copy, Clone, Debug)]
+#[repr(C)]
+struct MosaicParams {
+    pub transform: Mat4,
+    /// Size of a block, in texture coordinates
+    pub block_size: Vec2,
+    pub _padding: Vec2,
+}
+
+pub struct MosaicPipeline(wgpu::RenderPipeline);
+
+impl MosaicPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        texture_format: wgpu::TextureFormat,
+    ) -> Self {
+        let shader_module = device.create_shader_module(include_wgsl!("mosaic.wgsl"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("MosaicPipeline Layout"),
+            bind_group_layouts: &[&bind_group_layouts.texture],
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                range: 0..(mem::size_of::<MosaicParams>() as u32),
+            }],
+        });
+
+        Self(pipelines::make_pipeline(
+            device,
+            texture_format,
+            shader_module,
+            layout,
+            PosColTexVertex::desc(),
+            // the effect passes overwrite the whole target
+            None,
+            "MosaicPipeline",
+        ))
+    }
+
+    /// Replaces each `block_size` block of the texture with the color of its center
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, PosColTexVertex>,
+        texture: &'a TextureBindGroup,
+        transform: Mat4,
+        block_size: Vec2,
+    ) {
+        render_pass.set_pipeline(&self.0);
+        render_pass.set_bind_group(0, &texture.0, &[]);
+        render_pass.set_push_constants(
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            0,
+            bytemuck::cast_slice(&[MosaicParams {
+                transform,
+                block_size,
+                _padding: Vec2::ZERO,
+            }]),
+        );
+        source.draw(render_pass);
+    }
+}
@@ -0,0 +1,38 @@
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) texture_coordinate: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coordinate: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+struct MosaicParams {
+    transform: mat4x4<f32>,
+    // size of a block, in texture coordinates
+    block_size: vec2<f32>,
+}
+
+var<push_constant> params: MosaicParams;
+
+@vertex
+fn vertex_main(input: VertexIn) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = params.transform * vec4<f32>(input.position, 1.0);
+    output.texture_coordinate = input.texture_coordinate;
+    return output;
+}
+
+@fragment
+fn fragment_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let block = floor(input.texture_coordinate / params.block_size);
+    let center = (block + 0.5) * params.block_size;
+    return textureSample(source_texture, source_sampler, center);
+}
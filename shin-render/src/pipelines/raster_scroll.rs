@@ -0,0 +1,79 @@
+use crate::vertices::{PosColTexVertex, VertexSource};
+use crate::{pipelines, BindGroupLayouts, TextureBindGroup};
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use std::mem;
+use wgpu::include_wgsl;
+
+#[derive(Pod, Zeroable, Copy, Clone, Debug)]
+#[repr(C)]
+struct RasterScrollParams {
+    pub transform: Mat4,
+    /// Horizontal offset of the rows at the wave crest, in texture coordinates
+    pub amplitude: f32,
+    /// Number of waves over the height of the texture
+    pub frequency: f32,
+    /// Phase of the wave, in radians
+    pub phase: f32,
+    pub _padding: f32,
+}
+
+pub struct RasterScrollPipeline(wgpu::RenderPipeline);
+
+impl RasterScrollPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        texture_format: wgpu::TextureFormat,
+    ) -> Self {
+        let shader_module = device.create_shader_module(include_wgsl!("raster_scroll.wgsl"));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("RasterScrollPipeline Layout"),
+            bind_group_layouts: &[&bind_group_layouts.texture],
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                range: 0..(mem::size_of::<RasterScrollParams>() as u32),
+            }],
+        });
+
+        Self(pipelines::make_pipeline(
+            device,
+            texture_format,
+            shader_module,
+            layout,
+            PosColTexVertex::desc(),
+            // the effect passes overwrite the whole target
+            None,
+            "RasterScrollPipeline",
+        ))
+    }
+
+    /// Shifts the rows of the texture horizontally along a sine wave
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        source: VertexSource<'a, PosColTexVertex>,
+        texture: &'a TextureBindGroup,
+        transform: Mat4,
+        amplitude: f32,
+        frequency: f32,
+        phase: f32,
+    ) {
+        render_pass.set_pipeline(&self.0);
+        render_pass.set_bind_group(0, &texture.0, &[]);
+        render_pass.set_push_constants(
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            0,
+            bytemuck::cast_slice(&[RasterScrollParams {
+                transform,
+                amplitude,
+                frequency,
+                phase,
+                _padding: 0.0,
+            }]),
+        );
+        source.draw(render_pass);
+    }
+}
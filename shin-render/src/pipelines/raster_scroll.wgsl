@@ -0,0 +1,46 @@
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) texture_coordinate: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coordinate: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+struct RasterScrollParams {
+    transform: mat4x4<f32>,
+    // horizontal offset of the rows at the wave crest, in texture coordinates
+    amplitude: f32,
+    // number of waves over the height of the texture
+    frequency: f32,
+    // phase of the wave, in radians
+    phase: f32,
+}
+
+var<push_constant> params: RasterScrollParams;
+
+@vertex
+fn vertex_main(input: VertexIn) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = params.transform * vec4<f32>(input.position, 1.0);
+    output.texture_coordinate = input.texture_coordinate;
+    return output;
+}
+
+@fragment
+fn fragment_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let tau = 6.283185307179586;
+    let offset = params.amplitude * sin(input.texture_coordinate.y * params.frequency * tau + params.phase);
+    let coordinate = input.texture_coordinate + vec2<f32>(offset, 0.0);
+    let color = textureSample(source_texture, source_sampler, coordinate);
+    // the rows are not wrapped around, the part scrolled in from the outside is transparent
+    let inside = coordinate.x >= 0.0 && coordinate.x <= 1.0;
+    return select(vec4<f32>(0.0), color, inside);
+}
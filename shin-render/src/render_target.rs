@@ -3,11 +3,15 @@ use crate::common_resources::GpuCommonResources;
 use crate::vertices::{PosColTexVertex, VertexSource};
 use crate::{SpriteVertexBuffer, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
 use glam::Mat4;
+use image::RgbaImage;
+use once_cell::sync::OnceCell;
 use std::borrow::Cow;
+use std::num::NonZeroU32;
 
 /// Describes a fullscreen intermediate render target.
 pub struct RenderTarget {
     texture: wgpu::Texture,
+    size: (u32, u32),
     srgb_view: wgpu::TextureView,
     raw_view: wgpu::TextureView,
     sampler: wgpu::Sampler,
@@ -36,7 +40,9 @@ impl RenderTarget {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: Self::SRGB_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[Self::RAW_FORMAT],
         });
         let srgb_view = texture.create_view(&wgpu::TextureViewDescriptor {
@@ -68,6 +74,7 @@ impl RenderTarget {
         let vertices = SpriteVertexBuffer::new_fullscreen(resources);
         Self {
             texture,
+            size,
             srgb_view,
             raw_view,
             sampler,
@@ -89,9 +96,12 @@ impl RenderTarget {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: Self::SRGB_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[],
         });
+        self.size = size;
         self.srgb_view = self.texture.create_view(&wgpu::TextureViewDescriptor {
             label: Some(&format!("{} TextureView", self.label)),
             ..Default::default()
@@ -104,6 +114,90 @@ impl RenderTarget {
         );
     }
 
+    /// Changes the filtering used when the render target is drawn scaled
+    pub fn set_filter_mode(&mut self, resources: &GpuCommonResources, filter: wgpu::FilterMode) {
+        self.sampler = resources.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&format!("{} Sampler", self.label)),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        self.bind_group = TextureBindGroup::new(
+            resources,
+            &self.srgb_view,
+            &self.sampler,
+            Some(&format!("{} TextureBindGroup", self.label)),
+        );
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Copy the contents of the render target to the CPU memory
+    ///
+    /// This waits for the GPU to finish all the submitted work, so it will stall the rendering
+    pub fn read_pixels(&self, resources: &GpuCommonResources) -> RgbaImage {
+        let (width, height) = self.size;
+        let unpadded_bytes_per_row = width * 4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (unpadded_bytes_per_row + align - 1) / align * align;
+
+        let buffer = resources.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{} Readback Buffer", self.label)),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        {
+            let mut encoder = resources.start_encoder();
+            encoder.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    texture: &self.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                        rows_per_image: None,
+                    },
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| {
+            result.expect("Mapping the readback buffer failed")
+        });
+        resources.device.poll(wgpu::Maintain::Wait);
+
+        let pixels = {
+            let data = slice.get_mapped_range();
+            // strip the row padding
+            data.chunks(padded_bytes_per_row as usize)
+                .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        buffer.unmap();
+
+        RgbaImage::from_raw(width, height, pixels).unwrap()
+    }
+
     pub fn projection_matrix(&self) -> Mat4 {
         let mut projection = Mat4::IDENTITY;
         projection.x_axis.x = 2.0 / VIRTUAL_WIDTH;
@@ -160,3 +254,39 @@ impl RenderTarget {
         &self.bind_group
     }
 }
+
+/// A [RenderTarget] that is only created when it's first rendered to
+///
+/// Most of the layer groups stay empty, so this avoids allocating a fullscreen texture for each of them.
+/// It also lets the layers be built without a GPU that supports render targets, e.g. for the software renderer.
+pub struct LazyRenderTarget {
+    label: String,
+    render_target: OnceCell<RenderTarget>,
+}
+
+impl LazyRenderTarget {
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            render_target: OnceCell::new(),
+        }
+    }
+
+    /// Gets the render target, creating it with the current render buffer size if needed
+    pub fn get(&self, resources: &GpuCommonResources) -> &RenderTarget {
+        self.render_target.get_or_init(|| {
+            RenderTarget::new(
+                resources,
+                resources.current_render_buffer_size(),
+                Some(&self.label),
+            )
+        })
+    }
+
+    /// Resizes the render target to the current render buffer size, if it was created
+    pub fn resize(&mut self, resources: &GpuCommonResources) {
+        if let Some(render_target) = self.render_target.get_mut() {
+            render_target.resize(resources, resources.current_render_buffer_size());
+        }
+    }
+}
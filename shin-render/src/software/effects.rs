@@ -0,0 +1,89 @@
+use crate::effect::EffectPass;
+use crate::software::{SoftwareTarget, SoftwareTexture};
+use crate::{Effect, EffectStack};
+use glam::{vec2, Mat4, Vec4};
+use std::f32::consts::TAU;
+
+impl SoftwareTarget {
+    /// Applies the effects to the contents of the target, running the same passes as [EffectStack]
+    pub fn apply_effects(&mut self, effects: &[Effect]) {
+        for pass in effects
+            .iter()
+            .filter(|e| !e.is_noop())
+            .flat_map(|e| e.passes())
+        {
+            self.apply_pass(pass);
+        }
+    }
+
+    fn apply_pass(&mut self, pass: EffectPass) {
+        // those mirror the shaders of the effect pipelines
+        match pass {
+            EffectPass::Blur { direction } => self.run_pass(|source, uv| {
+                let taps = 8;
+                let mut sum = Vec4::ZERO;
+                let mut weight_sum = 0.0;
+                for i in -taps..=taps {
+                    let t = i as f32 / taps as f32;
+                    let weight = (-2.0 * t * t).exp();
+                    sum += source.sample(uv + direction * t) * weight;
+                    weight_sum += weight;
+                }
+                sum / weight_sum
+            }),
+            EffectPass::Mosaic { block_size } => self.run_pass(|source, uv| {
+                let block = (uv / block_size).floor();
+                source.sample((block + 0.5) * block_size)
+            }),
+            EffectPass::ColorTransform { matrix, offset } => self.run_pass(|source, uv| {
+                (matrix * source.sample(uv) + offset).clamp(Vec4::ZERO, Vec4::ONE)
+            }),
+            EffectPass::RasterScroll {
+                amplitude,
+                frequency,
+                phase,
+            } => self.run_pass(|source, uv| {
+                let offset = amplitude * (uv.y * frequency * TAU + phase).sin();
+                let coordinate = uv + vec2(offset, 0.0);
+                if (0.0..=1.0).contains(&coordinate.x) {
+                    source.sample(coordinate)
+                } else {
+                    Vec4::ZERO
+                }
+            }),
+        }
+    }
+}
+
+impl EffectStack {
+    /// The software version of [EffectStack::render]
+    pub fn render_software(
+        &self,
+        target: &mut SoftwareTarget,
+        content: &impl super::SoftwareRenderable,
+        transform: Mat4,
+        projection: Mat4,
+    ) {
+        if self.is_empty() {
+            content.render_software(target, transform, projection);
+            return;
+        }
+
+        let mut content_target = SoftwareTarget::new(target.size());
+        let content_projection = content_target.projection_matrix();
+        content.render_software(&mut content_target, transform, content_projection);
+
+        self.draw_software(target, content_target, projection);
+    }
+
+    /// The software version of [EffectStack::draw]
+    pub fn draw_software(
+        &self,
+        target: &mut SoftwareTarget,
+        mut source: SoftwareTarget,
+        projection: Mat4,
+    ) {
+        source.apply_effects(self.effects());
+        target.draw_target(&source, projection);
+    }
+}
@@ -0,0 +1,73 @@
+//! Comparison of the rendered images against the golden ones, shared by the golden-image tests of the crates
+
+use image::RgbaImage;
+use std::path::Path;
+
+/// Maximum difference of a channel from the golden image, to allow for differences in float rounding
+const TOLERANCE: u8 = 2;
+
+/// Checks that `actual` matches the golden image `{golden_dir}/{name}.png`, panicking otherwise
+///
+/// A mismatching image is saved to `output_dir` for inspection.
+/// When the `SHIN_BLESS` environment variable is set, the golden image is overwritten instead.
+pub fn assert_golden(golden_dir: &Path, output_dir: &Path, name: &str, actual: &RgbaImage) {
+    let path = golden_dir.join(format!("{}.png", name));
+
+    if std::env::var_os("SHIN_BLESS").is_some() {
+        std::fs::create_dir_all(golden_dir).unwrap();
+        actual.save(&path).unwrap();
+        return;
+    }
+
+    let expected = match image::open(&path) {
+        Ok(expected) => expected.into_rgba8(),
+        Err(e) => panic!(
+            "Could not read the golden image {}: {} (run with SHIN_BLESS=1 to create it)",
+            path.display(),
+            e
+        ),
+    };
+
+    let save_actual = || {
+        std::fs::create_dir_all(output_dir).unwrap();
+        let actual_path = output_dir.join(format!("{}.png", name));
+        actual.save(&actual_path).unwrap();
+        actual_path
+    };
+
+    if expected.dimensions() != actual.dimensions() {
+        panic!(
+            "{}: the size is {:?}, expected {:?} (the result was saved to {})",
+            name,
+            actual.dimensions(),
+            expected.dimensions(),
+            save_actual().display()
+        );
+    }
+
+    let mismatches = expected
+        .enumerate_pixels()
+        .zip(actual.pixels())
+        .filter(|((_, _, expected), actual)| {
+            expected
+                .0
+                .iter()
+                .zip(actual.0.iter())
+                .any(|(&e, &a)| e.abs_diff(a) > TOLERANCE)
+        })
+        .map(|((x, y, expected), actual)| (x, y, *expected, *actual))
+        .collect::<Vec<_>>();
+
+    if let Some(&(x, y, expected, actual_pixel)) = mismatches.first() {
+        panic!(
+            "{}: {} pixels differ from the golden image, first at ({}, {}): {:?} != {:?} (the result was saved to {})",
+            name,
+            mismatches.len(),
+            x,
+            y,
+            actual_pixel.0,
+            expected.0,
+            save_actual().display()
+        );
+    }
+}
@@ -0,0 +1,359 @@
+//! A CPU implementation of the renderer, used as a reference in tests
+//!
+//! [SoftwareTarget] mirrors [RenderTarget](crate::RenderTarget) and the pipelines drawing into it:
+//! the same vertices and transforms produce the same picture, without needing a GPU.
+//! The colors are blended in linear space and only encoded to sRGB when the image is read back,
+//! so the results can differ from the GPU ones by a rounding step.
+
+mod effects;
+mod golden;
+mod raster;
+mod texture;
+
+pub use golden::assert_golden;
+pub use texture::SoftwareTexture;
+
+use crate::vertices::{PosColTexVertex, PosVertex, TextVertex, QUAD_INDICES};
+use crate::{VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+use glam::{vec2, Mat4, Vec2, Vec3, Vec4};
+use image::{GrayImage, RgbaImage};
+use raster::rasterize_triangle;
+use shin_core::format::picture::PictureChunk;
+use shin_core::time::Ticks;
+use texture::linear_to_srgb;
+
+/// A trait for elements that can be rendered by the software renderer
+///
+/// This is the counterpart of [Renderable](crate::Renderable), and the arguments have the same meaning:
+/// `transform` is inherited from the parent elements, while `projection` converts from the virtual screen space
+/// to the space of `target`.
+pub trait SoftwareRenderable {
+    fn render_software(&self, target: &mut SoftwareTarget, transform: Mat4, projection: Mat4);
+}
+
+/// How a drawn pixel is combined with the contents of the target
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Blend {
+    /// Same as the blend state of the sprite, fill & text pipelines
+    Alpha,
+    /// Overwrite the target, used by the effect passes
+    Replace,
+}
+
+/// An image to render into, the software counterpart of [RenderTarget](crate::RenderTarget)
+///
+/// The pixels are stored as non-premultiplied linear colors.
+#[derive(Clone)]
+pub struct SoftwareTarget {
+    size: (u32, u32),
+    pixels: Vec<Vec4>,
+}
+
+impl SoftwareTarget {
+    /// Creates a transparent target
+    pub fn new(size: (u32, u32)) -> Self {
+        Self {
+            size,
+            pixels: vec![Vec4::ZERO; size.0 as usize * size.1 as usize],
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Vec4 {
+        self.pixels[(y * self.size.0 + x) as usize]
+    }
+
+    pub fn clear(&mut self, color: Vec4) {
+        self.pixels.fill(color);
+    }
+
+    /// Same as [RenderTarget::projection_matrix](crate::RenderTarget::projection_matrix)
+    pub fn projection_matrix(&self) -> Mat4 {
+        let mut projection = Mat4::IDENTITY;
+        projection.x_axis.x = 2.0 / VIRTUAL_WIDTH;
+        projection.y_axis.y = -2.0 / VIRTUAL_HEIGHT; // in wgpu y is up, so we need to flip the y axis
+        projection.z_axis.z = 1.0 / 1000.0;
+        projection.w_axis.w = 1.0;
+
+        projection
+    }
+
+    /// Encodes the contents to an 8-bit sRGB image, like [RenderTarget::read_pixels](crate::RenderTarget::read_pixels)
+    pub fn to_image(&self) -> RgbaImage {
+        let (width, height) = self.size;
+        RgbaImage::from_fn(width, height, |x, y| {
+            let pixel = self.pixel(x, y);
+            image::Rgba([
+                linear_to_srgb(pixel.x),
+                linear_to_srgb(pixel.y),
+                linear_to_srgb(pixel.z),
+                (pixel.w.clamp(0.0, 1.0) * 255.0).round() as u8,
+            ])
+        })
+    }
+
+    fn put(&mut self, x: u32, y: u32, color: Vec4, blend: Blend) {
+        let dst = &mut self.pixels[(y * self.size.0 + x) as usize];
+        *dst = match blend {
+            Blend::Alpha => {
+                let src = color.clamp(Vec4::ZERO, Vec4::ONE);
+                let rgb = src.truncate() * src.w + dst.truncate() * (1.0 - src.w);
+                rgb.extend(src.w + dst.w * (1.0 - src.w))
+            }
+            Blend::Replace => color,
+        };
+    }
+
+    /// Rasterizes indexed triangles, `shade` computing the color of a pixel from the interpolated vertex
+    fn draw_triangles<V: Copy>(
+        &mut self,
+        vertices: &[V],
+        indices: &[u16],
+        position: impl Fn(&V) -> Vec3,
+        transform: Mat4,
+        blend: Blend,
+        shade: impl Fn([&V; 3], Vec3) -> Vec4,
+    ) {
+        for triangle in indices.chunks_exact(3) {
+            let triangle = [
+                &vertices[triangle[0] as usize],
+                &vertices[triangle[1] as usize],
+                &vertices[triangle[2] as usize],
+            ];
+            let clip = triangle.map(|v| transform * position(v).extend(1.0));
+
+            let size = self.size;
+            rasterize_triangle(size, clip, |x, y, weights| {
+                let color = shade(triangle, weights);
+                self.put(x, y, color, blend);
+            });
+        }
+    }
+
+    /// The software version of [GpuCommonResources::draw_sprite](crate::GpuCommonResources::draw_sprite)
+    pub fn draw_sprite(
+        &mut self,
+        vertices: &[PosColTexVertex],
+        indices: &[u16],
+        texture: &impl SoftwareTexture,
+        transform: Mat4,
+    ) {
+        self.draw_triangles(
+            vertices,
+            indices,
+            |v| v.position,
+            transform,
+            Blend::Alpha,
+            |[a, b, c], w| {
+                let uv = a.texture_coordinate * w.x
+                    + b.texture_coordinate * w.y
+                    + c.texture_coordinate * w.z;
+                let color = a.color * w.x + b.color * w.y + c.color * w.z;
+                texture.sample(uv) * color
+            },
+        );
+    }
+
+    /// Draws an image placed like [GpuImage](crate::GpuImage) does, with `origin` at the zero point
+    pub fn draw_image(&mut self, image: &RgbaImage, origin: Vec2, transform: Mat4) {
+        let vertices = PosColTexVertex::quad(
+            (
+                -origin.x,
+                -origin.y,
+                image.width() as f32 - origin.x,
+                image.height() as f32 - origin.y,
+            ),
+            Vec4::ONE,
+        );
+        self.draw_sprite(&vertices, &QUAD_INDICES, image, transform);
+    }
+
+    /// Draws the regions of a picture chunk, using the vertex lists stored in the PIC file
+    ///
+    /// `position` is where the chunk is placed in the picture, `origin` is the origin of the picture.
+    pub fn draw_picture_chunk(
+        &mut self,
+        chunk: &PictureChunk,
+        position: (u32, u32),
+        origin: Vec2,
+        transform: Mat4,
+    ) {
+        let (vertices, indices) = picture_chunk_vertices(chunk, position, origin);
+        self.draw_sprite(&vertices, &indices, &chunk.data, transform);
+    }
+
+    /// The software version of [GpuCommonResources::draw_fill](crate::GpuCommonResources::draw_fill)
+    pub fn draw_fill(
+        &mut self,
+        vertices: &[PosVertex],
+        indices: &[u16],
+        transform: Mat4,
+        color: Vec4,
+    ) {
+        self.draw_triangles(
+            vertices,
+            indices,
+            |v| v.position,
+            transform,
+            Blend::Alpha,
+            |_, _| color,
+        );
+    }
+
+    /// The software version of [GpuCommonResources::draw_text](crate::GpuCommonResources::draw_text)
+    ///
+    /// `atlas` holds the glyph coverage, like the `R8Unorm` font atlas texture.
+    pub fn draw_text(
+        &mut self,
+        vertices: &[TextVertex],
+        indices: &[u16],
+        atlas: &GrayImage,
+        transform: Mat4,
+        time: Ticks,
+    ) {
+        self.draw_triangles(
+            vertices,
+            indices,
+            |v| v.position.extend(0.0),
+            transform,
+            Blend::Alpha,
+            |[a, b, c], w| {
+                let tex_position =
+                    a.tex_position * w.x + b.tex_position * w.y + c.tex_position * w.z;
+                let color = a.color * w.x + b.color * w.y + c.color * w.z;
+                let vertex_time =
+                    a.time.as_f32() * w.x + b.time.as_f32() * w.y + c.time.as_f32() * w.z;
+
+                let coverage = atlas.sample(tex_position).x;
+                let fade_alpha = (time.as_f32() - vertex_time).clamp(0.0, 1.0);
+                color.extend(coverage * fade_alpha)
+            },
+        );
+    }
+
+    /// Draws the contents of another target over the whole virtual screen,
+    /// like the layers with an intermediate [RenderTarget](crate::RenderTarget) do
+    pub fn draw_target(&mut self, source: &SoftwareTarget, transform: Mat4) {
+        let w = VIRTUAL_WIDTH / 2.0;
+        let h = VIRTUAL_HEIGHT / 2.0;
+        let vertices = PosColTexVertex::quad((-w, -h, w, h), Vec4::ONE);
+        self.draw_sprite(&vertices, &QUAD_INDICES, source, transform);
+    }
+
+    /// Replaces each pixel with the result of `shader`, which gets the previous contents and the texture coordinate of the pixel center
+    ///
+    /// This is how the fullscreen effect passes are run.
+    fn run_pass(&mut self, shader: impl Fn(&SoftwareTarget, Vec2) -> Vec4) {
+        let source = self.clone();
+        let (width, height) = self.size;
+        for y in 0..height {
+            for x in 0..width {
+                let uv = vec2(
+                    (x as f32 + 0.5) / width as f32,
+                    (y as f32 + 0.5) / height as f32,
+                );
+                self.put(x, y, shader(&source, uv), Blend::Replace);
+            }
+        }
+    }
+}
+
+/// Builds the sprite vertices for the opaque & transparent regions of a picture chunk
+///
+/// The regions are given in the pixels of the chunk data, so the texture coordinates are normalized by its size.
+pub fn picture_chunk_vertices(
+    chunk: &PictureChunk,
+    (x, y): (u32, u32),
+    origin: Vec2,
+) -> (Vec<PosColTexVertex>, Vec<u16>) {
+    let data_size = vec2(chunk.data.width() as f32, chunk.data.height() as f32);
+    let offset = vec2(
+        (x + chunk.offset_x) as f32 - origin.x,
+        (y + chunk.offset_y) as f32 - origin.y,
+    );
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for region in chunk
+        .opaque_vertices
+        .iter()
+        .chain(chunk.transparent_vertices.iter())
+    {
+        let from = vec2(region.from_x as f32, region.from_y as f32);
+        let to = vec2(region.to_x as f32, region.to_y as f32);
+
+        let base = vertices.len() as u16;
+        vertices.extend(
+            PosColTexVertex::quad(
+                (
+                    offset.x + from.x,
+                    offset.y + from.y,
+                    offset.x + to.x,
+                    offset.y + to.y,
+                ),
+                Vec4::ONE,
+            )
+            .map(|mut v| {
+                // map the (0, 0)..(1, 1) coordinates of the quad to the region
+                let tex_from = from / data_size;
+                let tex_to = to / data_size;
+                v.texture_coordinate = tex_from + (tex_to - tex_from) * v.texture_coordinate;
+                v
+            }),
+        );
+        indices.extend(QUAD_INDICES.map(|i| base + i));
+    }
+
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use glam::{vec3, vec4};
+    use texture::srgb_to_linear;
+
+    #[test]
+    fn quad_has_no_seam() {
+        // a semi-transparent quad covering some pixels exactly on its diagonal
+        let mut target = SoftwareTarget::new((16, 16));
+        let vertices = PosVertex::quad((0.0, 0.0, 16.0, 16.0));
+        let to_pixels = Mat4::orthographic_rh(0.0, 16.0, 16.0, 0.0, -1.0, 1.0);
+        target.draw_fill(
+            &vertices,
+            &QUAD_INDICES,
+            to_pixels,
+            vec4(1.0, 0.0, 0.0, 0.5),
+        );
+
+        for y in 0..16 {
+            for x in 0..16 {
+                assert_eq!(target.pixel(x, y), vec4(0.5, 0.0, 0.0, 0.5), "({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn srgb_roundtrip() {
+        for value in 0..=255 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(value)), value);
+        }
+    }
+
+    #[test]
+    fn chunk_vertices() {
+        let chunk =
+            PictureChunk::from_image(0, 0, &RgbaImage::from_pixel(4, 2, image::Rgba([255; 4])));
+        let (vertices, indices) = picture_chunk_vertices(&chunk, (10, 20), vec2(1.0, 2.0));
+
+        assert_eq!(indices, QUAD_INDICES.to_vec());
+        // the bottom-left corner
+        assert_eq!(vertices[0].position, vec3(9.0, 20.0, 0.0));
+        // the top-right corner, the last row & column of the chunk data are padding
+        assert_eq!(vertices[3].position, vec3(13.0, 18.0, 0.0));
+        assert_eq!(vertices[3].texture_coordinate, vec2(4.0 / 5.0, 0.0));
+    }
+}
@@ -0,0 +1,69 @@
+use glam::{vec2, Vec2, Vec3, Vec4};
+
+fn edge(a: Vec2, b: Vec2, p: Vec2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// Calls `shade` for every pixel whose center is covered by the triangle, passing the pixel position and its barycentric coordinates
+///
+/// The vertices are in clip space. As all the projections used by the engine are orthographic,
+/// the attributes are interpolated linearly in screen space, without perspective correction.
+///
+/// Pixels on an edge shared by two triangles are only covered by one of them (like on the GPU),
+/// so the quads made of two triangles don't get a blended seam on the diagonal.
+pub fn rasterize_triangle(
+    (width, height): (u32, u32),
+    clip: [Vec4; 3],
+    mut shade: impl FnMut(u32, u32, Vec3),
+) {
+    let window = clip.map(|p| {
+        let ndc = p.truncate().truncate() / p.w;
+        vec2(
+            (ndc.x + 1.0) * 0.5 * width as f32,
+            (1.0 - ndc.y) * 0.5 * height as f32,
+        )
+    });
+
+    let mut area = edge(window[0], window[1], window[2]);
+    if area == 0.0 || !area.is_finite() {
+        return;
+    }
+    // make the weights positive inside the triangle regardless of the winding
+    let sign = area.signum();
+    area *= sign;
+
+    // the tie-breaking rule for the pixels exactly on an edge
+    // it only needs to give the opposite answers for the two directions of the edge
+    let includes_edge = |a: Vec2, b: Vec2| {
+        let direction = (b - a) * sign;
+        direction.y > 0.0 || (direction.y == 0.0 && direction.x < 0.0)
+    };
+    let edges = [
+        (window[1], window[2]),
+        (window[2], window[0]),
+        (window[0], window[1]),
+    ];
+    let includes = edges.map(|(a, b)| includes_edge(a, b));
+
+    let min = window[0].min(window[1]).min(window[2]);
+    let max = window[0].max(window[1]).max(window[2]);
+    let min_x = (min.x - 0.5).ceil().max(0.0) as u32;
+    let min_y = (min.y - 0.5).ceil().max(0.0) as u32;
+    let max_x = ((max.x - 0.5).floor() + 1.0).clamp(0.0, width as f32) as u32;
+    let max_y = ((max.y - 0.5).floor() + 1.0).clamp(0.0, height as f32) as u32;
+
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let p = vec2(x as f32 + 0.5, y as f32 + 0.5);
+            let weights = edges.map(|(a, b)| edge(a, b, p) * sign);
+
+            let covered = weights
+                .iter()
+                .zip(includes)
+                .all(|(&w, include)| w > 0.0 || (w == 0.0 && include));
+            if covered {
+                shade(x, y, Vec3::from(weights) / area);
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use crate::software::SoftwareTarget;
+use glam::{vec4, Vec2, Vec4};
+use image::{GrayImage, RgbaImage};
+use once_cell::sync::Lazy;
+
+static SRGB_TO_LINEAR: Lazy<[f32; 256]> = Lazy::new(|| {
+    let mut table = [0.0; 256];
+    for (i, value) in table.iter_mut().enumerate() {
+        let c = i as f32 / 255.0;
+        *value = if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        };
+    }
+    table
+});
+
+pub fn srgb_to_linear(value: u8) -> f32 {
+    SRGB_TO_LINEAR[value as usize]
+}
+
+pub fn linear_to_srgb(value: f32) -> u8 {
+    let c = value.clamp(0.0, 1.0);
+    let c = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round() as u8
+}
+
+/// A texture the software renderer can sample from
+///
+/// Mirrors how the GPU reads the texture formats used by the engine: the color channels of the images are sRGB-encoded
+/// and are decoded before filtering, while the font atlas (an `R8Unorm` texture on the GPU) is linear.
+pub trait SoftwareTexture {
+    fn size(&self) -> (u32, u32);
+
+    /// Reads a single texel, converted to linear color
+    fn texel(&self, x: u32, y: u32) -> Vec4;
+
+    /// Samples the texture with bilinear filtering and clamp-to-edge addressing
+    fn sample(&self, uv: Vec2) -> Vec4 {
+        let (width, height) = self.size();
+        if width == 0 || height == 0 {
+            return Vec4::ZERO;
+        }
+
+        let x = uv.x * width as f32 - 0.5;
+        let y = uv.y * height as f32 - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+
+        let clamp_x = |x: f32| x.clamp(0.0, (width - 1) as f32) as u32;
+        let clamp_y = |y: f32| y.clamp(0.0, (height - 1) as f32) as u32;
+        let (x0, x1) = (clamp_x(x0), clamp_x(x0 + 1.0));
+        let (y0, y1) = (clamp_y(y0), clamp_y(y0 + 1.0));
+
+        let top = self.texel(x0, y0).lerp(self.texel(x1, y0), fx);
+        let bottom = self.texel(x0, y1).lerp(self.texel(x1, y1), fx);
+        top.lerp(bottom, fy)
+    }
+}
+
+impl SoftwareTexture for RgbaImage {
+    fn size(&self) -> (u32, u32) {
+        self.dimensions()
+    }
+
+    fn texel(&self, x: u32, y: u32) -> Vec4 {
+        let [r, g, b, a] = self.get_pixel(x, y).0;
+        vec4(
+            srgb_to_linear(r),
+            srgb_to_linear(g),
+            srgb_to_linear(b),
+            a as f32 / 255.0,
+        )
+    }
+}
+
+impl SoftwareTexture for GrayImage {
+    fn size(&self) -> (u32, u32) {
+        self.dimensions()
+    }
+
+    fn texel(&self, x: u32, y: u32) -> Vec4 {
+        // like a single-channel texture on the GPU, the value ends up in the red channel
+        vec4(self.get_pixel(x, y).0[0] as f32 / 255.0, 0.0, 0.0, 1.0)
+    }
+}
+
+impl SoftwareTexture for SoftwareTarget {
+    fn size(&self) -> (u32, u32) {
+        SoftwareTarget::size(self)
+    }
+
+    fn texel(&self, x: u32, y: u32) -> Vec4 {
+        self.pixel(x, y)
+    }
+}
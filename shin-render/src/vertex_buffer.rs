@@ -1,6 +1,6 @@
-use crate::vertices::{PosColTexVertex, PosVertex, TextVertex, VertexSource};
+use crate::vertices::{PosColTexVertex, PosVertex, TextVertex, VertexSource, QUAD_INDICES};
 use crate::{GpuCommonResources, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
-use glam::{vec2, vec3, vec4, Vec4};
+use glam::{vec4, Vec4};
 use std::sync::atomic::{AtomicU32, Ordering};
 use wgpu::util::DeviceExt;
 
@@ -85,16 +85,16 @@ impl<T: Vertex> VertexBuffer<T> {
         }
     }
 
-    // pub fn vertex_source_slice(&self, range: std::ops::Range<u32>) -> VertexSource<T> {
-    //     assert!(range.end <= self.num_vertices.load(Ordering::SeqCst));
-    //
-    //     VertexSource::VertexBuffer {
-    //         vertex_buffer: &self.buffer,
-    //         vertices: range,
-    //         instances: 0..1,
-    //         phantom: std::marker::PhantomData,
-    //     }
-    // }
+    pub fn vertex_source_slice(&self, range: std::ops::Range<u32>) -> VertexSource<'_, T> {
+        assert!(range.end <= self.num_vertices.load(Ordering::SeqCst));
+
+        VertexSource::VertexBuffer {
+            vertex_buffer: &self.buffer,
+            vertices: range,
+            instances: 0..1,
+            phantom: std::marker::PhantomData,
+        }
+    }
 }
 
 pub struct IndexBuffer {
@@ -135,34 +135,8 @@ impl SpriteVertexBuffer {
         (l, t, r, b): (f32, f32, f32, f32),
         color: Vec4,
     ) -> Self {
-        let vertices = [
-            // 0
-            PosColTexVertex {
-                position: vec3(l, b, 0.0),
-                color,
-                texture_coordinate: vec2(0.0, 1.0),
-            },
-            // 1
-            PosColTexVertex {
-                position: vec3(l, t, 0.0),
-                color,
-                texture_coordinate: vec2(0.0, 0.0),
-            },
-            // 2
-            PosColTexVertex {
-                position: vec3(r, b, 0.0),
-                color,
-                texture_coordinate: vec2(1.0, 1.0),
-            },
-            // 3
-            PosColTexVertex {
-                position: vec3(r, t, 0.0),
-                color,
-                texture_coordinate: vec2(1.0, 0.0),
-            },
-        ];
-
-        let indices = [0, 1, 2, 2, 1, 3];
+        let vertices = PosColTexVertex::quad((l, t, r, b), color);
+        let indices = QUAD_INDICES;
 
         Self {
             vertex_buffer: VertexBuffer::new(
@@ -199,26 +173,8 @@ pub struct PosVertexBuffer {
 
 impl PosVertexBuffer {
     pub fn new(resources: &GpuCommonResources, (l, t, r, b): (f32, f32, f32, f32)) -> Self {
-        let vertices = [
-            // 0
-            PosVertex {
-                position: vec3(l, b, 0.0),
-            },
-            // 1
-            PosVertex {
-                position: vec3(l, t, 0.0),
-            },
-            // 2
-            PosVertex {
-                position: vec3(r, b, 0.0),
-            },
-            // 3
-            PosVertex {
-                position: vec3(r, t, 0.0),
-            },
-        ];
-
-        let indices = [0, 1, 2, 2, 1, 3];
+        let vertices = PosVertex::quad((l, t, r, b));
+        let indices = QUAD_INDICES;
 
         Self {
             vertex_buffer: VertexBuffer::new(
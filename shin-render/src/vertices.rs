@@ -1,4 +1,4 @@
-use glam::{Vec2, Vec3, Vec4};
+use glam::{vec2, vec3, Vec2, Vec3, Vec4};
 use shin_core::time::Ticks;
 use std::ops::Range;
 
@@ -13,6 +13,38 @@ pub struct PosColTexVertex {
     pub texture_coordinate: Vec2,
 }
 
+impl PosColTexVertex {
+    /// Vertices of a rectangle covered by the whole texture, to be drawn with [QUAD_INDICES]
+    pub fn quad((l, t, r, b): (f32, f32, f32, f32), color: Vec4) -> [Self; 4] {
+        [
+            // 0
+            PosColTexVertex {
+                position: vec3(l, b, 0.0),
+                color,
+                texture_coordinate: vec2(0.0, 1.0),
+            },
+            // 1
+            PosColTexVertex {
+                position: vec3(l, t, 0.0),
+                color,
+                texture_coordinate: vec2(0.0, 0.0),
+            },
+            // 2
+            PosColTexVertex {
+                position: vec3(r, b, 0.0),
+                color,
+                texture_coordinate: vec2(1.0, 1.0),
+            },
+            // 3
+            PosColTexVertex {
+                position: vec3(r, t, 0.0),
+                color,
+                texture_coordinate: vec2(1.0, 0.0),
+            },
+        ]
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, wrld::Desc, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct PosVertex {
@@ -20,6 +52,33 @@ pub struct PosVertex {
     pub position: Vec3,
 }
 
+impl PosVertex {
+    /// Vertices of a rectangle, to be drawn with [QUAD_INDICES]
+    pub fn quad((l, t, r, b): (f32, f32, f32, f32)) -> [Self; 4] {
+        [
+            // 0
+            PosVertex {
+                position: vec3(l, b, 0.0),
+            },
+            // 1
+            PosVertex {
+                position: vec3(l, t, 0.0),
+            },
+            // 2
+            PosVertex {
+                position: vec3(r, b, 0.0),
+            },
+            // 3
+            PosVertex {
+                position: vec3(r, t, 0.0),
+            },
+        ]
+    }
+}
+
+/// Indices of the two triangles making up a quad built by [PosColTexVertex::quad] or [PosVertex::quad]
+pub const QUAD_INDICES: [u16; 6] = [0, 1, 2, 2, 1, 3];
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, wrld::Desc, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct TextVertex {
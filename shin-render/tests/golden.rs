@@ -0,0 +1,406 @@
+//! Golden-image tests of the software renderer
+//!
+//! The scenes are rendered with [SoftwareTarget] and compared against the images in `tests/golden`.
+//! After an intended change in the rendering, run the tests with `SHIN_BLESS=1` to overwrite them.
+
+use glam::{vec2, vec3, vec4, Mat4, Vec4};
+use image::{GrayImage, Rgba, RgbaImage};
+use shin_core::format::picture::{PicVertexEntry, PictureChunk};
+use shin_core::time::Ticks;
+use shin_render::software::{self, SoftwareRenderable, SoftwareTarget};
+use shin_render::vertices::{PosVertex, TextVertex, QUAD_INDICES};
+use shin_render::{Effect, EffectStack, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+use std::path::{Path, PathBuf};
+
+/// A quarter of the virtual resolution, to keep the golden images small
+const SIZE: (u32, u32) = (480, 270);
+
+fn assert_golden(name: &str, actual: &RgbaImage) {
+    software::assert_golden(
+        &PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden"),
+        Path::new(env!("CARGO_TARGET_TMPDIR")),
+        name,
+        actual,
+    );
+}
+
+/// A test picture with a gradient, a grid and a semi-transparent & fully transparent part
+fn test_picture(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        let u = x as f32 / width as f32;
+        let v = y as f32 / height as f32;
+
+        if (x / 16 + y / 16) % 7 == 3 {
+            // holes
+            return Rgba([0, 0, 0, 0]);
+        }
+
+        let alpha = if v > 0.75 { 128 } else { 255 };
+        if x % 32 == 0 || y % 32 == 0 {
+            Rgba([255, 255, 255, alpha])
+        } else {
+            Rgba([(u * 255.0) as u8, (v * 255.0) as u8, 160, alpha])
+        }
+    })
+}
+
+/// A font atlas with a few synthetic glyphs, 32x32 each
+fn test_atlas() -> GrayImage {
+    GrayImage::from_fn(96, 32, |x, y| {
+        let glyph = x / 32;
+        let p = vec2((x % 32) as f32 + 0.5, y as f32 + 0.5) - vec2(16.0, 16.0);
+        let coverage = match glyph {
+            // a disc
+            0 => 11.0 - p.length(),
+            // a ring
+            1 => 3.0 - (p.length() - 9.0).abs(),
+            // a cross
+            _ => 3.0 - p.x.abs().min(p.y.abs()),
+        };
+        image::Luma([(coverage.clamp(0.0, 1.0) * 255.0) as u8])
+    })
+}
+
+fn background() -> SoftwareTarget {
+    let mut target = SoftwareTarget::new(SIZE);
+    target.clear(vec4(0.05, 0.05, 0.1, 1.0));
+    target
+}
+
+/// Splits a picture into chunks, like the PIC files do
+fn split_into_chunks(picture: &RgbaImage, chunk_size: u32) -> Vec<PictureChunk> {
+    let mut chunks = Vec::new();
+    for y in (0..picture.height()).step_by(chunk_size as usize) {
+        for x in (0..picture.width()).step_by(chunk_size as usize) {
+            let width = chunk_size.min(picture.width() - x);
+            let height = chunk_size.min(picture.height() - y);
+            let view = image::imageops::crop_imm(picture, x, y, width, height).to_image();
+            chunks.push(PictureChunk::from_image(x, y, &view));
+        }
+    }
+    chunks
+}
+
+#[test]
+fn sprite_transform() {
+    let picture = test_picture(256, 192);
+    let origin = vec2(128.0, 96.0);
+
+    let mut target = background();
+    let projection = target.projection_matrix();
+    target.draw_image(&picture, origin, projection);
+    target.draw_image(
+        &picture,
+        origin,
+        projection
+            * Mat4::from_translation(vec3(-500.0, 200.0, 0.0))
+            * Mat4::from_rotation_z(std::f32::consts::FRAC_PI_6)
+            * Mat4::from_scale(vec3(1.5, 0.75, 1.0)),
+    );
+    target.draw_image(
+        &picture,
+        origin,
+        // mirrored, rotated around a point outside of the picture
+        projection
+            * Mat4::from_translation(vec3(600.0, -250.0, 0.0))
+            * Mat4::from_rotation_z(-1.0)
+            * Mat4::from_scale(vec3(-2.0, 2.0, 1.0))
+            * Mat4::from_translation(vec3(-50.0, 0.0, 0.0)),
+    );
+
+    assert_golden("sprite_transform", &target.to_image());
+}
+
+#[test]
+fn picture_chunks() {
+    let picture = test_picture(700, 400);
+    let chunks = split_into_chunks(&picture, 128);
+    let origin = vec2(350.0, 200.0);
+    let transform = Mat4::from_rotation_z(0.2) * Mat4::from_scale(vec3(2.0, 2.0, 1.0));
+
+    let mut target = background();
+    let projection = target.projection_matrix();
+    for chunk in &chunks {
+        target.draw_picture_chunk(chunk, (0, 0), origin, projection * transform);
+    }
+
+    assert_golden("picture_chunks", &target.to_image());
+}
+
+#[test]
+fn picture_chunk_regions() {
+    // a chunk with separate opaque & transparent regions, only the pixels inside them are drawn
+    let mut data = RgbaImage::from_pixel(65, 65, Rgba([255, 0, 0, 255]));
+    for (x, y, pixel) in data.enumerate_pixels_mut() {
+        if x >= 32 {
+            *pixel = Rgba([0, 0, 255, (y * 4).min(255) as u8]);
+        }
+    }
+    let chunk = PictureChunk {
+        offset_x: 16,
+        offset_y: 8,
+        opaque_vertices: vec![PicVertexEntry {
+            from_x: 0,
+            from_y: 0,
+            to_x: 32,
+            to_y: 64,
+        }],
+        transparent_vertices: vec![
+            PicVertexEntry {
+                from_x: 32,
+                from_y: 0,
+                to_x: 64,
+                to_y: 16,
+            },
+            PicVertexEntry {
+                from_x: 32,
+                from_y: 48,
+                to_x: 64,
+                to_y: 64,
+            },
+        ],
+        data,
+    };
+
+    let mut target = background();
+    let projection = target.projection_matrix();
+    target.draw_picture_chunk(
+        &chunk,
+        (0, 0),
+        vec2(40.0, 36.0),
+        projection * Mat4::from_scale(vec3(6.0, 6.0, 1.0)),
+    );
+
+    assert_golden("picture_chunk_regions", &target.to_image());
+}
+
+#[test]
+fn fills() {
+    let mut target = background();
+    let projection = target.projection_matrix();
+
+    let colors = [
+        vec4(1.0, 0.0, 0.0, 0.5),
+        vec4(0.0, 1.0, 0.0, 0.5),
+        vec4(0.0, 0.0, 1.0, 0.5),
+        vec4(1.0, 1.0, 1.0, 0.25),
+    ];
+    for (i, color) in colors.into_iter().enumerate() {
+        let offset = vec2(i as f32 * 150.0 - 400.0, i as f32 * 100.0 - 300.0);
+        let vertices = PosVertex::quad((offset.x, offset.y, offset.x + 500.0, offset.y + 300.0));
+        target.draw_fill(
+            &vertices,
+            &QUAD_INDICES,
+            projection * Mat4::from_rotation_z(i as f32 * 0.1),
+            color,
+        );
+    }
+
+    assert_golden("fills", &target.to_image());
+}
+
+#[test]
+fn text_fade() {
+    let atlas = test_atlas();
+    let atlas_size = vec2(atlas.width() as f32, atlas.height() as f32);
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for i in 0..12 {
+        let glyph = i % 3;
+        let position = vec2(-720.0 + i as f32 * 120.0, -40.0);
+        let color = [
+            vec3(1.0, 1.0, 1.0),
+            vec3(1.0, 0.8, 0.2),
+            vec3(0.4, 0.8, 1.0),
+        ][glyph as usize];
+
+        let base = vertices.len() as u16;
+        // the glyphs are scaled up, like the big text is
+        for (corner, tex_corner) in [
+            (vec2(0.0, 96.0), vec2(0.0, 32.0)),
+            (vec2(0.0, 0.0), vec2(0.0, 0.0)),
+            (vec2(96.0, 96.0), vec2(32.0, 32.0)),
+            (vec2(96.0, 0.0), vec2(32.0, 0.0)),
+        ] {
+            vertices.push(TextVertex {
+                position: position + corner,
+                tex_position: (vec2(glyph as f32 * 32.0, 0.0) + tex_corner) / atlas_size,
+                color,
+                time: Ticks::from_f32(i as f32 * 0.25),
+                fade: 1.0,
+            });
+        }
+        indices.extend(QUAD_INDICES.map(|i| base + i));
+    }
+
+    let mut target = background();
+    let projection = target.projection_matrix();
+    // some glyphs are fully shown, some are fading in and some didn't appear yet
+    target.draw_text(
+        &vertices,
+        &indices,
+        &atlas,
+        projection,
+        Ticks::from_f32(1.5),
+    );
+
+    assert_golden("text_fade", &target.to_image());
+}
+
+/// A layer with its own render target, like the layer groups
+struct Group {
+    picture: RgbaImage,
+    fill_color: Vec4,
+    transform: Mat4,
+}
+
+impl SoftwareRenderable for Group {
+    fn render_software(&self, target: &mut SoftwareTarget, transform: Mat4, projection: Mat4) {
+        let mut inner = SoftwareTarget::new(target.size());
+        let inner_projection = inner.projection_matrix();
+        let transform = transform * self.transform;
+
+        let fill = PosVertex::quad((-600.0, -300.0, 600.0, 300.0));
+        inner.draw_fill(
+            &fill,
+            &QUAD_INDICES,
+            inner_projection * transform,
+            self.fill_color,
+        );
+        inner.draw_image(
+            &self.picture,
+            vec2(128.0, 96.0),
+            inner_projection * transform,
+        );
+
+        target.draw_target(&inner, projection);
+    }
+}
+
+#[test]
+fn nested_targets() {
+    let inner = Group {
+        picture: test_picture(256, 192),
+        fill_color: vec4(0.2, 0.6, 0.3, 0.5),
+        transform: Mat4::from_scale(vec3(0.5, 0.5, 1.0)),
+    };
+
+    let mut target = background();
+    let projection = target.projection_matrix();
+    let effects = EffectStack::new("Test");
+    effects.render_software(
+        &mut target,
+        &inner,
+        Mat4::from_rotation_z(0.3) * Mat4::from_translation(vec3(300.0, 0.0, 0.0)),
+        projection,
+    );
+    inner.render_software(
+        &mut target,
+        Mat4::from_translation(vec3(-500.0, 250.0, 0.0)),
+        projection,
+    );
+
+    assert_golden("nested_targets", &target.to_image());
+}
+
+fn effect_scene(effects: &[Effect]) -> RgbaImage {
+    let picture = test_picture(256, 192);
+
+    let mut layer = SoftwareTarget::new(SIZE);
+    let projection = layer.projection_matrix();
+    layer.draw_image(
+        &picture,
+        vec2(128.0, 96.0),
+        projection * Mat4::from_scale(vec3(3.0, 3.0, 1.0)),
+    );
+    layer.apply_effects(effects);
+
+    let mut target = background();
+    target.draw_target(&layer, projection);
+    target.to_image()
+}
+
+#[test]
+fn effects() {
+    assert_golden(
+        "effect_blur",
+        &effect_scene(&[Effect::Blur { radius: 24.0 }]),
+    );
+    assert_golden(
+        "effect_mosaic",
+        &effect_scene(&[Effect::Mosaic { size: 40.0 }]),
+    );
+    assert_golden(
+        "effect_color",
+        &effect_scene(&[Effect::sepia(1.0)
+            .then_color(Effect::tint(
+                vec4(1.0, 0.9, 0.9, 0.8),
+                vec4(0.0, 0.0, 0.1, 0.0),
+            ))
+            .unwrap()]),
+    );
+    assert_golden(
+        "effect_raster_scroll",
+        &effect_scene(&[Effect::RasterScroll {
+            amplitude: 40.0,
+            wavelength: 270.0,
+            phase: 1.0,
+        }]),
+    );
+    // no-op effects are skipped
+    assert_eq!(
+        effect_scene(&[Effect::IDENTITY_COLOR, Effect::Blur { radius: 0.0 }]),
+        effect_scene(&[])
+    );
+}
+
+#[test]
+fn full_size_matches_source() {
+    // at the virtual resolution, the pixels of a picture end up on the screen unchanged
+    let picture = test_picture(300, 200);
+    let position = vec2(123.0, 45.0);
+    let origin = vec2(VIRTUAL_WIDTH / 2.0, VIRTUAL_HEIGHT / 2.0) - position;
+
+    let draw = |draw: &dyn Fn(&mut SoftwareTarget, Mat4)| {
+        let mut target = SoftwareTarget::new((VIRTUAL_WIDTH as u32, VIRTUAL_HEIGHT as u32));
+        target.clear(Vec4::new(0.0, 0.0, 0.0, 1.0));
+        let projection = target.projection_matrix();
+        draw(&mut target, projection);
+        target.to_image()
+    };
+
+    let merged = draw(&|target, projection| target.draw_image(&picture, origin, projection));
+    let chunked = draw(&|target, projection| {
+        for chunk in split_into_chunks(&picture, 64) {
+            target.draw_picture_chunk(&chunk, (0, 0), origin, projection);
+        }
+    });
+    assert!(
+        merged == chunked,
+        "the chunks are not drawn the same as the merged picture"
+    );
+
+    for (x, y, &source) in picture.enumerate_pixels() {
+        let actual = merged
+            .get_pixel(x + position.x as u32, y + position.y as u32)
+            .0;
+        match source.0[3] {
+            255 => assert_eq!(actual, source.0, "({x}, {y})"),
+            0 => assert_eq!(actual, [0, 0, 0, 255], "({x}, {y})"),
+            // the semi-transparent pixels are blended with the background, which the other tests cover
+            _ => {}
+        }
+    }
+
+    assert!(
+        merged
+            .enumerate_pixels()
+            .filter(|&(x, y, _)| {
+                !(position.x as u32..position.x as u32 + picture.width()).contains(&x)
+                    || !(position.y as u32..position.y as u32 + picture.height()).contains(&y)
+            })
+            .all(|(_, _, pixel)| pixel.0 == [0, 0, 0, 255]),
+        "the picture was drawn outside of its bounds"
+    );
+}
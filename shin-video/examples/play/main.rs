@@ -82,7 +82,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         pipelines,
     });
 
-    let audio_manager = AudioManager::new();
+    let audio_manager = AudioManager::new().unwrap();
 
     // let file = File::open("ship1.mp4").unwrap();
     let file = File::open("op1.mp4").unwrap();
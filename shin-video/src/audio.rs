@@ -95,11 +95,134 @@ impl<S: Read + Seek> AudioFrameSource for AacFrameSource<S> {
         }
     }
 
-    fn samples_seek(&mut self, _sample_position: u32) -> anyhow::Result<u32> {
-        todo!("Seeking AAC stream")
+    fn samples_seek(&mut self, sample_position: u32) -> anyhow::Result<u32> {
+        // the track timescale is not necessarily the sample rate
+        let timescale = self.track.get_mp4_track_info(|t| t.timescale()) as u64;
+        let sample_rate = self.sample_rate() as u64;
+
+        let time = sample_position as u64 * timescale / sample_rate;
+        let (sample_number, start_time) = self.track.find_sample_at(time).with_context(|| {
+            format!(
+                "Seek position {} is out of bounds of the AAC track",
+                sample_position
+            )
+        })?;
+
+        self.track.seek_to_sample(sample_number);
+        self.decoder.reset();
+        self.samples_position = (start_time * sample_rate / timescale) as u32;
+
+        Ok(sample_position - self.samples_position)
     }
 
     fn current_sample_position(&self) -> u32 {
         self.samples_position
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+
+    const TIMESCALE: u32 = 90000;
+    // 1024 samples at 48000 Hz in the track timescale
+    const FRAME_DURATION: u32 = 1920;
+
+    /// An AAC track with 10 full frames followed by 2 half frames, the payloads are not valid AAC
+    ///
+    /// Seeking does not decode anything, so only the sample table matters.
+    fn make_source() -> AacFrameSource<Cursor<Vec<u8>>> {
+        let config = mp4::Mp4Config {
+            major_brand: "isom".parse().unwrap(),
+            minor_version: 512,
+            compatible_brands: vec!["isom".parse().unwrap()],
+            timescale: 1000,
+        };
+        let mut writer = mp4::Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+        writer
+            .add_track(&mp4::TrackConfig {
+                track_type: mp4::TrackType::Audio,
+                timescale: TIMESCALE,
+                language: "und".to_string(),
+                media_conf: mp4::MediaConfig::AacConfig(mp4::AacConfig {
+                    bitrate: 128000,
+                    profile: mp4::AudioObjectType::AacLowComplexity,
+                    freq_index: mp4::SampleFreqIndex::Freq48000,
+                    chan_conf: mp4::ChannelConfig::Stereo,
+                }),
+            })
+            .unwrap();
+
+        let durations = [FRAME_DURATION; 10]
+            .into_iter()
+            .chain([FRAME_DURATION / 2; 2]);
+        let mut start_time = 0;
+        for (i, duration) in durations.enumerate() {
+            writer
+                .write_sample(
+                    1,
+                    &mp4::Mp4Sample {
+                        start_time,
+                        duration,
+                        rendering_offset: 0,
+                        is_sync: true,
+                        bytes: vec![i as u8; 8].into(),
+                    },
+                )
+                .unwrap();
+            start_time += duration as u64;
+        }
+        writer.write_end().unwrap();
+
+        let data = writer.into_writer().into_inner();
+        let size = data.len() as u64;
+        let reader = mp4::Mp4Reader::read_header(Cursor::new(data), size).unwrap();
+        let track = Mp4TrackReader::new(Arc::new(Mutex::new(reader)), 1).unwrap();
+        AacFrameSource::new(track).unwrap()
+    }
+
+    fn next_sample_start(source: &mut AacFrameSource<Cursor<Vec<u8>>>) -> u64 {
+        source.track.next_sample().unwrap().unwrap().start_time
+    }
+
+    #[test]
+    fn find_sample_at() {
+        let source = make_source();
+        let track = &source.track;
+
+        assert_eq!(track.find_sample_at(0), Some((1, 0)));
+        assert_eq!(track.find_sample_at(1919), Some((1, 0)));
+        assert_eq!(track.find_sample_at(1920), Some((2, 1920)));
+        // the half frames are in the second entry of the time-to-sample table
+        assert_eq!(track.find_sample_at(19200), Some((11, 19200)));
+        assert_eq!(track.find_sample_at(20200), Some((12, 20160)));
+        assert_eq!(track.find_sample_at(21119), Some((12, 20160)));
+        assert_eq!(track.find_sample_at(21120), None);
+    }
+
+    #[test]
+    fn samples_seek() {
+        let mut source = make_source();
+        assert_eq!(source.sample_rate(), 48000);
+
+        // the seek lands on the start of the frame, the rest has to be skipped after decoding
+        assert_eq!(source.samples_seek(1500).unwrap(), 1500 - 1024);
+        assert_eq!(source.current_sample_position(), 1024);
+        assert_eq!(next_sample_start(&mut source), 1920);
+
+        // seeking back works too
+        assert_eq!(source.samples_seek(0).unwrap(), 0);
+        assert_eq!(source.current_sample_position(), 0);
+        assert_eq!(next_sample_start(&mut source), 0);
+
+        // 10 full frames and one half frame
+        let position = 10 * 1024 + 512 + 88;
+        assert_eq!(source.samples_seek(position).unwrap(), 88);
+        assert_eq!(source.current_sample_position(), 10 * 1024 + 512);
+        assert_eq!(next_sample_start(&mut source), 20160);
+
+        assert!(source.samples_seek(11 * 1024).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::VecDeque;
+use std::io::{Read, Seek};
+use std::iter::Peekable;
+
+use super::y4m::{BitsPerSample, Colorspace, Frame, FrameSize, PlaneSize};
+use super::{FrameTiming, H264Decoder};
+use crate::mp4::Mp4TrackReader;
+use crate::mp4_bitstream_converter::Mp4BitstreamConverter;
+use anyhow::{anyhow, bail, Context, Result};
+use openh264::decoder::{DecodedYUV, Decoder};
+use openh264_sys2::{
+    dsErrorFree, SBufferInfo, DECODER_OPTION_END_OF_STREAM,
+    DECODER_OPTION_NUM_OF_FRAMES_REMAINING_IN_BUFFER,
+};
+use std::os::raw::c_int;
+use std::ptr::{addr_of_mut, null_mut};
+use tracing::{debug, error, trace};
+
+/// Decodes h264 in-process using openh264, without any external dependencies.
+///
+/// The decoding happens on a separate thread, the frames are sent to the game through a channel.
+pub struct SoftwareH264Decoder {
+    frame_receiver: Peekable<std::sync::mpsc::IntoIter<Result<(FrameTiming, Frame), String>>>,
+    frame_size: Option<FrameSize>,
+}
+
+/// Copy the planes out of the decoder's buffers, dropping the stride padding
+///
+/// The strides are those of the luma and the chroma planes, the size is the size of the luma plane.
+fn copy_frame(planes: [&[u8]; 3], strides: [usize; 2], (width, height): (usize, usize)) -> Frame {
+    // openh264 always outputs 4:2:0
+    let (chroma_width, chroma_height) = (width / 2, height / 2);
+
+    fn copy_plane(data: &[u8], stride: usize, width: usize, height: usize) -> Vec<u8> {
+        data.chunks(stride)
+            .take(height)
+            .flat_map(|row| &row[..width])
+            .copied()
+            .collect()
+    }
+
+    let planes = [
+        copy_plane(planes[0], strides[0], width, height),
+        copy_plane(planes[1], strides[1], chroma_width, chroma_height),
+        copy_plane(planes[2], strides[1], chroma_width, chroma_height),
+    ];
+
+    let luma_size = PlaneSize::new(width as u32, height as u32, BitsPerSample::B8);
+    let chroma_size = PlaneSize::new(chroma_width as u32, chroma_height as u32, BitsPerSample::B8);
+
+    Frame::new(
+        planes,
+        None,
+        FrameSize {
+            colorspace: Colorspace::C420mpeg2,
+            plane_sizes: [luma_size, chroma_size, chroma_size],
+        },
+    )
+}
+
+fn copy_decoded_frame(yuv: &DecodedYUV) -> Frame {
+    let (stride_y, stride_u, _) = yuv.strides_yuv();
+    copy_frame(
+        [
+            yuv.y_with_stride(),
+            yuv.u_with_stride(),
+            yuv.v_with_stride(),
+        ],
+        [stride_y, stride_u],
+        yuv.dimension_y(),
+    )
+}
+
+/// Take the frames still held by the decoder (because of the frame reordering) after the last sample
+fn flush_frames(decoder: &mut Decoder) -> Result<Vec<Frame>> {
+    let mut frames = Vec::new();
+
+    // the openh264 crate doesn't expose flushing, so this goes through the raw API
+    unsafe {
+        let raw = decoder.raw_api();
+
+        let mut end_of_stream: c_int = 1;
+        raw.set_option(
+            DECODER_OPTION_END_OF_STREAM,
+            addr_of_mut!(end_of_stream).cast(),
+        );
+
+        loop {
+            let mut remaining: c_int = 0;
+            raw.get_option(
+                DECODER_OPTION_NUM_OF_FRAMES_REMAINING_IN_BUFFER,
+                addr_of_mut!(remaining).cast(),
+            );
+            if remaining <= 0 {
+                break;
+            }
+
+            let mut planes = [null_mut::<u8>(); 3];
+            let mut info = SBufferInfo::default();
+            let state = raw.flush_frame(&mut planes as *mut _, &mut info);
+            if state != dsErrorFree {
+                bail!("Flushing openh264 decoder failed with state {}", state);
+            }
+            if info.iBufferStatus == 0 || planes.iter().any(|p| p.is_null()) {
+                // the decoder reports frames it can't output, nothing more to get
+                break;
+            }
+
+            let buffer = info.UsrData.sSystemBuffer;
+            let height = buffer.iHeight as usize;
+            let strides = [buffer.iStride[0] as usize, buffer.iStride[1] as usize];
+            let planes = [
+                std::slice::from_raw_parts(planes[0], height * strides[0]),
+                std::slice::from_raw_parts(planes[1], height / 2 * strides[1]),
+                std::slice::from_raw_parts(planes[2], height / 2 * strides[1]),
+            ];
+
+            frames.push(copy_frame(
+                planes,
+                strides,
+                (buffer.iWidth as usize, height),
+            ));
+        }
+    }
+
+    Ok(frames)
+}
+
+fn decode_thread<S: Read + Seek>(
+    mut track: Mp4TrackReader<S>,
+    frame_sender: std::sync::mpsc::SyncSender<Result<(FrameTiming, Frame), String>>,
+) {
+    let mut decoder = match Decoder::new() {
+        Ok(decoder) => decoder,
+        Err(e) => {
+            let _ = frame_sender.send(Err(format!("Creating openh264 decoder: {}", e)));
+            return;
+        }
+    };
+
+    let mut bitstream_converter: Mp4BitstreamConverter =
+        track.get_mp4_track_info(Mp4BitstreamConverter::for_mp4_track);
+    let mut buffer = Vec::new();
+
+    // the decoder might delay some frames, so the timings are matched with the frames in the decoding order
+    // NOTE: this ignores the sample.rendering_offset, same as the ffmpeg decoder
+    let mut pending_timings = VecDeque::new();
+    let mut frame_number = 0;
+
+    let send_frame = |pending_timings: &mut VecDeque<FrameTiming>, frame: Frame| {
+        let Some(timing) = pending_timings.pop_front() else {
+            error!("The decoder returned more frames than there were samples, dropping the frame");
+            return true;
+        };
+
+        if frame_sender.send(Ok((timing, frame))).is_err() {
+            debug!("Game closed the channel, stopping decoding");
+            return false;
+        }
+        true
+    };
+
+    loop {
+        let sample = match track.next_sample() {
+            Ok(Some(sample)) => sample,
+            Ok(None) => {
+                debug!("EOF from mp4, flushing the decoder");
+                break;
+            }
+            Err(e) => {
+                error!("Error reading sample from mp4: {}", e);
+                break;
+            }
+        };
+
+        pending_timings.push_back(FrameTiming {
+            frame_number,
+            start_time: sample.start_time,
+            duration: sample.duration,
+        });
+        frame_number += 1;
+
+        bitstream_converter.convert_packet(&sample.bytes, &mut buffer);
+        trace!("Decoding sample ({} bytes)", buffer.len());
+
+        let frame = match decoder.decode(&buffer) {
+            Ok(Some(yuv)) => copy_decoded_frame(&yuv),
+            Ok(None) => continue,
+            Err(e) => {
+                let _ = frame_sender.send(Err(format!("Decoding h264 sample: {}", e)));
+                return;
+            }
+        };
+
+        if !send_frame(&mut pending_timings, frame) {
+            return;
+        }
+    }
+
+    let frames = match flush_frames(&mut decoder) {
+        Ok(frames) => frames,
+        Err(e) => {
+            let _ = frame_sender.send(Err(format!("{:#}", e)));
+            return;
+        }
+    };
+    debug!(
+        "Flushed {} frames ({} timings left)",
+        frames.len(),
+        pending_timings.len()
+    );
+
+    for frame in frames {
+        if !send_frame(&mut pending_timings, frame) {
+            return;
+        }
+    }
+}
+
+impl SoftwareH264Decoder {
+    pub fn new<S: Read + Seek + Send + 'static>(track: Mp4TrackReader<S>) -> Result<Self> {
+        // a small buffer to smooth out the decoding time spikes (like on I-frames)
+        let (frame_sender, frame_receiver) = std::sync::mpsc::sync_channel(2);
+
+        std::thread::Builder::new()
+            .name("h264-decoder".to_string())
+            .spawn(move || decode_thread(track, frame_sender))
+            .context("Spawning the decoder thread")?;
+
+        Ok(Self {
+            frame_receiver: frame_receiver.into_iter().peekable(),
+            frame_size: None,
+        })
+    }
+}
+
+impl H264Decoder for SoftwareH264Decoder {
+    fn read_frame(&mut self) -> Result<Option<(FrameTiming, Frame)>> {
+        match self.frame_receiver.next() {
+            Some(Ok((timing, frame))) => {
+                self.frame_size = Some(*frame.size());
+                Ok(Some((timing, frame)))
+            }
+            Some(Err(e)) => Err(anyhow!(e)),
+            None => Ok(None),
+        }
+    }
+
+    fn frame_size(&mut self) -> Result<FrameSize> {
+        match self.frame_size {
+            Some(size) => Ok(size),
+            None => match self.frame_receiver.peek() {
+                Some(Ok((_, frame))) => {
+                    self.frame_size = Some(*frame.size());
+                    Ok(*frame.size())
+                }
+                Some(Err(e)) => Err(anyhow!(e.clone())),
+                None => Err(anyhow!("No frames available, don't know the format")),
+            },
+        }
+    }
+}
@@ -6,5 +6,6 @@ mod timer;
 mod video_player;
 mod yuv_texture;
 
+pub use h264_decoder::{H264Decoder, H264DecoderBackend};
 pub use video_player::VideoPlayer;
 pub use yuv_texture::YuvTexture;
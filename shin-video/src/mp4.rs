@@ -40,6 +40,34 @@ impl<S: Read + Seek> Mp4TrackReader<S> {
         f(track)
     }
 
+    /// Find the sample that is playing at `time` (in the track timescale)
+    ///
+    /// Returns the sample number and its start time, `None` if the time is past the end of the track.
+    pub fn find_sample_at(&self, time: u64) -> Option<(u32, u64)> {
+        self.get_mp4_track_info(|track| {
+            let mut sample_number = 1;
+            let mut start_time = 0;
+            for entry in &track.trak.mdia.minf.stbl.stts.entries {
+                let entry_duration = entry.sample_count as u64 * entry.sample_delta as u64;
+                if time < start_time + entry_duration {
+                    let samples_in = ((time - start_time) / entry.sample_delta as u64) as u32;
+                    return Some((
+                        sample_number + samples_in,
+                        start_time + samples_in as u64 * entry.sample_delta as u64,
+                    ));
+                }
+                sample_number += entry.sample_count;
+                start_time += entry_duration;
+            }
+            None
+        })
+    }
+
+    /// Make the next [Mp4TrackReader::next_sample] call return the sample with this (1-based) number
+    pub fn seek_to_sample(&mut self, sample_number: u32) {
+        self.samples_position = sample_number;
+    }
+
     pub fn next_sample(&mut self) -> Result<Option<Mp4Sample>> {
         if self.samples_position > self.samples_count {
             return Ok(None);
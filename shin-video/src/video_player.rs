@@ -9,14 +9,14 @@ use shin_render::{GpuCommonResources, Renderable, SpriteVertexBuffer};
 use std::io::{Read, Seek};
 use tracing::{error, info, trace};
 
-use crate::h264_decoder::{Frame, FrameTiming, H264Decoder};
+use crate::h264_decoder::{Frame, FrameTiming, H264Decoder, H264DecoderBackend};
 use crate::mp4::Mp4;
 use crate::timer::Timer;
 use crate::YuvTexture;
 
 pub struct VideoPlayer {
     timer: Timer,
-    video_decoder: H264Decoder,
+    video_decoder: Box<dyn H264Decoder>,
     video_texture: YuvTexture,
     vertex_buffer: SpriteVertexBuffer,
     pending_frame: Option<(FrameTiming, Frame)>,
@@ -27,6 +27,15 @@ impl VideoPlayer {
         resources: &GpuCommonResources,
         audio_manager: &AudioManager,
         mp4: Mp4<S>,
+    ) -> Result<VideoPlayer> {
+        Self::with_decoder_backend(resources, audio_manager, mp4, H264DecoderBackend::default())
+    }
+
+    pub fn with_decoder_backend<S: Read + Seek + Send + 'static>(
+        resources: &GpuCommonResources,
+        audio_manager: &AudioManager,
+        mp4: Mp4<S>,
+        decoder_backend: H264DecoderBackend,
     ) -> Result<VideoPlayer> {
         // TODO: use the audio track
         // if we are using audio the timer should be tracking the audio playback
@@ -38,6 +47,7 @@ impl VideoPlayer {
                     track: TrackId::Main,
                     fade_in: Tween::MS_15,
                     loop_start: None,
+                    loop_end: None,
                     volume: Volume::default(),
                     pan: Pan::default(),
                 },
@@ -55,8 +65,9 @@ impl VideoPlayer {
             None => Timer::new_independent(time_base),
         };
 
-        let mut video_decoder =
-            H264Decoder::new(mp4.video_track).context("Initializing H264Decoder")?;
+        let mut video_decoder = decoder_backend
+            .create_decoder(mp4.video_track)
+            .context("Initializing H264Decoder")?;
         let video_texture = YuvTexture::new(
             resources,
             video_decoder
@@ -1,14 +1,38 @@
-use crate::asset::{asset_paths, AnyAssetServer};
+use crate::asset::{AnyAssetServer, AssetIo, AssetServer};
 use crate::layer::MessageboxTextures;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use futures::try_join;
 use shin_core::format::font::LazyFont;
 use shin_core::format::scenario::Scenario;
+use shin_core::game::{AssetPaths, GameId, GameProfile};
+use shin_tasks::AsyncComputeTaskPool;
 use std::sync::Arc;
+use tracing::info;
+
+/// Use the specified game profile, or detect it from the scenario
+pub async fn select_game_profile<Io: AssetIo>(
+    asset_server: &AssetServer<Io>,
+    game: Option<GameId>,
+) -> Result<&'static GameProfile> {
+    let profile = match game {
+        Some(game) => GameProfile::get(game),
+        None => {
+            // all the games known so far store the scenario at the same path
+            let scenario_path = GameProfile::get(GameId::Umineko).asset_paths.scenario;
+            let data = asset_server.read_raw(scenario_path).await?;
+            GameProfile::detect(&data.into())
+                .context("Detecting the game, use --game to specify it explicitly")?
+        }
+    };
+    info!("Running {}", profile.title);
+
+    Ok(profile)
+}
 
 // TODO: this can be done with a macro
 #[derive(Clone)]
 pub struct AdvAssets {
+    pub profile: &'static GameProfile,
     pub scenario: Arc<Scenario>,
     pub fonts: AdvFonts,
     pub messagebox_textures: Arc<MessageboxTextures>,
@@ -22,14 +46,21 @@ pub struct AdvFonts {
 }
 
 impl AdvAssets {
-    pub async fn load(asset_server: &AnyAssetServer) -> Result<Self> {
+    pub async fn load(
+        asset_server: &AnyAssetServer,
+        profile: &'static GameProfile,
+    ) -> Result<Self> {
+        let paths = &profile.asset_paths;
         let result = try_join!(
-            asset_server.load(asset_paths::SCENARIO),
-            AdvFonts::load(asset_server),
-            asset_server.load(asset_paths::MSGTEX),
+            load_scenario(asset_server, profile),
+            AdvFonts::load(asset_server, paths),
+            asset_server.load(paths.msgtex),
         )?;
+        // the messagebox is shown all the time, no need to ever reload it
+        asset_server.pin::<MessageboxTextures>(paths.msgtex);
 
         Ok(Self {
+            profile,
             scenario: result.0,
             fonts: result.1,
             messagebox_textures: result.2,
@@ -37,13 +68,30 @@ impl AdvAssets {
     }
 }
 
+/// The scenario is parsed according to the game profile, so it can't go through [Asset](crate::asset::Asset)
+async fn load_scenario(
+    asset_server: &AnyAssetServer,
+    profile: &'static GameProfile,
+) -> Result<Arc<Scenario>> {
+    let data = asset_server.read_raw(profile.asset_paths.scenario).await?;
+    let scenario = AsyncComputeTaskPool::get()
+        .spawn(async move { profile.load_scenario(data.into()) })
+        .await
+        .context("Parsing the scenario")?;
+
+    Ok(Arc::new(scenario))
+}
+
 impl AdvFonts {
-    pub async fn load(asset_server: &AnyAssetServer) -> Result<Self> {
+    pub async fn load(asset_server: &AnyAssetServer, paths: &AssetPaths) -> Result<Self> {
         let result = try_join!(
-            asset_server.load(asset_paths::SYSTEM_FNT),
-            asset_server.load(asset_paths::NEWRODIN_MEDIUM_FNT),
-            asset_server.load(asset_paths::NEWRODIN_BOLD_FNT),
+            asset_server.load(paths.system_font),
+            asset_server.load(paths.medium_font),
+            asset_server.load(paths.bold_font),
         )?;
+        for path in [paths.system_font, paths.medium_font, paths.bold_font] {
+            asset_server.pin::<LazyFont>(path);
+        }
 
         Ok(Self {
             system_font: result.0,
@@ -0,0 +1,74 @@
+use super::prelude::*;
+use shin_core::vm::command::types::AudioWaitStatus;
+use std::fmt::{Debug, Formatter};
+
+pub struct BGMSYNC {
+    token: Option<command::token::BGMSYNC>,
+    sync_time: Ticks,
+    last_position: Ticks,
+}
+
+impl StartableCommand for command::runtime::BGMSYNC {
+    fn apply_state(&self, _state: &mut VmState) {
+        // nothing to do
+    }
+
+    fn start(
+        self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        _adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        Yield(
+            BGMSYNC {
+                token: Some(self.token),
+                // the unit is not known for sure, ticks are the most likely
+                sync_time: Ticks::from_i32(self.sync_time),
+                last_position: Ticks::ZERO,
+            }
+            .into(),
+        )
+    }
+}
+
+impl UpdatableCommand for BGMSYNC {
+    fn update(
+        &mut self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        adv_state: &mut AdvState,
+        is_fast_forwarding: bool,
+    ) -> Option<CommandResult> {
+        let bgm_player = &adv_state.bgm_player;
+
+        let stopped = bgm_player
+            .get_wait_status()
+            .contains(AudioWaitStatus::STOPPED);
+
+        let finished = match bgm_player.position() {
+            Some(position) if !stopped => {
+                // if the position went backwards, the BGM has looped and we have missed the sync point
+                let looped = position.as_f32() < self.last_position.as_f32();
+                self.last_position = position;
+
+                looped || position.as_f32() >= self.sync_time.as_f32()
+            }
+            // nothing to sync to
+            _ => true,
+        };
+
+        if finished || is_fast_forwarding {
+            Some(self.token.take().unwrap().finish())
+        } else {
+            None
+        }
+    }
+}
+
+impl Debug for BGMSYNC {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("BGMSYNC").field(&self.sync_time).finish()
+    }
+}
@@ -0,0 +1,56 @@
+use super::prelude::*;
+use shin_core::vm::command::types::AudioWaitStatus;
+use std::fmt::{Debug, Formatter};
+
+pub struct BGMWAIT {
+    token: Option<command::token::BGMWAIT>,
+    target_status: AudioWaitStatus,
+}
+
+impl StartableCommand for command::runtime::BGMWAIT {
+    fn apply_state(&self, _state: &mut VmState) {
+        // nothing to do
+    }
+
+    fn start(
+        self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        _adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        Yield(
+            BGMWAIT {
+                token: Some(self.token),
+                target_status: self.target_status,
+            }
+            .into(),
+        )
+    }
+}
+
+impl UpdatableCommand for BGMWAIT {
+    fn update(
+        &mut self,
+        _context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        adv_state: &mut AdvState,
+        _is_fast_forwarding: bool,
+    ) -> Option<CommandResult> {
+        let status = adv_state.bgm_player.get_wait_status();
+        let finished = !(status & self.target_status).is_empty();
+
+        if finished {
+            Some(self.token.take().unwrap().finish())
+        } else {
+            None
+        }
+    }
+}
+
+impl Debug for BGMWAIT {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("BGMWAIT").field(&self.target_status).finish()
+    }
+}
@@ -1,5 +1,6 @@
 use super::prelude::*;
 use shin_core::time::{Easing, Tween};
+use tracing::debug;
 
 impl StartableCommand for command::runtime::LAYERCTRL {
     fn apply_state(&self, state: &mut VmState) {
@@ -90,7 +91,12 @@ impl StartableCommand for command::runtime::LAYERCTRL {
             tweener.enqueue(target_value as f32, Tween { duration, easing })
         });
 
-        if !self.property_id.is_implemented() && changed {
+        if self.property_id.is_guessed() && changed {
+            debug!(
+                "LAYERCTRL: the effect of the property is guessed (property_id={:?}, value={})",
+                self.property_id, target_value
+            );
+        } else if !self.property_id.is_implemented() && changed {
             warn!(
                 "LAYERCTRL: property is not implemented yet (property_id={:?}, value={})",
                 self.property_id, target_value
@@ -81,7 +81,7 @@ impl UpdatableCommand for LAYERLOAD {
         adv_state: &mut AdvState,
         _is_fast_forwarding: bool,
     ) -> Option<CommandResult> {
-        if self.load_task.as_ref().unwrap().is_finished() {
+        if adv_state.synchronous_loading || self.load_task.as_ref().unwrap().is_finished() {
             let layer = self.load_task.take().unwrap().block_on();
 
             match self.layer_id.repr() {
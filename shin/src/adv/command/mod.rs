@@ -18,7 +18,9 @@ mod prelude {
 mod autosave;
 mod bgmplay;
 mod bgmstop;
+mod bgmsync;
 mod bgmvol;
+mod bgmwait;
 mod chars;
 mod debugout;
 mod evbegin;
@@ -40,6 +42,7 @@ mod pageback;
 mod planeclear;
 mod planeselect;
 mod saveinfo;
+mod seonce;
 mod sepan;
 mod seplay;
 mod sestop;
@@ -49,6 +52,7 @@ mod sewait;
 mod sget;
 mod showchars;
 mod sset;
+mod sysse;
 mod tipsget;
 mod trophy;
 mod unlock;
@@ -56,6 +60,8 @@ mod voiceplay;
 mod wait;
 mod wipe;
 
+use bgmsync::BGMSYNC;
+use bgmwait::BGMWAIT;
 use layerload::LAYERLOAD;
 use layerwait::LAYERWAIT;
 use moviewait::MOVIEWAIT;
@@ -108,6 +114,10 @@ pub enum ExecutingCommand {
     SEWAIT,
     #[derivative(Debug = "transparent")]
     MOVIEWAIT,
+    #[derivative(Debug = "transparent")]
+    BGMWAIT,
+    #[derivative(Debug = "transparent")]
+    BGMSYNC,
 }
 
 impl StartableCommand for RuntimeCommand {
@@ -129,19 +139,19 @@ impl StartableCommand for RuntimeCommand {
             RuntimeCommand::BGMPLAY(v) => v.apply_state(state),
             RuntimeCommand::BGMSTOP(v) => v.apply_state(state),
             RuntimeCommand::BGMVOL(v) => v.apply_state(state),
-            // RuntimeCommand::BGMWAIT(v) => v.apply_state(state),
-            // RuntimeCommand::BGMSYNC(v) => v.apply_state(state),
+            RuntimeCommand::BGMWAIT(v) => v.apply_state(state),
+            RuntimeCommand::BGMSYNC(v) => v.apply_state(state),
             RuntimeCommand::SEPLAY(v) => v.apply_state(state),
             RuntimeCommand::SESTOP(v) => v.apply_state(state),
             RuntimeCommand::SESTOPALL(v) => v.apply_state(state),
             RuntimeCommand::SEVOL(v) => v.apply_state(state),
             RuntimeCommand::SEPAN(v) => v.apply_state(state),
             RuntimeCommand::SEWAIT(v) => v.apply_state(state),
-            // RuntimeCommand::SEONCE(v) => v.apply_state(state),
+            RuntimeCommand::SEONCE(v) => v.apply_state(state),
             RuntimeCommand::VOICEPLAY(v) => v.apply_state(state),
             // RuntimeCommand::VOICESTOP(v) => v.apply_state(state),
             // RuntimeCommand::VOICEWAIT(v) => v.apply_state(state),
-            // RuntimeCommand::SYSSE(v) => v.apply_state(state),
+            RuntimeCommand::SYSSE(v) => v.apply_state(state),
             RuntimeCommand::SAVEINFO(v) => v.apply_state(state),
             RuntimeCommand::AUTOSAVE(v) => v.apply_state(state),
             RuntimeCommand::EVBEGIN(v) => v.apply_state(state),
@@ -200,19 +210,19 @@ impl StartableCommand for RuntimeCommand {
             RuntimeCommand::BGMPLAY(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::BGMSTOP(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::BGMVOL(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::BGMWAIT(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::BGMSYNC(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::BGMWAIT(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::BGMSYNC(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::SEPLAY(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::SESTOP(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::SESTOPALL(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::SEVOL(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::SEPAN(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::SEWAIT(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::SEONCE(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::SEONCE(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::VOICEPLAY(v) => v.start(context, scenario, vm_state, adv_state),
             // RuntimeCommand::VOICESTOP(v) => v.start(context, scenario, vm_state, adv_state),
             // RuntimeCommand::VOICEWAIT(v) => v.start(context, scenario, vm_state, adv_state),
-            // RuntimeCommand::SYSSE(v) => v.start(context, scenario, vm_state, adv_state),
+            RuntimeCommand::SYSSE(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::SAVEINFO(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::AUTOSAVE(v) => v.start(context, scenario, vm_state, adv_state),
             RuntimeCommand::EVBEGIN(v) => v.start(context, scenario, vm_state, adv_state),
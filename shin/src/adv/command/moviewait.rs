@@ -47,7 +47,8 @@ impl UpdatableCommand for MOVIEWAIT {
         adv_state: &mut AdvState,
         _is_fast_forwarding: bool,
     ) -> Option<CommandResult> {
-        let UserLayer::MovieLayer(layer) = adv_state.get_layer(vm_state, self.layer_id).unwrap() else {
+        let UserLayer::MovieLayer(layer) = adv_state.get_layer(vm_state, self.layer_id).unwrap()
+        else {
             unreachable!()
         };
         let finished = layer.is_finished();
@@ -1,4 +1,5 @@
 use super::prelude::*;
+use shin_core::vm::command::types::MessageboxType;
 
 impl StartableCommand for command::runtime::MSGINIT {
     fn apply_state(&self, state: &mut VmState) {
@@ -12,10 +13,22 @@ impl StartableCommand for command::runtime::MSGINIT {
         _vm_state: &VmState,
         adv_state: &mut AdvState,
     ) -> CommandStartResult {
+        let mut style = self.messagebox_style;
+        if !adv_state
+            .profile
+            .supports_messagebox_type(style.messagebox_type)
+        {
+            warn!(
+                "MSGINIT: {:?} is not used by {}, falling back to Neutral",
+                style.messagebox_type, adv_state.profile.title
+            );
+            style.messagebox_type = MessageboxType::Neutral;
+        }
+
         adv_state
             .root_layer_group
             .message_layer_mut()
-            .set_style(self.messagebox_style);
+            .set_style(style);
         self.token.finish().into()
     }
 }
@@ -0,0 +1,34 @@
+use super::prelude::*;
+use shin_core::vm::command::types::{Pan, Volume};
+
+impl StartableCommand for command::runtime::SEONCE {
+    fn apply_state(&self, _state: &mut VmState) {
+        // one-shot SEs are not saved
+    }
+
+    fn start(
+        self,
+        context: &UpdateContext,
+        scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        // the arguments are assumed to mirror those of SEPLAY without the slot:
+        // arg1 is the SE id, arg2 and arg3 are the volume and the pan in 1/1000, the meaning of arg4 and arg5 is unknown
+        let se_id = self.arg1;
+        let volume = Volume((self.arg2 as f32 / 1000.0).clamp(0.0, 1.0));
+        let pan = Pan((self.arg3 as f32 / 1000.0).clamp(-1.0, 1.0));
+
+        let se_info = scenario.info_tables().se_info(se_id);
+
+        let audio = context
+            .asset_server
+            // TODO: sync - bad!!
+            .load_sync(se_info.path())
+            .expect("Failed to load SE");
+
+        adv_state.se_player.play_once(audio, volume, pan);
+
+        self.token.finish().into()
+    }
+}
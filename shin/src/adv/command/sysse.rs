@@ -0,0 +1,34 @@
+use super::prelude::*;
+use shin_core::format::sysse::SystemSoundBundle;
+
+impl StartableCommand for command::runtime::SYSSE {
+    fn apply_state(&self, _state: &mut VmState) {
+        // nothing to do
+    }
+
+    fn start(
+        self,
+        context: &UpdateContext,
+        _scenario: &Arc<Scenario>,
+        _vm_state: &VmState,
+        adv_state: &mut AdvState,
+    ) -> CommandStartResult {
+        let system_sounds: Arc<SystemSoundBundle> = context
+            .asset_server
+            // TODO: sync - bad!!
+            .load_sync(adv_state.profile.asset_paths.sysse)
+            .expect("Failed to load system sounds");
+
+        if let Some(audio) = system_sounds.get(self.sysse_id as usize) {
+            adv_state.se_player.play_system(audio.clone());
+        } else {
+            warn!(
+                "SYSSE: there is no system sound {} (only {} are available)",
+                self.sysse_id,
+                system_sounds.len()
+            );
+        }
+
+        self.token.finish().into()
+    }
+}
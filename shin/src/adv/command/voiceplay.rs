@@ -1,18 +1,33 @@
 use super::prelude::*;
+use shin_core::format::scenario::info::voice_path;
 
 impl StartableCommand for command::runtime::VOICEPLAY {
     fn apply_state(&self, _state: &mut VmState) {
-        warn!("TODO: VOICEPLAY state: {:?}", self);
+        // voices are not saved
     }
 
     fn start(
         self,
-        _context: &UpdateContext,
+        context: &UpdateContext,
         _scenario: &Arc<Scenario>,
         _vm_state: &VmState,
-        _adv_state: &mut AdvState,
+        adv_state: &mut AdvState,
     ) -> CommandStartResult {
-        warn!("TODO: VOICEPLAY: {:?}", self);
+        if self.flags != 0 {
+            warn!("TODO: VOICEPLAY: ignoring flags={}", self.flags);
+        }
+
+        let path = voice_path(self.name.as_str());
+        match context
+            .asset_server
+            // TODO: sync - bad!!
+            .load_sync(&path)
+        {
+            Ok(audio) => adv_state.voice_player.play(audio, self.volume),
+            // a missing voice should not stop the game
+            Err(e) => warn!("VOICEPLAY: failed to load {}: {:?}", path, e),
+        }
+
         self.token.finish().into()
     }
 }
@@ -0,0 +1,336 @@
+//! An interactive debugger for the scenario VM.
+//!
+//! It can pause the execution before a command is started, step over commands and set breakpoints.
+//! All the interaction happens through an egui overlay window.
+
+use egui::{Color32, Context, RichText, ScrollArea, TextStyle, Ui, Window};
+use once_cell::sync::OnceCell;
+use shin_core::format::scenario::instructions::{CodeAddress, MessageId};
+use shin_core::format::scenario::listing::CodeListing;
+use shin_core::format::scenario::Scenario;
+use shin_core::vm::breakpoint::BreakpointObserver;
+use shin_core::vm::command::RuntimeCommand;
+use shin_core::vm::{CallStackSlot, Scripter};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// How many instructions to show before the current one in the disassembly view
+const DISASSEMBLY_CONTEXT_BEFORE: usize = 8;
+/// How many instructions to show after the current one in the disassembly view
+const DISASSEMBLY_CONTEXT_AFTER: usize = 24;
+/// Instruction text is cut to this length, because some of them (like MSGSET) can be very long
+const DISASSEMBLY_MAX_WIDTH: usize = 120;
+const MEMORY_VIEW_COLUMNS: usize = 8;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum RunState {
+    Running,
+    /// Pause before the next command is started
+    PauseRequested,
+    Paused,
+    /// Execute the held command and pause before the next one
+    Step,
+    /// Execute commands until we get back to the same (or shallower) call stack depth
+    StepOver {
+        depth: usize,
+    },
+}
+
+/// A request issued from the debugger UI
+///
+/// The overlays only get a shared reference, so the requests are queued and applied on the next update
+#[derive(Debug, Copy, Clone)]
+pub enum DebuggerRequest {
+    Pause,
+    Continue,
+    Step,
+    StepOver,
+    ToggleBreakpoint(CodeAddress),
+    JumpToMessage(MessageId),
+}
+
+struct DebuggerUiState {
+    message_id_input: String,
+    message_error: Option<String>,
+}
+
+pub struct Debugger {
+    state: RunState,
+    /// The command that was fetched from the VM, but not started because the execution was paused
+    held_command: Option<RuntimeCommand>,
+    breakpoints: BTreeMap<CodeAddress, BreakpointObserver>,
+    /// Built lazily, as sweeping over the whole scenario takes some time
+    listing: OnceCell<CodeListing>,
+    requests: RefCell<Vec<DebuggerRequest>>,
+    ui_state: RefCell<DebuggerUiState>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            state: RunState::Running,
+            held_command: None,
+            breakpoints: BTreeMap::new(),
+            listing: OnceCell::new(),
+            requests: RefCell::new(Vec::new()),
+            ui_state: RefCell::new(DebuggerUiState {
+                message_id_input: String::new(),
+                message_error: None,
+            }),
+        }
+    }
+
+    /// Forget the listing of the old scenario, after it was reloaded
+    pub fn reset_listing(&mut self) {
+        self.listing = OnceCell::new();
+    }
+
+    pub fn listing(&self, scenario: &Scenario) -> &CodeListing {
+        self.listing.get_or_init(|| CodeListing::new(scenario))
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state == RunState::Paused
+    }
+
+    /// Take the command that was held when the execution was paused
+    ///
+    /// Returns `None` if there is no such command or the execution is still paused
+    pub fn take_held_command(&mut self) -> Option<RuntimeCommand> {
+        if self.is_paused() {
+            None
+        } else {
+            self.held_command.take()
+        }
+    }
+
+    /// Decide whether the execution should be paused before starting a freshly fetched command
+    pub fn should_break(&mut self, scripter: &Scripter) -> bool {
+        // update all the observers, so that the hits are not reported twice
+        let mut breakpoint_hit = false;
+        for bp in self.breakpoints.values_mut() {
+            breakpoint_hit |= bp.update();
+        }
+
+        match self.state {
+            RunState::Running => breakpoint_hit,
+            RunState::PauseRequested | RunState::Step => true,
+            RunState::StepOver { depth } => {
+                breakpoint_hit || scripter.ctx().call_stack().len() <= depth
+            }
+            RunState::Paused => unreachable!("should_break called while paused"),
+        }
+    }
+
+    /// Pause the execution, holding the command until it's resumed
+    pub fn hold(&mut self, command: RuntimeCommand) {
+        assert!(self.held_command.is_none());
+        self.held_command = Some(command);
+        self.state = RunState::Paused;
+    }
+
+    pub fn take_requests(&self) -> Vec<DebuggerRequest> {
+        std::mem::take(&mut self.requests.borrow_mut())
+    }
+
+    fn request(&self, request: DebuggerRequest) {
+        self.requests.borrow_mut().push(request);
+    }
+
+    pub fn pause(&mut self) {
+        if !self.is_paused() {
+            self.state = RunState::PauseRequested;
+        }
+    }
+
+    pub fn resume(&mut self) {
+        self.state = RunState::Running;
+    }
+
+    pub fn step(&mut self) {
+        self.state = RunState::Step;
+    }
+
+    pub fn step_over(&mut self, scripter: &Scripter) {
+        self.state = RunState::StepOver {
+            depth: scripter.ctx().call_stack().len(),
+        };
+    }
+
+    pub fn toggle_breakpoint(&mut self, scripter: &mut Scripter, address: CodeAddress) {
+        if self.breakpoints.remove(&address).is_none() {
+            self.breakpoints
+                .insert(address, scripter.add_breakpoint(address).into());
+        }
+    }
+
+    pub fn show_window(&self, ctx: &Context, scenario: &Scenario, scripter: &Scripter) {
+        Window::new("VM Debugger")
+            .default_width(700.0)
+            .show(ctx, |ui| {
+                self.controls_ui(ui, scenario, scripter);
+                ui.separator();
+                ui.columns(2, |columns| {
+                    self.disassembly_ui(&mut columns[0], scenario, scripter);
+                    self.stacks_ui(&mut columns[1], scripter);
+                });
+                ui.separator();
+                self.memory_ui(ui, scripter);
+            });
+    }
+
+    fn controls_ui(&self, ui: &mut Ui, scenario: &Scenario, scripter: &Scripter) {
+        ui.horizontal(|ui| {
+            let state = match self.state {
+                RunState::Running => "Running",
+                RunState::PauseRequested => "Pausing...",
+                RunState::Paused => "Paused",
+                RunState::Step | RunState::StepOver { .. } => "Stepping...",
+            };
+            ui.monospace(format!("{:08x} {}", scripter.position().0, state));
+
+            if self.is_paused() {
+                if ui.button("Continue").clicked() {
+                    self.request(DebuggerRequest::Continue);
+                }
+                if ui.button("Step").clicked() {
+                    self.request(DebuggerRequest::Step);
+                }
+                if ui.button("Step over").clicked() {
+                    self.request(DebuggerRequest::StepOver);
+                }
+            } else if ui.button("Pause").clicked() {
+                self.request(DebuggerRequest::Pause);
+            }
+        });
+        if let Some(command) = &self.held_command {
+            ui.monospace(format!("Next: {}", command));
+        }
+
+        let mut ui_state = self.ui_state.borrow_mut();
+        ui.horizontal(|ui| {
+            ui.label("Jump to message:");
+            ui.text_edit_singleline(&mut ui_state.message_id_input);
+            if ui.button("Go").clicked() {
+                ui_state.message_error = match ui_state.message_id_input.trim().parse::<u32>() {
+                    Ok(id) => {
+                        if self.listing(scenario).find_message(MessageId(id)).is_some() {
+                            self.request(DebuggerRequest::JumpToMessage(MessageId(id)));
+                            None
+                        } else {
+                            Some(format!("Message {} not found", id))
+                        }
+                    }
+                    Err(e) => Some(format!("Invalid message id: {}", e)),
+                };
+            }
+        });
+        if let Some(error) = &ui_state.message_error {
+            ui.colored_label(Color32::RED, error);
+        }
+    }
+
+    fn disassembly_ui(&self, ui: &mut Ui, scenario: &Scenario, scripter: &Scripter) {
+        ui.label("Disassembly (click to toggle a breakpoint)");
+
+        let listing = self.listing(scenario);
+        let position = scripter.position();
+        let addresses = listing.addresses();
+        let index = listing.index_of(position);
+        let range = index.saturating_sub(DISASSEMBLY_CONTEXT_BEFORE)
+            ..(index + DISASSEMBLY_CONTEXT_AFTER).min(addresses.len());
+
+        ScrollArea::vertical()
+            .id_source("debugger_disassembly")
+            .max_height(400.0)
+            .show(ui, |ui| {
+                for &address in &addresses[range] {
+                    let instruction = match scenario.instruction_reader(address).read() {
+                        Ok(instruction) => format!("{:?}", instruction),
+                        Err(e) => format!("<error: {}>", e),
+                    };
+                    let instruction = if instruction.len() > DISASSEMBLY_MAX_WIDTH {
+                        let mut end = DISASSEMBLY_MAX_WIDTH;
+                        while !instruction.is_char_boundary(end) {
+                            end -= 1;
+                        }
+                        format!("{}...", &instruction[..end])
+                    } else {
+                        instruction
+                    };
+
+                    let has_breakpoint = self.breakpoints.contains_key(&address);
+                    let marker = match (address == position, has_breakpoint) {
+                        (true, true) => "●>",
+                        (true, false) => " >",
+                        (false, true) => "● ",
+                        (false, false) => "  ",
+                    };
+                    let mut text =
+                        RichText::new(format!("{} {:08x} {}", marker, address.0, instruction))
+                            .monospace();
+                    if has_breakpoint {
+                        text = text.color(Color32::RED);
+                    }
+
+                    if ui.selectable_label(address == position, text).clicked() {
+                        self.request(DebuggerRequest::ToggleBreakpoint(address));
+                    }
+                }
+            });
+    }
+
+    fn stacks_ui(&self, ui: &mut Ui, scripter: &Scripter) {
+        let ctx = scripter.ctx();
+
+        ui.label("Call stack");
+        ScrollArea::vertical()
+            .id_source("debugger_call_stack")
+            .max_height(180.0)
+            .show(ui, |ui| {
+                for (i, slot) in ctx.call_stack().iter().enumerate().rev() {
+                    match slot {
+                        CallStackSlot::ReturnAddress(address) => {
+                            ui.monospace(format!("{:>3}: {:08x}", i, address.0))
+                        }
+                        CallStackSlot::Value(value) => {
+                            ui.monospace(format!("{:>3}: {} (value)", i, value))
+                        }
+                    };
+                }
+            });
+
+        ui.label("Data stack");
+        ScrollArea::vertical()
+            .id_source("debugger_data_stack")
+            .max_height(180.0)
+            .show(ui, |ui| {
+                for (i, value) in ctx.arguments_stack().iter().enumerate().rev() {
+                    ui.monospace(format!("{:>3}: {}", i, value));
+                }
+            });
+    }
+
+    fn memory_ui(&self, ui: &mut Ui, scripter: &Scripter) {
+        let memory = scripter.ctx().memory();
+
+        ui.label("Memory");
+        let row_height = ui.text_style_height(&TextStyle::Monospace);
+        let rows = (memory.len() + MEMORY_VIEW_COLUMNS - 1) / MEMORY_VIEW_COLUMNS;
+        ScrollArea::vertical()
+            .id_source("debugger_memory")
+            .max_height(200.0)
+            .show_rows(ui, row_height, rows, |ui, row_range| {
+                for row in row_range {
+                    let start = row * MEMORY_VIEW_COLUMNS;
+                    let end = (start + MEMORY_VIEW_COLUMNS).min(memory.len());
+                    let values = memory[start..end]
+                        .iter()
+                        .map(|v| format!("{:>11}", v))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    ui.monospace(format!("{:04x}: {}", start, values));
+                }
+            });
+    }
+}
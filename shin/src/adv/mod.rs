@@ -1,25 +1,37 @@
 pub mod assets;
 mod command;
+mod debugger;
+mod prefetcher;
+mod trace;
 mod vm_state;
 
 pub use command::{CommandStartResult, ExecutingCommand, StartableCommand, UpdatableCommand};
+pub use trace::TraceRecorder;
 pub use vm_state::VmState;
 
-use crate::adv::assets::AdvAssets;
-use crate::audio::{BgmPlayer, SePlayer};
+use crate::adv::assets::{AdvAssets, AdvFonts};
+use crate::adv::debugger::{Debugger, DebuggerRequest};
+use crate::adv::prefetcher::Prefetcher;
+use crate::asset::{is_asset_changed, AnyAssetServer};
+use crate::audio::{BgmPlayer, SePlayer, VoicePlayer};
 use crate::input::actions::AdvMessageAction;
 use crate::input::ActionState;
 use crate::layer::{
-    AnyLayer, AnyLayerMut, LayerGroup, MessageLayer, RootLayerGroup, ScreenLayer, UserLayer,
+    AnyLayer, AnyLayerMut, LayerGroup, LayerInspector, MessageLayer, RootLayerGroup, ScreenLayer,
+    UserLayer,
 };
 use crate::render::overlay::{OverlayCollector, OverlayVisitable};
 use crate::update::{Updatable, UpdateContext};
+use anyhow::Result;
 use egui::Window;
 use glam::Mat4;
 use itertools::Itertools;
+use pollster::FutureExt;
 use shin_audio::AudioManager;
 use shin_core::format::scenario::instructions::CodeAddress;
+use shin_core::format::scenario::listing::CodeListing;
 use shin_core::format::scenario::Scenario;
+use shin_core::game::GameProfile;
 use shin_core::vm::breakpoint::BreakpointObserver;
 use shin_core::vm::command::types::{LayerId, VLayerId, VLayerIdRepr, PLANES_COUNT};
 use shin_core::vm::command::CommandResult;
@@ -28,7 +40,7 @@ use shin_render::{GpuCommonResources, Renderable};
 use smallvec::{smallvec, SmallVec};
 use std::borrow::Cow;
 use std::sync::Arc;
-use tracing::{debug, warn};
+use tracing::{debug, info, warn};
 pub use vm_state::layers::LayerSelection;
 use vm_state::layers::ITER_VLAYER_SMALL_VECTOR_SIZE;
 
@@ -40,6 +52,10 @@ pub struct Adv {
     action_state: ActionState<AdvMessageAction>,
     current_command: Option<ExecutingCommand>,
     fast_forward_to_bp: Option<BreakpointObserver>,
+    debugger: Debugger,
+    layer_inspector: LayerInspector,
+    trace_recorder: Option<TraceRecorder>,
+    prefetcher: Option<Prefetcher>,
 }
 
 impl Adv {
@@ -63,6 +79,10 @@ impl Adv {
             action_state: ActionState::new(),
             current_command: None,
             fast_forward_to_bp: None,
+            debugger: Debugger::new(),
+            layer_inspector: LayerInspector::new(),
+            trace_recorder: None,
+            prefetcher: None,
         }
     }
 
@@ -70,11 +90,142 @@ impl Adv {
         assert!(self.fast_forward_to_bp.is_none());
         self.fast_forward_to_bp = Some(self.scripter.add_breakpoint(addr).into());
     }
+
+    /// Record all the executed commands & state snapshots with the recorder
+    pub fn record_trace(&mut self, recorder: TraceRecorder) {
+        self.trace_recorder = Some(recorder);
+    }
+
+    /// Stop recording the trace, flushing it to the output
+    pub fn finish_trace(&mut self) -> Result<()> {
+        match self.trace_recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Block on the layer loads, for the runs that have to be deterministic
+    pub fn load_synchronously(&mut self) {
+        self.adv_state.synchronous_loading = true;
+    }
+
+    /// Load the assets the scenario is going to use ahead of time, keeping at most `budget` bytes of them
+    pub fn prefetch(&mut self, budget: usize) {
+        self.prefetcher = Some(Prefetcher::new(budget));
+    }
+
+    pub fn scripter_mut(&mut self) -> &mut Scripter {
+        &mut self.scripter
+    }
+
+    /// Apply the changes of the asset files (see [AssetWatcher](crate::asset::AssetWatcher))
+    pub fn reload_assets(
+        &mut self,
+        resources: &GpuCommonResources,
+        asset_server: &AnyAssetServer,
+        changed_paths: &[String],
+    ) {
+        let profile = self.adv_state.profile;
+        let paths = &profile.asset_paths;
+        let is_changed = |path: &str| is_asset_changed(changed_paths, path);
+
+        if is_changed(paths.scenario) {
+            self.reload_scenario(asset_server);
+        }
+
+        if [paths.system_font, paths.medium_font, paths.bold_font]
+            .into_iter()
+            .any(is_changed)
+        {
+            match AdvFonts::load(asset_server, paths).block_on() {
+                Ok(fonts) => self
+                    .adv_state
+                    .root_layer_group
+                    .message_layer_mut()
+                    .set_fonts(resources, fonts),
+                Err(e) => warn!("Reloading the fonts: {:?}", e),
+            }
+        }
+
+        let page_layer = self
+            .adv_state
+            .root_layer_group
+            .screen_layer_mut()
+            .page_layer_mut();
+        for plane in 0..PLANES_COUNT {
+            let layer_group = page_layer.plane_mut(plane as u32);
+            for layer_id in layer_group.get_layer_ids().collect::<Vec<_>>() {
+                let layer = layer_group.get_layer_mut(layer_id).unwrap();
+                layer.reload_assets(resources, asset_server, changed_paths);
+            }
+        }
+    }
+
+    /// Continue running from the same place in the new version of the scenario, if it can be found there
+    fn reload_scenario(&mut self, asset_server: &AnyAssetServer) {
+        let profile = self.adv_state.profile;
+        let scenario = match asset_server
+            .read_raw(profile.asset_paths.scenario)
+            .block_on()
+            .and_then(|data| profile.load_scenario(data.into()))
+        {
+            Ok(scenario) => scenario,
+            Err(e) => {
+                warn!("Reloading the scenario: {:?}", e);
+                return;
+            }
+        };
+
+        let old_listing = CodeListing::new(&self.scenario);
+        let new_listing = CodeListing::new(&scenario);
+        if let Err(e) = self.scripter.relocate(&scenario, |address| {
+            old_listing.map_address(&new_listing, address)
+        }) {
+            warn!(
+                "Could not continue in the reloaded scenario, restart to apply the changes: {}",
+                e
+            );
+            return;
+        }
+
+        info!("Reloaded the scenario at {:?}", self.scripter.position());
+        self.scenario = Arc::new(scenario);
+        self.debugger.reset_listing();
+        if let Some(prefetcher) = &mut self.prefetcher {
+            *prefetcher = Prefetcher::new(prefetcher.budget());
+        }
+    }
+
+    fn handle_debugger_requests(&mut self) {
+        for request in self.debugger.take_requests() {
+            debug!("Debugger request: {:?}", request);
+            match request {
+                DebuggerRequest::Pause => self.debugger.pause(),
+                DebuggerRequest::Continue => self.debugger.resume(),
+                DebuggerRequest::Step => self.debugger.step(),
+                DebuggerRequest::StepOver => self.debugger.step_over(&self.scripter),
+                DebuggerRequest::ToggleBreakpoint(addr) => {
+                    self.debugger.toggle_breakpoint(&mut self.scripter, addr)
+                }
+                DebuggerRequest::JumpToMessage(id) => {
+                    let Some(addr) = self.debugger.listing(&self.scenario).find_message(id) else {
+                        warn!("Debugger: message {:?} not found", id);
+                        continue;
+                    };
+                    // replace the running fast-forward (if any)
+                    self.fast_forward_to_bp = None;
+                    self.fast_forward_to(addr);
+                    self.debugger.resume();
+                }
+            }
+        }
+    }
 }
 
 impl Updatable for Adv {
     fn update(&mut self, context: &UpdateContext) {
         self.action_state.update(context.raw_input_state);
+        self.adv_state.audio_manager.update();
 
         let fast_forward_button_held = self
             .action_state
@@ -94,8 +245,16 @@ impl Updatable for Adv {
                 .fast_forward();
         }
 
+        self.handle_debugger_requests();
+        self.layer_inspector
+            .apply_requests(&mut self.adv_state.root_layer_group);
+
         let mut result = CommandResult::None;
         loop {
+            if self.debugger.is_paused() {
+                break;
+            }
+
             // check the fast forward breakpoint; delete if hit
             if self
                 .fast_forward_to_bp
@@ -109,24 +268,40 @@ impl Updatable for Adv {
             let is_fast_forwarding = fast_forward_button_held || self.fast_forward_to_bp.is_some();
 
             // TODO: maybe yield if spent too much time in this loop?
-            let runtime_command = if let Some(command) = &mut self.current_command {
-                match command.update(
-                    context,
-                    &self.scenario,
-                    &self.vm_state,
-                    &mut self.adv_state,
-                    is_fast_forwarding,
-                ) {
-                    None => break,
-                    Some(result) => {
-                        self.current_command = None;
-                        self.scripter.run(result).expect("scripter run failed")
+            let runtime_command = if let Some(command) = self.debugger.take_held_command() {
+                command
+            } else {
+                let runtime_command = if let Some(command) = &mut self.current_command {
+                    match command.update(
+                        context,
+                        &self.scenario,
+                        &self.vm_state,
+                        &mut self.adv_state,
+                        is_fast_forwarding,
+                    ) {
+                        None => break,
+                        Some(result) => {
+                            self.current_command = None;
+                            self.scripter.run(result).expect("scripter run failed")
+                        }
                     }
+                } else {
+                    self.scripter.run(result).expect("scripter run failed")
+                };
+
+                if self.debugger.should_break(&self.scripter) {
+                    debug!("Debugger: pausing at {:?}", self.scripter.position());
+                    self.debugger.hold(runtime_command);
+                    break;
                 }
-            } else {
-                self.scripter.run(result).expect("scripter run failed")
+
+                runtime_command
             };
 
+            if let Some(recorder) = &mut self.trace_recorder {
+                recorder.record_command(self.scripter.position(), &runtime_command);
+            }
+
             runtime_command.apply_state(&mut self.vm_state);
 
             match runtime_command.start(
@@ -145,6 +320,18 @@ impl Updatable for Adv {
             }
         }
 
+        if let Some(recorder) = &mut self.trace_recorder {
+            recorder.finish_frame(&self.vm_state);
+        }
+
+        if let Some(prefetcher) = &mut self.prefetcher {
+            prefetcher.update(
+                context.asset_server,
+                &self.scenario,
+                self.scripter.position(),
+            );
+        }
+
         self.adv_state.update(context);
     }
 }
@@ -187,10 +374,39 @@ impl OverlayVisitable for Adv {
                     },
                     true,
                 );
+                if let Some(prefetcher) = &self.prefetcher {
+                    collector.overlay(
+                        "Prefetcher",
+                        |_ctx, top_left| {
+                            top_left.label(format!(
+                                "Prefetched: {:.1} / {:.1} MiB",
+                                prefetcher.used_bytes() as f64 / (1024.0 * 1024.0),
+                                prefetcher.budget() as f64 / (1024.0 * 1024.0),
+                            ));
+                        },
+                        false,
+                    );
+                }
+                collector.overlay(
+                    "VM Debugger",
+                    |ctx, _top_left| {
+                        self.debugger
+                            .show_window(ctx, &self.scenario, &self.scripter);
+                    },
+                    false,
+                );
                 self.adv_state
                     .root_layer_group
                     .message_layer()
                     .visit_overlay(collector);
+                collector.overlay(
+                    "Layer Inspector",
+                    |ctx, _top_left| {
+                        self.layer_inspector
+                            .show_window(ctx, &self.adv_state.root_layer_group);
+                    },
+                    false,
+                );
                 collector.overlay(
                     "User Layers",
                     |ctx, _top_left| {
@@ -224,10 +440,16 @@ impl OverlayVisitable for Adv {
 }
 
 pub struct AdvState {
+    pub profile: &'static GameProfile,
     pub root_layer_group: RootLayerGroup,
     pub audio_manager: Arc<AudioManager>,
     pub bgm_player: BgmPlayer,
     pub se_player: SePlayer,
+    pub voice_player: VoicePlayer,
+    /// Wait for the layers to load instead of checking the load tasks every frame
+    ///
+    /// This makes the frame at which a load finishes independent of the thread timing.
+    pub synchronous_loading: bool,
 }
 
 impl AdvState {
@@ -237,14 +459,17 @@ impl AdvState {
         assets: AdvAssets,
     ) -> Self {
         Self {
+            profile: assets.profile,
             root_layer_group: RootLayerGroup::new(
                 resources,
-                ScreenLayer::new(resources),
+                ScreenLayer::new(),
                 MessageLayer::new(resources, assets.fonts, assets.messagebox_textures),
             ),
             audio_manager: audio_manager.clone(),
             bgm_player: BgmPlayer::new(audio_manager.clone()),
-            se_player: SePlayer::new(audio_manager),
+            se_player: SePlayer::new(audio_manager.clone()),
+            voice_player: VoicePlayer::new(audio_manager),
+            synchronous_loading: false,
         }
     }
 
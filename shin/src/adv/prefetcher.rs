@@ -0,0 +1,299 @@
+//! Warming up the asset cache with the assets the scenario is going to need soon.
+//!
+//! The upcoming assets are found by [find_upcoming_assets], starting from the current position of the [Scripter](shin_core::vm::Scripter).
+//! The prefetcher holds strong references to the assets it loaded, which keeps them in the [AssetServer](crate::asset::AssetServer) cache
+//! until the commands get to them. The assets nearest to the current position are prefetched first, until the memory budget runs out.
+//! The size of an asset is only known once it is loaded, so the loads that are in flight are charged to the budget with an estimated size.
+
+use crate::asset::bustup::Bustup;
+use crate::asset::picture::Picture;
+use crate::asset::{AnyAssetServer, Asset};
+use anyhow::Result;
+use bevy_utils::{HashMap, HashSet};
+use pollster::FutureExt;
+use shin_core::format::audio::AudioFile;
+use shin_core::format::scenario::instructions::CodeAddress;
+use shin_core::format::scenario::lookahead::{find_upcoming_assets, AssetReference};
+use shin_core::format::scenario::Scenario;
+use shin_tasks::{IoTaskPool, Task};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// How far to look ahead, in instructions
+const LOOKAHEAD_INSTRUCTIONS: usize = 4096;
+/// Do not load too many assets at once, so that the prefetching does not slow down the loads the scenario is waiting for
+const MAX_IN_FLIGHT: usize = 4;
+/// Size charged for a load in flight before any asset finished loading (roughly a full-screen picture)
+const DEFAULT_SIZE_ESTIMATE: usize = 4 * 1024 * 1024;
+
+enum PrefetchedAsset {
+    Picture(Arc<Picture>),
+    Bustup(Arc<Bustup>),
+    Audio(Arc<AudioFile>),
+}
+
+impl PrefetchedAsset {
+    fn size_in_bytes(&self) -> usize {
+        match self {
+            PrefetchedAsset::Picture(picture) => picture.size_in_bytes(),
+            PrefetchedAsset::Bustup(bustup) => bustup.size_in_bytes(),
+            PrefetchedAsset::Audio(audio) => audio.size_in_bytes(),
+        }
+    }
+}
+
+enum PrefetchEntry {
+    Loading(Task<Result<PrefetchedAsset>>),
+    Loaded(PrefetchedAsset),
+    /// Remembered so that the load is not retried over and over
+    Failed,
+    /// Loaded, but did not fit into the budget. The size is remembered to know when it fits again without loading it
+    Released(usize),
+}
+
+pub struct Prefetcher {
+    /// Maximum total size of the prefetched assets, in bytes
+    budget: usize,
+    position: Option<CodeAddress>,
+    /// Paths of the upcoming assets, nearest first
+    upcoming: Vec<(AssetReference, String)>,
+    entries: HashMap<String, PrefetchEntry>,
+    /// Total size & count of the assets that finished loading so far, to estimate the size of the ones in flight
+    loaded_bytes: usize,
+    loaded_count: usize,
+}
+
+impl Prefetcher {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            position: None,
+            upcoming: Vec::new(),
+            entries: HashMap::default(),
+            loaded_bytes: 0,
+            loaded_count: 0,
+        }
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Total size of the assets that finished loading, in bytes
+    pub fn used_bytes(&self) -> usize {
+        self.entries
+            .values()
+            .map(|entry| match entry {
+                PrefetchEntry::Loaded(asset) => asset.size_in_bytes(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Size charged to the budget for a load in flight: the average size of the assets loaded so far
+    fn size_estimate(&self) -> usize {
+        self.loaded_bytes
+            .checked_div(self.loaded_count)
+            .unwrap_or(DEFAULT_SIZE_ESTIMATE)
+    }
+
+    pub fn update(
+        &mut self,
+        asset_server: &Arc<AnyAssetServer>,
+        scenario: &Scenario,
+        position: CodeAddress,
+    ) {
+        if self.position != Some(position) {
+            self.position = Some(position);
+            self.look_ahead(scenario, position);
+        }
+
+        for entry in self.entries.values_mut() {
+            if matches!(entry, PrefetchEntry::Loading(task) if task.is_finished()) {
+                let PrefetchEntry::Loading(task) = std::mem::replace(entry, PrefetchEntry::Failed)
+                else {
+                    unreachable!()
+                };
+                match task.block_on() {
+                    Ok(asset) => {
+                        self.loaded_bytes += asset.size_in_bytes();
+                        self.loaded_count += 1;
+                        *entry = PrefetchEntry::Loaded(asset);
+                    }
+                    Err(e) => warn!("Prefetcher: {:?}", e),
+                }
+            }
+        }
+
+        self.apply_budget(|reference, path| spawn_load(asset_server.clone(), reference, path));
+    }
+
+    /// Go over the upcoming assets nearest first, releasing the first one that does not fit into the budget and all the ones after it
+    ///
+    /// The loads in flight are charged with [Self::size_estimate], new loads are started with `spawn_load` while they fit.
+    fn apply_budget(
+        &mut self,
+        mut spawn_load: impl FnMut(&AssetReference, String) -> Task<Result<PrefetchedAsset>>,
+    ) {
+        let estimate = self.size_estimate();
+        let mut used = 0;
+        let mut full = false;
+        let mut in_flight = self
+            .entries
+            .values()
+            .filter(|entry| matches!(entry, PrefetchEntry::Loading(_)))
+            .count();
+        for (reference, path) in &self.upcoming {
+            if full {
+                // dropping a task in flight cancels it
+                self.entries.remove(path);
+                continue;
+            }
+
+            match self.entries.get(path) {
+                Some(PrefetchEntry::Loaded(asset)) => {
+                    let size = asset.size_in_bytes();
+                    if used + size > self.budget {
+                        self.entries
+                            .insert(path.clone(), PrefetchEntry::Released(size));
+                        full = true;
+                    } else {
+                        used += size;
+                    }
+                }
+                Some(&PrefetchEntry::Released(size)) => {
+                    if used + size > self.budget {
+                        full = true;
+                    } else {
+                        // fits now, load it again on the next update
+                        self.entries.remove(path);
+                    }
+                }
+                Some(PrefetchEntry::Loading(_)) => {
+                    if used + estimate > self.budget {
+                        self.entries.remove(path);
+                        full = true;
+                    } else {
+                        used += estimate;
+                    }
+                }
+                Some(PrefetchEntry::Failed) => {}
+                None => {
+                    if used + estimate > self.budget {
+                        full = true;
+                    } else if in_flight < MAX_IN_FLIGHT {
+                        debug!("Prefetcher: loading {}", path);
+                        let task = spawn_load(reference, path.clone());
+                        self.entries
+                            .insert(path.clone(), PrefetchEntry::Loading(task));
+                        used += estimate;
+                        in_flight += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    fn look_ahead(&mut self, scenario: &Scenario, position: CodeAddress) {
+        let tables = scenario.info_tables();
+        self.upcoming = find_upcoming_assets(scenario, position, LOOKAHEAD_INSTRUCTIONS)
+            .into_iter()
+            .filter_map(|upcoming| {
+                let path = upcoming.reference.path(tables)?;
+                Some((upcoming.reference, path))
+            })
+            .collect();
+
+        // the assets that are not upcoming anymore are either in use (and kept alive by their users) or not needed
+        let upcoming = self
+            .upcoming
+            .iter()
+            .map(|(_, path)| path.as_str())
+            .collect::<HashSet<_>>();
+        self.entries
+            .retain(|path, _| upcoming.contains(path.as_str()));
+    }
+}
+
+fn spawn_load(
+    asset_server: Arc<AnyAssetServer>,
+    reference: &AssetReference,
+    path: String,
+) -> Task<Result<PrefetchedAsset>> {
+    let reference = reference.clone();
+    IoTaskPool::get().spawn(async move {
+        Ok(match reference {
+            AssetReference::Picture(_) => {
+                PrefetchedAsset::Picture(asset_server.load::<Picture, _>(&path).await?)
+            }
+            AssetReference::Bustup(_) => {
+                PrefetchedAsset::Bustup(asset_server.load::<Bustup, _>(&path).await?)
+            }
+            AssetReference::Bgm(_) | AssetReference::Se(_) | AssetReference::Voice(_) => {
+                PrefetchedAsset::Audio(asset_server.load::<AudioFile, _>(&path).await?)
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shin_tasks::TaskPool;
+
+    /// A prefetcher with the upcoming assets `/0`, `/1`, ... and loads that never finish
+    fn prefetcher(budget: usize, upcoming: usize) -> Prefetcher {
+        IoTaskPool::init(TaskPool::default);
+
+        let mut prefetcher = Prefetcher::new(budget);
+        prefetcher.upcoming = (0..upcoming)
+            .map(|i| (AssetReference::Picture(i as i32), format!("/{}", i)))
+            .collect();
+        prefetcher
+    }
+
+    fn apply_budget(prefetcher: &mut Prefetcher) -> Vec<String> {
+        let mut started = Vec::new();
+        prefetcher.apply_budget(|_, path| {
+            started.push(path);
+            IoTaskPool::get().spawn(std::future::pending())
+        });
+        started
+    }
+
+    #[test]
+    fn loads_in_flight_are_charged() {
+        let mut prefetcher = prefetcher(DEFAULT_SIZE_ESTIMATE * 5 / 2, 4);
+
+        assert_eq!(apply_budget(&mut prefetcher), vec!["/0", "/1"]);
+        // the pending loads keep their place in the budget
+        assert!(apply_budget(&mut prefetcher).is_empty());
+        assert_eq!(prefetcher.entries.len(), 2);
+    }
+
+    #[test]
+    fn loads_in_flight_block_released_assets() {
+        let mut prefetcher = prefetcher(DEFAULT_SIZE_ESTIMATE * 2, 3);
+        prefetcher.entries.insert(
+            "/1".to_string(),
+            PrefetchEntry::Released(DEFAULT_SIZE_ESTIMATE + 1),
+        );
+
+        assert_eq!(apply_budget(&mut prefetcher), vec!["/0"]);
+        assert!(matches!(
+            prefetcher.entries.get("/1"),
+            Some(PrefetchEntry::Released(_))
+        ));
+        assert!(!prefetcher.entries.contains_key("/2"));
+    }
+
+    #[test]
+    fn estimate_follows_the_loaded_sizes() {
+        let mut prefetcher = prefetcher(1000, 4);
+        prefetcher.loaded_bytes = 900;
+        prefetcher.loaded_count = 3;
+
+        assert_eq!(prefetcher.size_estimate(), 300);
+        assert_eq!(apply_budget(&mut prefetcher), vec!["/0", "/1", "/2"]);
+    }
+}
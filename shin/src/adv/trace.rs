@@ -0,0 +1,68 @@
+//! Records the commands executed by the ADV scene to a text file.
+//!
+//! The format is line-based, so that two traces can be compared with a plain `diff`:
+//!
+//! ```text
+//! F 12
+//! C 0001a2b4 MSGSET ...
+//! S VmState { ... }
+//! ```
+//!
+//! `F` marks the start of a frame in which at least one command was executed,
+//! `C` lines list the commands with their code addresses,
+//! and an `S` line with a [VmState] snapshot closes the frame.
+
+use crate::adv::VmState;
+use anyhow::{Context, Result};
+use shin_core::format::scenario::instructions::CodeAddress;
+use shin_core::vm::command::RuntimeCommand;
+use std::io::Write;
+use tracing::error;
+
+pub struct TraceRecorder {
+    output: Box<dyn Write>,
+    frame: u64,
+    frame_has_commands: bool,
+}
+
+impl TraceRecorder {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            output,
+            frame: 0,
+            frame_has_commands: false,
+        }
+    }
+
+    pub fn record_command(&mut self, position: CodeAddress, command: &RuntimeCommand) {
+        if !self.frame_has_commands {
+            self.frame_has_commands = true;
+            writeln!(self.output, "F {}", self.frame).expect("Writing the trace failed");
+        }
+        writeln!(self.output, "C {:08x} {}", position.0, command)
+            .expect("Writing the trace failed");
+    }
+
+    /// Finish the frame, writing the state snapshot if anything has happened
+    pub fn finish_frame(&mut self, vm_state: &VmState) {
+        if self.frame_has_commands {
+            writeln!(self.output, "S {:?}", vm_state).expect("Writing the trace failed");
+        }
+        self.frame += 1;
+        self.frame_has_commands = false;
+    }
+
+    /// Flush the trace to the output
+    pub fn finish(mut self) -> Result<()> {
+        self.output.flush().context("Flushing the trace")
+    }
+}
+
+impl Drop for TraceRecorder {
+    fn drop(&mut self) {
+        // the recorders that were not finished explicitly can't report the error
+        if let Err(e) = self.output.flush() {
+            error!("Flushing the trace failed: {}", e);
+        }
+    }
+}
@@ -6,6 +6,7 @@ use layers::LayersState;
 use shin_core::format::save::PersistData;
 use shin_core::vm::command::types::MessageboxStyle;
 
+#[derive(Debug)]
 pub struct SaveInfo {
     pub info: [String; 4],
 }
@@ -38,6 +39,7 @@ impl MessageState {
     }
 }
 
+#[derive(Debug)]
 pub struct VmState {
     pub save_info: SaveInfo,
     pub messagebox_state: MessageState,
@@ -2,9 +2,24 @@ use crate::asset::Asset;
 use anyhow::Context;
 use anyhow::Result;
 use shin_core::format::audio::{read_audio, AudioFile};
+use shin_core::format::sysse::{read_system_sounds, SystemSoundBundle};
 
 impl Asset for AudioFile {
     fn load_from_bytes(data: Vec<u8>) -> Result<Self> {
         read_audio(&data).context("Parsing audio file")
     }
+
+    fn size_in_bytes(&self) -> usize {
+        AudioFile::size_in_bytes(self)
+    }
+}
+
+impl Asset for SystemSoundBundle {
+    fn load_from_bytes(data: Vec<u8>) -> Result<Self> {
+        read_system_sounds(&data).context("Parsing system sound bundle")
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        SystemSoundBundle::size_in_bytes(self)
+    }
 }
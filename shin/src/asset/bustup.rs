@@ -16,6 +16,29 @@ pub struct Bustup {
 }
 
 impl Bustup {
+    /// Assembles a bustup with a single expression from already decoded images
+    #[cfg(test)]
+    pub fn from_images(
+        base_picture: LazyGpuImage,
+        emotion: &str,
+        face_picture: Option<LazyGpuImage>,
+        mouth_pictures: Vec<LazyGpuImage>,
+    ) -> Self {
+        let mut emotions = HashMap::default();
+        emotions.insert(
+            emotion.to_owned(),
+            BustupExpression {
+                face_picture,
+                mouth_pictures,
+            },
+        );
+
+        Self {
+            base_picture,
+            emotions,
+        }
+    }
+
     pub fn base_gpu_image(&self, resources: &GpuCommonResources) -> &GpuImage {
         self.base_picture.gpu_image(resources)
     }
@@ -25,13 +48,7 @@ impl Bustup {
         resources: &GpuCommonResources,
         emotion: &str,
     ) -> Option<&GpuImage> {
-        self.emotions
-            .get(emotion)
-            .with_context(|| format!("No emotion {} in bustup", emotion))
-            .unwrap()
-            .face_picture
-            .as_ref()
-            .map(|pic| pic.gpu_image(resources))
+        self.face_image(emotion).map(|pic| pic.gpu_image(resources))
     }
 
     pub fn mouth_gpu_image(
@@ -40,11 +57,20 @@ impl Bustup {
         emotion: &str,
         mouth_intensity: f32,
     ) -> Option<&GpuImage> {
-        let emotion = self
-            .emotions
-            .get(emotion)
-            .with_context(|| format!("No emotion {} in bustup", emotion))
-            .unwrap();
+        self.mouth_image(emotion, mouth_intensity)
+            .map(|pic| pic.gpu_image(resources))
+    }
+
+    pub fn base_image(&self) -> &LazyGpuImage {
+        &self.base_picture
+    }
+
+    pub fn face_image(&self, emotion: &str) -> Option<&LazyGpuImage> {
+        self.emotion(emotion).face_picture.as_ref()
+    }
+
+    pub fn mouth_image(&self, emotion: &str, mouth_intensity: f32) -> Option<&LazyGpuImage> {
+        let emotion = self.emotion(emotion);
 
         if emotion.mouth_pictures.is_empty() {
             return None;
@@ -54,7 +80,14 @@ impl Bustup {
         let mouth_index =
             ((emotion.mouth_pictures.len() - 1) as f32 * mouth_intensity).round() as usize;
 
-        Some(emotion.mouth_pictures[mouth_index].gpu_image(resources))
+        Some(&emotion.mouth_pictures[mouth_index])
+    }
+
+    fn emotion(&self, emotion: &str) -> &BustupExpression {
+        self.emotions
+            .get(emotion)
+            .with_context(|| format!("No emotion {} in bustup", emotion))
+            .unwrap()
     }
 }
 
@@ -114,4 +147,14 @@ impl Asset for Bustup {
                 .collect(),
         })
     }
+
+    fn size_in_bytes(&self) -> usize {
+        self.base_picture.size_in_bytes()
+            + self
+                .emotions
+                .values()
+                .flat_map(|e| e.face_picture.iter().chain(e.mouth_pictures.iter()))
+                .map(|p| p.size_in_bytes())
+                .sum::<usize>()
+    }
 }
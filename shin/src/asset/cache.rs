@@ -0,0 +1,313 @@
+//! Strong cache of the loaded assets with a least-recently-used retention policy.
+//!
+//! The [AssetServer](super::AssetServer) keeps the recently used assets alive here, so that an asset dropped by its last user
+//! (e.g. a bustup that reappears two lines later) does not have to be read & decoded again.
+//! The total size of the cached assets (as reported by [Asset::size_in_bytes]) is kept under a budget,
+//! except for the pinned assets which are never evicted.
+
+use crate::asset::Asset;
+use bevy_utils::{HashMap, HashSet};
+use std::any::{Any, TypeId};
+use std::sync::Arc;
+use tracing::trace;
+
+pub const DEFAULT_CACHE_BUDGET: usize = 512 * 1024 * 1024;
+
+type CacheKey = (TypeId, String);
+
+fn key<T: Asset>(path: &str) -> CacheKey {
+    (TypeId::of::<T>(), path.to_string())
+}
+
+struct CacheEntry {
+    asset: Arc<dyn Any + Send + Sync>,
+    size: usize,
+    last_used: u64,
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CacheStats {
+    /// Loads served without reading the asset
+    pub hits: u64,
+    /// Loads that had to read & decode the asset
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub pinned: usize,
+    pub used_bytes: usize,
+    pub budget: usize,
+}
+
+pub(super) struct AssetCache {
+    budget: usize,
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Pins are remembered even for the assets that are not in the cache (yet)
+    pinned: HashSet<CacheKey>,
+    /// Incremented on each access, used to find the least recently used entry
+    clock: u64,
+    used_bytes: usize,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl AssetCache {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            entries: HashMap::default(),
+            pinned: HashSet::default(),
+            clock: 0,
+            used_bytes: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    pub fn get<T: Asset>(&mut self, path: &str) -> Option<Arc<T>> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(&key::<T>(path))?;
+        entry.last_used = self.clock;
+
+        Some(
+            entry
+                .asset
+                .clone()
+                .downcast::<T>()
+                .unwrap_or_else(|_| unreachable!()),
+        )
+    }
+
+    pub fn contains<T: Asset>(&self, path: &str) -> bool {
+        self.entries.contains_key(&key::<T>(path))
+    }
+
+    pub fn insert<T: Asset>(&mut self, path: &str, asset: Arc<T>) {
+        self.clock += 1;
+        let entry = CacheEntry {
+            size: asset.size_in_bytes(),
+            asset,
+            last_used: self.clock,
+        };
+
+        self.used_bytes += entry.size;
+        if let Some(old) = self.entries.insert(key::<T>(path), entry) {
+            self.used_bytes -= old.size;
+        }
+
+        self.evict();
+    }
+
+    /// Remove the entries with the path, regardless of their type
+    pub fn invalidate(&mut self, path: &str) {
+        let mut freed = 0;
+        self.entries.retain(|(_, entry_path), entry| {
+            let keep = entry_path != path;
+            if !keep {
+                freed += entry.size;
+            }
+            keep
+        });
+        self.used_bytes -= freed;
+    }
+
+    pub fn record_hit(&mut self) {
+        self.hits += 1;
+    }
+
+    pub fn record_miss(&mut self) {
+        self.misses += 1;
+    }
+
+    pub fn pin<T: Asset>(&mut self, path: &str) {
+        self.pinned.insert(key::<T>(path));
+    }
+
+    pub fn unpin<T: Asset>(&mut self, path: &str) {
+        self.pinned.remove(&key::<T>(path));
+        self.evict();
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+            entries: self.entries.len(),
+            pinned: self
+                .entries
+                .keys()
+                .filter(|k| self.pinned.contains(*k))
+                .count(),
+            used_bytes: self.used_bytes,
+            budget: self.budget,
+        }
+    }
+
+    /// Drop the least recently used unpinned entries until the cache fits into the budget
+    fn evict(&mut self) {
+        while self.used_bytes > self.budget {
+            let Some(key) = self
+                .entries
+                .iter()
+                .filter(|(key, _)| !self.pinned.contains(*key))
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                // only pinned entries are left
+                break;
+            };
+
+            let entry = self.entries.remove(&key).unwrap();
+            trace!("Evicting asset {} ({} bytes)", key.1, entry.size);
+            self.used_bytes -= entry.size;
+            self.evictions += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    struct Blob(usize);
+
+    impl Asset for Blob {
+        fn load_from_bytes(data: Vec<u8>) -> Result<Self> {
+            Ok(Self(data.len()))
+        }
+
+        fn size_in_bytes(&self) -> usize {
+            self.0
+        }
+    }
+
+    struct OtherBlob(usize);
+
+    impl Asset for OtherBlob {
+        fn load_from_bytes(data: Vec<u8>) -> Result<Self> {
+            Ok(Self(data.len()))
+        }
+
+        fn size_in_bytes(&self) -> usize {
+            self.0
+        }
+    }
+
+    fn cached(cache: &AssetCache) -> Vec<&str> {
+        let mut paths = cache
+            .entries
+            .keys()
+            .map(|(_, path)| path.as_str())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = AssetCache::new(30);
+        cache.insert("/a", Arc::new(Blob(10)));
+        cache.insert("/b", Arc::new(Blob(10)));
+        cache.insert("/c", Arc::new(Blob(10)));
+        assert!(cache.get::<Blob>("/a").is_some());
+
+        cache.insert("/d", Arc::new(Blob(10)));
+        assert_eq!(cached(&cache), ["/a", "/c", "/d"]);
+
+        cache.insert("/e", Arc::new(Blob(20)));
+        assert_eq!(cached(&cache), ["/d", "/e"]);
+
+        let stats = cache.stats();
+        assert_eq!(stats.evictions, 3);
+        assert_eq!(stats.used_bytes, 30);
+    }
+
+    #[test]
+    fn keeps_pinned_entries() {
+        let mut cache = AssetCache::new(20);
+        cache.pin::<Blob>("/a");
+        cache.insert("/a", Arc::new(Blob(10)));
+        cache.insert("/b", Arc::new(Blob(10)));
+        cache.insert("/c", Arc::new(Blob(10)));
+        assert_eq!(cached(&cache), ["/a", "/c"]);
+
+        cache.insert("/a", Arc::new(OtherBlob(10)));
+        assert_eq!(cached(&cache), ["/a", "/a"]);
+
+        // the pin is per type, an unpinned entry larger than the budget is evicted right away
+        cache.insert("/d", Arc::new(Blob(30)));
+        assert!(cache.contains::<Blob>("/a"));
+        assert!(!cache.contains::<OtherBlob>("/a"));
+        assert_eq!(cached(&cache), ["/a"]);
+
+        // pinned entries stay even when they do not fit
+        cache.pin::<Blob>("/e");
+        cache.insert("/e", Arc::new(Blob(30)));
+        assert_eq!(cached(&cache), ["/a", "/e"]);
+        assert_eq!(cache.stats().pinned, 2);
+        assert_eq!(cache.stats().used_bytes, 40);
+
+        cache.unpin::<Blob>("/a");
+        assert_eq!(cached(&cache), ["/e"]);
+        assert_eq!(cache.stats().used_bytes, 30);
+    }
+
+    #[test]
+    fn shrinking_the_budget_evicts() {
+        let mut cache = AssetCache::new(100);
+        cache.insert("/a", Arc::new(Blob(10)));
+        cache.insert("/b", Arc::new(Blob(20)));
+        cache.insert("/c", Arc::new(Blob(30)));
+        assert!(cache.get::<Blob>("/a").is_some());
+
+        cache.set_budget(40);
+        assert_eq!(cached(&cache), ["/a", "/c"]);
+        assert_eq!(cache.stats().used_bytes, 40);
+
+        cache.set_budget(0);
+        assert!(cached(&cache).is_empty());
+        assert_eq!(cache.stats().used_bytes, 0);
+        assert_eq!(cache.stats().evictions, 3);
+    }
+
+    #[test]
+    fn used_bytes_after_replace() {
+        let mut cache = AssetCache::new(100);
+        cache.insert("/a", Arc::new(Blob(10)));
+        cache.insert("/a", Arc::new(OtherBlob(20)));
+        cache.insert("/b", Arc::new(Blob(30)));
+        assert_eq!(cache.stats().used_bytes, 60);
+
+        // replacing an entry only counts the new asset
+        cache.insert("/b", Arc::new(Blob(5)));
+        assert_eq!(cache.stats().used_bytes, 35);
+        assert_eq!(cache.get::<Blob>("/b").unwrap().0, 5);
+    }
+
+    #[test]
+    fn invalidate_removes_all_types() {
+        let mut cache = AssetCache::new(100);
+        cache.insert("/a", Arc::new(Blob(10)));
+        cache.insert("/a", Arc::new(OtherBlob(20)));
+        cache.insert("/b", Arc::new(Blob(5)));
+        assert_eq!(cache.stats().used_bytes, 35);
+
+        // invalidation removes all the types with the path
+        cache.invalidate("/a");
+        assert_eq!(cached(&cache), ["/b"]);
+        assert_eq!(cache.stats().used_bytes, 5);
+
+        cache.invalidate("/b");
+        cache.invalidate("/missing");
+        assert_eq!(cache.stats().used_bytes, 0);
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
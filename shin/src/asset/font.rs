@@ -7,4 +7,8 @@ impl Asset for LazyFont {
     fn load_from_bytes(data: Vec<u8>) -> anyhow::Result<Self> {
         read_lazy_font(&mut Cursor::new(data)).context("Reading font")
     }
+
+    fn size_in_bytes(&self) -> usize {
+        LazyFont::size_in_bytes(self)
+    }
 }
@@ -3,9 +3,50 @@ use anyhow::bail;
 use std::path::{Path, PathBuf};
 use tracing::{debug, instrument, trace};
 
+/// The asset directory that was found and the game data in it
+#[derive(Debug)]
+pub struct LocatedAssets {
+    pub dir: PathBuf,
+    pub io: LayeredAssetIo,
+}
+
+/// File extensions of the Switch dumps that can contain the game data
+const DUMP_EXTENSIONS: &[&str] = &["nsp", "nca", "romfs", "istorage"];
+
+fn is_dump_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map_or(false, |ext| {
+                DUMP_EXTENSIONS
+                    .iter()
+                    .any(|dump_ext| ext.eq_ignore_ascii_case(dump_ext))
+            })
+}
+
+#[instrument]
+fn try_dump(path: &Path) -> Option<LayeredAssetIo> {
+    trace!("Trying dump {:?}...", path);
+    let mut result = LayeredAssetIo::new();
+    match result.try_with_dump(path) {
+        Ok(_) => {
+            trace!("Using dump {:?}", path);
+            Some(result)
+        }
+        Err(err) => {
+            trace!("Cannot use {:?} as a dump: {:#}", path, err);
+            None
+        }
+    }
+}
+
 #[instrument]
 fn try_assets_directory(path: &Path) -> anyhow::Result<Option<LayeredAssetIo>> {
     debug!("Trying assets directory {:?}...", path);
+    if path.is_file() {
+        return Ok(try_dump(path));
+    }
     if !path.is_dir() {
         debug!("Cannot use {:?} as assets directory, not a directory", path);
         return Ok(None);
@@ -27,6 +68,18 @@ fn try_assets_directory(path: &Path) -> anyhow::Result<Option<LayeredAssetIo>> {
         }
     }
 
+    if result.is_empty() {
+        // no extracted data, look for a dump of the game
+        let mut dumps = std::fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| is_dump_file(path))
+            .collect::<Vec<_>>();
+        dumps.sort();
+        if let Some(io) = dumps.iter().find_map(|dump| try_dump(dump)) {
+            return Ok(Some(io));
+        }
+    }
+
     if result.is_empty() {
         trace!("Cannot use {:?} as assets directory, no data found", path);
         Ok(None)
@@ -49,8 +102,13 @@ fn try_assets_directory(path: &Path) -> anyhow::Result<Option<LayeredAssetIo>> {
 /// 5. The user's shared data directory (see `dirs::data_dir`, `/home/alice/.local/share/shin/assets` / `C:\Users\Alice\AppData\Roaming\shin\assets` / `/Users/Alice/Library/Application Support/shin/assets`)
 ///
 /// The used asset directory is the first one having a "data" directory or a "data.rom" file.
+///
+/// If neither is present, the game data is searched for in the Switch dumps in the directory (`*.nsp`, `*.nca`, `*.romfs` or `*.istorage` files, see [switch](shin_core::format::switch)), so the RomFS does not need to be extracted.
+/// The `--assets-dir` option can also point to such a dump directly.
+///
+/// The mods (see [mods](super::mods)) are not loaded here, they are looked for in the "mods" subdirectory of the returned directory.
 #[allow(clippy::match_result_ok)]
-pub fn locate_assets(cli_assets: Option<&Path>) -> anyhow::Result<LayeredAssetIo> {
+pub fn locate_assets(cli_assets: Option<&Path>) -> anyhow::Result<LocatedAssets> {
     // First, try the assets directory specified on the command line
     // Then, try the assets directory specified in the environment
     // Then, try the assets directory next to the executable
@@ -85,8 +143,13 @@ pub fn locate_assets(cli_assets: Option<&Path>) -> anyhow::Result<LayeredAssetIo
     }
 
     for path in try_list.iter() {
-        if let Some(result) = try_assets_directory(path)? {
-            return Ok(result);
+        if let Some(io) = try_assets_directory(path)? {
+            // when pointed at a dump file, the mods are looked for next to it
+            let dir = match path.parent() {
+                Some(parent) if path.is_file() => parent.to_path_buf(),
+                _ => path.clone(),
+            };
+            return Ok(LocatedAssets { dir, io });
         }
     }
 
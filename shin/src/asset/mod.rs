@@ -1,22 +1,20 @@
 mod audio;
 pub mod bustup;
+mod cache;
 mod font;
 mod locate;
+mod mods;
+pub mod movie;
 pub mod picture;
 mod scenario;
 mod server;
 pub mod texture_archive;
-pub mod movie;
-
-pub mod asset_paths {
-    pub const SCENARIO: &str = "/main.snr";
-    pub const SYSTEM_FNT: &str = "/system.fnt";
-    pub const MSGTEX: &str = "/msgtex.txa";
-    pub const NEWRODIN_MEDIUM_FNT: &str = "/newrodin-medium.fnt";
-    pub const NEWRODIN_BOLD_FNT: &str = "/newrodin-bold.fnt";
-}
+mod watcher;
 
 pub use locate::locate_assets;
+pub use mods::apply_mods;
 pub use server::{
-    AnyAssetIo, AnyAssetServer, Asset, AssetIo, AssetServer, DirAssetIo, LayeredAssetIo, RomAssetIo,
+    is_asset_changed, normalize_asset_path, AnyAssetIo, AnyAssetServer, Asset, AssetIo,
+    AssetServer, DirAssetIo, LayeredAssetIo, RomAssetIo,
 };
+pub use watcher::AssetWatcher;
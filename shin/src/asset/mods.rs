@@ -0,0 +1,344 @@
+//! Mod packs: sets of asset overrides stacked over the game data.
+//!
+//! The mods are looked for in the `mods` directory next to the game data (see [locate_assets](super::locate_assets)),
+//! or in the directory passed with `--mods-dir`. Each mod is either
+//! - a directory with a `mod.toml` manifest, holding the files in a `data` subdirectory and/or a `data.rom` file
+//! - a ROM file `<name>.rom` with the manifest next to it, named `<name>.toml`
+//!
+//! The manifest looks like this:
+//! ```toml
+//! name = "English translation"
+//! # the mods with higher priority take precedence, defaults to 0
+//! priority = 10
+//! # optional, the mod is skipped when running other games
+//! game = "umineko"
+//! ```
+
+use crate::asset::LayeredAssetIo;
+use anyhow::{bail, Context, Result};
+use bevy_utils::HashMap;
+use serde::Deserialize;
+use shin_core::game::GameId;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ModManifest {
+    name: String,
+    #[serde(default)]
+    priority: i32,
+    game: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ModPack {
+    pub name: String,
+    pub priority: i32,
+    /// The game the mod is made for, `None` if it's not specified
+    pub game: Option<GameId>,
+    pub path: PathBuf,
+    io: LayeredAssetIo,
+}
+
+impl ModPack {
+    fn load(path: &Path) -> Result<Option<Self>> {
+        let (manifest_path, io) = if path.is_dir() {
+            let manifest_path = path.join("mod.toml");
+            if !manifest_path.is_file() {
+                debug!("Skipping {:?}, it has no mod.toml", path);
+                return Ok(None);
+            }
+
+            let mut io = LayeredAssetIo::new();
+            let data_dir = path.join("data");
+            if data_dir.is_dir() {
+                io.try_with_dir(&data_dir)?;
+            }
+            let data_rom = path.join("data.rom");
+            if data_rom.is_file() {
+                io.try_with_rom(&data_rom)?;
+            }
+            if io.is_empty() {
+                bail!("The mod has neither a \"data\" directory nor a \"data.rom\" file");
+            }
+
+            (manifest_path, io)
+        } else if path.extension().map_or(false, |ext| ext == "rom") {
+            let manifest_path = path.with_extension("toml");
+            if !manifest_path.is_file() {
+                bail!(
+                    "The mod ROM has no manifest, expected it at {:?}",
+                    manifest_path
+                );
+            }
+
+            let mut io = LayeredAssetIo::new();
+            io.try_with_rom(path)?;
+
+            (manifest_path, io)
+        } else {
+            // the manifests of the ROM mods are picked up together with the ROMs
+            return Ok(None);
+        };
+
+        let manifest = std::fs::read_to_string(&manifest_path)
+            .with_context(|| format!("Reading the manifest {:?}", manifest_path))?;
+        let manifest: ModManifest = toml::from_str(&manifest)
+            .with_context(|| format!("Parsing the manifest {:?}", manifest_path))?;
+        let game: Option<GameId> = manifest
+            .game
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .with_context(|| format!("Parsing the manifest {:?}", manifest_path))?;
+
+        Ok(Some(Self {
+            name: manifest.name,
+            priority: manifest.priority,
+            game,
+            path: path.to_path_buf(),
+            io,
+        }))
+    }
+}
+
+/// Two mods providing the same file
+#[derive(Debug, Clone)]
+pub struct ModConflict {
+    pub path: String,
+    /// The mod the file is taken from
+    pub winner: String,
+    /// The mods with lower priority, their versions of the file are not used
+    pub overridden: Vec<String>,
+}
+
+/// Load the mods from the directory, skipping the ones made for other games.
+///
+/// The result is sorted by priority, highest first. A missing directory means there are no mods,
+/// while the mods that fail to load are skipped with a warning, so that a broken mod does not prevent the game from starting.
+pub fn load_mods(mods_dir: &Path, game: GameId) -> Result<Vec<ModPack>> {
+    if !mods_dir.is_dir() {
+        debug!("No mods directory at {:?}", mods_dir);
+        return Ok(Vec::new());
+    }
+
+    let mut entries = std::fs::read_dir(mods_dir)
+        .with_context(|| format!("Listing the mods directory {:?}", mods_dir))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Listing the mods directory {:?}", mods_dir))?;
+    // make the order of the mods with equal priority independent of the file system
+    entries.sort();
+
+    let mut mods = Vec::new();
+    for path in entries {
+        match ModPack::load(&path) {
+            Ok(Some(mod_pack)) => {
+                if mod_pack.game.map_or(false, |mod_game| mod_game != game) {
+                    warn!(
+                        "Skipping mod {:?} ({:?}), it is made for {}",
+                        mod_pack.name,
+                        path,
+                        mod_pack.game.unwrap()
+                    );
+                    continue;
+                }
+                mods.push(mod_pack);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Skipping mod {:?}: {:?}", path, e),
+        }
+    }
+
+    // the sort is stable, so the mods with equal priority stay in the order of their paths
+    mods.sort_by_key(|mod_pack| std::cmp::Reverse(mod_pack.priority));
+
+    for mod_pack in &mods {
+        info!(
+            "Using mod {:?} (priority {}, {:?})",
+            mod_pack.name, mod_pack.priority, mod_pack.path
+        );
+    }
+
+    Ok(mods)
+}
+
+/// Find the files provided by more than one mod. The mods must be sorted by priority, as returned by [load_mods].
+pub fn find_conflicts(mods: &[ModPack]) -> Vec<ModConflict> {
+    let mut providers = HashMap::<String, Vec<&str>>::default();
+    for mod_pack in mods {
+        for path in mod_pack.io.list_files() {
+            providers.entry(path).or_default().push(&mod_pack.name);
+        }
+    }
+
+    let mut conflicts = providers
+        .into_iter()
+        .filter(|(_, providers)| providers.len() > 1)
+        .map(|(path, providers)| ModConflict {
+            path,
+            winner: providers[0].to_string(),
+            overridden: providers[1..].iter().map(|s| s.to_string()).collect(),
+        })
+        .collect::<Vec<_>>();
+    conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+
+    conflicts
+}
+
+/// Stack the mods over the game data, each mod being a single layer labeled with its name
+pub fn stack_mods(mods: Vec<ModPack>, base: LayeredAssetIo) -> LayeredAssetIo {
+    let mut result = LayeredAssetIo::new();
+    for mod_pack in mods {
+        result.with(format!("mod: {}", mod_pack.name), mod_pack.io.into());
+    }
+    result.extend(base);
+    result
+}
+
+/// Stack the mods from the directory over the game data, reporting the files more than one mod provides
+pub fn apply_mods(base: LayeredAssetIo, mods_dir: &Path, game: GameId) -> Result<LayeredAssetIo> {
+    let mods = load_mods(mods_dir, game)?;
+
+    let conflicts = find_conflicts(&mods);
+    let mut conflict_counts = HashMap::<(&str, &str), usize>::default();
+    for conflict in &conflicts {
+        debug!(
+            "Mod conflict: {} is provided by {:?}, overriding {:?}",
+            conflict.path, conflict.winner, conflict.overridden
+        );
+        for overridden in &conflict.overridden {
+            *conflict_counts
+                .entry((&conflict.winner, overridden))
+                .or_default() += 1;
+        }
+    }
+    for ((winner, overridden), count) in conflict_counts {
+        warn!(
+            "Mods {:?} and {:?} both provide {} files, using the ones from {:?}",
+            winner, overridden, count, winner
+        );
+    }
+
+    Ok(stack_mods(mods, base))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory in the system temp directory, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "shin-mods-test-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn write(&self, path: &str, contents: &str) {
+            let path = self.0.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+
+        /// A directory mod with the manifest and empty data files
+        fn add_mod(&self, dir: &str, manifest: &str, files: &[&str]) {
+            self.write(&format!("{}/mod.toml", dir), manifest);
+            for file in files {
+                self.write(&format!("{}/data/{}", dir, file), "");
+            }
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn names(mods: &[ModPack]) -> Vec<&str> {
+        mods.iter().map(|m| m.name.as_str()).collect()
+    }
+
+    #[test]
+    fn sorted_by_priority() {
+        let dir = TempDir::new("priority");
+        dir.add_mod("a", "name = \"A\"", &["a.txt"]);
+        dir.add_mod("b", "name = \"B\"\npriority = 10", &["b.txt"]);
+        dir.add_mod("c", "name = \"C\"\npriority = -5", &["c.txt"]);
+        dir.add_mod("d", "name = \"D\"\npriority = 10", &["d.txt"]);
+
+        let mods = load_mods(&dir.0, GameId::Umineko).unwrap();
+        // the mods with equal priority are in the order of their paths
+        assert_eq!(names(&mods), ["B", "D", "A", "C"]);
+    }
+
+    #[test]
+    fn skips_other_games_and_broken_mods() {
+        let dir = TempDir::new("filter");
+        dir.add_mod("any", "name = \"Any\"", &["a.txt"]);
+        dir.add_mod(
+            "umineko",
+            "name = \"Umineko\"\ngame = \"umineko\"",
+            &["b.txt"],
+        );
+        dir.add_mod(
+            "unknown_game",
+            "name = \"Other\"\ngame = \"other\"",
+            &["c.txt"],
+        );
+        dir.add_mod("no_data", "name = \"No data\"", &[]);
+        dir.add_mod(
+            "bad_manifest",
+            "name = \"Bad\"\nunknown_key = 1",
+            &["d.txt"],
+        );
+        dir.write("no_manifest/data/e.txt", "");
+        dir.write("orphan.toml", "name = \"Orphan\"");
+
+        let mods = load_mods(&dir.0, GameId::Umineko).unwrap();
+        assert_eq!(names(&mods), ["Any", "Umineko"]);
+    }
+
+    #[test]
+    fn missing_directory_means_no_mods() {
+        let dir = TempDir::new("missing");
+        let mods = load_mods(&dir.0.join("mods"), GameId::Umineko).unwrap();
+        assert!(mods.is_empty());
+    }
+
+    #[test]
+    fn conflicts() {
+        let dir = TempDir::new("conflicts");
+        dir.add_mod("a", "name = \"A\"", &["bg/1.pic", "bgm/1.nxa", "a.txt"]);
+        dir.add_mod("b", "name = \"B\"\npriority = 10", &["bg/1.pic", "b.txt"]);
+        dir.add_mod(
+            "c",
+            "name = \"C\"\npriority = 5",
+            &["bg/1.pic", "bgm/1.nxa"],
+        );
+
+        let mods = load_mods(&dir.0, GameId::Umineko).unwrap();
+        let conflicts = find_conflicts(&mods)
+            .into_iter()
+            .map(|c| (c.path, c.winner, c.overridden))
+            .collect::<Vec<_>>();
+
+        let s = |s: &str| s.to_string();
+        assert_eq!(
+            conflicts,
+            [
+                (s("/bg/1.pic"), s("B"), vec![s("C"), s("A")]),
+                (s("/bgm/1.nxa"), s("C"), vec![s("A")]),
+            ]
+        );
+    }
+}
@@ -12,13 +12,19 @@ pub struct Movie {
     // TODO: allow to start decoding the video before the first frame is requested
     // TODO: use a streaming reader instead of reading the whole video into memory (they're HUGE)
     mp4: Mp4<Cursor<Vec<u8>>>,
+    size: usize,
 }
 
 impl Asset for Movie {
     fn load_from_bytes(data: Vec<u8>) -> Result<Self> {
+        let size = data.len();
         let cursor = Cursor::new(data);
         let mp4 = Mp4::new(cursor).context("Reading Mp4")?;
-        Ok(Self { mp4 })
+        Ok(Self { mp4, size })
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        self.size
     }
 }
 
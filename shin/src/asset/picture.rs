@@ -1,6 +1,7 @@
 use crate::asset::Asset;
 use anyhow::Result;
-use glam::vec2;
+use glam::{vec2, Vec2};
+use image::RgbaImage;
 use shin_core::format::picture::SimpleMergedPicture;
 use shin_render::GpuCommonResources;
 use shin_render::{GpuImage, LazyGpuImage};
@@ -11,21 +12,34 @@ pub struct Picture {
 }
 
 impl Picture {
+    pub fn new(image: RgbaImage, origin: Vec2, label: Option<&str>) -> Self {
+        Self {
+            picture: LazyGpuImage::new(image, origin, label),
+        }
+    }
+
     pub fn gpu_image(&self, resources: &GpuCommonResources) -> &GpuImage {
         self.picture.gpu_image(resources)
     }
+
+    pub fn image(&self) -> &LazyGpuImage {
+        &self.picture
+    }
 }
 
 impl Asset for Picture {
     fn load_from_bytes(data: Vec<u8>) -> Result<Self> {
         let picture = shin_core::format::picture::read_picture::<SimpleMergedPicture>(&data, ())?;
         let picture_id = picture.picture_id;
-        let picture = LazyGpuImage::new(
+
+        Ok(Self::new(
             picture.image,
             vec2(picture.origin_x as f32, picture.origin_y as f32),
             Some(&format!("Picture {:08x}", picture_id)),
-        );
+        ))
+    }
 
-        Ok(Self { picture })
+    fn size_in_bytes(&self) -> usize {
+        self.picture.size_in_bytes()
     }
 }
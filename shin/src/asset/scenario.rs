@@ -5,4 +5,8 @@ impl Asset for Scenario {
     fn load_from_bytes(data: Vec<u8>) -> anyhow::Result<Self> {
         Scenario::new(data.into())
     }
+
+    fn size_in_bytes(&self) -> usize {
+        self.raw().len()
+    }
 }
@@ -1,9 +1,12 @@
+use crate::asset::cache::{AssetCache, CacheStats, DEFAULT_CACHE_BUDGET};
+use crate::render::overlay::{OverlayCollector, OverlayVisitable};
 use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
-use bevy_utils::HashMap;
+use bevy_utils::{HashMap, HashSet};
 use derive_more::From;
 use pollster::FutureExt;
-use shin_core::format::rom::RomReader;
+use shin_core::format::rom::{IndexEntry, RomReader};
+use shin_core::format::switch::{open_data_rom, SubReader};
 use shin_tasks::{AsyncComputeTaskPool, IoTaskPool};
 use std::fmt::Debug;
 use std::fs::File;
@@ -16,6 +19,8 @@ use tracing::debug;
 
 pub trait Asset: Send + Sync + Sized + 'static {
     fn load_from_bytes(data: Vec<u8>) -> Result<Self>;
+    /// Approximate amount of memory taken by the asset, counted against the cache budget
+    fn size_in_bytes(&self) -> usize;
 }
 
 struct AssetMap<T: Asset>(HashMap<String, Weak<T>>);
@@ -35,7 +40,12 @@ impl<T: Asset> DerefMut for AssetMap<T> {
 
 pub struct AssetServer<Io: AssetIo> {
     io: Io,
+    /// All the assets that are alive, so that they are not loaded twice
     loaded_assets: RwLock<anymap::Map<dyn core::any::Any + Send + Sync>>,
+    /// The recently used assets, kept alive even if nobody uses them
+    cache: Mutex<AssetCache>,
+    /// Paths of the assets that were changed on disk, the alive copies of those are not reused
+    stale: Mutex<HashSet<String>>,
 }
 
 impl<Io: AssetIo> AssetServer<Io> {
@@ -43,21 +53,55 @@ impl<Io: AssetIo> AssetServer<Io> {
         Self {
             io,
             loaded_assets: RwLock::new(anymap::Map::new()),
+            cache: Mutex::new(AssetCache::new(DEFAULT_CACHE_BUDGET)),
+            stale: Mutex::new(HashSet::default()),
         }
     }
 
+    pub fn io(&self) -> &Io {
+        &self.io
+    }
+
+    /// Take the IO back, dropping the cache
+    pub fn into_io(self) -> Io {
+        self.io
+    }
+
+    /// `path` has to be normalized with [normalize_asset_path]
+    fn get_alive<T: Asset>(&self, path: &str) -> Option<Arc<T>> {
+        if self.stale.lock().unwrap().contains(path) {
+            return None;
+        }
+
+        self.loaded_assets
+            .read()
+            .unwrap()
+            .get::<AssetMap<T>>()?
+            .get(path)?
+            .upgrade()
+    }
+
     pub async fn load<T: Asset, P: AsRef<str>>(&self, path: P) -> Result<Arc<T>> {
         let path = path.as_ref();
-
-        if let Some(loaded) = self.loaded_assets.read().unwrap().get::<AssetMap<T>>() {
-            if let Some(asset) = loaded.get(path) {
-                if let Some(asset) = asset.upgrade() {
-                    debug!("Loaded asset from cache: {}", path);
-                    return Ok(asset);
-                }
+        let key = normalize_asset_path(path);
+
+        {
+            let mut cache = self.cache.lock().unwrap();
+            if let Some(asset) = cache.get::<T>(&key) {
+                debug!("Loaded asset from cache: {}", path);
+                cache.record_hit();
+                return Ok(asset);
             }
         }
 
+        if let Some(asset) = self.get_alive::<T>(&key) {
+            debug!("Loaded asset from cache (still in use): {}", path);
+            let mut cache = self.cache.lock().unwrap();
+            cache.record_hit();
+            cache.insert(&key, asset.clone());
+            return Ok(asset);
+        }
+
         debug!("Loading asset: {}", path);
 
         // could not find the asset in the cache, load it
@@ -77,11 +121,66 @@ impl<Io: AssetIo> AssetServer<Io> {
             .unwrap()
             .entry::<AssetMap<T>>()
             .or_insert_with(|| AssetMap(HashMap::default()))
-            .insert(path.to_string(), Arc::downgrade(&asset));
+            .insert(key.clone(), Arc::downgrade(&asset));
+
+        self.stale.lock().unwrap().remove(&key);
+        let mut cache = self.cache.lock().unwrap();
+        cache.record_miss();
+        cache.insert(&key, asset.clone());
 
         Ok(asset)
     }
 
+    /// Forget the cached copies of the asset (of any type), so that the next load reads it again
+    ///
+    /// The users still holding the old copy are not affected.
+    pub fn invalidate(&self, path: &str) {
+        debug!("Invalidating asset: {}", path);
+        let key = normalize_asset_path(path);
+        self.cache.lock().unwrap().invalidate(&key);
+        self.stale.lock().unwrap().insert(key);
+    }
+
+    /// Keep the asset in the cache regardless of the budget, until [unpin](Self::unpin) is called
+    ///
+    /// Meant for the assets that are used all the time, like the UI textures and fonts.
+    pub fn pin<T: Asset>(&self, path: &str) {
+        let key = normalize_asset_path(path);
+        let mut cache = self.cache.lock().unwrap();
+        cache.pin::<T>(&key);
+        if !cache.contains::<T>(&key) {
+            if let Some(asset) = self.get_alive::<T>(&key) {
+                cache.insert(&key, asset);
+            }
+        }
+    }
+
+    #[allow(unused)]
+    pub fn unpin<T: Asset>(&self, path: &str) {
+        self.cache
+            .lock()
+            .unwrap()
+            .unpin::<T>(&normalize_asset_path(path));
+    }
+
+    /// Set the maximum total size of the cached (unpinned) assets, in bytes
+    pub fn set_cache_budget(&self, budget: usize) {
+        self.cache.lock().unwrap().set_budget(budget);
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.lock().unwrap().stats()
+    }
+
+    /// Read the raw bytes of a file, bypassing the cache
+    pub async fn read_raw<P: AsRef<str>>(&self, path: P) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+        self.io
+            .read_file(path)
+            .await
+            .with_context(|| format!("Reading file {:?}", path))
+    }
+
     /// Load an asset synchronously. This is useful for assets not requiring much CPU time to load.
     /// Though it might cause lockups if the loading is not blazing fast (tm).
     ///
@@ -91,6 +190,30 @@ impl<Io: AssetIo> AssetServer<Io> {
     }
 }
 
+impl<Io: AssetIo> OverlayVisitable for AssetServer<Io> {
+    fn visit_overlay(&self, collector: &mut OverlayCollector) {
+        collector.overlay(
+            "Asset Cache",
+            |_ctx, top_left| {
+                let stats = self.cache_stats();
+                let mib = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+                top_left.label(format!(
+                    "Asset cache: {:.1} / {:.1} MiB, {} assets ({} pinned)",
+                    mib(stats.used_bytes),
+                    mib(stats.budget),
+                    stats.entries,
+                    stats.pinned
+                ));
+                top_left.label(format!(
+                    "Asset cache: {} hits, {} misses, {} evictions",
+                    stats.hits, stats.misses, stats.evictions
+                ));
+            },
+            false,
+        );
+    }
+}
+
 pub type AnyAssetServer = AssetServer<AnyAssetIo>;
 
 impl AnyAssetServer {
@@ -101,12 +224,32 @@ impl AnyAssetServer {
     }
 
     #[allow(unused)]
-    pub fn new_rom(rom_path: impl AsRef<Path>) -> Self {
+    pub fn new_rom(rom_path: impl AsRef<Path>) -> Result<Self> {
         debug!("Using ROM for assets: {}", rom_path.as_ref().display());
-        Self::new(AnyAssetIo::new_rom(rom_path))
+        Ok(Self::new(AnyAssetIo::new_rom(rom_path)?))
     }
 }
 
+/// The form of an asset path used to match the paths coming from different places (the scenario, the file system):
+/// lowercase, `/`-separated, starting with `/`, without empty and `.` components
+///
+/// The file systems of the games are case-insensitive, so `Picture/BG.pic` and `/picture/bg.pic` are the same asset.
+pub fn normalize_asset_path(path: &str) -> String {
+    path.split(['/', '\\'])
+        .filter(|component| !component.is_empty() && *component != ".")
+        .fold(String::new(), |acc, component| {
+            acc + "/" + component.to_ascii_lowercase().as_str()
+        })
+}
+
+/// Whether `path` is one of `changed_paths`, compared in the [normalized](normalize_asset_path) form
+pub fn is_asset_changed(changed_paths: &[String], path: &str) -> bool {
+    let path = normalize_asset_path(path);
+    changed_paths
+        .iter()
+        .any(|changed| normalize_asset_path(changed) == path)
+}
+
 #[async_trait]
 pub trait AssetIo {
     async fn read_file(&self, path: &str) -> Result<Vec<u8>>;
@@ -159,6 +302,16 @@ impl<S: io::Read + io::Seek + Send + Sync + 'static> RomAssetIo<S> {
             label: label.map(|s| s.to_string()),
         }
     }
+
+    fn list_files(&self) -> Vec<String> {
+        self.rom
+            .lock()
+            .unwrap()
+            .traverse()
+            .filter(|(_, entry)| matches!(entry, IndexEntry::File(_)))
+            .map(|(path, _)| path)
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -193,6 +346,8 @@ impl<S: io::Read + io::Seek + Send + Sync + 'static> AssetIo for RomAssetIo<S> {
 pub enum AnyAssetIo {
     Dir(DirAssetIo),
     RomFile(RomAssetIo<BufReader<File>>),
+    /// A ROM inside a Switch dump (RomFS image, NCA or NSP)
+    DumpFile(RomAssetIo<SubReader<BufReader<File>>>),
     Layered(LayeredAssetIo),
 }
 
@@ -201,14 +356,78 @@ impl AnyAssetIo {
         Self::Dir(DirAssetIo::new(root_path))
     }
 
-    pub fn new_rom(rom_path: impl AsRef<Path>) -> Self {
+    pub fn new_rom(rom_path: impl AsRef<Path>) -> Result<Self> {
         let rom_path = rom_path.as_ref();
-        let rom =
-            RomReader::new(BufReader::new(File::open(rom_path).unwrap())).expect("Opening rom");
-        Self::RomFile(RomAssetIo::new(
+        let file = File::open(rom_path).with_context(|| format!("Opening {:?}", rom_path))?;
+        let rom = RomReader::new(BufReader::new(file))
+            .with_context(|| format!("Opening rom {:?}", rom_path))?;
+        Ok(Self::RomFile(RomAssetIo::new(
             rom,
             Some(&format!("{}", rom_path.display())),
-        ))
+        )))
+    }
+
+    pub fn new_dump(dump_path: impl AsRef<Path>) -> Result<Self> {
+        let dump_path = dump_path.as_ref();
+        let file = File::open(dump_path).with_context(|| format!("Opening {:?}", dump_path))?;
+        let data_rom = open_data_rom(BufReader::new(file))
+            .with_context(|| format!("Finding the game data in {:?}", dump_path))?;
+        let rom = RomReader::new(data_rom).context("Opening rom")?;
+        Ok(Self::DumpFile(RomAssetIo::new(
+            rom,
+            Some(&format!("{}", dump_path.display())),
+        )))
+    }
+
+    /// Root directories of the loose files, the ones that can change while the engine is running
+    pub fn dir_roots(&self) -> Vec<PathBuf> {
+        match self {
+            Self::Dir(io) => vec![io.root_path.clone()],
+            Self::RomFile(_) | Self::DumpFile(_) => vec![],
+            Self::Layered(io) => io.io.iter().flat_map(|(_, io)| io.dir_roots()).collect(),
+        }
+    }
+
+    /// Paths of all the files this IO can read, in no particular order
+    pub fn list_files(&self) -> Vec<String> {
+        match self {
+            Self::Dir(io) => {
+                let mut result = Vec::new();
+                list_dir(&io.root_path, &io.root_path, &mut result);
+                result
+            }
+            Self::RomFile(io) => io.list_files(),
+            Self::DumpFile(io) => io.list_files(),
+            Self::Layered(io) => io.list_files(),
+        }
+    }
+}
+
+fn list_dir(root: &Path, dir: &Path, result: &mut Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            list_dir(root, &path, result);
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            result.push(
+                relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .fold(String::new(), |acc, c| acc + "/" + c.as_ref()),
+            );
+        }
+    }
+}
+
+impl OverlayVisitable for AnyAssetIo {
+    fn visit_overlay(&self, collector: &mut OverlayCollector) {
+        if let Self::Layered(io) = self {
+            io.visit_overlay(collector);
+        }
     }
 }
 
@@ -218,6 +437,7 @@ impl AssetIo for AnyAssetIo {
         match self {
             Self::Dir(io) => io.read_file(path).await,
             Self::RomFile(io) => io.read_file(path).await,
+            Self::DumpFile(io) => io.read_file(path).await,
             Self::Layered(io) => io.read_file(path).await,
         }
     }
@@ -225,20 +445,28 @@ impl AssetIo for AnyAssetIo {
 
 #[derive(Debug, Default)]
 pub struct LayeredAssetIo {
-    io: Vec<AnyAssetIo>,
+    /// The layers with their labels, in the order they are searched
+    io: Vec<(String, AnyAssetIo)>,
+    /// Index of the layer each asset was last read from
+    served_by: Mutex<HashMap<String, usize>>,
 }
 
 impl LayeredAssetIo {
     pub fn new() -> Self {
-        Self { io: Vec::new() }
+        Self::default()
     }
 
     pub fn is_empty(&self) -> bool {
         self.io.is_empty()
     }
 
-    pub fn with(&mut self, io: AnyAssetIo) {
-        self.io.push(io);
+    pub fn with(&mut self, label: impl Into<String>, io: AnyAssetIo) {
+        self.io.push((label.into(), io));
+    }
+
+    /// Add the layers of another [LayeredAssetIo] after the ones of this one
+    pub fn extend(&mut self, other: LayeredAssetIo) {
+        self.io.extend(other.io);
     }
 
     pub fn try_with_dir(&mut self, dir_path: impl AsRef<Path>) -> Result<()> {
@@ -255,7 +483,10 @@ impl LayeredAssetIo {
                 dir_path
             );
         }
-        self.with(AnyAssetIo::new_dir(dir_path.to_path_buf()));
+        self.with(
+            dir_path.display().to_string(),
+            AnyAssetIo::new_dir(dir_path.to_path_buf()),
+        );
         Ok(())
     }
 
@@ -270,9 +501,47 @@ impl LayeredAssetIo {
         if !meta.is_file() {
             bail!("{:?} is not a file, cannot use as asset ROM", rom_path);
         }
-        self.with(AnyAssetIo::new_rom(rom_path));
+        self.with(
+            rom_path.display().to_string(),
+            AnyAssetIo::new_rom(rom_path)?,
+        );
+        Ok(())
+    }
+
+    /// Add the game data found in a Switch dump (a RomFS image, a decrypted NCA or an NSP)
+    pub fn try_with_dump(&mut self, dump_path: impl AsRef<Path>) -> Result<()> {
+        let dump_path = dump_path.as_ref();
+        let io = AnyAssetIo::new_dump(dump_path)?;
+        self.with(dump_path.display().to_string(), io);
         Ok(())
     }
+
+    /// Paths of all the files provided by any of the layers, in no particular order
+    pub fn list_files(&self) -> Vec<String> {
+        self.io
+            .iter()
+            .flat_map(|(_, io)| io.list_files())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    pub fn layer_labels(&self) -> impl Iterator<Item = &str> {
+        self.io.iter().map(|(label, _)| label.as_str())
+    }
+
+    /// The assets read so far with the labels of the layers they were read from, sorted by path
+    pub fn served_by(&self) -> Vec<(String, String)> {
+        let mut result = self
+            .served_by
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(path, &layer)| (path.clone(), self.io[layer].0.clone()))
+            .collect::<Vec<_>>();
+        result.sort();
+        result
+    }
 }
 
 #[async_trait]
@@ -280,9 +549,15 @@ impl AssetIo for LayeredAssetIo {
     async fn read_file(&self, path: &str) -> Result<Vec<u8>> {
         let mut errors = Vec::new();
 
-        for io in &self.io {
+        for (index, (_, io)) in self.io.iter().enumerate() {
             match io.read_file(path).await {
-                Ok(data) => return Ok(data),
+                Ok(data) => {
+                    self.served_by
+                        .lock()
+                        .unwrap()
+                        .insert(path.to_string(), index);
+                    return Ok(data);
+                }
                 Err(err) => errors.push(err),
             }
         }
@@ -294,3 +569,70 @@ impl AssetIo for LayeredAssetIo {
         ))
     }
 }
+
+impl OverlayVisitable for LayeredAssetIo {
+    fn visit_overlay(&self, collector: &mut OverlayCollector) {
+        collector.overlay(
+            "Asset Layers",
+            |ctx, _top_left| {
+                egui::Window::new("Asset Layers")
+                    .resizable(true)
+                    .default_width(400.0)
+                    .show(ctx, |ui| {
+                        ui.label("Layers (searched top to bottom):");
+                        for label in self.layer_labels() {
+                            ui.monospace(label);
+                        }
+                        ui.separator();
+
+                        let served_by = self.served_by();
+                        ui.label(format!("Assets read: {}", served_by.len()));
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            egui::Grid::new("asset_layers_grid")
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    for (path, layer) in served_by {
+                                        ui.monospace(path);
+                                        ui.monospace(layer);
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                    });
+            },
+            false,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        assert_eq!(
+            normalize_asset_path("/picture/bg_001.pic"),
+            "/picture/bg_001.pic"
+        );
+        assert_eq!(
+            normalize_asset_path("Picture/BG_001.pic"),
+            "/picture/bg_001.pic"
+        );
+        assert_eq!(
+            normalize_asset_path("./picture//bg_001.pic"),
+            "/picture/bg_001.pic"
+        );
+        assert_eq!(
+            normalize_asset_path("/./picture\\BG_001.PIC"),
+            "/picture/bg_001.pic"
+        );
+    }
+
+    #[test]
+    fn changed() {
+        let changed = vec!["/Picture/BG_001.pic".to_string()];
+        assert!(is_asset_changed(&changed, "./picture/bg_001.pic"));
+        assert!(!is_asset_changed(&changed, "/picture/bg_002.pic"));
+    }
+}
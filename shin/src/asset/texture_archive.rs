@@ -16,6 +16,9 @@ pub trait TextureArchiveBuilder {
 
 pub trait TextureArchive: Sync + Send + 'static {
     type Builder: TextureArchiveBuilder<Output = Self>;
+
+    /// Total size of the textures, in bytes
+    fn size_in_bytes(&self) -> usize;
 }
 
 impl<T: TextureArchive> Asset for T {
@@ -33,4 +36,8 @@ impl<T: TextureArchive> Asset for T {
 
         Ok(builder.build())
     }
+
+    fn size_in_bytes(&self) -> usize {
+        TextureArchive::size_in_bytes(self)
+    }
 }
@@ -0,0 +1,122 @@
+//! Watching the loose asset files for changes, to reload them without restarting the engine.
+//!
+//! The directories are polled for modification times instead of using the OS notification APIs:
+//! the loose `data` directories (see [locate_assets](super::locate_assets)) are small, so a rescan every now and then is cheap.
+
+use super::normalize_asset_path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct AssetWatcher {
+    changes: Receiver<String>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AssetWatcher {
+    /// Start watching the directories. The changes are reported as [normalized](super::normalize_asset_path) asset paths (relative to the root).
+    pub fn new(roots: Vec<PathBuf>) -> Self {
+        let (sender, changes) = channel();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = std::thread::Builder::new()
+            .name("asset watcher".to_string())
+            .spawn({
+                let stop = stop.clone();
+                move || watch(roots, sender, stop)
+            })
+            .expect("Failed to spawn the asset watcher thread");
+
+        Self {
+            changes,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Get the paths of the assets that were changed since the last call
+    pub fn poll_changes(&self) -> Vec<String> {
+        let mut result = self.changes.try_iter().collect::<Vec<_>>();
+        result.sort();
+        result.dedup();
+        result
+    }
+}
+
+impl Drop for AssetWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+fn watch(roots: Vec<PathBuf>, sender: Sender<String>, stop: Arc<AtomicBool>) {
+    let mut modification_times = roots
+        .iter()
+        .map(|root| {
+            let mut times = HashMap::new();
+            scan(root, root, &mut times);
+            debug!("Watching {} files in {}", times.len(), root.display());
+            times
+        })
+        .collect::<Vec<_>>();
+
+    while !stop.load(Ordering::SeqCst) {
+        std::thread::sleep(POLL_INTERVAL);
+
+        for (root, old_times) in roots.iter().zip(modification_times.iter_mut()) {
+            let mut new_times = HashMap::new();
+            scan(root, root, &mut new_times);
+
+            for (path, time) in new_times.iter() {
+                if old_times.get(path) != Some(time) {
+                    debug!("Asset changed: {}", path);
+                    if sender.send(normalize_asset_path(path)).is_err() {
+                        // the watcher was dropped
+                        return;
+                    }
+                }
+            }
+
+            *old_times = new_times;
+        }
+    }
+}
+
+fn scan(root: &Path, dir: &Path, times: &mut HashMap<String, SystemTime>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Could not scan {}: {}", dir.display(), e);
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+
+        if metadata.is_dir() {
+            scan(root, &path, times);
+        } else if let (Ok(relative), Ok(modified)) = (path.strip_prefix(root), metadata.modified())
+        {
+            let asset_path = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .fold(String::new(), |acc, c| acc + "/" + c.as_ref());
+            times.insert(asset_path, modified);
+        }
+    }
+}
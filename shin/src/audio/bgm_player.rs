@@ -1,10 +1,10 @@
-use kira::track::{TrackBuilder, TrackHandle, TrackId, TrackRoutes};
-use shin_core::time::Tween;
-use shin_core::vm::command::types::{Pan, Volume};
+use kira::track::{TrackBuilder, TrackHandle, TrackRoutes};
+use shin_core::time::{Ticks, Tween};
+use shin_core::vm::command::types::{AudioWaitStatus, Pan, Volume};
 use std::sync::Arc;
 use tracing::warn;
 
-use shin_audio::{AudioData, AudioFile, AudioHandle, AudioManager, AudioSettings};
+use shin_audio::{AudioBus, AudioData, AudioFile, AudioHandle, AudioManager, AudioSettings};
 
 pub struct BgmPlayer {
     audio_manager: Arc<AudioManager>,
@@ -15,10 +15,11 @@ pub struct BgmPlayer {
 
 impl BgmPlayer {
     pub fn new(audio_manager: Arc<AudioManager>) -> Self {
+        let bus_track = audio_manager.bus_track(AudioBus::Bgm);
         let mut manager = audio_manager.kira_manager().lock().unwrap();
 
         let bgm_track = manager
-            .add_sub_track(TrackBuilder::new().routes(TrackRoutes::parent(TrackId::Main)))
+            .add_sub_track(TrackBuilder::new().routes(TrackRoutes::parent(bus_track)))
             .expect("Failed to create bgm track");
 
         drop(manager);
@@ -39,12 +40,14 @@ impl BgmPlayer {
         fade_in: Tween,
     ) {
         let loop_start = repeat.then_some(bgm.info().loop_start);
+        let loop_end = repeat.then_some(bgm.info().loop_end);
         let kira_data = AudioData::from_audio_file(
             bgm,
             AudioSettings {
                 track: self.bgm_track.id(),
                 fade_in,
                 loop_start,
+                loop_end,
                 volume,
                 pan: Pan::default(),
             },
@@ -74,6 +77,19 @@ impl BgmPlayer {
             warn!("Tried to stop BGM, but no BGM is currently playing");
         }
     }
+
+    pub fn get_wait_status(&self) -> AudioWaitStatus {
+        if let Some(handle) = self.current_bgm.as_ref() {
+            handle.get_wait_status()
+        } else {
+            AudioWaitStatus::STOPPED
+        }
+    }
+
+    /// Returns the playback position of the current BGM, `None` if no BGM is playing
+    pub fn position(&self) -> Option<Ticks> {
+        self.current_bgm.as_ref().map(|handle| handle.position())
+    }
 }
 
 // TODO: make it renderable and updatable, as it can display they track name when the BGM starts
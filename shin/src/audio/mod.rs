@@ -1,5 +1,29 @@
 mod bgm_player;
 mod se_player;
+mod voice_player;
 
 pub use bgm_player::BgmPlayer;
 pub use se_player::{SePlayer, SE_SLOT_COUNT};
+pub use voice_player::VoicePlayer;
+
+use anyhow::{Context, Result};
+use shin_audio::MixerSettings;
+use shin_core::format::save::Savedata;
+use shin_core::game::GameProfile;
+use std::path::Path;
+
+/// Reads the mixer settings from a game save file, using the defaults if there is none
+pub fn load_mixer_settings(
+    profile: &GameProfile,
+    savedata_path: Option<&Path>,
+) -> Result<MixerSettings> {
+    let Some(path) = savedata_path else {
+        return Ok(MixerSettings::default());
+    };
+
+    let data = std::fs::read(path).context("Reading the save file")?;
+    let savedata =
+        Savedata::decode_with_key(&data, profile.save_key()?).context("Decoding the save file")?;
+
+    Ok(MixerSettings::from(&savedata.settings))
+}
@@ -1,5 +1,5 @@
 use kira::track::{TrackBuilder, TrackHandle, TrackId, TrackRoutes};
-use shin_audio::{AudioData, AudioFile, AudioHandle, AudioManager, AudioSettings};
+use shin_audio::{AudioBus, AudioData, AudioFile, AudioHandle, AudioManager, AudioSettings};
 use shin_core::time::Tween;
 use shin_core::vm::command::types::{AudioWaitStatus, Pan, Volume};
 use std::sync::Arc;
@@ -11,17 +11,25 @@ pub struct SePlayer {
     audio_manager: Arc<AudioManager>,
     se_tracks: [TrackHandle; SE_SLOT_COUNT],
     se_slots: [Option<AudioHandle>; SE_SLOT_COUNT],
+    /// Track for the SEs that are played without a slot
+    once_track: TrackHandle,
+    /// Handles of the SEs that are played without a slot, kept until they finish playing
+    once_handles: Vec<AudioHandle>,
 }
 
 impl SePlayer {
     pub fn new(audio_manager: Arc<AudioManager>) -> Self {
+        let bus_track = audio_manager.bus_track(AudioBus::Se);
         let mut manager = audio_manager.kira_manager().lock().unwrap();
 
         let se_tracks = [(); SE_SLOT_COUNT].map(|_| {
             manager
-                .add_sub_track(TrackBuilder::new().routes(TrackRoutes::parent(TrackId::Main)))
+                .add_sub_track(TrackBuilder::new().routes(TrackRoutes::parent(bus_track)))
                 .expect("Failed to create se track")
         });
+        let once_track = manager
+            .add_sub_track(TrackBuilder::new().routes(TrackRoutes::parent(bus_track)))
+            .expect("Failed to create se track");
 
         drop(manager);
 
@@ -29,6 +37,8 @@ impl SePlayer {
             audio_manager,
             se_tracks,
             se_slots: [(); SE_SLOT_COUNT].map(|_| None),
+            once_track,
+            once_handles: Vec::new(),
         }
     }
 
@@ -44,12 +54,14 @@ impl SePlayer {
         let slot = slot as usize;
 
         let loop_start = repeat.then_some(se.info().loop_start);
+        let loop_end = repeat.then_some(se.info().loop_end);
         let kira_data = AudioData::from_audio_file(
             se,
             AudioSettings {
                 track: self.se_tracks[slot].id(),
                 fade_in,
                 loop_start,
+                loop_end,
                 volume,
                 pan,
             },
@@ -64,6 +76,36 @@ impl SePlayer {
         self.se_slots[slot] = Some(handle);
     }
 
+    /// Plays a SE without a slot. It can't be stopped or waited on, it just plays until the end.
+    pub fn play_once(&mut self, se: Arc<AudioFile>, volume: Volume, pan: Pan) {
+        self.play_unslotted(self.once_track.id(), se, volume, pan);
+    }
+
+    /// Plays a system sound (menu cursor, confirm, etc). Like [SePlayer::play_once], but uses the system sound volume.
+    pub fn play_system(&mut self, se: Arc<AudioFile>) {
+        let track = self.audio_manager.bus_track(AudioBus::System);
+        self.play_unslotted(track, se, Volume::default(), Pan::default());
+    }
+
+    fn play_unslotted(&mut self, track: TrackId, se: Arc<AudioFile>, volume: Volume, pan: Pan) {
+        let kira_data = AudioData::from_audio_file(
+            se,
+            AudioSettings {
+                track,
+                fade_in: Tween::IMMEDIATE,
+                loop_start: None,
+                loop_end: None,
+                volume,
+                pan,
+            },
+        );
+
+        // drop the handles of the SEs that have already finished
+        self.once_handles
+            .retain(|handle| !handle.get_wait_status().contains(AudioWaitStatus::STOPPED));
+        self.once_handles.push(self.audio_manager.play(kira_data));
+    }
+
     pub fn set_volume(&mut self, slot: i32, volume: Volume, tween: Tween) {
         let slot = slot as usize;
 
@@ -0,0 +1,44 @@
+use shin_core::time::Tween;
+use shin_core::vm::command::types::{Pan, Volume};
+use std::sync::Arc;
+
+use shin_audio::{AudioBus, AudioData, AudioFile, AudioHandle, AudioManager, AudioSettings};
+
+pub struct VoicePlayer {
+    audio_manager: Arc<AudioManager>,
+    current_voice: Option<AudioHandle>,
+}
+
+impl VoicePlayer {
+    pub fn new(audio_manager: Arc<AudioManager>) -> Self {
+        Self {
+            audio_manager,
+            current_voice: None,
+        }
+    }
+
+    /// Plays a voice, stopping the one that is playing
+    ///
+    /// The voice is played on the [AudioBus::Voice] track directly, so that the BGM is ducked while it plays (if voice focus is enabled)
+    pub fn play(&mut self, voice: Arc<AudioFile>, volume: Volume) {
+        let kira_data = AudioData::from_audio_file(
+            voice,
+            AudioSettings {
+                track: self.audio_manager.bus_track(AudioBus::Voice),
+                fade_in: Tween::IMMEDIATE,
+                loop_start: None,
+                loop_end: None,
+                volume,
+                pan: Pan::default(),
+            },
+        );
+
+        let handle = self.audio_manager.play(kira_data);
+
+        if let Some(mut old_handle) = self.current_voice.take() {
+            old_handle.stop(Tween::MS_15).unwrap();
+        }
+
+        self.current_voice = Some(handle);
+    }
+}
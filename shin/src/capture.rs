@@ -0,0 +1,209 @@
+//! Captures the rendered frames for screenshots and video recordings.
+//!
+//! Screenshots are saved as PNG files. Videos are encoded by piping the raw frames to an `ffmpeg` process,
+//! the mixed audio is collected separately and muxed in when the recording is finished.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use image::imageops::FilterType;
+use image::RgbaImage;
+use kira::dsp::Frame;
+use tracing::{error, info, warn};
+
+/// Encodes the captured frames & audio into a video file using ffmpeg
+pub struct VideoRecorder {
+    output_path: PathBuf,
+    video_path: PathBuf,
+    audio_path: PathBuf,
+    size: (u32, u32),
+    sample_rate: u32,
+    ffmpeg: Child,
+    ffmpeg_stdin: ChildStdin,
+    audio: BufWriter<File>,
+}
+
+impl VideoRecorder {
+    pub fn new(output_path: &Path, size: (u32, u32), fps: u32, sample_rate: u32) -> Result<Self> {
+        let video_path = output_path.with_extension("video.mkv");
+        let audio_path = output_path.with_extension("audio.f32");
+
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{}x{}", size.0, size.1)])
+            .args(["-r", &fps.to_string()])
+            .args(["-i", "-"])
+            .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+            .arg(&video_path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .context("Spawning ffmpeg, is it installed?")?;
+        let ffmpeg_stdin = ffmpeg.stdin.take().unwrap();
+
+        let audio =
+            BufWriter::new(File::create(&audio_path).context("Creating the temporary audio file")?);
+
+        info!(
+            "Recording a {}x{} video to {:?}",
+            size.0, size.1, output_path
+        );
+
+        Ok(Self {
+            output_path: output_path.to_owned(),
+            video_path,
+            audio_path,
+            size,
+            sample_rate,
+            ffmpeg,
+            ffmpeg_stdin,
+            audio,
+        })
+    }
+
+    pub fn push_frame(&mut self, image: &RgbaImage) -> Result<()> {
+        if image.dimensions() != self.size {
+            // the window was resized, but the video size can't change
+            let image =
+                image::imageops::resize(image, self.size.0, self.size.1, FilterType::Triangle);
+            self.ffmpeg_stdin.write_all(image.as_raw())
+        } else {
+            self.ffmpeg_stdin.write_all(image.as_raw())
+        }
+        .context("Writing the frame to ffmpeg")
+    }
+
+    pub fn push_audio(&mut self, frames: &[Frame]) -> Result<()> {
+        for frame in frames {
+            self.audio.write_all(&frame.left.to_le_bytes())?;
+            self.audio.write_all(&frame.right.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the encoder to finish and mux in the recorded audio
+    pub fn finish(self) -> Result<()> {
+        let Self {
+            output_path,
+            video_path,
+            audio_path,
+            sample_rate,
+            mut ffmpeg,
+            ffmpeg_stdin,
+            audio,
+            ..
+        } = self;
+
+        // closing the stdin signals the end of the stream
+        drop(ffmpeg_stdin);
+        let status = ffmpeg.wait().context("Waiting for ffmpeg")?;
+        if !status.success() {
+            bail!("ffmpeg failed to encode the video: {}", status);
+        }
+        audio
+            .into_inner()
+            .context("Flushing the temporary audio file")?;
+
+        let status = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error"])
+            .arg("-i")
+            .arg(&video_path)
+            .args(["-f", "f32le", "-ac", "2"])
+            .args(["-ar", &sample_rate.to_string()])
+            .arg("-i")
+            .arg(&audio_path)
+            .args(["-c:v", "copy", "-c:a", "aac", "-shortest"])
+            .arg(&output_path)
+            .status()
+            .context("Spawning ffmpeg")?;
+        if !status.success() {
+            bail!("ffmpeg failed to mux the audio: {}", status);
+        }
+
+        std::fs::remove_file(&video_path).context("Removing the temporary video file")?;
+        std::fs::remove_file(&audio_path).context("Removing the temporary audio file")?;
+
+        info!("Finished recording the video to {:?}", output_path);
+
+        Ok(())
+    }
+}
+
+pub struct FrameCapture {
+    screenshot_dir: PathBuf,
+    screenshot_requested: bool,
+    recorder: Option<VideoRecorder>,
+}
+
+impl FrameCapture {
+    pub fn new(screenshot_dir: PathBuf, recorder: Option<VideoRecorder>) -> Self {
+        Self {
+            screenshot_dir,
+            screenshot_requested: false,
+            recorder,
+        }
+    }
+
+    /// Save the next rendered frame as a screenshot
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    /// Whether the next frame should be copied to the CPU
+    pub fn wants_frame(&self) -> bool {
+        self.screenshot_requested || self.recorder.is_some()
+    }
+
+    pub fn push_audio(&mut self, frames: &[Frame]) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.push_audio(frames) {
+                error!("Failed to record audio, stopping the recording: {:?}", e);
+                self.recorder = None;
+            }
+        }
+    }
+
+    pub fn push_frame(&mut self, image: RgbaImage) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.push_frame(&image) {
+                error!("Failed to record a frame, stopping the recording: {:?}", e);
+                self.recorder = None;
+            }
+        }
+
+        if std::mem::take(&mut self.screenshot_requested) {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis();
+            let path = self
+                .screenshot_dir
+                .join(format!("screenshot_{}.png", timestamp));
+            // don't stall the rendering while encoding the PNG
+            std::thread::spawn(move || {
+                let result = path
+                    .parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .context("Creating the screenshot directory")
+                    .and_then(|_| image.save(&path).context("Saving the screenshot"));
+                match result {
+                    Ok(()) => info!("Saved a screenshot to {:?}", path),
+                    Err(e) => warn!("Failed to save a screenshot: {:?}", e),
+                }
+            });
+        }
+    }
+
+    /// Finish the recording, if any
+    pub fn finish(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            if let Err(e) = recorder.finish() {
+                error!("Failed to finish the recording: {:?}", e);
+            }
+        }
+    }
+}
@@ -1,5 +1,7 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use clap_num::maybe_hex;
+use glam::{vec4, Vec4};
+use shin_core::game::GameId;
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -9,10 +11,149 @@ pub struct Cli {
     /// Search this directory for assets
     ///
     /// The directory must contain either a directory named "data" or a file named "data.rom".
+    /// Without them, an unencrypted Switch dump (NSP, NCA or RomFS image) in the directory is used, this option can also point to the dump itself.
     /// Consult the README for more information.
     #[clap(short, long)]
     pub assets_dir: Option<PathBuf>,
+    /// Load the mods from this directory instead of the "mods" directory in the assets directory
+    ///
+    /// Each mod is either a directory with a `mod.toml` manifest or a ROM file with a manifest next to it.
+    /// Consult the README for more information.
+    #[clap(long, conflicts_with = "no_mods")]
+    pub mods_dir: Option<PathBuf>,
+    /// Do not load any mods, use the game data as is
+    #[clap(long)]
+    pub no_mods: bool,
+    /// The game the assets belong to, detected automatically if not specified
+    ///
+    /// One of: umineko, higurashi, kaleidoscope, konosuba, sugarstyle
+    #[clap(long)]
+    pub game: Option<GameId>,
     /// Automatically fast-forward the scenario to the specified address (useful for debugging)
     #[clap(long, value_parser=maybe_hex::<u32>)]
     pub fast_forward_to: Option<u32>,
+    /// Write the executed commands & VM state snapshots to this file (useful for regression testing)
+    #[clap(long)]
+    pub trace: Option<PathBuf>,
+    /// Watch the loose asset files (the "data" directory) and reload them when they change
+    ///
+    /// Pictures, bustups and fonts are updated in place. The scenario is reloaded too, if the running code can be found in the new version.
+    #[clap(long)]
+    pub hot_reload: bool,
+    /// How much memory the recently used assets can take, in MiB
+    ///
+    /// The assets are kept in memory after their last use, so that they do not have to be loaded again when they are needed soon after.
+    #[clap(long, default_value = "512")]
+    pub asset_cache_budget: usize,
+    /// How much memory the assets loaded ahead of time (by looking ahead in the scenario) can take, in MiB
+    ///
+    /// Set to 0 to disable prefetching.
+    #[clap(long, default_value = "256")]
+    pub prefetch_budget: usize,
+
+    /// Take the audio settings (volumes, voice focus) from this game save file
+    #[clap(long)]
+    pub savedata: Option<PathBuf>,
+
+    /// Where to output the audio
+    ///
+    /// `auto` uses the audio device if there is one and plays silently otherwise.
+    #[clap(long, value_enum, default_value_t = AudioOutput::Auto)]
+    pub audio: AudioOutput,
+    /// Mix all the audio into this WAV file instead of playing it
+    ///
+    /// In headless mode the audio is rendered in lockstep with the frames, which makes it usable for audio regression tests.
+    #[clap(long, conflicts_with_all = ["record", "audio"])]
+    pub audio_dump: Option<PathBuf>,
+
+    /// How the game screen is scaled to the window
+    ///
+    /// Can also be changed at runtime in the "Presentation" overlay.
+    #[clap(long, value_enum, default_value_t = Scaling::Fit)]
+    pub scaling: Scaling,
+    /// The resolution the game screen is rendered at
+    #[clap(long, value_enum, default_value_t = Resolution::Native)]
+    pub render_resolution: Resolution,
+    /// Color of the bars around the game screen, as a hex RGB value (like `000000`)
+    #[clap(long, default_value = "000000", value_parser = parse_color)]
+    pub bar_color: Vec4,
+
+    /// Directory to save screenshots (taken with F12) to
+    #[clap(long, default_value = "screenshots")]
+    pub screenshot_dir: PathBuf,
+    /// Record a video of the playback to this file (requires ffmpeg)
+    ///
+    /// While recording, the time is advanced in fixed steps, independent of the real time.
+    /// The audio is captured into the video instead of being played.
+    #[clap(long)]
+    pub record: Option<PathBuf>,
+    /// Frame rate of the recorded video
+    #[clap(long, default_value = "60", value_parser = clap::value_parser!(u32).range(1..), requires = "record")]
+    pub record_fps: u32,
+
+    /// Run without a window and audio output, advancing the time in fixed steps
+    ///
+    /// This makes the run deterministic, so that the trace (see `--trace`) can be compared against a golden one.
+    /// The layers are still rendered with wgpu, falling back to a software adapter if there is no GPU.
+    #[clap(long)]
+    pub headless: bool,
+    /// Number of frames to run in headless mode
+    #[clap(long, default_value = "36000", requires = "headless")]
+    pub headless_frames: u64,
+    /// Frame rate of the fixed time step in headless mode
+    #[clap(long, default_value = "60", value_parser = clap::value_parser!(u32).range(1..), requires = "headless")]
+    pub headless_fps: u32,
+    /// Stop the headless run when the VM reaches the specified address
+    #[clap(long, value_parser=maybe_hex::<u32>, requires = "headless")]
+    pub stop_at: Option<u32>,
+    /// Feed the input from a script in headless mode
+    ///
+    /// Each line of the script has the form `<frame> <action> [<duration in frames>]`,
+    /// where action is either `advance` or `fast-forward`. Lines starting with `#` are ignored.
+    #[clap(long, requires = "headless")]
+    pub input_script: Option<PathBuf>,
+    /// Press the "advance" button every other frame in headless mode
+    #[clap(long, requires = "headless")]
+    pub auto_advance: bool,
+    /// Compare the recorded trace with this one after the headless run, failing if they differ
+    #[clap(long, requires = "trace")]
+    pub golden_trace: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AudioOutput {
+    /// Use the audio device if available, play silently otherwise
+    Auto,
+    /// Use the audio device, failing if it's not available
+    Cpal,
+    /// Play silently
+    Null,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Scaling {
+    /// Scale to fit the window keeping the aspect ratio
+    Fit,
+    /// Scale by a whole number, so that the pixels stay square
+    Integer,
+    /// Fill the whole window, ignoring the aspect ratio
+    Stretch,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// Render at the size the screen takes in the window
+    Native,
+    /// Render at the resolution of the game (1920x1080) and scale the result
+    Virtual,
+}
+
+fn parse_color(s: &str) -> Result<Vec4, String> {
+    let hex = s.trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err("expected 6 hex digits".to_string());
+    }
+    let value = u32::from_str_radix(hex, 16).map_err(|e| e.to_string())?;
+    let channel = |shift: u32| ((value >> shift) & 0xff) as f32 / 255.0;
+    Ok(vec4(channel(16), channel(8), channel(0), 1.0))
 }
@@ -0,0 +1,397 @@
+//! Runs the game without a window or audio output.
+//!
+//! The time is advanced in fixed steps and the input is either scripted or generated,
+//! so two runs on the same assets produce the same command trace.
+//! This allows to check for regressions by comparing the trace against a golden one.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use shin_audio::{AudioBackend, AudioManager, WavSink, MANUAL_BACKEND_SAMPLE_RATE};
+use shin_core::format::scenario::instructions::CodeAddress;
+use shin_core::vm::breakpoint::BreakpointObserver;
+use shin_render::{
+    BindGroupLayouts, GpuCommonResources, Pipelines, SRGB_TEXTURE_FORMAT, VIRTUAL_HEIGHT,
+    VIRTUAL_WIDTH,
+};
+use shin_tasks::create_task_pools;
+use tracing::{debug, info};
+
+use crate::adv::assets::{select_game_profile, AdvAssets};
+use crate::adv::{Adv, TraceRecorder};
+use crate::asset::{apply_mods, locate_assets, AnyAssetServer, AssetServer};
+use crate::audio::load_mixer_settings;
+use crate::cli::Cli;
+use crate::input::inputs::KeyCode;
+use crate::input::RawInputState;
+use crate::time::Time;
+use crate::update::{Updatable, UpdateContext};
+use crate::window::request_device;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ScriptedAction {
+    Advance,
+    FastForward,
+}
+
+impl ScriptedAction {
+    fn key_code(self) -> KeyCode {
+        match self {
+            ScriptedAction::Advance => KeyCode::Enter,
+            ScriptedAction::FastForward => KeyCode::LControl,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct ScriptedInput {
+    frame: u64,
+    duration: u64,
+    action: ScriptedAction,
+}
+
+/// A list of inputs to be fed to the game at specific frames
+struct InputScript {
+    inputs: Vec<ScriptedInput>,
+    auto_advance: bool,
+}
+
+impl InputScript {
+    fn load(path: Option<&Path>, auto_advance: bool) -> Result<Self> {
+        let inputs = match path {
+            Some(path) => {
+                let file = File::open(path).context("Opening the input script")?;
+                BufReader::new(file)
+                    .lines()
+                    .enumerate()
+                    .map(|(i, line)| {
+                        let line = line.context("Reading the input script")?;
+                        Self::parse_line(&line)
+                            .with_context(|| format!("Parsing line {} of the input script", i + 1))
+                    })
+                    .filter_map(Result::transpose)
+                    .collect::<Result<Vec<_>>>()?
+            }
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            inputs,
+            auto_advance,
+        })
+    }
+
+    fn parse_line(line: &str) -> Result<Option<ScriptedInput>> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+
+        let mut parts = line.split_whitespace();
+        let frame = parts
+            .next()
+            .unwrap()
+            .parse()
+            .context("Parsing the frame number")?;
+        let action = match parts.next() {
+            Some("advance") => ScriptedAction::Advance,
+            Some("fast-forward") => ScriptedAction::FastForward,
+            Some(action) => bail!("Unknown action: {}", action),
+            None => bail!("Missing action"),
+        };
+        let duration = parts
+            .next()
+            .map(|d| d.parse().context("Parsing the duration"))
+            .transpose()?
+            .unwrap_or(1);
+        if let Some(extra) = parts.next() {
+            bail!("Unexpected token: {}", extra);
+        }
+
+        Ok(Some(ScriptedInput {
+            frame,
+            duration,
+            action,
+        }))
+    }
+
+    fn input_for_frame(&self, frame: u64) -> RawInputState {
+        let mut input = RawInputState::new();
+        for scripted in &self.inputs {
+            if (scripted.frame..scripted.frame + scripted.duration).contains(&frame) {
+                input.keyboard.insert(scripted.action.key_code());
+            }
+        }
+        // release the button every other frame, so that it registers as a new press
+        if self.auto_advance && frame % 2 == 0 {
+            input.keyboard.insert(ScriptedAction::Advance.key_code());
+        }
+        input
+    }
+}
+
+pub(crate) async fn create_resources() -> Result<GpuCommonResources> {
+    let backends = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all());
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    let adapter =
+        match wgpu::util::initialize_adapter_from_env_or_default(&instance, backends, None).await {
+            Some(adapter) => adapter,
+            // a machine running the regression tests might not have a GPU
+            None => instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    force_fallback_adapter: true,
+                    ..Default::default()
+                })
+                .await
+                .context("Failed to find appropriate wgpu adapter")?,
+        };
+
+    info!("Selected an adapter {:?}", adapter.get_info(),);
+
+    let (device, queue) = request_device(&adapter).await?;
+
+    let bind_group_layouts = BindGroupLayouts::new(&device);
+    // there's no surface, so use the same format as the render targets
+    let pipelines = Pipelines::new(&device, &bind_group_layouts, SRGB_TEXTURE_FORMAT);
+
+    Ok(GpuCommonResources {
+        device,
+        queue,
+        render_buffer_size: RwLock::new((VIRTUAL_WIDTH as u32, VIRTUAL_HEIGHT as u32)),
+        bind_group_layouts,
+        pipelines,
+    })
+}
+
+/// Compare the traces line by line, reporting the first difference
+fn compare_traces(actual: &str, golden: &str) -> Result<()> {
+    let mut actual_lines = actual.lines();
+    let mut golden_lines = golden.lines();
+    for line_number in 1.. {
+        match (actual_lines.next(), golden_lines.next()) {
+            (None, None) => return Ok(()),
+            (Some(a), Some(g)) if a == g => continue,
+            (a, g) => {
+                return Err(anyhow!(
+                    "Traces differ at line {}:\n  golden: {}\n  actual: {}",
+                    line_number,
+                    g.unwrap_or("<end of trace>"),
+                    a.unwrap_or("<end of trace>"),
+                ))
+            }
+        }
+    }
+    unreachable!()
+}
+
+pub async fn run(cli: Cli) -> Result<()> {
+    tracing_subscriber::fmt::init();
+
+    create_task_pools();
+
+    let resources = Arc::new(create_resources().await?);
+    let audio_manager = Arc::new(
+        AudioManager::with_backend(AudioBackend::Manual)
+            .context("Failed to create the audio manager")?,
+    );
+
+    let located_assets = locate_assets(cli.assets_dir.as_deref()).context(
+        "Failed to locate assets. Consult the README for instructions on how to set up the game.",
+    )?;
+    // the game is detected from the game data alone, the mods can target a specific game
+    let base_asset_server = AssetServer::new(located_assets.io);
+    let profile = select_game_profile(&base_asset_server, cli.game).await?;
+    let mut asset_io = base_asset_server.into_io();
+    if !cli.no_mods {
+        let mods_dir = cli
+            .mods_dir
+            .clone()
+            .unwrap_or_else(|| located_assets.dir.join("mods"));
+        asset_io =
+            apply_mods(asset_io, &mods_dir, profile.id).context("Failed to load the mods")?;
+    }
+    debug!("Asset IO: {:#?}", asset_io);
+    let asset_server = Arc::new(AnyAssetServer::new(asset_io.into()));
+    asset_server.set_cache_budget(cli.asset_cache_budget * 1024 * 1024);
+    audio_manager.set_mixer_settings(
+        load_mixer_settings(profile, cli.savedata.as_deref())
+            .context("Failed to load the audio settings")?,
+    );
+
+    let adv_assets = AdvAssets::load(&asset_server, profile)
+        .await
+        .context("Loading assets failed")?;
+
+    let mut adv = Adv::new(&resources, audio_manager.clone(), adv_assets, 0, 42);
+    // the frame at which a layer is loaded ends up in the trace
+    adv.load_synchronously();
+
+    if let Some(addr) = cli.fast_forward_to {
+        debug!("Fast forwarding to {}", addr);
+        adv.fast_forward_to(CodeAddress(addr));
+    }
+    if cli.prefetch_budget > 0 {
+        adv.prefetch(cli.prefetch_budget * 1024 * 1024);
+    }
+    if let Some(trace_path) = &cli.trace {
+        let file = File::create(trace_path).context("Creating the trace file")?;
+        adv.record_trace(TraceRecorder::new(Box::new(BufWriter::new(file))));
+    }
+    let mut stop_at: Option<BreakpointObserver> = cli
+        .stop_at
+        .map(|addr| adv.scripter_mut().add_breakpoint(CodeAddress(addr)).into());
+
+    let input_script = InputScript::load(cli.input_script.as_deref(), cli.auto_advance)?;
+    let mut audio_dump = cli
+        .audio_dump
+        .as_deref()
+        .map(|path| WavSink::create(path, MANUAL_BACKEND_SAMPLE_RATE))
+        .transpose()
+        .context("Failed to create the audio dump")?;
+
+    let frame_duration = Duration::from_secs_f64(1.0 / cli.headless_fps as f64);
+    // the absolute value does not matter, only the differences between the updates are used
+    let startup = Instant::now();
+    let mut time = Time::new(startup);
+
+    let mut frame = 0;
+    while frame < cli.headless_frames {
+        time.update_with_instant(startup + frame_duration * frame as u32);
+        let input = input_script.input_for_frame(frame);
+
+        adv.update(&UpdateContext {
+            time: &time,
+            gpu_resources: &resources,
+            asset_server: &asset_server,
+            raw_input_state: &input,
+        });
+        let audio = audio_manager.advance(frame_duration);
+        if let Some(audio_dump) = audio_dump.as_mut() {
+            audio_dump.write(&audio).context("Writing the audio dump")?;
+        }
+
+        frame += 1;
+
+        if stop_at.as_mut().map_or(false, |bp| bp.update()) {
+            info!("Reached the stop address at frame {}", frame);
+            break;
+        }
+    }
+
+    info!("Headless run finished after {} frames", frame);
+
+    adv.finish_trace()?;
+    if let Some(audio_dump) = audio_dump {
+        audio_dump.finalize()?;
+    }
+
+    if let (Some(trace), Some(golden)) = (&cli.trace, &cli.golden_trace) {
+        let trace = std::fs::read_to_string(trace).context("Reading the recorded trace")?;
+        let golden = std::fs::read_to_string(golden).context("Reading the golden trace")?;
+        compare_traces(&trace, &golden)?;
+        info!("The trace matches the golden one");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_line() {
+        let parse = |line| InputScript::parse_line(line).unwrap().unwrap();
+
+        let input = parse("120 advance");
+        assert_eq!(input.frame, 120);
+        assert_eq!(input.action, ScriptedAction::Advance);
+        assert_eq!(input.duration, 1);
+
+        let input = parse("  300\tfast-forward 45 ");
+        assert_eq!(input.frame, 300);
+        assert_eq!(input.action, ScriptedAction::FastForward);
+        assert_eq!(input.duration, 45);
+    }
+
+    #[test]
+    fn parse_line_skips_comments_and_blanks() {
+        assert!(InputScript::parse_line("").unwrap().is_none());
+        assert!(InputScript::parse_line("   ").unwrap().is_none());
+        assert!(InputScript::parse_line("# 10 advance").unwrap().is_none());
+    }
+
+    #[test]
+    fn parse_line_errors() {
+        for line in [
+            "advance",
+            "10",
+            "10 jump",
+            "10 advance x",
+            "10 advance 5 6",
+            "-1 advance",
+        ] {
+            assert!(
+                InputScript::parse_line(line).is_err(),
+                "{:?} should not parse",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn input_for_frame() {
+        let script = InputScript {
+            inputs: vec![InputScript::parse_line("10 fast-forward 3")
+                .unwrap()
+                .unwrap()],
+            auto_advance: false,
+        };
+        let pressed = |frame| {
+            script
+                .input_for_frame(frame)
+                .keyboard
+                .contains(&KeyCode::LControl)
+        };
+        assert!(!pressed(9));
+        assert!(pressed(10));
+        assert!(pressed(12));
+        assert!(!pressed(13));
+    }
+
+    #[test]
+    fn compare_traces() {
+        let trace = "0 SAVEINFO\n5 LAYERLOAD\n";
+        super::compare_traces(trace, trace).unwrap();
+
+        let error = super::compare_traces("0 SAVEINFO\n5 LAYERLOAD\n", "0 SAVEINFO\n6 LAYERLOAD\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("line 2"), "{}", error);
+        assert!(error.contains("golden: 6 LAYERLOAD"), "{}", error);
+        assert!(error.contains("actual: 5 LAYERLOAD"), "{}", error);
+
+        // a trace that stops early is a difference too
+        let error = super::compare_traces("0 SAVEINFO\n", "0 SAVEINFO\n5 LAYERLOAD\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("actual: <end of trace>"), "{}", error);
+        super::compare_traces("0 SAVEINFO\n5 LAYERLOAD\n", "0 SAVEINFO\n").unwrap_err();
+    }
+
+    #[test]
+    fn rejects_zero_fps() {
+        use clap::Parser;
+
+        let parse = |fps| Cli::try_parse_from(["shin", "--headless", "--headless-fps", fps]);
+        assert_eq!(parse("30").unwrap().headless_fps, 30);
+        assert!(parse("0").is_err());
+    }
+}
@@ -1,15 +1,19 @@
 use crate::asset::bustup::Bustup;
+use crate::asset::{is_asset_changed, AnyAssetServer};
 use crate::layer::{Layer, LayerProperties};
 use crate::update::{Updatable, UpdateContext};
 use glam::Mat4;
+use shin_render::software::{SoftwareRenderable, SoftwareTarget};
 use shin_render::GpuCommonResources;
 use shin_render::GpuImage;
 use shin_render::Renderable;
 use std::fmt::Debug;
 use std::sync::Arc;
+use tracing::warn;
 
 pub struct BustupLayer {
     bustup: Arc<Bustup>,
+    bustup_path: String,
     bustup_name: Option<String>,
     emotion: String,
 
@@ -20,6 +24,7 @@ impl BustupLayer {
     pub fn new(
         resources: &GpuCommonResources,
         bustup: Arc<Bustup>,
+        bustup_path: String,
         bustup_name: Option<String>,
         emotion: &str,
     ) -> Self {
@@ -28,11 +33,32 @@ impl BustupLayer {
 
         Self {
             bustup,
+            bustup_path,
             bustup_name,
             emotion: emotion.to_owned(),
             properties: LayerProperties::new(),
         }
     }
+
+    /// Load the bustup again if its file has changed
+    pub fn reload(
+        &mut self,
+        resources: &GpuCommonResources,
+        asset_server: &AnyAssetServer,
+        changed_paths: &[String],
+    ) {
+        if !is_asset_changed(changed_paths, &self.bustup_path) {
+            return;
+        }
+
+        match asset_server.load_sync::<Bustup, _>(&self.bustup_path) {
+            Ok(bustup) => {
+                bustup.base_gpu_image(resources);
+                self.bustup = bustup;
+            }
+            Err(e) => warn!("Reloading {}: {:?}", self.bustup_path, e),
+        }
+    }
 }
 
 impl Renderable for BustupLayer {
@@ -73,6 +99,21 @@ impl Renderable for BustupLayer {
     }
 }
 
+impl SoftwareRenderable for BustupLayer {
+    fn render_software(&self, target: &mut SoftwareTarget, transform: Mat4, projection: Mat4) {
+        let total_transform = projection * self.properties.compute_transform(transform);
+
+        let images = [
+            Some(self.bustup.base_image()),
+            self.bustup.face_image(&self.emotion),
+            self.bustup.mouth_image(&self.emotion, 0.0),
+        ];
+        for image in images.into_iter().flatten() {
+            target.draw_image(image.image(), image.origin(), total_transform);
+        }
+    }
+}
+
 impl Updatable for BustupLayer {
     fn update(&mut self, ctx: &UpdateContext) {
         self.properties.update(ctx);
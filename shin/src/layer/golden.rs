@@ -0,0 +1,257 @@
+//! Golden-image tests of the layers, rendered with the software renderer
+//!
+//! The scenes are built from the real layers and positioned through their [LayerProperties],
+//! so the tests cover the property -> transform & effect mapping along with the rendering.
+//! The golden images are in `tests/golden`, run the tests with `SHIN_BLESS=1` to overwrite them.
+//!
+//! The layers still need GPU resources to be created, a software adapter is enough for that.
+
+use super::{BustupLayer, Layer, LayerProperties, PictureLayer, ScreenLayer, TileLayer};
+use crate::asset::bustup::Bustup;
+use crate::asset::picture::Picture;
+use crate::asset::{AnyAssetIo, AnyAssetServer};
+use crate::input::RawInputState;
+use crate::time::Time;
+use crate::update::{Updatable, UpdateContext};
+use glam::{vec2, vec4, Mat4};
+use image::{Rgba, RgbaImage};
+use once_cell::sync::Lazy;
+use shin_core::vm::command::types::{LayerId, LayerProperty};
+use shin_render::software::{self, SoftwareRenderable, SoftwareTarget};
+use shin_render::{GpuCommonResources, LazyGpuImage};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A quarter of the virtual resolution, to keep the golden images small
+const SIZE: (u32, u32) = (480, 270);
+
+static RESOURCES: Lazy<Arc<GpuCommonResources>> = Lazy::new(|| {
+    Arc::new(
+        pollster::block_on(crate::headless::create_resources())
+            .expect("Creating the GPU resources"),
+    )
+});
+
+fn assert_golden(name: &str, actual: &RgbaImage) {
+    software::assert_golden(
+        &PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden"),
+        &std::env::temp_dir().join("shin-golden"),
+        name,
+        actual,
+    );
+}
+
+fn set_properties(properties: &mut LayerProperties, values: &[(LayerProperty, f32)]) {
+    for &(property, value) in values {
+        properties
+            .property_tweener_mut(property)
+            .fast_forward_to(value);
+    }
+}
+
+/// Runs a zero-length update, which applies the effects of the layer properties
+fn update(layer: &mut impl Updatable) {
+    let time = Time::new(Instant::now());
+    let asset_server = Arc::new(AnyAssetServer::new(AnyAssetIo::new_dir(
+        std::env::temp_dir(),
+    )));
+
+    layer.update(&UpdateContext {
+        time: &time,
+        gpu_resources: &RESOURCES,
+        asset_server: &asset_server,
+        raw_input_state: &RawInputState::new(),
+    });
+}
+
+fn render(layer: &impl SoftwareRenderable) -> RgbaImage {
+    let mut target = SoftwareTarget::new(SIZE);
+    target.clear(vec4(0.05, 0.05, 0.1, 1.0));
+    let projection = target.projection_matrix();
+
+    layer.render_software(&mut target, Mat4::IDENTITY, projection);
+
+    target.to_image()
+}
+
+/// A test image with a gradient, a grid and a semi-transparent & fully transparent part
+fn test_image(width: u32, height: u32, tint: [u8; 3]) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        let u = x as f32 / width as f32;
+        let v = y as f32 / height as f32;
+
+        if (x / 16 + y / 16) % 7 == 3 {
+            // holes
+            return Rgba([0, 0, 0, 0]);
+        }
+
+        let alpha = if v > 0.75 { 128 } else { 255 };
+        if x % 32 == 0 || y % 32 == 0 {
+            Rgba([255, 255, 255, alpha])
+        } else {
+            Rgba([
+                (u * tint[0] as f32) as u8,
+                (v * tint[1] as f32) as u8,
+                tint[2],
+                alpha,
+            ])
+        }
+    })
+}
+
+fn picture_layer(width: u32, height: u32) -> PictureLayer {
+    let picture = Picture::new(
+        test_image(width, height, [255, 255, 160]),
+        vec2(width as f32 / 2.0, height as f32 / 2.0),
+        Some("Test Picture"),
+    );
+
+    PictureLayer::new(
+        &RESOURCES,
+        Arc::new(picture),
+        "test.pic".to_string(),
+        Some("test".to_string()),
+    )
+}
+
+#[test]
+fn picture_transform() {
+    use LayerProperty::*;
+
+    let mut plain = picture_layer(300, 200);
+    set_properties(plain.properties_mut(), &[(TranslateX, -700.0)]);
+
+    let mut scaled_rotated = picture_layer(300, 200);
+    set_properties(
+        scaled_rotated.properties_mut(),
+        &[
+            (TranslateX, -100.0),
+            (TranslateY, 150.0),
+            // a twelfth of a turn
+            (Rotation, 1000.0 / 12.0),
+            (ScaleX, 1500.0),
+            (ScaleY, 750.0),
+        ],
+    );
+
+    // mirrored and rotated around a point outside of the picture
+    let mut mirrored = picture_layer(300, 200);
+    set_properties(
+        mirrored.properties_mut(),
+        &[
+            (TranslateX, 550.0),
+            (TranslateY2, -250.0),
+            (Rotation2, -150.0),
+            (RotationOriginX, 200.0),
+            (ScaleX, -1000.0),
+            (ScaleY2, 1500.0),
+            (ScaleOriginY, 100.0),
+        ],
+    );
+
+    let mut target = SoftwareTarget::new(SIZE);
+    target.clear(vec4(0.05, 0.05, 0.1, 1.0));
+    let projection = target.projection_matrix();
+    for layer in [&plain, &scaled_rotated, &mirrored] {
+        layer.render_software(&mut target, Mat4::IDENTITY, projection);
+    }
+
+    assert_golden("layer_picture_transform", &target.to_image());
+}
+
+#[test]
+fn bustup() {
+    use LayerProperty::*;
+
+    let base = LazyGpuImage::new(
+        test_image(200, 400, [80, 200, 255]),
+        vec2(100.0, 400.0),
+        None,
+    );
+    // the faces & mouths are positioned relative to the base with their own origin
+    let face = LazyGpuImage::new(
+        RgbaImage::from_pixel(80, 60, Rgba([255, 200, 40, 255])),
+        vec2(40.0, 330.0),
+        None,
+    );
+    let mouths = vec![
+        LazyGpuImage::new(
+            RgbaImage::from_pixel(30, 10, Rgba([200, 40, 40, 255])),
+            vec2(15.0, 290.0),
+            None,
+        ),
+        LazyGpuImage::new(
+            RgbaImage::from_pixel(30, 20, Rgba([120, 20, 20, 255])),
+            vec2(15.0, 295.0),
+            None,
+        ),
+    ];
+    let bustup = Bustup::from_images(base, "smile", Some(face), mouths);
+
+    let mut layer = BustupLayer::new(
+        &RESOURCES,
+        Arc::new(bustup),
+        "test.bup".to_string(),
+        Some("test".to_string()),
+        "smile",
+    );
+    set_properties(
+        layer.properties_mut(),
+        &[
+            (TranslateX, 200.0),
+            (TranslateY, 500.0),
+            (ScaleX, 2000.0),
+            (ScaleY, 2000.0),
+        ],
+    );
+
+    assert_golden("layer_bustup", &render(&layer));
+}
+
+#[test]
+fn layer_groups() {
+    use LayerProperty::*;
+
+    let mut screen = ScreenLayer::new();
+
+    let background = TileLayer::new(&RESOURCES, 0xf246, -960, -540, 1920, 1080);
+    // a half-transparent red tile, rotated around its center
+    let mut tile = TileLayer::new(&RESOURCES, 0x8f00, -200, -200, 400, 400);
+    set_properties(
+        tile.properties_mut(),
+        &[(TranslateX, -400.0), (Rotation, 125.0)],
+    );
+    // the per-layer effects are applied to this layer only
+    let mut picture = picture_layer(400, 300);
+    set_properties(
+        picture.properties_mut(),
+        &[(TranslateX, 300.0), (Prop20, 1000.0), (PixelizeSize, 12.0)],
+    );
+
+    let plane = screen.page_layer_mut().plane_mut(0);
+    plane.add_layer(LayerId::new(0), background.into());
+    plane.add_layer(LayerId::new(1), tile.into());
+    plane.add_layer(LayerId::new(2), picture.into());
+    // the whole plane is shrunk and tinted with sepia
+    set_properties(
+        plane.properties_mut(),
+        &[
+            (ScaleX, 800.0),
+            (ScaleY, 800.0),
+            (TranslateY, -50.0),
+            (Prop21, 1000.0),
+        ],
+    );
+
+    // a layer on the second plane is drawn over the first plane, without its effects
+    let overlay = TileLayer::new(&RESOURCES, 0xc0f0, -960, 340, 1920, 200);
+    screen
+        .page_layer_mut()
+        .plane_mut(1)
+        .add_layer(LayerId::new(0), overlay.into());
+
+    update(&mut screen);
+
+    assert_golden("layer_groups", &render(&screen));
+}
@@ -0,0 +1,284 @@
+//! An overlay for inspecting the layer tree and tweaking layer properties at runtime.
+//!
+//! Mostly useful when reverse engineering what the unknown `PropNN` properties do.
+
+use crate::layer::{Layer, LayerProperties, RootLayerGroup};
+use egui::{Color32, Context, DragValue, RichText, ScrollArea, Ui, Window};
+use itertools::Itertools;
+use shin_core::vm::command::types::{LayerId, LayerProperty, PLANES_COUNT};
+use std::cell::RefCell;
+use tracing::debug;
+
+/// Identifies a layer in the layer tree
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LayerPath {
+    RootLayerGroup,
+    ScreenLayer,
+    PageLayer,
+    Plane(u32),
+    MessageLayer,
+    UserLayer { plane: u32, id: LayerId },
+}
+
+impl LayerPath {
+    fn resolve<'a>(&self, root: &'a RootLayerGroup) -> Option<&'a LayerProperties> {
+        let page_layer = root.screen_layer().page_layer();
+        Some(match *self {
+            LayerPath::RootLayerGroup => root.properties(),
+            LayerPath::ScreenLayer => root.screen_layer().properties(),
+            LayerPath::PageLayer => page_layer.properties(),
+            LayerPath::Plane(plane) => page_layer.plane(plane).properties(),
+            LayerPath::MessageLayer => root.message_layer().properties(),
+            LayerPath::UserLayer { plane, id } => {
+                page_layer.plane(plane).get_layer(id)?.properties()
+            }
+        })
+    }
+
+    fn resolve_mut<'a>(&self, root: &'a mut RootLayerGroup) -> Option<&'a mut LayerProperties> {
+        Some(match *self {
+            LayerPath::RootLayerGroup => root.properties_mut(),
+            LayerPath::ScreenLayer => root.screen_layer_mut().properties_mut(),
+            LayerPath::PageLayer => root.screen_layer_mut().page_layer_mut().properties_mut(),
+            LayerPath::Plane(plane) => root
+                .screen_layer_mut()
+                .page_layer_mut()
+                .plane_mut(plane)
+                .properties_mut(),
+            LayerPath::MessageLayer => root.message_layer_mut().properties_mut(),
+            LayerPath::UserLayer { plane, id } => root
+                .screen_layer_mut()
+                .page_layer_mut()
+                .plane_mut(plane)
+                .get_layer_mut(id)?
+                .properties_mut(),
+        })
+    }
+}
+
+/// Modification requested from the inspector UI
+///
+/// The overlays only get a shared reference, so the requests are queued and applied on the next update
+#[derive(Debug, Copy, Clone)]
+#[allow(clippy::enum_variant_names)]
+enum InspectorRequest {
+    SetProperty {
+        path: LayerPath,
+        property: LayerProperty,
+        value: f32,
+    },
+    SetPaused {
+        path: LayerPath,
+        paused: bool,
+    },
+    SetAllPaused(bool),
+}
+
+struct InspectorUiState {
+    selected: Option<LayerPath>,
+    only_changed: bool,
+}
+
+pub struct LayerInspector {
+    requests: RefCell<Vec<InspectorRequest>>,
+    ui_state: RefCell<InspectorUiState>,
+}
+
+impl LayerInspector {
+    pub fn new() -> Self {
+        Self {
+            requests: RefCell::new(Vec::new()),
+            ui_state: RefCell::new(InspectorUiState {
+                selected: None,
+                only_changed: true,
+            }),
+        }
+    }
+
+    fn request(&self, request: InspectorRequest) {
+        self.requests.borrow_mut().push(request);
+    }
+
+    /// Apply the modifications requested from the UI
+    pub fn apply_requests(&self, root: &mut RootLayerGroup) {
+        for request in std::mem::take(&mut *self.requests.borrow_mut()) {
+            debug!("Layer inspector request: {:?}", request);
+            match request {
+                InspectorRequest::SetProperty {
+                    path,
+                    property,
+                    value,
+                } => {
+                    if let Some(properties) = path.resolve_mut(root) {
+                        properties
+                            .property_tweener_mut(property)
+                            .fast_forward_to(value);
+                    }
+                }
+                InspectorRequest::SetPaused { path, paused } => {
+                    if let Some(properties) = path.resolve_mut(root) {
+                        properties.set_paused(paused);
+                    }
+                }
+                InspectorRequest::SetAllPaused(paused) => {
+                    for path in all_paths(root) {
+                        if let Some(properties) = path.resolve_mut(root) {
+                            properties.set_paused(paused);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn show_window(&self, ctx: &Context, root: &RootLayerGroup) {
+        let mut ui_state = self.ui_state.borrow_mut();
+
+        // the selected layer might have been unloaded
+        if let Some(selected) = ui_state.selected {
+            if selected.resolve(root).is_none() {
+                ui_state.selected = None;
+            }
+        }
+
+        Window::new("Layer Inspector")
+            .default_width(800.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Pause all tweens").clicked() {
+                        self.request(InspectorRequest::SetAllPaused(true));
+                    }
+                    if ui.button("Resume all tweens").clicked() {
+                        self.request(InspectorRequest::SetAllPaused(false));
+                    }
+                    ui.checkbox(&mut ui_state.only_changed, "Only non-default properties");
+                });
+                ui.separator();
+                ui.columns(2, |columns| {
+                    ScrollArea::vertical()
+                        .id_source("layer_inspector_tree")
+                        .show(&mut columns[0], |ui| {
+                            self.tree_ui(ui, root, &mut ui_state);
+                        });
+                    ScrollArea::vertical()
+                        .id_source("layer_inspector_properties")
+                        .show(&mut columns[1], |ui| {
+                            self.properties_ui(ui, root, &ui_state);
+                        });
+                });
+            });
+    }
+
+    fn tree_ui(&self, ui: &mut Ui, root: &RootLayerGroup, ui_state: &mut InspectorUiState) {
+        let mut item = |ui: &mut Ui, path: LayerPath, label: String| {
+            let properties = path.resolve(root).unwrap();
+            let label = if properties.is_paused() {
+                format!("{} [paused]", label)
+            } else {
+                label
+            };
+            if ui
+                .selectable_label(ui_state.selected == Some(path), label)
+                .clicked()
+            {
+                ui_state.selected = Some(path);
+            }
+        };
+
+        let page_layer = root.screen_layer().page_layer();
+
+        item(ui, LayerPath::RootLayerGroup, "RootLayerGroup".to_string());
+        ui.indent("root_children", |ui| {
+            item(ui, LayerPath::ScreenLayer, "ScreenLayer".to_string());
+            ui.indent("screen_children", |ui| {
+                item(ui, LayerPath::PageLayer, "PageLayer".to_string());
+                ui.indent("page_children", |ui| {
+                    for plane in 0..PLANES_COUNT as u32 {
+                        item(ui, LayerPath::Plane(plane), format!("Plane {}", plane));
+                        let layer_group = page_layer.plane(plane);
+                        ui.indent(("plane_children", plane), |ui| {
+                            for id in layer_group.get_layer_ids().sorted() {
+                                let layer = layer_group.get_layer(id).unwrap();
+                                item(
+                                    ui,
+                                    LayerPath::UserLayer { plane, id },
+                                    format!("{:>3}: {:?}", id.raw(), layer),
+                                );
+                            }
+                        });
+                    }
+                });
+            });
+            item(ui, LayerPath::MessageLayer, "MessageLayer".to_string());
+        });
+    }
+
+    fn properties_ui(&self, ui: &mut Ui, root: &RootLayerGroup, ui_state: &InspectorUiState) {
+        let Some(path) = ui_state.selected else {
+            ui.label("Select a layer to see its properties");
+            return;
+        };
+        let properties = path.resolve(root).unwrap();
+
+        let mut paused = properties.is_paused();
+        if ui.checkbox(&mut paused, "Pause tweens").changed() {
+            self.request(InspectorRequest::SetPaused { path, paused });
+        }
+
+        egui::Grid::new("layer_inspector_properties_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Property");
+                ui.strong("Current");
+                ui.strong("Target");
+                ui.end_row();
+
+                for (property, tweener) in properties.iter() {
+                    let is_default =
+                        tweener.is_idle() && tweener.value() == property.initial_value() as f32;
+                    if ui_state.only_changed && is_default {
+                        continue;
+                    }
+
+                    let mut name = RichText::new(format!("{:?}", property)).monospace();
+                    if !property.is_implemented() {
+                        // make it obvious which properties are not handled by the engine
+                        name = name.color(Color32::from_rgb(200, 120, 0));
+                    }
+                    ui.label(name);
+                    ui.monospace(format!("{:.2}", tweener.value()));
+
+                    let mut target = tweener.target_value();
+                    if ui.add(DragValue::new(&mut target).speed(1.0)).changed() {
+                        self.request(InspectorRequest::SetProperty {
+                            path,
+                            property,
+                            value: target,
+                        });
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+fn all_paths(root: &RootLayerGroup) -> Vec<LayerPath> {
+    let page_layer = root.screen_layer().page_layer();
+
+    let mut paths = vec![
+        LayerPath::RootLayerGroup,
+        LayerPath::ScreenLayer,
+        LayerPath::PageLayer,
+        LayerPath::MessageLayer,
+    ];
+    for plane in 0..PLANES_COUNT as u32 {
+        paths.push(LayerPath::Plane(plane));
+        paths.extend(
+            page_layer
+                .plane(plane)
+                .get_layer_ids()
+                .map(|id| LayerPath::UserLayer { plane, id }),
+        );
+    }
+    paths
+}
@@ -6,27 +6,27 @@ use shin_core::vm::command::types::LayerId;
 
 use crate::layer::{Layer, LayerProperties, UserLayer};
 use crate::update::{Updatable, UpdateContext};
+use shin_render::software::{SoftwareRenderable, SoftwareTarget};
 use shin_render::GpuCommonResources;
-use shin_render::{RenderTarget, Renderable};
+use shin_render::{Effect, EffectStack, LazyRenderTarget, Renderable};
 
 pub struct LayerGroup {
     layers: HashMap<LayerId, UserLayer>,
-    render_target: RenderTarget,
+    /// Effects of the individual layers, only present for the layers that have any
+    layer_effects: HashMap<LayerId, EffectStack>,
+    render_target: LazyRenderTarget,
     properties: LayerProperties,
+    effects: EffectStack,
 }
 
 impl LayerGroup {
-    pub fn new(resources: &GpuCommonResources) -> Self {
-        let render_target = RenderTarget::new(
-            resources,
-            resources.current_render_buffer_size(),
-            Some("LayerGroup RenderTarget"),
-        );
-
+    pub fn new() -> Self {
         Self {
             layers: HashMap::new(),
-            render_target,
+            layer_effects: HashMap::new(),
+            render_target: LazyRenderTarget::new("LayerGroup RenderTarget"),
             properties: LayerProperties::new(),
+            effects: EffectStack::new("LayerGroup"),
         }
     }
 
@@ -39,6 +39,7 @@ impl LayerGroup {
     }
 
     pub fn remove_layer(&mut self, id: LayerId) {
+        self.layer_effects.remove(&id);
         if self.layers.remove(&id).is_none() {
             // this warning is too noisy
             // needs to be more specific to be useful
@@ -75,8 +76,20 @@ impl LayerGroup {
 impl Updatable for LayerGroup {
     fn update(&mut self, context: &UpdateContext) {
         self.properties.update(context);
-        for layer in self.layers.values_mut() {
+        self.effects.set_effects(self.properties.compute_effects());
+        for (&id, layer) in self.layers.iter_mut() {
             layer.update(context);
+
+            let effects = layer.properties().compute_effects();
+            if effects.iter().all(Effect::is_noop) {
+                // drop the render targets of the layers whose effects have ended
+                self.layer_effects.remove(&id);
+            } else {
+                self.layer_effects
+                    .entry(id)
+                    .or_insert_with(|| EffectStack::new(&format!("Layer {:?}", id)))
+                    .set_effects(effects);
+            }
         }
     }
 }
@@ -89,11 +102,11 @@ impl Renderable for LayerGroup {
         transform: Mat4,
         projection: Mat4,
     ) {
+        let render_target = self.render_target.get(resources);
         {
             let mut encoder = resources.start_encoder();
-            let mut render_pass = self
-                .render_target
-                .begin_srgb_render_pass(&mut encoder, Some("LayerGroup RenderPass"));
+            let mut render_pass =
+                render_target.begin_srgb_render_pass(&mut encoder, Some("LayerGroup RenderPass"));
 
             let ordered_layers = self
                 .layers
@@ -105,29 +118,52 @@ impl Renderable for LayerGroup {
                 .collect::<Vec<_>>();
 
             let transform = self.properties.compute_transform(transform);
-            let projection = self.render_target.projection_matrix();
+            let projection = render_target.projection_matrix();
 
             for (id, l) in ordered_layers {
                 render_pass.push_debug_group(&format!("Layer {:?}", id));
-                l.render(resources, &mut render_pass, transform, projection);
+                match self.layer_effects.get(id) {
+                    Some(effects) => {
+                        effects.render(resources, &mut render_pass, l, transform, projection)
+                    }
+                    None => l.render(resources, &mut render_pass, transform, projection),
+                }
                 render_pass.pop_debug_group();
             }
         }
 
         render_pass.push_debug_group("LayerGroup Render");
-        // TODO use layer pseudo-pipeline
-        resources.draw_sprite(
-            render_pass,
-            self.render_target.vertex_source(),
-            self.render_target.bind_group(),
-            projection,
-        );
+        self.effects
+            .draw(resources, render_pass, render_target, projection);
         render_pass.pop_debug_group();
     }
 
     fn resize(&mut self, resources: &GpuCommonResources) {
-        self.render_target
-            .resize(resources, resources.current_render_buffer_size());
+        self.render_target.resize(resources);
+        self.effects.resize(resources);
+        for effects in self.layer_effects.values_mut() {
+            effects.resize(resources);
+        }
+    }
+}
+
+impl SoftwareRenderable for LayerGroup {
+    fn render_software(&self, target: &mut SoftwareTarget, transform: Mat4, projection: Mat4) {
+        let mut group_target = SoftwareTarget::new(target.size());
+
+        let transform = self.properties.compute_transform(transform);
+        let group_projection = group_target.projection_matrix();
+
+        for (id, l) in self.layers.iter().sorted_by_key(|&(id, _)| *id) {
+            match self.layer_effects.get(id) {
+                Some(effects) => {
+                    effects.render_software(&mut group_target, l, transform, group_projection)
+                }
+                None => l.render_software(&mut group_target, transform, group_projection),
+            }
+        }
+
+        self.effects.draw_software(target, group_target, projection);
     }
 }
 
@@ -58,10 +58,11 @@ impl FontAtlas {
         &self.atlas.provider().font
     }
 
-    pub fn texture_bind_group(&self) -> &TextureBindGroup {
-        self.atlas.texture_bind_group()
+    pub fn page_bind_group(&self, page: usize) -> Arc<TextureBindGroup> {
+        self.atlas.page_bind_group(page)
     }
 
+    /// Size of each of the atlas pages
     pub fn texture_size(&self) -> (u32, u32) {
         self.atlas.texture_size()
     }
@@ -70,7 +71,20 @@ impl FontAtlas {
         let glyph_id = self.get_font().get_character_mapping()[charcode as usize];
         self.atlas
             .get_image(resources, glyph_id)
-            .expect("Could not fit image in atlas")
+            .expect("The atlas failed to allocate a glyph")
+    }
+
+    /// Current place of a glyph that is in use (see [Self::get_glyph])
+    pub fn peek_glyph(&self, charcode: u16) -> AtlasImage {
+        let glyph_id = self.get_font().get_character_mapping()[charcode as usize];
+        self.atlas
+            .peek_image(glyph_id)
+            .expect("The glyph is not in use")
+    }
+
+    /// Changes when the glyphs in use are moved in the atlas, their places have to be looked up again then
+    pub fn generation(&self) -> u64 {
+        self.atlas.generation()
     }
 
     pub fn free_glyph(&self, charcode: u16) {
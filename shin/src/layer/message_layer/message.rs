@@ -4,12 +4,14 @@ use crate::update::{Updatable, UpdateContext};
 use glam::{vec2, Mat4, Vec2};
 use shin_core::format::font::GlyphTrait;
 use shin_core::layout::{
-    Action, ActionType, Block, BlockExitCondition, LayoutedMessage, LayoutingMode,
+    Action, ActionType, Block, BlockExitCondition, LayoutedChar, LayoutedMessage, LayoutingMode,
 };
 use shin_core::time::Ticks;
 use shin_core::vm::command::types::MessageTextLayout;
 use shin_render::vertices::TextVertex;
-use shin_render::{GpuCommonResources, Renderable, VertexBuffer};
+use shin_render::{GpuCommonResources, Renderable, TextureBindGroup, VertexBuffer};
+use std::collections::BTreeMap;
+use std::ops::Range;
 use std::sync::Arc;
 use tracing::warn;
 
@@ -21,13 +23,21 @@ pub struct MessageMetrics {
     pub height: f32,
 }
 
+/// The atlas pages used by the message and the vertices to draw with each of them
+type PageVertices = Vec<(Arc<TextureBindGroup>, Range<u32>)>;
+
 pub struct Message {
     time: Ticks,
     font_atlas: Arc<FontAtlas>,
+    /// [FontAtlas::generation] the vertices were built with
+    atlas_generation: u64,
+    base_position: Vec2,
+    chars: Vec<LayoutedChar>,
     used_codepoints: Vec<u16>,
     actions: Vec<Action>,
     blocks: Vec<Block>,
     vertex_buffer: VertexBuffer<TextVertex>,
+    pages: PageVertices,
     sent_signals: u32,
     received_signals: u32,
     completed_blocks: u32,
@@ -126,85 +136,29 @@ impl Message {
             })
             .chain(chars);
 
-        let mut used_codepoints = Vec::new();
-        let mut vertices = Vec::new();
-        for char in all_chars_iter {
-            // TODO: support for BOLD font
-            let glyph_info = font_atlas
-                .get_font()
-                .get_glyph_for_character(char.codepoint)
-                .get_info();
-
-            let atlas_size = font_atlas.texture_size();
-            let atlas_size = vec2(atlas_size.0 as f32, atlas_size.1 as f32);
-
-            let AtlasImage {
-                position: tex_position,
-                size: _, // the atlas size is not to be trusted, as it can be larger than the actual texture (even larger than the power of 2 padded texture...)
-            } = font_atlas.get_glyph(context.gpu_resources, char.codepoint);
-            // save the codepoint to free it from the atlas later
-            used_codepoints.push(char.codepoint);
-
-            // just use the actual size of the glyph
-            let tex_size = glyph_info.actual_size();
-            let tex_size = vec2(tex_size.0 as f32, tex_size.1 as f32);
-
-            // scale texture coordinates to the size of the texture
-            let tex_position = tex_position / atlas_size;
-            let tex_size = tex_size / atlas_size;
-
-            let position = base_position
-                + char.position
-                + vec2(
-                    glyph_info.bearing_x as f32 * char.size.horizontal_scale,
-                    -glyph_info.bearing_y as f32 * char.size.scale,
-                );
-            let size = char.size.size();
-
-            let time = char.time;
-            let fade = char.fade;
-            let color = char.color;
-
-            // TODO: do the fade calculation here
-
-            // helper macro to reduce vertex creation boilerplate
-            macro_rules! v {
-                (($x:expr, $y:expr), ($tex_x:expr, $tex_y:expr)) => {
-                    TextVertex {
-                        position: position + vec2($x, $y) * size,
-                        tex_position: tex_position + vec2($tex_x, $tex_y) * tex_size,
-                        color,
-                        time,
-                        fade,
-                    }
-                };
-            }
+        let chars = all_chars_iter.collect::<Vec<_>>();
 
-            vertices.extend([
-                // Top left triangle
-                v!((0.0, 0.0), (0.0, 0.0)),
-                v!((1.0, 0.0), (1.0, 0.0)),
-                v!((0.0, 1.0), (0.0, 1.0)),
-                // Bottom right triangle
-                v!((1.0, 1.0), (1.0, 1.0)),
-                v!((0.0, 1.0), (0.0, 1.0)),
-                v!((1.0, 0.0), (1.0, 0.0)),
-            ]);
+        // save the codepoints to free them from the atlas later
+        let used_codepoints = chars.iter().map(|c| c.codepoint).collect::<Vec<_>>();
+        // put all the glyphs into the atlas before looking at their places, adding a glyph can move the others
+        for &codepoint in &used_codepoints {
+            font_atlas.get_glyph(context.gpu_resources, codepoint);
         }
-
-        let vertex_buffer = VertexBuffer::new(
-            context.gpu_resources,
-            &vertices,
-            Some("Message VertexBuffer"),
-        );
+        let atlas_generation = font_atlas.generation();
+        let (vertex_buffer, pages) =
+            build_vertices(context.gpu_resources, &font_atlas, base_position, &chars);
 
         Self {
             time: Ticks::ZERO,
             font_atlas,
+            atlas_generation,
+            base_position,
+            chars,
             used_codepoints,
             actions,
             blocks,
             vertex_buffer,
+            pages,
             sent_signals: 0,
             received_signals: 0,
             completed_blocks: 0,
@@ -326,8 +280,108 @@ impl Message {
     }
 }
 
+/// Builds the vertices of the glyphs, grouped by the atlas page. The glyphs must be in the atlas.
+fn build_vertices(
+    resources: &GpuCommonResources,
+    font_atlas: &FontAtlas,
+    base_position: Vec2,
+    chars: &[LayoutedChar],
+) -> (VertexBuffer<TextVertex>, PageVertices) {
+    // the glyphs can end up on different atlas pages, so group the vertices by page to draw them with different textures
+    let mut page_vertices = BTreeMap::<usize, Vec<TextVertex>>::new();
+    for char in chars {
+        // TODO: support for BOLD font
+        let glyph_info = font_atlas
+            .get_font()
+            .get_glyph_for_character(char.codepoint)
+            .get_info();
+
+        let atlas_size = font_atlas.texture_size();
+        let atlas_size = vec2(atlas_size.0 as f32, atlas_size.1 as f32);
+
+        let AtlasImage {
+            page,
+            position: tex_position,
+            size: _, // the atlas size is not to be trusted, as it can be larger than the actual texture (even larger than the power of 2 padded texture...)
+        } = font_atlas.peek_glyph(char.codepoint);
+
+        // just use the actual size of the glyph
+        let tex_size = glyph_info.actual_size();
+        let tex_size = vec2(tex_size.0 as f32, tex_size.1 as f32);
+
+        // scale texture coordinates to the size of the texture
+        let tex_position = tex_position / atlas_size;
+        let tex_size = tex_size / atlas_size;
+
+        let position = base_position
+            + char.position
+            + vec2(
+                glyph_info.bearing_x as f32 * char.size.horizontal_scale,
+                -glyph_info.bearing_y as f32 * char.size.scale,
+            );
+        let size = char.size.size();
+
+        let time = char.time;
+        let fade = char.fade;
+        let color = char.color;
+
+        // TODO: do the fade calculation here
+
+        // helper macro to reduce vertex creation boilerplate
+        macro_rules! v {
+            (($x:expr, $y:expr), ($tex_x:expr, $tex_y:expr)) => {
+                TextVertex {
+                    position: position + vec2($x, $y) * size,
+                    tex_position: tex_position + vec2($tex_x, $tex_y) * tex_size,
+                    color,
+                    time,
+                    fade,
+                }
+            };
+        }
+
+        page_vertices.entry(page).or_default().extend([
+            // Top left triangle
+            v!((0.0, 0.0), (0.0, 0.0)),
+            v!((1.0, 0.0), (1.0, 0.0)),
+            v!((0.0, 1.0), (0.0, 1.0)),
+            // Bottom right triangle
+            v!((1.0, 1.0), (1.0, 1.0)),
+            v!((0.0, 1.0), (0.0, 1.0)),
+            v!((1.0, 0.0), (1.0, 0.0)),
+        ]);
+    }
+
+    let mut vertices = Vec::new();
+    let mut pages = Vec::new();
+    for (page, page_vertices) in page_vertices {
+        let start = vertices.len() as u32;
+        vertices.extend(page_vertices);
+        pages.push((
+            font_atlas.page_bind_group(page),
+            start..vertices.len() as u32,
+        ));
+    }
+
+    let vertex_buffer = VertexBuffer::new(resources, &vertices, Some("Message VertexBuffer"));
+
+    (vertex_buffer, pages)
+}
+
 impl Updatable for Message {
     fn update(&mut self, context: &UpdateContext) {
+        // the glyphs were moved in the atlas
+        let atlas_generation = self.font_atlas.generation();
+        if atlas_generation != self.atlas_generation {
+            (self.vertex_buffer, self.pages) = build_vertices(
+                context.gpu_resources,
+                &self.font_atlas,
+                self.base_position,
+                &self.chars,
+            );
+            self.atlas_generation = atlas_generation;
+        }
+
         if let Some(block) = self.current_block() {
             if !block.completed(self.time) {
                 self.time += context.time_delta_ticks();
@@ -359,22 +413,27 @@ impl Renderable for Message {
         let scaled_distance = OUTLINE_DISTANCE / vec2(atlas_size.0 as f32, atlas_size.1 as f32);
 
         render_pass.push_debug_group("Message");
-        resources.draw_text_outline(
-            render_pass,
-            self.vertex_buffer.vertex_source(),
-            self.font_atlas.texture_bind_group(),
-            total_transform,
-            self.time,
-            scaled_distance,
-        );
-
-        resources.draw_text(
-            render_pass,
-            self.vertex_buffer.vertex_source(),
-            self.font_atlas.texture_bind_group(),
-            total_transform,
-            self.time,
-        );
+        // draw all the outlines first, so that they do not cover the neighbouring glyphs on other pages
+        for (texture, vertices) in &self.pages {
+            resources.draw_text_outline(
+                render_pass,
+                self.vertex_buffer.vertex_source_slice(vertices.clone()),
+                texture,
+                total_transform,
+                self.time,
+                scaled_distance,
+            );
+        }
+
+        for (texture, vertices) in &self.pages {
+            resources.draw_text(
+                render_pass,
+                self.vertex_buffer.vertex_source_slice(vertices.clone()),
+                texture,
+                total_transform,
+                self.time,
+            );
+        }
         render_pass.pop_debug_group();
     }
 
@@ -15,11 +15,14 @@ use crate::update::{Updatable, UpdateContext};
 use message::{Message, MessageStatus};
 use shin_core::time::Ticks;
 use shin_core::vm::command::types::{MessageboxStyle, MessageboxType};
+use shin_render::software::{SoftwareRenderable, SoftwareTarget};
 use shin_render::{GpuCommonResources, Renderable};
 
 pub struct MessageLayer {
     props: LayerProperties,
     style: MessageboxStyle,
+    fonts: AdvFonts,
+    /// Atlas of [AdvFonts::medium_font], the only one used for the messages for now
     font_atlas: Arc<FontAtlas>,
     message: Option<Message>,
     messagebox: Messagebox,
@@ -34,12 +37,21 @@ impl MessageLayer {
         Self {
             props: LayerProperties::new(),
             style: MessageboxStyle::default(),
-            font_atlas: Arc::new(FontAtlas::new(resources, fonts.medium_font)),
+            font_atlas: Arc::new(FontAtlas::new(resources, fonts.medium_font.clone())),
+            fonts,
             message: None,
             messagebox: Messagebox::new(textures, resources),
         }
     }
 
+    /// Swap all the fonts, the following messages use the new ones (the current one keeps the old ones)
+    pub fn set_fonts(&mut self, resources: &GpuCommonResources, fonts: AdvFonts) {
+        if !Arc::ptr_eq(&self.fonts.medium_font, &fonts.medium_font) {
+            self.font_atlas = Arc::new(FontAtlas::new(resources, fonts.medium_font.clone()));
+        }
+        self.fonts = fonts;
+    }
+
     pub fn set_style(&mut self, style: MessageboxStyle) {
         self.style = style;
 
@@ -132,6 +144,13 @@ impl Renderable for MessageLayer {
     }
 }
 
+impl SoftwareRenderable for MessageLayer {
+    fn render_software(&self, _target: &mut SoftwareTarget, _transform: Mat4, _projection: Mat4) {
+        // TODO: the messagebox textures & the glyphs are only kept on the GPU,
+        // a CPU copy of them is needed to draw the message layer here
+    }
+}
+
 impl Updatable for MessageLayer {
     fn update(&mut self, ctx: &UpdateContext) {
         self.messagebox.update(ctx);
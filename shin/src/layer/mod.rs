@@ -1,4 +1,7 @@
 mod bustup_layer;
+#[cfg(test)]
+mod golden;
+mod inspector;
 mod layer_group;
 mod message_layer;
 mod movie_layer;
@@ -14,11 +17,12 @@ use derivative::Derivative;
 use derive_more::From;
 use enum_dispatch::enum_dispatch;
 use enum_map::{enum_map, EnumMap};
-use glam::{vec3, Mat4};
+use glam::{vec3, vec4, Mat4};
 use std::f32::consts::PI;
 use tracing::{debug, warn};
 
 pub use bustup_layer::BustupLayer;
+pub use inspector::LayerInspector;
 pub use layer_group::LayerGroup;
 pub use message_layer::{MessageLayer, MessageboxTextures};
 pub use movie_layer::MovieLayer;
@@ -40,7 +44,8 @@ use shin_core::format::scenario::info::{BustupInfoItem, MovieInfoItem, PictureIn
 use shin_core::format::scenario::Scenario;
 use shin_core::time::{Ticks, Tweener};
 use shin_core::vm::command::types::{LayerProperty, LayerType};
-use shin_render::{GpuCommonResources, Renderable};
+use shin_render::software::{SoftwareRenderable, SoftwareTarget};
+use shin_render::{Effect, GpuCommonResources, Renderable};
 
 fn initial_values() -> EnumMap<LayerProperty, i32> {
     enum_map! {
@@ -56,6 +61,10 @@ pub struct LayerProperties {
     wobbler_rotation: Wobbler,
     wobbler_scale_x: Wobbler,
     wobbler_scale_y: Wobbler,
+    /// Phase of the raster scroll wave, in 1000th of the wave
+    raster_scroll_phase: f32,
+    /// When set, tweens & wobblers are frozen (used by the layer inspector)
+    paused: bool,
 }
 
 impl LayerProperties {
@@ -68,6 +77,8 @@ impl LayerProperties {
             wobbler_rotation: Wobbler::new(),
             wobbler_scale_x: Wobbler::new(),
             wobbler_scale_y: Wobbler::new(),
+            raster_scroll_phase: 0.0,
+            paused: false,
         }
     }
 
@@ -83,10 +94,23 @@ impl LayerProperties {
         &mut self.properties[property]
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = (LayerProperty, &Tweener)> {
+        self.properties.iter()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
     pub fn init(&mut self) {
         for (prop, val) in initial_values() {
             self.properties[prop].fast_forward_to(val as f32);
         }
+        self.raster_scroll_phase = 0.0;
     }
 
     pub fn compute_transform(&self, base_transform: Mat4) -> Mat4 {
@@ -152,10 +176,52 @@ impl LayerProperties {
             .into_iter()
             .fold(Mat4::IDENTITY, |acc, t| t * acc)
     }
+
+    /// Computes the post-processing effects to apply to the layer, in the order they should be applied
+    ///
+    /// The meaning of the `Prop20`-`Prop31` range is a guess based on their initial values,
+    /// so the colors might not match the original engine exactly.
+    pub fn compute_effects(&self) -> Vec<Effect> {
+        macro_rules! get {
+            ($property:ident) => {
+                self.get_property_value(LayerProperty::$property)
+            };
+        }
+
+        let color = [
+            Effect::negative(get!(Prop20) / 1000.0),
+            Effect::sepia(get!(Prop21) / 1000.0),
+            Effect::tint(
+                vec4(get!(Prop28), get!(Prop29), get!(Prop30), get!(Prop31)) / 1000.0,
+                vec4(get!(Prop23), get!(Prop24), get!(Prop25), get!(Prop26)) / 1000.0,
+            ),
+        ]
+        .into_iter()
+        .fold(Effect::IDENTITY_COLOR, |acc, e| acc.then_color(e).unwrap());
+
+        vec![
+            Effect::RasterScroll {
+                amplitude: get!(Prop76),
+                wavelength: get!(Prop77),
+                phase: self.raster_scroll_phase / 1000.0 * 2.0 * PI,
+            },
+            Effect::Mosaic {
+                size: get!(PixelizeSize),
+            },
+            Effect::Blur {
+                radius: get!(Prop69),
+            },
+            color,
+        ]
+    }
 }
 
 impl Updatable for LayerProperties {
     fn update(&mut self, context: &UpdateContext) {
+        if self.paused {
+            return;
+        }
+
         let dt = context.time_delta_ticks();
 
         for property in self.properties.values_mut() {
@@ -187,6 +253,10 @@ impl Updatable for LayerProperties {
         wobble!(wobbler_rotation, WobbleRotationMode, WobbleRotationPeriod);
         wobble!(wobbler_scale_x, WobbleScaleXMode, WobbleScaleXPeriod);
         wobble!(wobbler_scale_y, WobbleScaleYMode, WobbleScaleYPeriod);
+
+        // the speed is in 1000th of the wave per tick
+        self.raster_scroll_phase =
+            (self.raster_scroll_phase + get!(Prop78) * dt.as_f32()).rem_euclid(1000.0);
     }
 }
 
@@ -269,7 +339,7 @@ impl UserLayer {
                     .load::<Picture, _>(pic_info.path())
                     .await
                     .expect("Failed to load picture");
-                PictureLayer::new(resources, pic, Some(name.to_string())).into()
+                PictureLayer::new(resources, pic, pic_info.path(), Some(name.to_string())).into()
             }
             LayerType::Bustup => {
                 let [bup_id, _, _, _, _, _, _, _] = params;
@@ -284,7 +354,14 @@ impl UserLayer {
                     .await
                     .expect("Failed to load bustup");
 
-                BustupLayer::new(resources, bup, Some(name.to_string()), emotion.as_str()).into()
+                BustupLayer::new(
+                    resources,
+                    bup,
+                    bup_info.path(),
+                    Some(name.to_string()),
+                    emotion.as_str(),
+                )
+                .into()
             }
             LayerType::Movie => {
                 let [movie_id, _volume, _flags, _, _, _, _, _] = params;
@@ -316,6 +393,32 @@ impl UserLayer {
             }
         }
     }
+
+    /// Reload the displayed asset if its file has changed (see [AssetWatcher](crate::asset::AssetWatcher))
+    pub fn reload_assets(
+        &mut self,
+        resources: &GpuCommonResources,
+        asset_server: &AnyAssetServer,
+        changed_paths: &[String],
+    ) {
+        match self {
+            UserLayer::PictureLayer(l) => l.reload(resources, asset_server, changed_paths),
+            UserLayer::BustupLayer(l) => l.reload(resources, asset_server, changed_paths),
+            UserLayer::NullLayer(_) | UserLayer::TileLayer(_) | UserLayer::MovieLayer(_) => {}
+        }
+    }
+}
+
+impl SoftwareRenderable for UserLayer {
+    fn render_software(&self, target: &mut SoftwareTarget, transform: Mat4, projection: Mat4) {
+        match self {
+            UserLayer::NullLayer(l) => l.render_software(target, transform, projection),
+            UserLayer::PictureLayer(l) => l.render_software(target, transform, projection),
+            UserLayer::BustupLayer(l) => l.render_software(target, transform, projection),
+            UserLayer::TileLayer(l) => l.render_software(target, transform, projection),
+            UserLayer::MovieLayer(l) => l.render_software(target, transform, projection),
+        }
+    }
 }
 
 impl Renderable for UserLayer {
@@ -399,3 +502,106 @@ impl<'a> AnyLayerMut<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec4;
+
+    /// The effects computed with the given properties set, without the ones that do nothing
+    fn effects_with(values: &[(LayerProperty, f32)]) -> Vec<Effect> {
+        let mut properties = LayerProperties::new();
+        for &(property, value) in values {
+            properties
+                .property_tweener_mut(property)
+                .fast_forward_to(value);
+        }
+
+        properties
+            .compute_effects()
+            .into_iter()
+            .filter(|e| !e.is_noop())
+            .collect()
+    }
+
+    #[test]
+    fn default_effects_are_noops() {
+        // otherwise every layer would allocate the effect render targets
+        assert_eq!(effects_with(&[]), vec![]);
+
+        let mut properties = LayerProperties::new();
+        properties
+            .property_tweener_mut(LayerProperty::PixelizeSize)
+            .fast_forward_to(8.0);
+        properties.init();
+        assert!(properties.compute_effects().iter().all(|e| e.is_noop()));
+    }
+
+    #[test]
+    fn filter_effects() {
+        assert_eq!(
+            effects_with(&[(LayerProperty::PixelizeSize, 8.0)]),
+            vec![Effect::Mosaic { size: 8.0 }]
+        );
+        assert_eq!(
+            effects_with(&[(LayerProperty::Prop69, 4.0)]),
+            vec![Effect::Blur { radius: 4.0 }]
+        );
+        assert_eq!(
+            effects_with(&[
+                (LayerProperty::Prop76, 10.0),
+                (LayerProperty::Prop77, 100.0)
+            ]),
+            vec![Effect::RasterScroll {
+                amplitude: 10.0,
+                wavelength: 100.0,
+                phase: 0.0,
+            }]
+        );
+        // a wave without a wavelength does nothing
+        assert_eq!(effects_with(&[(LayerProperty::Prop76, 10.0)]), vec![]);
+    }
+
+    #[test]
+    fn color_effects() {
+        assert_eq!(
+            effects_with(&[(LayerProperty::Prop20, 1000.0)]),
+            vec![Effect::negative(1.0)]
+        );
+        assert_eq!(
+            effects_with(&[(LayerProperty::Prop21, 1000.0)]),
+            vec![Effect::sepia(1.0)]
+        );
+        assert_eq!(
+            effects_with(&[
+                (LayerProperty::Prop28, 500.0),
+                (LayerProperty::Prop23, 250.0)
+            ]),
+            vec![Effect::tint(
+                Vec4::new(0.5, 1.0, 1.0, 1.0),
+                Vec4::new(0.25, 0.0, 0.0, 0.0)
+            )]
+        );
+    }
+
+    #[test]
+    fn effect_order() {
+        let effects = effects_with(&[
+            (LayerProperty::Prop20, 1000.0),
+            (LayerProperty::Prop69, 4.0),
+            (LayerProperty::PixelizeSize, 8.0),
+            (LayerProperty::Prop76, 10.0),
+            (LayerProperty::Prop77, 100.0),
+        ]);
+
+        assert!(matches!(
+            effects.as_slice(),
+            [
+                Effect::RasterScroll { .. },
+                Effect::Mosaic { .. },
+                Effect::Blur { .. },
+                Effect::ColorTransform { .. },
+            ]
+        ));
+    }
+}
@@ -3,6 +3,7 @@ use crate::layer::{Layer, LayerProperties};
 use crate::update::{Updatable, UpdateContext};
 use glam::Mat4;
 use shin_audio::AudioManager;
+use shin_render::software::{SoftwareRenderable, SoftwareTarget};
 use shin_render::{GpuCommonResources, RenderTarget, Renderable};
 use shin_video::VideoPlayer;
 use std::fmt::Debug;
@@ -79,6 +80,12 @@ impl Renderable for MovieLayer {
     }
 }
 
+impl SoftwareRenderable for MovieLayer {
+    fn render_software(&self, _target: &mut SoftwareTarget, _transform: Mat4, _projection: Mat4) {
+        // TODO: the video frames are decoded straight into GPU textures, so there is nothing to draw here
+    }
+}
+
 impl Updatable for MovieLayer {
     fn update(&mut self, ctx: &UpdateContext) {
         self.video_player
@@ -1,6 +1,7 @@
 use crate::layer::{Layer, LayerProperties};
 use crate::update::{Updatable, UpdateContext};
 use glam::Mat4;
+use shin_render::software::{SoftwareRenderable, SoftwareTarget};
 use shin_render::GpuCommonResources;
 use shin_render::Renderable;
 use std::fmt::Debug;
@@ -32,6 +33,10 @@ impl Renderable for NullLayer {
     }
 }
 
+impl SoftwareRenderable for NullLayer {
+    fn render_software(&self, _target: &mut SoftwareTarget, _transform: Mat4, _projection: Mat4) {}
+}
+
 impl Updatable for NullLayer {
     fn update(&mut self, _ctx: &UpdateContext) {}
 }
@@ -2,30 +2,27 @@ use crate::layer::{Layer, LayerGroup, LayerProperties};
 use crate::update::{Updatable, UpdateContext};
 use glam::Mat4;
 use shin_core::vm::command::types::PLANES_COUNT;
-use shin_render::{GpuCommonResources, RenderTarget, Renderable};
+use shin_render::software::{SoftwareRenderable, SoftwareTarget};
+use shin_render::{EffectStack, GpuCommonResources, LazyRenderTarget, Renderable};
 
 pub struct PageLayer {
     planes: [LayerGroup; PLANES_COUNT],
     properties: LayerProperties,
-    render_target: RenderTarget,
+    render_target: LazyRenderTarget,
+    effects: EffectStack,
 }
 
 impl PageLayer {
-    pub fn new(resources: &GpuCommonResources) -> Self {
-        let render_target = RenderTarget::new(
-            resources,
-            resources.current_render_buffer_size(),
-            Some("LayerGroup RenderTarget"),
-        );
-
+    pub fn new() -> Self {
         Self {
             planes: [
-                LayerGroup::new(resources),
-                LayerGroup::new(resources),
-                LayerGroup::new(resources),
-                LayerGroup::new(resources),
+                LayerGroup::new(),
+                LayerGroup::new(),
+                LayerGroup::new(),
+                LayerGroup::new(),
             ],
-            render_target,
+            render_target: LazyRenderTarget::new("PageLayer RenderTarget"),
+            effects: EffectStack::new("PageLayer"),
             properties: LayerProperties::new(),
         }
     }
@@ -41,6 +38,8 @@ impl PageLayer {
 
 impl Updatable for PageLayer {
     fn update(&mut self, context: &UpdateContext) {
+        self.properties.update(context);
+        self.effects.set_effects(self.properties.compute_effects());
         for plane in self.planes.iter_mut() {
             plane.update(context);
         }
@@ -55,14 +54,14 @@ impl Renderable for PageLayer {
         transform: Mat4,
         projection: Mat4,
     ) {
+        let render_target = self.render_target.get(resources);
         {
             let mut encoder = resources.start_encoder();
-            let mut render_pass = self
-                .render_target
-                .begin_srgb_render_pass(&mut encoder, Some("PageLayer RenderPass"));
+            let mut render_pass =
+                render_target.begin_srgb_render_pass(&mut encoder, Some("PageLayer RenderPass"));
 
             let transform = self.properties.compute_transform(transform);
-            let projection = self.render_target.projection_matrix();
+            let projection = render_target.projection_matrix();
 
             for (i, plane) in self.planes.iter().enumerate() {
                 render_pass.push_debug_group(&format!("Plane {}", i));
@@ -74,19 +73,29 @@ impl Renderable for PageLayer {
         }
 
         render_pass.push_debug_group("PageLayer Render");
-        // TODO use layer pseudo-pipeline
-        resources.draw_sprite(
-            render_pass,
-            self.render_target.vertex_source(),
-            self.render_target.bind_group(),
-            projection,
-        );
+        self.effects
+            .draw(resources, render_pass, render_target, projection);
         render_pass.pop_debug_group();
     }
 
     fn resize(&mut self, resources: &GpuCommonResources) {
-        self.render_target
-            .resize(resources, resources.current_render_buffer_size());
+        self.render_target.resize(resources);
+        self.effects.resize(resources);
+    }
+}
+
+impl SoftwareRenderable for PageLayer {
+    fn render_software(&self, target: &mut SoftwareTarget, transform: Mat4, projection: Mat4) {
+        let mut page_target = SoftwareTarget::new(target.size());
+
+        let transform = self.properties.compute_transform(transform);
+        let page_projection = page_target.projection_matrix();
+
+        for plane in self.planes.iter() {
+            plane.render_software(&mut page_target, transform, page_projection);
+        }
+
+        self.effects.draw_software(target, page_target, projection);
     }
 }
 
@@ -1,14 +1,18 @@
 use crate::asset::picture::Picture;
+use crate::asset::{is_asset_changed, AnyAssetServer};
 use crate::layer::{Layer, LayerProperties};
 use crate::update::{Updatable, UpdateContext};
 use glam::Mat4;
+use shin_render::software::{SoftwareRenderable, SoftwareTarget};
 use shin_render::GpuCommonResources;
 use shin_render::Renderable;
 use std::fmt::Debug;
 use std::sync::Arc;
+use tracing::warn;
 
 pub struct PictureLayer {
     picture: Arc<Picture>,
+    picture_path: String,
     picture_name: Option<String>,
 
     props: LayerProperties,
@@ -18,6 +22,7 @@ impl PictureLayer {
     pub fn new(
         resources: &GpuCommonResources,
         picture: Arc<Picture>,
+        picture_path: String,
         picture_name: Option<String>,
     ) -> Self {
         // ensure the picture is loaded to gpu
@@ -25,10 +30,31 @@ impl PictureLayer {
 
         Self {
             picture,
+            picture_path,
             picture_name,
             props: LayerProperties::new(),
         }
     }
+
+    /// Load the picture again if its file has changed
+    pub fn reload(
+        &mut self,
+        resources: &GpuCommonResources,
+        asset_server: &AnyAssetServer,
+        changed_paths: &[String],
+    ) {
+        if !is_asset_changed(changed_paths, &self.picture_path) {
+            return;
+        }
+
+        match asset_server.load_sync::<Picture, _>(&self.picture_path) {
+            Ok(picture) => {
+                picture.gpu_image(resources);
+                self.picture = picture;
+            }
+            Err(e) => warn!("Reloading {}: {:?}", self.picture_path, e),
+        }
+    }
 }
 
 impl Renderable for PictureLayer {
@@ -55,6 +81,14 @@ impl Renderable for PictureLayer {
     }
 }
 
+impl SoftwareRenderable for PictureLayer {
+    fn render_software(&self, target: &mut SoftwareTarget, transform: Mat4, projection: Mat4) {
+        let total_transform = projection * self.props.compute_transform(transform);
+        let image = self.picture.image();
+        target.draw_image(image.image(), image.origin(), total_transform);
+    }
+}
+
 impl Updatable for PictureLayer {
     fn update(&mut self, ctx: &UpdateContext) {
         self.props.update(ctx);
@@ -2,12 +2,14 @@ use crate::layer::screen_layer::ScreenLayer;
 use crate::layer::{Layer, LayerProperties, MessageLayer};
 use crate::update::{Updatable, UpdateContext};
 use glam::Mat4;
-use shin_render::{GpuCommonResources, RenderTarget, Renderable};
+use shin_render::software::{SoftwareRenderable, SoftwareTarget};
+use shin_render::{EffectStack, GpuCommonResources, RenderTarget, Renderable};
 
 pub struct RootLayerGroup {
     screen_layer: ScreenLayer,
     message_layer: MessageLayer,
     render_target: RenderTarget,
+    effects: EffectStack,
     properties: LayerProperties,
 }
 
@@ -27,6 +29,7 @@ impl RootLayerGroup {
             screen_layer,
             message_layer,
             render_target,
+            effects: EffectStack::new("RootLayerGroup"),
             properties: LayerProperties::new(),
         }
     }
@@ -51,6 +54,7 @@ impl RootLayerGroup {
 impl Updatable for RootLayerGroup {
     fn update(&mut self, context: &UpdateContext) {
         self.properties.update(context);
+        self.effects.set_effects(self.properties.compute_effects());
         self.screen_layer.update(context);
         self.message_layer.update(context);
     }
@@ -85,19 +89,31 @@ impl Renderable for RootLayerGroup {
         }
 
         render_pass.push_debug_group("RootLayerGroup Render");
-        // TODO use layer pseudo-pipeline
-        resources.draw_sprite(
-            render_pass,
-            self.render_target.vertex_source(),
-            self.render_target.bind_group(),
-            projection,
-        );
+        self.effects
+            .draw(resources, render_pass, &self.render_target, projection);
         render_pass.pop_debug_group();
     }
 
     fn resize(&mut self, resources: &GpuCommonResources) {
         self.render_target
             .resize(resources, resources.current_render_buffer_size());
+        self.effects.resize(resources);
+    }
+}
+
+impl SoftwareRenderable for RootLayerGroup {
+    fn render_software(&self, target: &mut SoftwareTarget, transform: Mat4, projection: Mat4) {
+        let mut group_target = SoftwareTarget::new(target.size());
+
+        let transform = self.properties.compute_transform(transform);
+        let group_projection = group_target.projection_matrix();
+
+        self.screen_layer
+            .render_software(&mut group_target, transform, group_projection);
+        self.message_layer
+            .render_software(&mut group_target, transform, group_projection);
+
+        self.effects.draw_software(target, group_target, projection);
     }
 }
 
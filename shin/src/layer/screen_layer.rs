@@ -2,25 +2,24 @@ use crate::layer::page_layer::PageLayer;
 use crate::layer::{Layer, LayerProperties};
 use crate::update::{Updatable, UpdateContext};
 use glam::Mat4;
-use shin_render::{GpuCommonResources, RenderTarget, Renderable};
+use shin_render::software::{SoftwareRenderable, SoftwareTarget};
+use shin_render::{EffectStack, GpuCommonResources, LazyRenderTarget, Renderable};
 
 pub struct ScreenLayer {
     page_layer: PageLayer,
     properties: LayerProperties,
-    render_target: RenderTarget,
+    render_target: LazyRenderTarget,
+    effects: EffectStack,
     // TODO: a TransitionLayer (two kinds??) should be here
 }
 
 impl ScreenLayer {
-    pub fn new(resources: &GpuCommonResources) -> Self {
+    pub fn new() -> Self {
         Self {
-            page_layer: PageLayer::new(resources),
+            page_layer: PageLayer::new(),
             properties: LayerProperties::new(),
-            render_target: RenderTarget::new(
-                resources,
-                resources.current_render_buffer_size(),
-                Some("ScreenLayer RenderTarget"),
-            ),
+            render_target: LazyRenderTarget::new("ScreenLayer RenderTarget"),
+            effects: EffectStack::new("ScreenLayer"),
         }
     }
 
@@ -37,6 +36,7 @@ impl Updatable for ScreenLayer {
     fn update(&mut self, context: &UpdateContext) {
         self.page_layer.update(context);
         self.properties.update(context);
+        self.effects.set_effects(self.properties.compute_effects());
     }
 }
 
@@ -48,32 +48,44 @@ impl Renderable for ScreenLayer {
         transform: Mat4,
         projection: Mat4,
     ) {
+        let render_target = self.render_target.get(resources);
         {
             let mut encoder = resources.start_encoder();
-            let mut render_pass = self
-                .render_target
-                .begin_srgb_render_pass(&mut encoder, Some("PageLayer RenderPass"));
+            let mut render_pass =
+                render_target.begin_srgb_render_pass(&mut encoder, Some("ScreenLayer RenderPass"));
 
             let transform = self.properties.compute_transform(transform);
-            let projection = self.render_target.projection_matrix();
+            let projection = render_target.projection_matrix();
 
             self.page_layer
                 .render(resources, &mut render_pass, transform, projection);
         }
 
         render_pass.push_debug_group("ScreenLayer Render");
-        // TODO use layer pseudo-pipeline
-        resources.draw_sprite(
-            render_pass,
-            self.render_target.vertex_source(),
-            self.render_target.bind_group(),
-            projection,
-        );
+        self.effects
+            .draw(resources, render_pass, render_target, projection);
         render_pass.pop_debug_group();
     }
 
     fn resize(&mut self, resources: &GpuCommonResources) {
         self.page_layer.resize(resources);
+        self.render_target.resize(resources);
+        self.effects.resize(resources);
+    }
+}
+
+impl SoftwareRenderable for ScreenLayer {
+    fn render_software(&self, target: &mut SoftwareTarget, transform: Mat4, projection: Mat4) {
+        let mut screen_target = SoftwareTarget::new(target.size());
+
+        let transform = self.properties.compute_transform(transform);
+        let screen_projection = screen_target.projection_matrix();
+
+        self.page_layer
+            .render_software(&mut screen_target, transform, screen_projection);
+
+        self.effects
+            .draw_software(target, screen_target, projection);
     }
 }
 
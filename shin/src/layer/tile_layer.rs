@@ -1,6 +1,8 @@
 use crate::layer::{Layer, LayerProperties};
 use crate::update::{Updatable, UpdateContext};
 use glam::{vec4, Mat4, Vec4};
+use shin_render::software::{SoftwareRenderable, SoftwareTarget};
+use shin_render::vertices::{PosVertex, QUAD_INDICES};
 use shin_render::Renderable;
 use shin_render::{GpuCommonResources, PosVertexBuffer};
 use std::fmt::Debug;
@@ -8,6 +10,7 @@ use std::sync::Arc;
 
 pub struct TileLayer {
     vertex_color: Vec4,
+    rect: (f32, f32, f32, f32),
     vertex_buffer: Arc<PosVertexBuffer>,
 
     props: LayerProperties,
@@ -47,6 +50,7 @@ impl TileLayer {
 
         Self {
             vertex_color,
+            rect,
             vertex_buffer: Arc::new(vertex_buffer),
 
             props: LayerProperties::new(),
@@ -77,6 +81,19 @@ impl Renderable for TileLayer {
     }
 }
 
+impl SoftwareRenderable for TileLayer {
+    fn render_software(&self, target: &mut SoftwareTarget, transform: Mat4, projection: Mat4) {
+        let total_transform = projection * self.props.compute_transform(transform);
+
+        target.draw_fill(
+            &PosVertex::quad(self.rect),
+            &QUAD_INDICES,
+            total_transform,
+            self.vertex_color,
+        );
+    }
+}
+
 impl Updatable for TileLayer {
     fn update(&mut self, ctx: &UpdateContext) {
         self.props.update(ctx);
@@ -9,8 +9,10 @@ mod asset;
 // mod camera;
 mod adv;
 mod audio;
+mod capture;
 mod cli;
 mod fps_counter;
+mod headless;
 mod input;
 mod layer;
 mod render;
@@ -18,8 +20,13 @@ mod time;
 mod update;
 mod window;
 
-fn main() {
+fn main() -> anyhow::Result<()> {
     let cli = cli::Cli::parse();
 
-    pollster::block_on(window::run(cli));
+    if cli.headless {
+        pollster::block_on(headless::run(cli))
+    } else {
+        pollster::block_on(window::run(cli));
+        Ok(())
+    }
 }
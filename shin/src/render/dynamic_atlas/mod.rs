@@ -0,0 +1,457 @@
+mod pages;
+
+use crate::render::overlay::{OverlayCollector, OverlayVisitable};
+use crate::render::svg;
+use glam::{vec2, Vec2};
+use once_cell::sync::Lazy;
+use pages::{AtlasPages, PageAllocation};
+use shin_render::{GpuCommonResources, TextureBindGroup};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info};
+use usvg_text_layout::fontdb;
+
+pub trait ImageProvider {
+    const IMAGE_FORMAT: wgpu::TextureFormat;
+    const MIPMAP_LEVELS: u32;
+    type Id: Copy + Eq + std::hash::Hash;
+
+    fn get_image(&self, id: Self::Id) -> (Vec<Vec<u8>>, (u32, u32));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasImage {
+    /// Index of the atlas page the image is on, see [DynamicAtlas::page_bind_group]
+    pub page: usize,
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+/// The texture of a single atlas page
+struct PageTexture {
+    texture: wgpu::Texture,
+    texture_bind_group: Arc<TextureBindGroup>,
+}
+
+struct AtlasState<Id> {
+    pages: AtlasPages<Id>,
+    /// One for each of the pages
+    textures: Vec<PageTexture>,
+}
+
+/// Dynamic texture atlas, (for now) used for text rendering.
+///
+/// The images are put on multiple textures (pages) of the same size. New pages are added when the existing ones are full even after evicting the unused images
+/// and repacking the images in use, the empty pages at the end are removed. See [AtlasPages] for the details.
+///
+/// Repacking moves the images in use, so the users that keep the places of the images around have to look them up again when the [generation](Self::generation) changes.
+pub struct DynamicAtlas<P: ImageProvider> {
+    image_provider: P,
+
+    label: String,
+
+    texture_size: (u32, u32),
+    texture_sampler: wgpu::Sampler,
+
+    state: Mutex<AtlasState<P::Id>>,
+}
+
+impl<P: ImageProvider> DynamicAtlas<P> {
+    pub fn new(
+        resources: &GpuCommonResources,
+        image_provider: P,
+        texture_size: (u32, u32),
+        label: Option<&str>,
+    ) -> Self {
+        let label = label
+            .map(|s| format!("{} DynamicAtlas", s))
+            .unwrap_or_else(|| "DynamicAtlas".to_string());
+
+        // TODO: make sampler configurable
+        let texture_sampler = resources.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&format!("{} Sampler", label)),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let result = Self {
+            image_provider,
+            label,
+            texture_size,
+            texture_sampler,
+            state: Mutex::new(AtlasState {
+                pages: AtlasPages::new(texture_size),
+                textures: Vec::new(),
+            }),
+        };
+
+        let mut state = result.state.lock().unwrap();
+        result.sync_textures(resources, &mut state);
+        drop(state);
+
+        result
+    }
+
+    fn create_page_texture(&self, resources: &GpuCommonResources, index: usize) -> PageTexture {
+        let label = format!("{} Page {}", self.label, index);
+
+        let texture = resources.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&format!("{} Texture", label)),
+            size: wgpu::Extent3d {
+                width: self.texture_size.0,
+                height: self.texture_size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: P::MIPMAP_LEVELS,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: P::IMAGE_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let texture_bind_group = TextureBindGroup::new(
+            resources,
+            &texture_view,
+            &self.texture_sampler,
+            Some(&format!("{} TextureBindGroup", label)),
+        );
+
+        PageTexture {
+            texture,
+            texture_bind_group: Arc::new(texture_bind_group),
+        }
+    }
+
+    /// Creates the textures of the added pages and drops the ones of the removed pages
+    fn sync_textures(&self, resources: &GpuCommonResources, state: &mut AtlasState<P::Id>) {
+        let page_count = state.pages.pages().len();
+        state.textures.truncate(page_count);
+        while state.textures.len() < page_count {
+            let index = state.textures.len();
+            if index > 0 {
+                info!(label = self.label, "Adding atlas page {}", index);
+            }
+            state
+                .textures
+                .push(self.create_page_texture(resources, index));
+        }
+    }
+
+    /// The bind group of the texture of the given page
+    ///
+    /// It's reference-counted so that the users can keep it around while rendering without holding the atlas lock.
+    pub fn page_bind_group(&self, page: usize) -> Arc<TextureBindGroup> {
+        self.state.lock().unwrap().textures[page]
+            .texture_bind_group
+            .clone()
+    }
+
+    /// Size of each of the atlas pages
+    pub fn texture_size(&self) -> (u32, u32) {
+        self.texture_size
+    }
+
+    /// Changes every time the images in use are moved, see [AtlasPages::generation]
+    pub fn generation(&self) -> u64 {
+        self.state.lock().unwrap().pages.generation()
+    }
+
+    /// Uploads the image to its place in the atlas
+    fn upload(
+        &self,
+        resources: &GpuCommonResources,
+        texture: &wgpu::Texture,
+        allocation: &PageAllocation,
+        mip_data: Vec<Vec<u8>>,
+        (width, height): (u32, u32),
+    ) {
+        assert_eq!(mip_data.len(), P::MIPMAP_LEVELS as usize);
+
+        let format = P::IMAGE_FORMAT.describe();
+        // no compressed textures support for now
+        assert_eq!(format.block_dimensions, (1, 1));
+
+        let x: u32 = allocation.allocation.rectangle.min.x.try_into().unwrap();
+        let y: u32 = allocation.allocation.rectangle.min.y.try_into().unwrap();
+
+        for (mip_level, data) in (0..P::MIPMAP_LEVELS).zip(mip_data) {
+            let mip_scale = 1 << mip_level;
+
+            assert_eq!(
+                data.len(),
+                (width * height / mip_scale / mip_scale) as usize * format.block_size as usize
+            );
+
+            // Upload the image to the atlas
+            let texture_copy_view = wgpu::ImageCopyTexture {
+                texture,
+                mip_level,
+                origin: wgpu::Origin3d {
+                    x: x / mip_scale,
+                    y: y / mip_scale,
+                    z: 0,
+                },
+                aspect: Default::default(),
+            };
+
+            resources.queue.write_texture(
+                texture_copy_view,
+                &data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(
+                        NonZeroU32::new(width * format.block_size as u32 / mip_scale).unwrap(),
+                    ),
+                    rows_per_image: Some(NonZeroU32::new(height / mip_scale).unwrap()),
+                },
+                wgpu::Extent3d {
+                    width: width / mip_scale,
+                    height: height / mip_scale,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    /// Gets rid of the unused images on the pages that have no images in use, leaving them with no fragmentation
+    ///
+    /// This happens automatically when the atlas runs out of space, but can be triggered manually too.
+    pub fn defragment(&self) {
+        let evicted = self.state.lock().unwrap().pages.defragment();
+        debug!(
+            label = self.label,
+            "Defragmented the atlas, evicted {} images", evicted
+        );
+    }
+
+    /// Gets an image from the atlas, or adds it if it's not already there.
+    /// Increases the ref count of the image.
+    pub fn get_image(&self, resources: &GpuCommonResources, id: P::Id) -> Option<AtlasImage> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(allocation) = state.pages.acquire(id) {
+            return Some(allocation.as_atlas_image());
+        }
+
+        // The image is not in atlas. We need to actually upload it to GPU
+        let (mip_data, size) = self.image_provider.get_image(id);
+        let placement = state.pages.insert(id, size);
+        self.sync_textures(resources, &mut state);
+
+        self.upload(
+            resources,
+            &state.textures[placement.allocation.page].texture,
+            &placement.allocation,
+            mip_data,
+            size,
+        );
+        if !placement.moved.is_empty() {
+            info!(
+                label = self.label,
+                "Repacked atlas page {}, moved {} images",
+                placement.allocation.page,
+                placement.moved.len()
+            );
+        }
+        for (moved_id, allocation) in &placement.moved {
+            let (mip_data, size) = self.image_provider.get_image(*moved_id);
+            self.upload(
+                resources,
+                &state.textures[allocation.page].texture,
+                allocation,
+                mip_data,
+                size,
+            );
+        }
+
+        Some(placement.allocation.as_atlas_image())
+    }
+
+    /// Gets the current place of an image in use, without changing its ref count
+    pub fn peek_image(&self, id: P::Id) -> Option<AtlasImage> {
+        Some(self.state.lock().unwrap().pages.peek(id)?.as_atlas_image())
+    }
+
+    pub fn free_image(&self, id: P::Id) {
+        let mut state = self.state.lock().unwrap();
+        state.pages.release(id);
+        // the pages at the end might have been dropped
+        let page_count = state.pages.pages().len();
+        state.textures.truncate(page_count);
+    }
+
+    pub fn provider(&self) -> &P {
+        &self.image_provider
+    }
+
+    #[allow(unused)]
+    pub fn provider_mut(&mut self) -> &mut P {
+        &mut self.image_provider
+    }
+
+    /// Fraction of free space over all the pages
+    pub fn free_space(&self) -> f32 {
+        self.state.lock().unwrap().pages.free_space()
+    }
+}
+
+/// Fonts for the allocation maps, loaded once because loading the system fonts is slow
+static OVERLAY_FONTS: Lazy<fontdb::Database> = Lazy::new(svg::font_database);
+
+/// Draws the allocation map of an atlas page, filling the available space
+fn draw_allocator(ui: &mut egui::Ui, allocator: &etagere::BucketedAtlasAllocator) {
+    let mut svg_bytes = Vec::new();
+    allocator.dump_svg(&mut svg_bytes).unwrap();
+
+    let tree = svg::parse_svg(&svg_bytes, &OVERLAY_FONTS).unwrap();
+
+    let svg_size = vec2(tree.size.width() as f32, tree.size.height() as f32);
+    let min_scale = 1.0 / 12.0;
+    let min_size = svg_size * min_scale;
+
+    let mut size = ui.available_size();
+    size.x = size.x.max(min_size.x);
+    size.y = size.y.max(min_size.y);
+    let (_id, mut rect) = ui.allocate_space(size);
+
+    // fiddle with rect to make aspect ratio correct
+    let aspect_ratio = min_size.x / min_size.y;
+    let rect_aspect_ratio = rect.width() / rect.height();
+    // shrinking the size as needed, but keeping the center of the rect the same
+    if aspect_ratio > rect_aspect_ratio {
+        let new_height = rect.width() / aspect_ratio;
+        let old_height = rect.height();
+        rect.min.y += (old_height - new_height) / 2.0;
+        rect.max.y -= (old_height - new_height) / 2.0;
+    } else {
+        let new_width = rect.height() * aspect_ratio;
+        let old_width = rect.width();
+        rect.min.x += (old_width - new_width) / 2.0;
+        rect.max.x -= (old_width - new_width) / 2.0;
+    }
+
+    // rasterize at the size the map is shown at, so that the thin lines stay visible
+    let scale = rect.width() * ui.ctx().pixels_per_point() / svg_size.x;
+    let texture = allocator_texture(ui, &svg_bytes, &tree, scale);
+
+    ui.painter().with_clip_rect(rect).image(
+        texture.id(),
+        rect,
+        egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+        egui::Color32::from_white_alpha(128),
+    );
+}
+
+/// Rasterizes the allocation map, reusing the texture from the previous frame if neither the map nor the scale changed
+fn allocator_texture(
+    ui: &egui::Ui,
+    svg_bytes: &[u8],
+    tree: &usvg::Tree,
+    scale: f32,
+) -> egui::TextureHandle {
+    let mut hasher = DefaultHasher::new();
+    svg_bytes.hash(&mut hasher);
+    scale.to_bits().hash(&mut hasher);
+    let key = hasher.finish();
+
+    let id = ui.id().with("allocator texture");
+    if let Some((cached_key, texture)) =
+        ui.data_mut(|d| d.get_temp::<(u64, egui::TextureHandle)>(id))
+    {
+        if cached_key == key {
+            return texture;
+        }
+    }
+
+    let image = svg::rasterize_svg(tree, scale).expect("Rasterizing the allocation map");
+    let size = [image.width() as usize, image.height() as usize];
+    let texture = ui.ctx().load_texture(
+        "atlas allocation map",
+        egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw()),
+        egui::TextureOptions::LINEAR,
+    );
+    ui.data_mut(|d| d.insert_temp(id, (key, texture.clone())));
+
+    texture
+}
+
+impl<P: ImageProvider> OverlayVisitable for DynamicAtlas<P> {
+    fn visit_overlay(&self, collector: &mut OverlayCollector) {
+        collector.overlay(
+            &self.label,
+            |ctx, _top_left| {
+                egui::Window::new(&self.label)
+                    .resizable(true)
+                    .default_width(256.0)
+                    .default_height(256.0 + 32.0)
+                    .show(ctx, |ui| {
+                        let state = self.state.lock().unwrap();
+                        let pages = &state.pages;
+                        let stats = pages.stats();
+
+                        ui.label(format!(
+                            "Page size: {}x{}\nPages: {}\nFree space: {:.2}%\nImages: {} in use, {} unused\nEvictions: {}, defragmentations: {}, repacks: {}, dropped pages: {}",
+                            self.texture_size.0,
+                            self.texture_size.1,
+                            pages.pages().len(),
+                            100.0 * pages.free_space(),
+                            pages.active_count(),
+                            pages.eviction_ready_count(),
+                            stats.evictions,
+                            stats.defragmentations,
+                            stats.repacks,
+                            stats.dropped_pages,
+                        ));
+                        let defragment = ui.button("Defragment").clicked();
+
+                        for (index, page) in pages.pages().iter().enumerate() {
+                            egui::CollapsingHeader::new(format!(
+                                "Page {}: {} images in use, {:.2}% free",
+                                index,
+                                page.active_count,
+                                100.0 * page.allocator.free_space() as f32
+                                    / page.allocator.size().area() as f32
+                            ))
+                            .id_source(index)
+                            .default_open(index == 0)
+                            .show(ui, |ui| draw_allocator(ui, &page.allocator));
+                        }
+
+                        drop(state);
+                        if defragment {
+                            self.defragment();
+                        }
+                    });
+            },
+            false,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocation_map_rasterizes() {
+        let mut allocator = etagere::BucketedAtlasAllocator::new(etagere::size2(256, 256));
+        allocator.allocate(etagere::size2(64, 64)).unwrap();
+
+        let mut svg_bytes = Vec::new();
+        allocator.dump_svg(&mut svg_bytes).unwrap();
+        let tree = svg::parse_svg(&svg_bytes, &OVERLAY_FONTS).unwrap();
+        let image = svg::rasterize_svg(&tree, 0.5).unwrap();
+
+        assert_eq!(image.dimensions(), (128, 128));
+        // both the allocated and the free space are drawn
+        assert!(image.pixels().all(|p| p.0[3] > 0));
+    }
+}
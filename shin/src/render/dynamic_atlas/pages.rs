@@ -0,0 +1,532 @@
+//! The bookkeeping of the atlas pages: which image is where, which ones are in use and which ones can be evicted.
+//!
+//! It is kept apart from the textures, the [DynamicAtlas](super::DynamicAtlas) uploads the images to the places decided here.
+
+use super::AtlasImage;
+use bevy_utils::HashMap;
+use glam::vec2;
+use std::hash::Hash;
+
+/// An image placed on one of the atlas pages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageAllocation {
+    pub page: usize,
+    pub allocation: etagere::Allocation,
+}
+
+impl PageAllocation {
+    pub fn as_atlas_image(&self) -> AtlasImage {
+        let pos = self.allocation.rectangle.min;
+        let size = self.allocation.rectangle.size();
+
+        AtlasImage {
+            page: self.page,
+            position: vec2(pos.x as f32, pos.y as f32),
+            size: vec2(size.width as f32, size.height as f32),
+        }
+    }
+}
+
+struct ActiveImage {
+    allocation: PageAllocation,
+    /// Size of the image, the allocation can be larger because of the alignment
+    size: etagere::Size,
+    ref_count: usize,
+}
+
+struct UnusedImage {
+    allocation: PageAllocation,
+    size: etagere::Size,
+}
+
+/// The allocator of a single page
+pub struct Page {
+    pub allocator: etagere::BucketedAtlasAllocator,
+    /// Number of images on this page that are in use
+    pub active_count: usize,
+}
+
+/// Counters describing how the atlas was used, shown in the overlay
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AtlasStats {
+    /// Number of unused images removed from the atlas to make space for new ones
+    pub evictions: u64,
+    /// Number of times a page without images in use was reset to get rid of fragmentation
+    pub defragmentations: u64,
+    /// Number of times the images in use on a page were packed anew to make space for a new one
+    pub repacks: u64,
+    /// Number of the empty pages removed from the end
+    pub dropped_pages: u64,
+}
+
+/// Where a new image was put
+#[derive(Debug)]
+pub struct Placement<Id> {
+    pub allocation: PageAllocation,
+    /// The images in use that were moved to make space for the new one, they have to be uploaded again to their new places
+    pub moved: Vec<(Id, PageAllocation)>,
+}
+
+pub struct AtlasPages<Id> {
+    page_size: etagere::Size,
+    pages: Vec<Page>,
+    /// These are the images that are currently in the atlas and cannot be evicted.
+    active: HashMap<Id, ActiveImage>,
+    /// These are images still in the atlas, but can be evicted.
+    eviction_ready: HashMap<Id, UnusedImage>,
+    stats: AtlasStats,
+    generation: u64,
+}
+
+impl<Id: Copy + Eq + Hash> AtlasPages<Id> {
+    pub fn new(page_size: (u32, u32)) -> Self {
+        let mut result = Self {
+            page_size: etagere::Size::new(
+                page_size.0.try_into().unwrap(),
+                page_size.1.try_into().unwrap(),
+            ),
+            pages: Vec::new(),
+            active: HashMap::default(),
+            eviction_ready: HashMap::default(),
+            stats: AtlasStats::default(),
+            generation: 0,
+        };
+        // always have at least one page
+        result.pages.push(result.new_page());
+        result
+    }
+
+    fn new_allocator(&self) -> etagere::BucketedAtlasAllocator {
+        etagere::BucketedAtlasAllocator::with_options(
+            self.page_size,
+            &etagere::AllocatorOptions {
+                alignment: etagere::Size::new(8, 8), // TODO: make this configurable
+                vertical_shelves: false,
+                num_columns: 1,
+            },
+        )
+    }
+
+    fn new_page(&self) -> Page {
+        Page {
+            allocator: self.new_allocator(),
+            active_count: 0,
+        }
+    }
+
+    pub fn pages(&self) -> &[Page] {
+        &self.pages
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.active.len()
+    }
+
+    pub fn eviction_ready_count(&self) -> usize {
+        self.eviction_ready.len()
+    }
+
+    pub fn stats(&self) -> AtlasStats {
+        self.stats
+    }
+
+    /// Changes every time images in use are moved, the users have to look up their places again then
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Fraction of free space over all the pages
+    pub fn free_space(&self) -> f32 {
+        let (free, total) = self.pages.iter().fold((0, 0), |(free, total), page| {
+            (
+                free + page.allocator.free_space(),
+                total + page.allocator.size().area(),
+            )
+        });
+        free as f32 / total as f32
+    }
+
+    /// Place of an image in use
+    pub fn peek(&self, id: Id) -> Option<PageAllocation> {
+        self.active.get(&id).map(|image| image.allocation)
+    }
+
+    /// Increases the ref count of an image that is in the atlas, `None` if it has to be [inserted](Self::insert)
+    pub fn acquire(&mut self, id: Id) -> Option<PageAllocation> {
+        if let Some(image) = self.active.get_mut(&id) {
+            image.ref_count += 1;
+            return Some(image.allocation);
+        }
+
+        // The image is already allocated, but not in use, so we can restore it
+        let UnusedImage { allocation, size } = self.eviction_ready.remove(&id)?;
+        self.pages[allocation.page].active_count += 1;
+        self.active.insert(
+            id,
+            ActiveImage {
+                allocation,
+                size,
+                ref_count: 1,
+            },
+        );
+        Some(allocation)
+    }
+
+    /// Finds a place for a new image with ref count of 1, evicting unused images, repacking the pages and adding new ones as needed
+    pub fn insert(&mut self, id: Id, size: (u32, u32)) -> Placement<Id> {
+        assert!(
+            !self.active.contains_key(&id) && !self.eviction_ready.contains_key(&id),
+            "The image is already in the atlas"
+        );
+        let size = etagere::Size::new(size.0.try_into().unwrap(), size.1.try_into().unwrap());
+        assert!(
+            size.width <= self.page_size.width && size.height <= self.page_size.height,
+            "Image of size {}x{} can never fit into the atlas page of size {}x{}",
+            size.width,
+            size.height,
+            self.page_size.width,
+            self.page_size.height
+        );
+
+        let (allocation, moved) = self.allocate(size);
+        self.pages[allocation.page].active_count += 1;
+        self.active.insert(
+            id,
+            ActiveImage {
+                allocation,
+                size,
+                ref_count: 1,
+            },
+        );
+
+        Placement { allocation, moved }
+    }
+
+    /// Decreases the ref count of an image, making it evictable when it is not used anymore
+    pub fn release(&mut self, id: Id) {
+        let image = self
+            .active
+            .get_mut(&id)
+            .expect("Attempt to free an image not in atlas");
+
+        image.ref_count -= 1;
+        if image.ref_count == 0 {
+            let ActiveImage {
+                allocation, size, ..
+            } = self.active.remove(&id).unwrap();
+            self.pages[allocation.page].active_count -= 1;
+            self.eviction_ready
+                .insert(id, UnusedImage { allocation, size });
+            self.drop_trailing_pages();
+        }
+    }
+
+    /// Gets rid of the unused images on the pages that have no images in use, leaving them with no fragmentation
+    ///
+    /// This happens automatically when the atlas runs out of space, but can be triggered manually too.
+    /// Returns the number of evicted images.
+    pub fn defragment(&mut self) -> usize {
+        let mut evicted = 0;
+        for page_index in 0..self.pages.len() {
+            if self.pages[page_index].active_count == 0 {
+                evicted += self.evict_page(page_index);
+            }
+        }
+        evicted
+    }
+
+    fn allocate(&mut self, size: etagere::Size) -> (PageAllocation, Vec<(Id, PageAllocation)>) {
+        // First, try to find free space without evicting anything
+        for (page_index, page) in self.pages.iter_mut().enumerate() {
+            if let Some(allocation) = page.allocator.allocate(size) {
+                return (
+                    PageAllocation {
+                        page: page_index,
+                        allocation,
+                    },
+                    Vec::new(),
+                );
+            }
+        }
+
+        // seems like we are out of space
+        // go over the pages that would have enough space after evicting their unused images, the ones with the most of it first
+        let mut evictable_area = vec![0; self.pages.len()];
+        for image in self.eviction_ready.values() {
+            evictable_area[image.allocation.page] += image.allocation.allocation.rectangle.area();
+        }
+        let mut candidates = self
+            .pages
+            .iter()
+            .zip(evictable_area)
+            .enumerate()
+            .map(|(index, (page, evictable))| (index, page.allocator.free_space() + evictable))
+            .filter(|&(_, available)| available >= size.area())
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|&(index, available)| (std::cmp::Reverse(available), index));
+
+        for (page_index, _) in candidates {
+            self.evict_page(page_index);
+            if let Some(allocation) = self.pages[page_index].allocator.allocate(size) {
+                return (
+                    PageAllocation {
+                        page: page_index,
+                        allocation,
+                    },
+                    Vec::new(),
+                );
+            }
+
+            // there is enough space, but it's fragmented by the images in use
+            if let Some(result) = self.repack(page_index, size) {
+                return result;
+            }
+        }
+
+        // all the pages are full of images in use, add a new one
+        let page_index = self.pages.len();
+        let mut page = self.new_page();
+        let allocation = page
+            .allocator
+            .allocate(size)
+            .expect("Failed to allocate atlas space for image on an empty page");
+        self.pages.push(page);
+
+        (
+            PageAllocation {
+                page: page_index,
+                allocation,
+            },
+            Vec::new(),
+        )
+    }
+
+    /// Places the images in use on the page anew, together with a new image of `size`
+    ///
+    /// The page must not have unused images. Returns `None` (leaving the page as it was) if they do not fit.
+    fn repack(
+        &mut self,
+        page_index: usize,
+        size: etagere::Size,
+    ) -> Option<(PageAllocation, Vec<(Id, PageAllocation)>)> {
+        let mut images = self
+            .active
+            .iter()
+            .filter(|(_, image)| image.allocation.page == page_index)
+            .map(|(&id, image)| (Some(id), image.size))
+            .chain(std::iter::once((None, size)))
+            .collect::<Vec<_>>();
+        // the shelves are packed best when the tallest images go first
+        images.sort_by_key(|&(_, size)| std::cmp::Reverse((size.height, size.width)));
+
+        let mut allocator = self.new_allocator();
+        let mut new_allocation = None;
+        let mut moved = Vec::new();
+        for (id, size) in images {
+            let allocation = PageAllocation {
+                page: page_index,
+                allocation: allocator.allocate(size)?,
+            };
+            match id {
+                Some(id) => moved.push((id, allocation)),
+                None => new_allocation = Some(allocation),
+            }
+        }
+
+        self.pages[page_index].allocator = allocator;
+        for &(id, allocation) in &moved {
+            self.active.get_mut(&id).unwrap().allocation = allocation;
+        }
+        self.stats.repacks += 1;
+        self.generation += 1;
+
+        Some((new_allocation.unwrap(), moved))
+    }
+
+    /// Removes all the unused images from the page, returns the number of evicted images
+    fn evict_page(&mut self, page_index: usize) -> usize {
+        let page = &mut self.pages[page_index];
+        let mut evicted = 0;
+        self.eviction_ready.retain(|_, image| {
+            if image.allocation.page != page_index {
+                return true;
+            }
+            page.allocator.deallocate(image.allocation.allocation.id);
+            evicted += 1;
+            false
+        });
+        self.stats.evictions += evicted as u64;
+
+        if page.active_count == 0 && !page.allocator.is_empty() {
+            // the allocator does not merge all the free space back, reset it to start with a clean slate
+            page.allocator.clear();
+            self.stats.defragmentations += 1;
+        }
+
+        evicted
+    }
+
+    /// Removes the pages at the end that have no images in use, keeping the first one
+    fn drop_trailing_pages(&mut self) {
+        while self.pages.len() > 1 && self.pages.last().unwrap().active_count == 0 {
+            let page_index = self.pages.len() - 1;
+            self.eviction_ready
+                .retain(|_, image| image.allocation.page != page_index);
+            self.pages.pop();
+            self.stats.dropped_pages += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE_SIZE: (u32, u32) = (64, 64);
+
+    /// Checks that the images in use do not overlap and are on the existing pages
+    fn assert_consistent(pages: &AtlasPages<u32>) {
+        let active = pages.active.values().collect::<Vec<_>>();
+        for (i, a) in active.iter().enumerate() {
+            assert!(a.allocation.page < pages.pages.len());
+            for b in &active[i + 1..] {
+                assert!(
+                    a.allocation.page != b.allocation.page
+                        || !a
+                            .allocation
+                            .allocation
+                            .rectangle
+                            .intersects(&b.allocation.allocation.rectangle),
+                    "{:?} overlaps {:?}",
+                    a.allocation,
+                    b.allocation
+                );
+            }
+        }
+        for (index, page) in pages.pages.iter().enumerate() {
+            assert_eq!(
+                page.active_count,
+                active.iter().filter(|a| a.allocation.page == index).count()
+            );
+        }
+    }
+
+    #[test]
+    fn reuses_unused_images() {
+        let mut pages = AtlasPages::new(PAGE_SIZE);
+        let placement = pages.insert(1, (16, 16));
+        pages.release(1);
+        assert_eq!(pages.eviction_ready_count(), 1);
+
+        assert_eq!(pages.acquire(1), Some(placement.allocation));
+        assert_eq!(pages.acquire(2), None);
+        assert_eq!(pages.eviction_ready_count(), 0);
+    }
+
+    #[test]
+    fn evicts_unused_images_before_adding_pages() {
+        let mut pages = AtlasPages::new(PAGE_SIZE);
+        for id in 0..4 {
+            pages.insert(id, (32, 32));
+            pages.release(id);
+        }
+
+        pages.insert(4, (64, 64));
+        assert_eq!(pages.pages().len(), 1);
+        assert_eq!(pages.eviction_ready_count(), 0);
+        assert_eq!(pages.stats().evictions, 4);
+        assert_consistent(&pages);
+    }
+
+    #[test]
+    fn adds_pages_when_full_of_images_in_use() {
+        let mut pages = AtlasPages::new(PAGE_SIZE);
+        for id in 0..5 {
+            pages.insert(id, (32, 32));
+        }
+
+        assert_eq!(pages.pages().len(), 2);
+        assert_eq!(pages.peek(4).unwrap().page, 1);
+        assert_consistent(&pages);
+    }
+
+    #[test]
+    fn repacks_pages_with_images_in_use() {
+        let mut pages = AtlasPages::new(PAGE_SIZE);
+        // four 32-pixel shelves of small images
+        for id in 0..16 {
+            pages.insert(id, (16, 16));
+        }
+        // free one image on each of the shelves except the first one, the freed space is fragmented
+        for id in [4, 9, 14] {
+            pages.release(id);
+        }
+        let generation = pages.generation();
+
+        let placement = pages.insert(100, (48, 16));
+        assert_eq!(placement.allocation.page, 0);
+        assert_eq!(pages.pages().len(), 1);
+        assert_eq!(pages.stats().repacks, 1);
+        assert_ne!(pages.generation(), generation);
+        for (id, allocation) in &placement.moved {
+            assert_eq!(pages.peek(*id), Some(*allocation));
+        }
+        assert_consistent(&pages);
+    }
+
+    #[test]
+    fn repack_keeps_the_page_when_it_does_not_help() {
+        let mut pages = AtlasPages::new(PAGE_SIZE);
+        for id in 0..3 {
+            pages.insert(id, (32, 32));
+        }
+        let before = (0..3).map(|id| pages.peek(id)).collect::<Vec<_>>();
+
+        // the free space is enough, but a tall image can't fit next to the images in use
+        let placement = pages.insert(3, (8, 64));
+        assert_eq!(placement.allocation.page, 1);
+        assert!(placement.moved.is_empty());
+        assert_eq!(pages.stats().repacks, 0);
+        assert_eq!((0..3).map(|id| pages.peek(id)).collect::<Vec<_>>(), before);
+        assert_consistent(&pages);
+    }
+
+    #[test]
+    fn drops_empty_trailing_pages() {
+        let mut pages = AtlasPages::new(PAGE_SIZE);
+        pages.insert(0, (64, 64));
+        pages.insert(1, (64, 64));
+        pages.insert(2, (64, 64));
+        assert_eq!(pages.pages().len(), 3);
+
+        // the middle page is kept while the last one is in use
+        pages.release(1);
+        assert_eq!(pages.pages().len(), 3);
+
+        pages.release(2);
+        assert_eq!(pages.pages().len(), 1);
+        assert_eq!(pages.stats().dropped_pages, 2);
+        // the unused images of the dropped pages are forgotten
+        assert_eq!(pages.eviction_ready_count(), 0);
+        assert_eq!(pages.acquire(1), None);
+
+        // the first page is always kept
+        pages.release(0);
+        assert_eq!(pages.pages().len(), 1);
+        assert_eq!(pages.eviction_ready_count(), 1);
+        assert_consistent(&pages);
+    }
+
+    #[test]
+    fn defragment_clears_pages_without_images_in_use() {
+        let mut pages = AtlasPages::new(PAGE_SIZE);
+        pages.insert(0, (64, 64));
+        pages.insert(1, (16, 16));
+        pages.release(0);
+
+        assert_eq!(pages.defragment(), 1);
+        assert!(pages.pages()[0].allocator.is_empty());
+        assert_eq!(pages.peek(1).unwrap().page, 1);
+        assert_consistent(&pages);
+    }
+}
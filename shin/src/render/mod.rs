@@ -1,2 +1,4 @@
 pub mod dynamic_atlas;
 pub mod overlay;
+pub mod presentation;
+pub mod svg;
@@ -0,0 +1,81 @@
+//! An overlay to change how the game screen is presented in the window at runtime.
+
+use crate::render::overlay::{OverlayCollector, OverlayVisitable};
+use egui::{ComboBox, Window};
+use shin_render::{PresentationSettings, RenderResolution, ScalingMode};
+use std::cell::RefCell;
+
+pub struct PresentationOverlay {
+    current: PresentationSettings,
+    /// The overlays only get a shared reference, so the change is applied on the next update
+    requested: RefCell<Option<PresentationSettings>>,
+}
+
+impl PresentationOverlay {
+    pub fn new(current: PresentationSettings) -> Self {
+        Self {
+            current,
+            requested: RefCell::new(None),
+        }
+    }
+
+    /// Takes the settings selected in the overlay, if they were changed
+    pub fn take_request(&mut self) -> Option<PresentationSettings> {
+        let requested = self.requested.get_mut().take()?;
+        self.current = requested;
+        Some(requested)
+    }
+}
+
+impl OverlayVisitable for PresentationOverlay {
+    fn visit_overlay(&self, collector: &mut OverlayCollector) {
+        collector.overlay(
+            "Presentation",
+            |ctx, _top_left| {
+                let mut settings = self.current;
+
+                Window::new("Presentation")
+                    .resizable(false)
+                    .show(ctx, |ui| {
+                        ComboBox::from_label("Scaling")
+                            .selected_text(format!("{:?}", settings.scaling))
+                            .show_ui(ui, |ui| {
+                                for mode in
+                                    [ScalingMode::Fit, ScalingMode::Integer, ScalingMode::Stretch]
+                                {
+                                    ui.selectable_value(
+                                        &mut settings.scaling,
+                                        mode,
+                                        format!("{:?}", mode),
+                                    );
+                                }
+                            });
+                        ComboBox::from_label("Resolution")
+                            .selected_text(format!("{:?}", settings.resolution))
+                            .show_ui(ui, |ui| {
+                                for resolution in
+                                    [RenderResolution::Native, RenderResolution::Virtual]
+                                {
+                                    ui.selectable_value(
+                                        &mut settings.resolution,
+                                        resolution,
+                                        format!("{:?}", resolution),
+                                    );
+                                }
+                            });
+                        ui.horizontal(|ui| {
+                            let mut color = settings.bar_color.to_array();
+                            ui.color_edit_button_rgba_unmultiplied(&mut color);
+                            settings.bar_color = color.into();
+                            ui.label("Bar color");
+                        });
+                    });
+
+                if settings != self.current {
+                    *self.requested.borrow_mut() = Some(settings);
+                }
+            },
+            false,
+        );
+    }
+}
@@ -0,0 +1,384 @@
+//! Rasterization of SVG images, used to put vector UI assets into a [DynamicAtlas] at any scale.
+//!
+//! The rendering is done by resvg, so all of the static SVG is supported: solid, gradient and pattern paints, embedded (`data:` URL) images and text.
+//! Text is converted to paths using the system fonts, falling back to the fonts bundled with egui.
+
+use crate::render::dynamic_atlas::{AtlasImage, DynamicAtlas, ImageProvider};
+use crate::render::overlay::{OverlayCollector, OverlayVisitable};
+use anyhow::{anyhow, Context, Result};
+use image::RgbaImage;
+use shin_render::{GpuCommonResources, TextureBindGroup};
+use std::sync::Arc;
+use usvg_text_layout::{fontdb, TreeTextToPath};
+
+/// The system fonts, along with the fonts bundled with egui, so that text can be rendered even on systems without fonts
+pub fn font_database() -> fontdb::Database {
+    let mut fontdb = fontdb::Database::new();
+    fontdb.load_system_fonts();
+    load_bundled_fonts(&mut fontdb);
+
+    fontdb
+}
+
+fn load_bundled_fonts(fontdb: &mut fontdb::Database) {
+    let bundled = egui::FontDefinitions::default();
+    let proportional = bundled.families[&egui::FontFamily::Proportional][0].clone();
+    let mut proportional_family = None;
+    for (name, data) in bundled.font_data {
+        let first_face = fontdb.len();
+        fontdb.load_font_data(data.font.into_owned());
+        if name == proportional {
+            proportional_family = fontdb.faces()[first_face..]
+                .first()
+                .map(|face| face.families[0].0.clone());
+        }
+    }
+
+    // usvg falls back to the serif family when none of the requested families are found
+    let serif = fontdb::Query {
+        families: &[fontdb::Family::Serif],
+        ..Default::default()
+    };
+    if fontdb.query(&serif).is_none() {
+        if let Some(family) = proportional_family {
+            fontdb.set_serif_family(family);
+        }
+    }
+}
+
+/// Parses an SVG image, converting its text nodes to paths with the fonts from `fontdb`
+pub fn parse_svg(data: &[u8], fontdb: &fontdb::Database) -> Result<usvg::Tree> {
+    let mut tree = usvg::Tree::from_data(data, &usvg::Options::default()).context("Parsing SVG")?;
+    tree.convert_text(fontdb);
+    Ok(tree)
+}
+
+/// Size of the SVG image in pixels when rendered at the given scale
+pub fn svg_pixel_size(tree: &usvg::Tree, scale: f32) -> (u32, u32) {
+    let width = (tree.size.width() as f32 * scale).ceil() as u32;
+    let height = (tree.size.height() as f32 * scale).ceil() as u32;
+    // zero-sized textures are not a thing
+    (width.max(1), height.max(1))
+}
+
+/// Renders the SVG image, the size of one SVG user unit being `scale` pixels
+pub fn rasterize_svg(tree: &usvg::Tree, scale: f32) -> Result<RgbaImage> {
+    let (width, height) = svg_pixel_size(tree, scale);
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| anyhow!("Invalid SVG raster size: {}x{}", width, height))?;
+
+    resvg::render(
+        tree,
+        usvg::FitTo::Zoom(scale),
+        tiny_skia::Transform::default(),
+        pixmap.as_mut(),
+    )
+    .ok_or_else(|| anyhow!("Failed to render SVG"))?;
+
+    // tiny-skia works with premultiplied alpha, the rest of the engine does not
+    let data = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+
+    Ok(RgbaImage::from_raw(width, height, data).unwrap())
+}
+
+/// Identifies an SVG image added to a [SvgAtlas]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SvgId(usize);
+
+/// Identifies a rasterization of an SVG image at a specific scale
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SvgImageId {
+    svg: SvgId,
+    // f32 is not Hash, so store its bits
+    scale_bits: u32,
+}
+
+impl SvgImageId {
+    pub fn new(svg: SvgId, scale: f32) -> Self {
+        Self {
+            svg,
+            scale_bits: scale.to_bits(),
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        f32::from_bits(self.scale_bits)
+    }
+}
+
+struct SvgImageProvider {
+    /// The sources of the images. The parsed trees are not Send, so the images are parsed each time they are rasterized
+    sources: Vec<Arc<[u8]>>,
+    fontdb: fontdb::Database,
+}
+
+impl ImageProvider for SvgImageProvider {
+    const IMAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+    const MIPMAP_LEVELS: u32 = 1;
+    type Id = SvgImageId;
+
+    fn get_image(&self, id: Self::Id) -> (Vec<Vec<u8>>, (u32, u32)) {
+        // the image was checked to parse when it was added
+        let tree = parse_svg(&self.sources[id.svg.0], &self.fontdb).unwrap();
+        let image = rasterize_svg(&tree, id.scale()).expect("Rasterizing SVG");
+        let size = image.dimensions();
+
+        (vec![image.into_raw()], size)
+    }
+}
+
+const TEXTURE_SIZE: (u32, u32) = (2048, 2048);
+
+/// An atlas of vector images, rasterized at the scales they are requested at
+pub struct SvgAtlas {
+    atlas: DynamicAtlas<SvgImageProvider>,
+}
+
+#[allow(unused)]
+impl SvgAtlas {
+    pub fn new(resources: &GpuCommonResources, label: Option<&str>) -> Self {
+        let provider = SvgImageProvider {
+            sources: Vec::new(),
+            fontdb: font_database(),
+        };
+        let label = label
+            .map(|s| format!("{} SvgAtlas", s))
+            .unwrap_or_else(|| "SvgAtlas".to_string());
+
+        Self {
+            atlas: DynamicAtlas::new(resources, provider, TEXTURE_SIZE, Some(&label)),
+        }
+    }
+
+    /// Adds an SVG image to the atlas, it will be rasterized when it's first requested
+    pub fn add_svg(&mut self, data: impl Into<Arc<[u8]>>) -> Result<SvgId> {
+        let data = data.into();
+        let provider = self.atlas.provider_mut();
+        parse_svg(&data, &provider.fontdb)?;
+
+        provider.sources.push(data);
+        Ok(SvgId(provider.sources.len() - 1))
+    }
+
+    /// Size of the image in pixels when rasterized at the given scale
+    pub fn image_size(&self, id: SvgId, scale: f32) -> (u32, u32) {
+        let provider = self.atlas.provider();
+        let tree = parse_svg(&provider.sources[id.0], &provider.fontdb).unwrap();
+        svg_pixel_size(&tree, scale)
+    }
+
+    pub fn get_image(&self, resources: &GpuCommonResources, id: SvgId, scale: f32) -> AtlasImage {
+        self.atlas
+            .get_image(resources, SvgImageId::new(id, scale))
+            .expect("The atlas failed to allocate an SVG image")
+    }
+
+    pub fn free_image(&self, id: SvgId, scale: f32) {
+        self.atlas.free_image(SvgImageId::new(id, scale));
+    }
+
+    pub fn page_bind_group(&self, page: usize) -> Arc<TextureBindGroup> {
+        self.atlas.page_bind_group(page)
+    }
+
+    /// Size of each of the atlas pages
+    pub fn texture_size(&self) -> (u32, u32) {
+        self.atlas.texture_size()
+    }
+}
+
+impl OverlayVisitable for SvgAtlas {
+    fn visit_overlay(&self, collector: &mut OverlayCollector) {
+        self.atlas.visit_overlay(collector);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn render(svg: &str, scale: f32) -> RgbaImage {
+        let tree = parse_svg(svg.as_bytes(), &font_database()).unwrap();
+        rasterize_svg(&tree, scale).unwrap()
+    }
+
+    fn assert_close(actual: Rgba<u8>, expected: [u8; 4]) {
+        let close = actual
+            .0
+            .iter()
+            .zip(expected)
+            .all(|(&a, e)| (a as i32 - e as i32).abs() <= 8);
+        assert!(close, "expected {:?}, got {:?}", expected, actual.0);
+    }
+
+    fn base64(data: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut result = String::new();
+        for chunk in data.chunks(3) {
+            let bytes = [
+                chunk[0],
+                *chunk.get(1).unwrap_or(&0),
+                *chunk.get(2).unwrap_or(&0),
+            ];
+            let value = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    result.push(ALPHABET[(value >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    result.push('=');
+                }
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn solid_fill_and_stroke() {
+        let image = render(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20">
+                <rect x="4" y="4" width="12" height="12" fill="#ff0000" stroke="#0000ff" stroke-width="4"/>
+            </svg>"##,
+            1.0,
+        );
+        assert_eq!(image.dimensions(), (20, 20));
+        assert_close(*image.get_pixel(10, 10), [255, 0, 0, 255]);
+        assert_close(*image.get_pixel(3, 10), [0, 0, 255, 255]);
+        assert_close(*image.get_pixel(0, 0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn scaled() {
+        let image = render(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="5">
+                <rect x="5" width="5" height="5" fill="#00ff00"/>
+            </svg>"##,
+            2.5,
+        );
+        assert_eq!(image.dimensions(), (25, 13));
+        assert_close(*image.get_pixel(5, 6), [0, 0, 0, 0]);
+        assert_close(*image.get_pixel(20, 6), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn gradients() {
+        let image = render(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="20">
+                <defs>
+                    <linearGradient id="linear">
+                        <stop offset="0" stop-color="#ff0000"/>
+                        <stop offset="1" stop-color="#0000ff"/>
+                    </linearGradient>
+                    <radialGradient id="radial">
+                        <stop offset="0" stop-color="#ffffff"/>
+                        <stop offset="1" stop-color="#000000"/>
+                    </radialGradient>
+                </defs>
+                <rect width="100" height="10" fill="url(#linear)"/>
+                <rect y="10" width="100" height="10" fill="url(#radial)"/>
+            </svg>"##,
+            1.0,
+        );
+
+        let left = image.get_pixel(1, 5);
+        let right = image.get_pixel(98, 5);
+        assert!(left.0[0] > 240 && left.0[2] < 15, "{:?}", left);
+        assert!(right.0[2] > 240 && right.0[0] < 15, "{:?}", right);
+
+        let center = image.get_pixel(50, 15);
+        let edge = image.get_pixel(1, 15);
+        assert!(center.0[0] > edge.0[0], "{:?} {:?}", center, edge);
+    }
+
+    #[test]
+    fn pattern() {
+        let image = render(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16">
+                <defs>
+                    <pattern id="checker" width="8" height="8" patternUnits="userSpaceOnUse">
+                        <rect width="4" height="4" fill="#ffffff"/>
+                        <rect x="4" y="4" width="4" height="4" fill="#ffffff"/>
+                    </pattern>
+                </defs>
+                <rect width="16" height="16" fill="#000000"/>
+                <rect width="16" height="16" fill="url(#checker)"/>
+            </svg>"##,
+            1.0,
+        );
+
+        assert_close(*image.get_pixel(2, 2), [255, 255, 255, 255]);
+        assert_close(*image.get_pixel(6, 2), [0, 0, 0, 255]);
+        assert_close(*image.get_pixel(10, 10), [255, 255, 255, 255]);
+        assert_close(*image.get_pixel(14, 10), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn embedded_image() {
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(4, 4, Rgba([255, 128, 0, 255]))
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+
+        let image = render(
+            &format!(
+                r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="20" height="20">
+                    <image x="5" y="5" width="10" height="10" xlink:href="data:image/png;base64,{}"/>
+                </svg>"##,
+                base64(&png)
+            ),
+            1.0,
+        );
+
+        assert_close(*image.get_pixel(10, 10), [255, 128, 0, 255]);
+        assert_close(*image.get_pixel(2, 2), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn text() {
+        let image = render(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="40">
+                <text x="5" y="30" font-size="30" font-family="No Such Font" fill="#000000">Hello</text>
+            </svg>"##,
+            1.0,
+        );
+
+        // an unknown family falls back to the serif family, which is always available thanks to the bundled fonts
+        assert!(image.pixels().any(|p| p.0[3] > 128));
+        // the text does not spill out of its box
+        assert_close(*image.get_pixel(2, 2), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn text_without_system_fonts() {
+        let mut fontdb = fontdb::Database::new();
+        load_bundled_fonts(&mut fontdb);
+
+        let tree = parse_svg(
+            br##"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="40">
+                <text x="5" y="30" font-size="30" fill="#000000">Hello</text>
+            </svg>"##,
+            &fontdb,
+        )
+        .unwrap();
+        let image = rasterize_svg(&tree, 1.0).unwrap();
+
+        assert!(image.pixels().any(|p| p.0[3] > 128));
+    }
+
+    #[test]
+    fn base64_encoding() {
+        assert_eq!(base64(b"a"), "YQ==");
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b"abc"), "YWJj");
+    }
+}
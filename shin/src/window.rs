@@ -1,4 +1,7 @@
+use std::fs::File;
+use std::io::BufWriter;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 use glam::Mat4;
@@ -12,31 +15,58 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 
-use crate::asset::locate_assets;
-use crate::cli::Cli;
+use crate::asset::{apply_mods, locate_assets, AssetServer, AssetWatcher};
+use crate::audio::load_mixer_settings;
+use crate::capture::{FrameCapture, VideoRecorder};
+use crate::cli::{AudioOutput, Cli, Resolution, Scaling};
 use crate::time::Time;
 use crate::{
-    adv::assets::AdvAssets,
-    adv::Adv,
+    adv::assets::{select_game_profile, AdvAssets},
+    adv::{Adv, TraceRecorder},
     asset::AnyAssetServer,
     fps_counter::FpsCounter,
     input::RawInputState,
     render::overlay::{OverlayManager, OverlayVisitable},
+    render::presentation::PresentationOverlay,
     update::{Updatable, UpdateContext},
 };
 
-use shin_audio::AudioManager;
+use shin_audio::{AudioBackend, AudioManager, MANUAL_BACKEND_SAMPLE_RATE};
 use shin_render::BindGroupLayouts;
 use shin_render::Camera;
 use shin_render::GpuCommonResources;
 use shin_render::Pillarbox;
 use shin_render::Pipelines;
+use shin_render::{PresentationSettings, RenderResolution, ScalingMode};
 use shin_render::{RenderTarget, Renderable};
 use shin_tasks::create_task_pools;
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+/// Request a device with the features & limits required by the engine
+pub async fn request_device(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue)> {
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::PUSH_CONSTANTS,
+                // WebGL doesn't support all of wgpu's features, so if
+                // we're building for the web we'll have to disable some.
+                limits: wgpu::Limits {
+                    max_texture_dimension_2d: 4096,
+                    max_push_constant_size: 256,
+
+                    ..wgpu::Limits::downlevel_webgl2_defaults()
+                },
+            },
+            // Some(&std::path::Path::new("trace")), // Trace path
+            None,
+        )
+        .await
+        .context("Failed to create wgpu device")
+}
+
 struct State {
     surface: wgpu::Surface,
     surface_config: wgpu::SurfaceConfiguration,
@@ -46,10 +76,16 @@ struct State {
     time: Time,
     render_target: RenderTarget,
     pillarbox: Pillarbox,
+    presentation_overlay: PresentationOverlay,
     asset_server: Arc<AnyAssetServer>,
+    asset_watcher: Option<AssetWatcher>,
     input: RawInputState,
     overlay_manager: OverlayManager,
     fps_counter: FpsCounter,
+    audio_manager: Arc<AudioManager>,
+    capture: FrameCapture,
+    /// When set, the time is advanced by this amount each frame instead of following the real time
+    fixed_frame_duration: Option<Duration>,
     adv: Adv,
 }
 
@@ -79,25 +115,7 @@ impl State {
         info!("Selected an adapter {:?}", adapter.get_info(),);
         debug!("Adapter limits: {:?}", adapter.limits());
 
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::PUSH_CONSTANTS,
-                    // WebGL doesn't support all of wgpu's features, so if
-                    // we're building for the web we'll have to disable some.
-                    limits: wgpu::Limits {
-                        max_texture_dimension_2d: 4096,
-                        max_push_constant_size: 256,
-
-                        ..wgpu::Limits::downlevel_webgl2_defaults()
-                    },
-                },
-                // Some(&std::path::Path::new("trace")), // Trace path
-                None,
-            )
-            .await
-            .context("Failed to create wgpu device")?;
+        let (device, queue) = request_device(&adapter).await?;
 
         // TODO: make a better selection?
         // TODO: rn we don't really support switching this
@@ -119,7 +137,19 @@ impl State {
         let bind_group_layouts = BindGroupLayouts::new(&device);
         let pipelines = Pipelines::new(&device, &bind_group_layouts, surface_texture_format);
 
-        let camera = Camera::new(window_size);
+        let presentation = PresentationSettings {
+            scaling: match cli.scaling {
+                Scaling::Fit => ScalingMode::Fit,
+                Scaling::Integer => ScalingMode::Integer,
+                Scaling::Stretch => ScalingMode::Stretch,
+            },
+            resolution: match cli.render_resolution {
+                Resolution::Native => RenderResolution::Native,
+                Resolution::Virtual => RenderResolution::Virtual,
+            },
+            bar_color: cli.bar_color,
+        };
+        let camera = Camera::with_presentation(window_size, presentation);
 
         let resources = Arc::new(GpuCommonResources {
             device,
@@ -131,32 +161,106 @@ impl State {
 
         let overlay = OverlayManager::new(&resources, surface_texture_format);
 
-        let render_target = RenderTarget::new(
+        let mut render_target = RenderTarget::new(
             &resources,
             camera.render_buffer_size(),
             Some("Window RenderTarget"),
         );
+        render_target.set_filter_mode(&resources, presentation_filter_mode(&presentation));
+
+        let mut pillarbox = Pillarbox::new(&resources);
+        pillarbox.set_color(presentation.bar_color);
+
+        let audio_manager = Arc::new(
+            if cli.record.is_some() {
+                // the audio is captured to the video instead
+                AudioManager::with_backend(AudioBackend::Manual)
+            } else if let Some(path) = &cli.audio_dump {
+                AudioManager::with_backend(AudioBackend::WavFile(path.clone()))
+            } else {
+                match cli.audio {
+                    AudioOutput::Auto => AudioManager::new(),
+                    AudioOutput::Cpal => AudioManager::with_backend(AudioBackend::Cpal),
+                    AudioOutput::Null => AudioManager::with_backend(AudioBackend::Null),
+                }
+            }
+            .context("Failed to create the audio manager")?,
+        );
 
-        let pillarbox = Pillarbox::new(&resources);
-
-        let audio_manager = Arc::new(AudioManager::new());
-
-        let asset_io = locate_assets(cli.assets_dir.as_deref()).context("Failed to locate assets. Consult the README for instructions on how to set up the game.")?;
+        let recorder = cli
+            .record
+            .as_deref()
+            .map(|path| {
+                VideoRecorder::new(
+                    path,
+                    camera.render_buffer_size(),
+                    cli.record_fps,
+                    MANUAL_BACKEND_SAMPLE_RATE,
+                )
+            })
+            .transpose()
+            .context("Failed to start the video recording")?;
+        let fixed_frame_duration = cli
+            .record
+            .as_ref()
+            .map(|_| Duration::from_secs_f64(1.0 / cli.record_fps as f64));
+        let capture = FrameCapture::new(cli.screenshot_dir.clone(), recorder);
+
+        let located_assets = locate_assets(cli.assets_dir.as_deref()).context("Failed to locate assets. Consult the README for instructions on how to set up the game.")?;
+
+        // the game is detected from the game data alone, the mods can target a specific game
+        let base_asset_server = AssetServer::new(located_assets.io);
+        let profile = pollster::block_on(select_game_profile(&base_asset_server, cli.game))?;
+        let mut asset_io = base_asset_server.into_io();
+        if !cli.no_mods {
+            let mods_dir = cli
+                .mods_dir
+                .clone()
+                .unwrap_or_else(|| located_assets.dir.join("mods"));
+            asset_io =
+                apply_mods(asset_io, &mods_dir, profile.id).context("Failed to load the mods")?;
+        }
 
         debug!("Asset IO: {:#?}", asset_io);
 
         let asset_server = Arc::new(AnyAssetServer::new(asset_io.into()));
+        asset_server.set_cache_budget(cli.asset_cache_budget * 1024 * 1024);
+        let asset_watcher = if cli.hot_reload {
+            let roots = asset_server.io().dir_roots();
+            if roots.is_empty() {
+                warn!("Hot reload requested, but the assets are not in a directory");
+                None
+            } else {
+                Some(AssetWatcher::new(roots))
+            }
+        } else {
+            None
+        };
+
+        audio_manager.set_mixer_settings(
+            load_mixer_settings(profile, cli.savedata.as_deref())
+                .context("Failed to load the audio settings")?,
+        );
 
-        let adv_assets =
-            pollster::block_on(AdvAssets::load(&asset_server)).expect("Loading assets failed");
+        let adv_assets = pollster::block_on(AdvAssets::load(&asset_server, profile))
+            .expect("Loading assets failed");
 
-        let mut adv = Adv::new(&resources, audio_manager, adv_assets, 0, 42);
+        let mut adv = Adv::new(&resources, audio_manager.clone(), adv_assets, 0, 42);
 
         if let Some(addr) = cli.fast_forward_to {
             debug!("Fast forwarding to {}", addr);
             adv.fast_forward_to(CodeAddress(addr));
         }
 
+        if cli.prefetch_budget > 0 {
+            adv.prefetch(cli.prefetch_budget * 1024 * 1024);
+        }
+
+        if let Some(trace_path) = &cli.trace {
+            let file = File::create(trace_path).context("Creating the trace file")?;
+            adv.record_trace(TraceRecorder::new(Box::new(BufWriter::new(file))));
+        }
+
         Ok(Self {
             surface,
             surface_config: config,
@@ -166,10 +270,15 @@ impl State {
             time: Time::default(),
             render_target,
             pillarbox,
+            presentation_overlay: PresentationOverlay::new(presentation),
             asset_server,
+            asset_watcher,
             input: RawInputState::new(),
             overlay_manager: overlay,
             fps_counter: FpsCounter::new(),
+            audio_manager,
+            capture,
+            fixed_frame_duration,
             adv,
         })
     }
@@ -204,6 +313,17 @@ impl State {
         }
     }
 
+    fn set_presentation(&mut self, presentation: PresentationSettings) {
+        debug!("Changing presentation settings to {:?}", presentation);
+
+        self.camera.set_presentation(presentation);
+        self.render_target
+            .set_filter_mode(&self.resources, presentation_filter_mode(&presentation));
+        self.pillarbox.set_color(presentation.bar_color);
+        // the render buffer size might change
+        self.resize(self.window_size);
+    }
+
     #[allow(unused_variables)]
     fn input(&mut self, event: &WindowEvent) -> bool {
         self.input.on_winit_event(event);
@@ -211,7 +331,24 @@ impl State {
     }
 
     fn update(&mut self) {
-        self.time.update();
+        match self.fixed_frame_duration {
+            Some(frame_duration) => {
+                let last_update = self.time.last_update().unwrap_or(self.time.startup());
+                self.time.update_with_instant(last_update + frame_duration);
+            }
+            None => self.time.update(),
+        }
+
+        if let Some(watcher) = &self.asset_watcher {
+            let changed_paths = watcher.poll_changes();
+            if !changed_paths.is_empty() {
+                for path in &changed_paths {
+                    self.asset_server.invalidate(path);
+                }
+                self.adv
+                    .reload_assets(&self.resources, &self.asset_server, &changed_paths);
+            }
+        }
 
         let mut input = self.input.clone();
 
@@ -219,11 +356,17 @@ impl State {
             .start_update(&self.time, &input, self.window_size);
         self.overlay_manager.visit_overlays(|collector| {
             self.fps_counter.visit_overlay(collector);
+            self.presentation_overlay.visit_overlay(collector);
             input.visit_overlay(collector);
+            self.asset_server.visit_overlay(collector);
+            self.asset_server.io().visit_overlay(collector);
             self.adv.visit_overlay(collector);
         });
         self.overlay_manager
             .finish_update(&self.resources, &mut input);
+        if let Some(presentation) = self.presentation_overlay.take_request() {
+            self.set_presentation(presentation);
+        }
 
         let update_context = UpdateContext {
             time: &self.time,
@@ -235,6 +378,11 @@ impl State {
         self.adv.update(&update_context);
         self.fps_counter.update(&update_context);
 
+        if let Some(frame_duration) = self.fixed_frame_duration {
+            let audio = self.audio_manager.advance(frame_duration);
+            self.capture.push_audio(&audio);
+        }
+
         // NOTE: it's important that the input is updated after everything else, as it clears some state after it should have been handled
         self.input.update();
     }
@@ -255,11 +403,24 @@ impl State {
             );
         }
 
+        if self.capture.wants_frame() {
+            let image = self.render_target.read_pixels(&self.resources);
+            self.capture.push_frame(image);
+        }
+
         let output = self.surface.get_current_texture()?;
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
+        let bar_color = self.camera.presentation().bar_color.as_dvec4();
+        let bar_color = wgpu::Color {
+            r: bar_color.x,
+            g: bar_color.y,
+            b: bar_color.z,
+            a: bar_color.w,
+        };
+
         {
             let mut encoder = self.resources.start_encoder();
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -268,7 +429,7 @@ impl State {
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLUE),
+                        load: wgpu::LoadOp::Clear(bar_color),
                         store: true,
                     },
                 })],
@@ -298,6 +459,15 @@ impl State {
     }
 }
 
+/// Pixel-perfect presentation needs the pixels to not be blended with their neighbours
+fn presentation_filter_mode(presentation: &PresentationSettings) -> wgpu::FilterMode {
+    if presentation.pixel_perfect() {
+        wgpu::FilterMode::Nearest
+    } else {
+        wgpu::FilterMode::Linear
+    }
+}
+
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
 pub async fn run(cli: Cli) {
     cfg_if::cfg_if! {
@@ -387,6 +557,15 @@ pub async fn run(cli: Cli) {
                                 },
                             ..
                         } => window.set_inner_size(PhysicalSize::new(1920, 1080)),
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F12),
+                                    ..
+                                },
+                            ..
+                        } => state.capture.request_screenshot(),
                         WindowEvent::Resized(physical_size) => {
                             state.resize((*physical_size).into());
                         }
@@ -412,6 +591,10 @@ pub async fn run(cli: Cli) {
                     Err(wgpu::SurfaceError::Timeout) => warn!("Surface timeout"),
                 }
             }
+            Event::LoopDestroyed => {
+                // make sure the video file is finalized
+                state.capture.finish();
+            }
             Event::RedrawEventsCleared => {
                 // RedrawRequested will only trigger once, unless we manually
                 // request it.